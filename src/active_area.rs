@@ -0,0 +1,145 @@
+// L5 有效画面区域（active area）：画面上下左右黑边的像素数。播放器据此判断画面的实际宽高比，
+// 例如 IMAX 片段切换画幅时正确处理遮幅。通过 dovi_tool editor 写入 RPU 的每一帧
+
+use serde::{Deserialize, Serialize};
+
+use crate::probe::MediaInfo;
+
+// 偏移量（像素）
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq)]
+pub struct Offsets {
+    pub top: u32,
+    pub bottom: u32,
+    pub left: u32,
+    pub right: u32,
+}
+
+impl Offsets {
+    // dovi_tool editor 的 JSON 配置：一个预设应用到所有帧，不裁剪画面
+    pub fn editor_config(&self) -> serde_json::Value {
+        serde_json::json!({
+            "active_area": {
+                "crop": false,
+                "presets": [{
+                    "id": 0,
+                    "left": self.left,
+                    "right": self.right,
+                    "top": self.top,
+                    "bottom": self.bottom,
+                }],
+                "edits": { "all": 0 },
+            }
+        })
+    }
+}
+
+impl std::fmt::Display for Offsets {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        write!(
+            f,
+            "top {}, bottom {}, left {}, right {}",
+            self.top, self.bottom, self.left, self.right
+        )
+    }
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Edge {
+    Top,
+    Bottom,
+    Left,
+    Right,
+}
+
+impl Edge {
+    pub const ALL: [Edge; 4] = [Edge::Top, Edge::Bottom, Edge::Left, Edge::Right];
+}
+
+impl std::fmt::Display for Edge {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        f.write_str(match self {
+            Edge::Top => "Top",
+            Edge::Bottom => "Bottom",
+            Edge::Left => "Left",
+            Edge::Right => "Right",
+        })
+    }
+}
+
+// 队列中每个文件的偏移输入。全部留空表示保留 RPU 中原有的 L5；
+// 只填写部分时其余按 0 处理，全部填 0 可以清除错误的黑边信息
+#[derive(Debug, Clone, Default, Serialize, Deserialize)]
+#[serde(default)]
+pub struct Form {
+    pub top: String,
+    pub bottom: String,
+    pub left: String,
+    pub right: String,
+}
+
+impl Form {
+    pub fn value(&self, edge: Edge) -> &str {
+        match edge {
+            Edge::Top => &self.top,
+            Edge::Bottom => &self.bottom,
+            Edge::Left => &self.left,
+            Edge::Right => &self.right,
+        }
+    }
+
+    pub fn set(&mut self, edge: Edge, value: String) {
+        match edge {
+            Edge::Top => self.top = value,
+            Edge::Bottom => self.bottom = value,
+            Edge::Left => self.left = value,
+            Edge::Right => self.right = value,
+        }
+    }
+
+    pub fn is_empty(&self) -> bool {
+        Edge::ALL
+            .iter()
+            .all(|edge| self.value(*edge).trim().is_empty())
+    }
+
+    // 解析偏移；分析出画面尺寸时检查黑边没有占满画面
+    pub fn parse(&self, info: Option<&MediaInfo>) -> Result<Option<Offsets>, String> {
+        if self.is_empty() {
+            return Ok(None);
+        }
+        let size = info
+            .and_then(|info| info.video.as_ref())
+            .map(|video| (video.width, video.height))
+            .filter(|(width, height)| *width > 0 && *height > 0);
+        let mut pixels = [0; 4];
+        for edge in Edge::ALL {
+            let value = self.value(edge).trim();
+            if value.is_empty() {
+                continue;
+            }
+            pixels[edge as usize] = value.parse::<u32>().map_err(|_| {
+                format!("Invalid {edge} offset \"{value}\": use a number of pixels")
+            })?;
+        }
+        let [top, bottom, left, right] = pixels;
+        let offsets = Offsets {
+            top,
+            bottom,
+            left,
+            right,
+        };
+        if let Some((width, height)) = size {
+            if top.saturating_add(bottom) >= height {
+                return Err(format!(
+                    "Top and bottom offsets ({top} + {bottom}) must be less than the height ({height})"
+                ));
+            }
+            if left.saturating_add(right) >= width {
+                return Err(format!(
+                    "Left and right offsets ({left} + {right}) must be less than the width ({width})"
+                ));
+            }
+        }
+        Ok(Some(offsets))
+    }
+}
@@ -0,0 +1,275 @@
+// ASS/SSA 字幕转换为干净的 SRT。ffmpeg 直接把 ASS 转为 mov_text 时会丢掉样式却留下定位和特效的痕迹：
+// 绘图命令（\p1）变成一串坐标文字，\pos / \move 的标牌和卡拉 OK 特效堆在画面底部。
+// 这里只保留对白文本和 mov_text 支持的粗体、斜体、下划线，其余样式和定位去掉，绘图事件整条丢弃
+
+// 一条对白
+struct Cue {
+    start: u64,
+    end: u64,
+    text: String,
+}
+
+// ASS 时间 "H:MM:SS.cc"（百分之一秒）转为毫秒
+fn parse_time(value: &str) -> Option<u64> {
+    let mut parts = value.trim().split(':');
+    let hours = parts.next()?.parse::<u64>().ok()?;
+    let minutes = parts.next()?.parse::<u64>().ok()?;
+    let (seconds, fraction) = parts.next()?.split_once('.')?;
+    let seconds = seconds.parse::<u64>().ok()?;
+    // 小数部分通常是两位，也接受一位或三位
+    let fraction_ms = match fraction.len() {
+        1 => fraction.parse::<u64>().ok()? * 100,
+        2 => fraction.parse::<u64>().ok()? * 10,
+        _ => fraction.get(..3)?.parse::<u64>().ok()?,
+    };
+    Some(((hours * 60 + minutes) * 60 + seconds) * 1000 + fraction_ms)
+}
+
+fn format_time(ms: u64) -> String {
+    format!(
+        "{:02}:{:02}:{:02},{:03}",
+        ms / 3_600_000,
+        ms / 60_000 % 60,
+        ms / 1000 % 60,
+        ms % 1000
+    )
+}
+
+// 把对白文本中的覆盖标签转换为 SRT 的 <b>/<i>/<u>，其余标签去掉；含绘图命令时返回 None
+fn clean_text(text: &str) -> Option<String> {
+    let mut output = String::new();
+    let mut open = Vec::<char>::new();
+    let mut rest = text;
+    while !rest.is_empty() {
+        if let Some(block) = rest.strip_prefix('{') {
+            let Some(end) = block.find('}') else {
+                // 没有闭合的大括号按普通文字处理
+                output.push_str(rest);
+                break;
+            };
+            for tag in block[..end].split('\\').map(str::trim) {
+                // \p1 及以上进入绘图模式，整条对白都是矢量图形
+                if let Some(scale) = tag.strip_prefix('p')
+                    && scale.parse::<u32>().is_ok_and(|scale| scale > 0)
+                {
+                    return None;
+                }
+                for style in ['b', 'i', 'u'] {
+                    let Some(value) = tag.strip_prefix(style) else {
+                        continue;
+                    };
+                    // \b 也可以是字重（\b700），\bord、\blur 等其他标签不是粗体
+                    let enabled = match value {
+                        "" | "0" => false,
+                        "1" => true,
+                        weight if style == 'b' && weight.parse::<u32>().is_ok() => {
+                            weight.parse::<u32>().is_ok_and(|weight| weight >= 600)
+                        }
+                        _ => continue,
+                    };
+                    let is_open = open.contains(&style);
+                    if enabled && !is_open {
+                        output.push_str(&format!("<{style}>"));
+                        open.push(style);
+                    } else if !enabled && is_open {
+                        output.push_str(&format!("</{style}>"));
+                        open.retain(|open| *open != style);
+                    }
+                }
+                // \r 或 \r<样式名> 恢复样式的默认设置
+                if tag.starts_with('r') {
+                    for style in open.drain(..).rev() {
+                        output.push_str(&format!("</{style}>"));
+                    }
+                }
+            }
+            rest = &block[end + 1..];
+        } else if let Some(after) = rest.strip_prefix("\\N") {
+            output.push('\n');
+            rest = after;
+        } else if let Some(after) = rest
+            .strip_prefix("\\n")
+            .or_else(|| rest.strip_prefix("\\h"))
+        {
+            output.push(' ');
+            rest = after;
+        } else {
+            let next = rest
+                .char_indices()
+                .skip(1)
+                .find(|(_, c)| *c == '{' || *c == '\\')
+                .map_or(rest.len(), |(index, _)| index);
+            output.push_str(&rest[..next]);
+            rest = &rest[next..];
+        }
+    }
+    for style in open.into_iter().rev() {
+        output.push_str(&format!("</{style}>"));
+    }
+    let lines = output
+        .lines()
+        .map(str::trim)
+        .filter(|line| !line.is_empty())
+        .collect::<Vec<_>>();
+    Some(lines.join("\n"))
+}
+
+// 转换整个 ASS/SSA 文件：读取 [Events] 中 Format 指定的 Start、End、Text 列，
+// 按开始时间排序，去掉重复（描边或阴影常用多层相同的对白）和空白对白
+pub fn to_srt(ass: &str) -> Result<String, String> {
+    let mut in_events = false;
+    let mut format = Vec::new();
+    let mut cues = Vec::new();
+    for line in ass
+        .lines()
+        .map(|line| line.trim_start_matches('\u{feff}').trim())
+    {
+        if line.starts_with('[') {
+            in_events = line.eq_ignore_ascii_case("[Events]");
+            continue;
+        }
+        if !in_events {
+            continue;
+        }
+        let Some((kind, value)) = line.split_once(':') else {
+            continue;
+        };
+        match kind.trim() {
+            "Format" => {
+                format = value
+                    .split(',')
+                    .map(|field| field.trim().to_ascii_lowercase())
+                    .collect();
+            }
+            "Dialogue" if !format.is_empty() => {
+                // Text 是最后一列，可以包含逗号
+                let fields = value.splitn(format.len(), ',').collect::<Vec<_>>();
+                let field = |name: &str| {
+                    format
+                        .iter()
+                        .position(|field| field == name)
+                        .and_then(|index| fields.get(index))
+                };
+                let (Some(start), Some(end), Some(text)) = (
+                    field("start").and_then(|value| parse_time(value)),
+                    field("end").and_then(|value| parse_time(value)),
+                    field("text"),
+                ) else {
+                    continue;
+                };
+                if let Some(text) = clean_text(text).filter(|text| !text.is_empty())
+                    && end > start
+                {
+                    cues.push(Cue { start, end, text });
+                }
+            }
+            _ => {}
+        }
+    }
+    if format.is_empty() {
+        return Err("No [Events] section with a Format line".to_string());
+    }
+
+    cues.sort_by_key(|cue| (cue.start, cue.end));
+    cues.dedup_by(|a, b| a.start == b.start && a.end == b.end && a.text == b.text);
+    Ok(cues
+        .iter()
+        .enumerate()
+        .map(|(number, cue)| {
+            format!(
+                "{}\n{} --> {}\n{}\n",
+                number + 1,
+                format_time(cue.start),
+                format_time(cue.end),
+                cue.text
+            )
+        })
+        .collect::<Vec<_>>()
+        .join("\n"))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    const EVENTS: &str = "[Events]\nFormat: Layer, Start, End, Style, Name, MarginL, MarginR, MarginV, Effect, Text\n";
+
+    fn dialogue(start: &str, end: &str, text: &str) -> String {
+        format!("Dialogue: 0,{start},{end},Default,,0,0,0,,{text}\n")
+    }
+
+    #[test]
+    fn dialogue_is_converted_to_sorted_srt() {
+        let ass = format!(
+            "\u{feff}[Script Info]\nTitle: Movie\n\n[V4+ Styles]\nFormat: Name, Fontname\nStyle: Default,Arial\n\n{EVENTS}{}{}{}{}",
+            dialogue(
+                "0:00:02.00",
+                "0:00:04.50",
+                r"{\i1}Hello{\i0}, world\Nsecond line"
+            ),
+            dialogue("0:00:01.00", "0:00:02.00", r"{\pos(10,10)\b1}First"),
+            // 描边层与正文相同，只保留一条
+            dialogue("0:00:01.00", "0:00:02.00", r"{\pos(10,10)\b1}First"),
+            // 绘图命令整条丢弃
+            dialogue("0:00:05.00", "0:00:06.00", r"{\p1}m 0 0 l 100 0 100 100"),
+        );
+        assert_eq!(
+            to_srt(&ass).unwrap(),
+            "1\n00:00:01,000 --> 00:00:02,000\n<b>First</b>\n\n\
+             2\n00:00:02,000 --> 00:00:04,500\n<i>Hello</i>, world\nsecond line\n"
+        );
+    }
+
+    #[test]
+    fn override_tags_are_mapped_or_dropped() {
+        for (text, expected) in [
+            (r"{\b700}Bold{\r}plain", "<b>Bold</b>plain"),
+            (r"{\bord2\blur1}Outline", "Outline"),
+            (r"{\u1}Under", "<u>Under</u>"),
+            (r"non\hbreaking", "non breaking"),
+            (r"{unclosed brace", "{unclosed brace"),
+        ] {
+            let ass = format!("{EVENTS}{}", dialogue("0:00:01.00", "0:00:02.00", text));
+            assert_eq!(
+                to_srt(&ass).unwrap(),
+                format!("1\n00:00:01,000 --> 00:00:02,000\n{expected}\n"),
+                "{text}"
+            );
+        }
+    }
+
+    #[test]
+    fn times_accept_one_to_three_fraction_digits() {
+        assert_eq!(parse_time("1:02:03.4"), Some(3_723_400));
+        assert_eq!(parse_time("0:00:00.05"), Some(50));
+        assert_eq!(parse_time("0:00:00.123"), Some(123));
+        for value in ["", "0:00:01", "0:00:xx.00", "0:00:01.", "1:02"] {
+            assert_eq!(parse_time(value), None, "{value}");
+        }
+        assert_eq!(format_time(3_723_400), "01:02:03,400");
+    }
+
+    #[test]
+    fn malformed_events_are_skipped() {
+        assert!(to_srt("").is_err());
+        assert!(to_srt("[Script Info]\nTitle: Movie\n").is_err());
+        // Format 行之前的对白无法解析列
+        assert!(
+            to_srt(&format!(
+                "[Events]\n{}",
+                dialogue("0:00:01.00", "0:00:02.00", "Hi")
+            ))
+            .is_err()
+        );
+
+        let ass = format!(
+            "{EVENTS}{}{}{}Dialogue: no commas\nComment: 0,0:00:01.00,0:00:02.00,Default,,0,0,0,,Note\n",
+            dialogue("0:00:0x.00", "0:00:02.00", "Bad time"),
+            // 结束时间不晚于开始时间
+            dialogue("0:00:02.00", "0:00:02.00", "Zero length"),
+            // 去掉标签后没有文字
+            dialogue("0:00:01.00", "0:00:02.00", r"{\pos(10,10)}  "),
+        );
+        assert_eq!(to_srt(&ass).unwrap(), "");
+    }
+}
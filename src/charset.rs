@@ -0,0 +1,201 @@
+// 外部字幕文件的字符编码检测。很多字幕（尤其是中文字幕组和旧的 SRT）使用 GBK、Big5、
+// Shift_JIS 或 Windows-1252 等本地编码，ffmpeg 默认按 UTF-8 读取，非 UTF-8 的字节会变成乱码或被丢弃
+
+// 检测结果
+#[derive(Debug, Clone, PartialEq)]
+pub enum Encoding {
+    Utf8,
+    // 带 BOM 的 UTF-16，已解码为文本
+    Utf16(String),
+    // 本地编码，名称可以直接传给 ffmpeg 的 -sub_charenc（iconv）
+    Legacy(&'static str),
+}
+
+// 有 BOM 时按 BOM 解码 UTF-16
+fn decode_utf16(bytes: &[u8]) -> Option<String> {
+    let (body, little_endian) = match bytes {
+        [0xFF, 0xFE, body @ ..] => (body, true),
+        [0xFE, 0xFF, body @ ..] => (body, false),
+        _ => return None,
+    };
+    let units = body
+        .chunks_exact(2)
+        .map(|pair| {
+            if little_endian {
+                u16::from_le_bytes([pair[0], pair[1]])
+            } else {
+                u16::from_be_bytes([pair[0], pair[1]])
+            }
+        })
+        .collect::<Vec<_>>();
+    Some(String::from_utf16_lossy(&units))
+}
+
+// 字幕语言对应的单字节代码页
+fn code_page(language: &str) -> Option<&'static str> {
+    Some(match language {
+        "rus" | "ukr" | "bel" | "bul" | "srp" | "mac" | "mkd" => "CP1251",
+        "pol" | "cze" | "ces" | "slo" | "slk" | "hun" | "hrv" | "slv" | "rum" | "ron" | "bos" => {
+            "CP1250"
+        }
+        "gre" | "ell" => "CP1253",
+        "tur" => "CP1254",
+        "heb" => "CP1255",
+        "ara" | "per" | "fas" => "CP1256",
+        "tha" => "CP874",
+        _ => return None,
+    })
+}
+
+// 按双字节编码的规则读取的统计
+#[derive(Default)]
+struct DoubleByte {
+    pairs: usize,
+    // 第二字节也是高位字节的数量；西文编码中重音字母后面通常是 ASCII 字母
+    high_trail: usize,
+    // 落在 GB2312 汉字区的数量
+    gb2312: usize,
+    // 首字节在 0x81–0x9F 的数量
+    low_lead: usize,
+}
+
+// 有不成对的高位字节时返回 None
+fn double_byte_stats(bytes: &[u8]) -> Option<DoubleByte> {
+    let mut stats = DoubleByte::default();
+    let mut index = 0;
+    while index < bytes.len() {
+        let lead = bytes[index];
+        if lead < 0x80 {
+            index += 1;
+            continue;
+        }
+        let trail = *bytes.get(index + 1)?;
+        if !(0x81..=0xFE).contains(&lead) || trail < 0x40 || trail == 0x7F || trail == 0xFF {
+            return None;
+        }
+        stats.pairs += 1;
+        if trail >= 0x80 {
+            stats.high_trail += 1;
+        }
+        if (0xB0..=0xF7).contains(&lead) && (0xA1..=0xFE).contains(&trail) {
+            stats.gb2312 += 1;
+        }
+        if lead <= 0x9F {
+            stats.low_lead += 1;
+        }
+        index += 2;
+    }
+    Some(stats)
+}
+
+// 检测字幕文件的编码。合法的 UTF-8（含 BOM）直接使用；否则按字幕语言（文件名中的语言标记或默认语言）
+// 选择编码，没有可用的语言时按字节分布猜测：高位字节成对出现且第二字节多为高位字节时为双字节的中日文编码，
+// 否则为西欧的 Windows-1252
+pub fn detect(bytes: &[u8], language: Option<&str>) -> Encoding {
+    if let Some(text) = decode_utf16(bytes) {
+        return Encoding::Utf16(text);
+    }
+    if std::str::from_utf8(bytes).is_ok() {
+        return Encoding::Utf8;
+    }
+    let language = language.unwrap_or_default().to_ascii_lowercase();
+    if let Some(page) = code_page(&language) {
+        return Encoding::Legacy(page);
+    }
+    match language.as_str() {
+        "jpn" => return Encoding::Legacy("SHIFT_JIS"),
+        "kor" => return Encoding::Legacy("CP949"),
+        _ => {}
+    }
+    let chinese = matches!(language.as_str(), "chi" | "zho");
+    let Some(stats) = double_byte_stats(bytes).filter(|stats| stats.high_trail * 2 >= stats.pairs)
+    else {
+        return Encoding::Legacy(if chinese { "GB18030" } else { "CP1252" });
+    };
+    if !chinese && stats.low_lead * 2 > stats.pairs {
+        // 日文假名在 Shift_JIS 中的首字节是 0x82/0x83，GBK 中很少出现 0x81–0x9F 的首字节
+        Encoding::Legacy("SHIFT_JIS")
+    } else if stats.gb2312 * 10 >= stats.pairs * 9 {
+        // 简体中文的常用字几乎都在 GB2312 区，繁体的 Big5 有大量 0x40–0x7E 的第二字节
+        Encoding::Legacy("GB18030")
+    } else {
+        Encoding::Legacy("BIG5")
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn unicode_files_are_detected() {
+        assert_eq!(detect(b"", None), Encoding::Utf8);
+        assert_eq!(detect("Hello, 世界".as_bytes(), None), Encoding::Utf8);
+        assert_eq!(detect(b"\xEF\xBB\xBFHi", Some("rus")), Encoding::Utf8);
+        assert_eq!(
+            detect(b"\xFF\xFEH\0i\0", None),
+            Encoding::Utf16("Hi".to_string())
+        );
+        assert_eq!(
+            detect(b"\xFE\xFF\0H\0i", None),
+            Encoding::Utf16("Hi".to_string())
+        );
+        // 只有 BOM，或末尾多出不成对的字节
+        assert_eq!(detect(b"\xFF\xFE", None), Encoding::Utf16(String::new()));
+        assert_eq!(
+            detect(b"\xFF\xFEH\0i", None),
+            Encoding::Utf16("H".to_string())
+        );
+    }
+
+    #[test]
+    fn subtitle_language_selects_the_code_page() {
+        let latin1 = b"caf\xE9";
+        for (language, expected) in [
+            ("rus", "CP1251"),
+            ("RUS", "CP1251"),
+            ("pol", "CP1250"),
+            ("gre", "CP1253"),
+            ("tur", "CP1254"),
+            ("heb", "CP1255"),
+            ("ara", "CP1256"),
+            ("tha", "CP874"),
+            ("jpn", "SHIFT_JIS"),
+            ("kor", "CP949"),
+        ] {
+            assert_eq!(
+                detect(latin1, Some(language)),
+                Encoding::Legacy(expected),
+                "{language}"
+            );
+        }
+    }
+
+    #[test]
+    fn legacy_encodings_are_guessed_from_bytes() {
+        // GBK "中文"
+        assert_eq!(
+            detect(b"\xD6\xD0\xCE\xC4", None),
+            Encoding::Legacy("GB18030")
+        );
+        // Big5 "中文"
+        assert_eq!(detect(b"\xA4\xA4\xA4\xE5", None), Encoding::Legacy("BIG5"));
+        // Shift_JIS "こんにちは"
+        assert_eq!(
+            detect(b"\x82\xB1\x82\xF1\x82\xC9\x82\xBF\x82\xCD", None),
+            Encoding::Legacy("SHIFT_JIS")
+        );
+        // 重音字母后面是 ASCII，不是双字节编码
+        assert_eq!(
+            detect(b"caf\xE9 cr\xE8me", None),
+            Encoding::Legacy("CP1252")
+        );
+        assert_eq!(
+            detect(b"caf\xE9 cr\xE8me", Some("chi")),
+            Encoding::Legacy("GB18030")
+        );
+        // 末尾不成对的高位字节
+        assert_eq!(detect(b"\xD6", None), Encoding::Legacy("CP1252"));
+        assert_eq!(detect(b"\xD6\xD0\xCE", None), Encoding::Legacy("CP1252"));
+    }
+}
@@ -0,0 +1,355 @@
+use iced::futures::StreamExt;
+use iced::futures::channel::mpsc;
+use std::path::{Path, PathBuf};
+use std::process::ExitCode;
+
+use crate::{
+    Message, ProcessOptions, command_line, pipeline, probe, processes, projects, self_test,
+    skip_list, temp_files,
+};
+
+// 无人值守运行的退出码，供脚本和计划任务判断结果
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Status {
+    Success = 0,
+    // 部分文件失败
+    PartialFailure = 1,
+    AllFailed = 2,
+    // 参数、命名队列或选项有误，没有处理任何文件
+    ConfigError = 3,
+    ToolsMissing = 4,
+}
+
+impl From<Status> for ExitCode {
+    fn from(status: Status) -> Self {
+        ExitCode::from(status as u8)
+    }
+}
+
+const USAGE: &str = "Usage: rebottle --cli [--output <folder>] [--queue <name>] [<file.mkv>...]
+       rebottle --self-test
+
+Converts the files without opening the window. With --queue, the named queue's
+options and output folder are used, and its files when none are given.
+--self-test converts a generated sample and reports each step.
+
+Exit codes:
+  0  all files converted (or skipped), or the self-test passed
+  1  some files failed
+  2  all files failed, or the self-test failed
+  3  invalid arguments, queue or options
+  4  required tools are missing";
+
+// 命令行参数
+#[derive(Debug, Default)]
+struct Args {
+    output: Option<PathBuf>,
+    queue: Option<String>,
+    files: Vec<PathBuf>,
+}
+
+fn parse_args(args: &[String]) -> Result<Args, String> {
+    let mut parsed = Args::default();
+    let mut args = args.iter();
+    while let Some(arg) = args.next() {
+        match arg.as_str() {
+            "--output" | "-o" => {
+                parsed.output = Some(PathBuf::from(args.next().ok_or("--output needs a folder")?));
+            }
+            "--queue" | "-q" => {
+                parsed.queue = Some(args.next().ok_or("--queue needs a name")?.clone());
+            }
+            flag if flag.starts_with('-') => return Err(format!("Unknown option {flag}")),
+            file => parsed.files.push(PathBuf::from(file)),
+        }
+    }
+    Ok(parsed)
+}
+
+// 在 PATH 中查找工具，与启动工具时的查找方式相同（Windows 上按 PATHEXT）
+pub fn find_tool(tool: &str) -> bool {
+    command_line::find(tool).is_some()
+}
+
+// 按选项和源文件需要的外部工具：分析每个源文件（需要 ffprobe），按预演得出的步骤收集各步骤
+// 使用的程序，如 dovi_tool 只在 profile 7、RPU 流程、裁剪或 L5 等需要时检查。
+// 无法分析的文件与处理时一样按杜比视界 HEVC 源组合步骤
+pub fn required_tools(options: &ProcessOptions, sources: &[pipeline::Source]) -> Vec<&'static str> {
+    let mut tools = vec!["ffprobe"];
+    for source in sources {
+        let steps = match probe::probe_media(&source.path) {
+            Ok(info) => pipeline::dry_run(source, &info, options).steps,
+            Err(_) => pipeline::plan(options, &probe::MediaInfo::default(), &[], true, source),
+        };
+        add_step_tools(options, &steps, &mut tools);
+    }
+    tools
+}
+
+// 把步骤使用的程序加入列表，不重复
+pub fn add_step_tools(
+    options: &ProcessOptions,
+    steps: &[pipeline::Step],
+    tools: &mut Vec<&'static str>,
+) {
+    for program in steps.iter().flat_map(|step| step.programs(options)) {
+        if !tools.contains(program) {
+            tools.push(program);
+        }
+    }
+}
+
+// 要处理的文件、输出文件夹和选项
+fn prepare(args: Args) -> Result<(Vec<pipeline::Source>, PathBuf, ProcessOptions), String> {
+    let queue = match &args.queue {
+        Some(name) => Some(
+            projects::Projects::load()
+                .get(name)
+                .cloned()
+                .ok_or_else(|| format!("Queue \"{name}\" not found"))?,
+        ),
+        None => None,
+    };
+    let options = queue
+        .as_ref()
+        .map(|queue| queue.options.clone())
+        .unwrap_or_default();
+    if let Some(error) = options.validation_error() {
+        return Err(error);
+    }
+    let output = args
+        .output
+        .or_else(|| queue.as_ref()?.output_folder.clone())
+        .ok_or("No output folder, use --output")?;
+    if !output.is_dir() {
+        return Err(format!(
+            "Output folder {} does not exist",
+            output.to_string_lossy()
+        ));
+    }
+
+    // 命令行给出的文件使用自动选择的音轨和字幕；命名队列中的文件保留 L5 偏移、裁剪范围、音轨和字幕选择、外部字幕、音频和字幕偏移以及拼接的分段
+    let saved = queue.map(|queue| queue.files).unwrap_or_default();
+    let sources = if args.files.is_empty() {
+        saved
+            .into_iter()
+            .map(|file| {
+                // 章节和画面尺寸只在设置了裁剪范围或 L5 偏移时需要
+                let trimmed =
+                    !file.trim_start.trim().is_empty() || !file.trim_end.trim().is_empty();
+                let info = if trimmed || !file.active_area.is_empty() {
+                    probe::probe_media(&file.path).ok()
+                } else {
+                    None
+                };
+                let chapters = info
+                    .as_ref()
+                    .map(|info| info.chapters.as_slice())
+                    .unwrap_or_default();
+                let trim = pipeline::Trim::parse(&file.trim_start, &file.trim_end, chapters)
+                    .map_err(|e| format!("{}: {e}", file.path.to_string_lossy()))?;
+                let active_area = file
+                    .active_area
+                    .parse(info.as_ref())
+                    .map_err(|e| format!("{}: {e}", file.path.to_string_lossy()))?;
+                let audio_offset = pipeline::parse_audio_offset(&file.audio_offset)
+                    .map_err(|e| format!("{}: {e}", file.path.to_string_lossy()))?;
+                let subtitle_offset = pipeline::parse_subtitle_offset(&file.subtitle_offset)
+                    .map_err(|e| format!("{}: {e}", file.path.to_string_lossy()))?;
+                Ok(pipeline::Source {
+                    path: file.path,
+                    active_area,
+                    version: 1,
+                    trim,
+                    audio_tracks: file.audio_tracks,
+                    subtitle_tracks: file.subtitle_tracks,
+                    external_subtitles: file.external_subtitles,
+                    audio_offset,
+                    subtitle_offset,
+                    parts: file.parts,
+                })
+            })
+            .collect::<Result<Vec<_>, String>>()?
+    } else {
+        args.files
+            .into_iter()
+            .map(|path| pipeline::Source {
+                path,
+                active_area: None,
+                version: 1,
+                trim: None,
+                audio_tracks: Vec::new(),
+                subtitle_tracks: None,
+                external_subtitles: Vec::new(),
+                audio_offset: None,
+                subtitle_offset: None,
+                parts: Vec::new(),
+            })
+            .collect()
+    };
+    if sources.is_empty() {
+        return Err("No files to convert".to_string());
+    }
+    if let Some(missing) = sources
+        .iter()
+        .flat_map(|source| {
+            std::iter::once(&source.path)
+                .chain(&source.parts)
+                .chain(&source.external_subtitles)
+        })
+        .find(|path| !path.is_file())
+    {
+        return Err(format!("{} not found", missing.to_string_lossy()));
+    }
+    Ok((sources, output, options))
+}
+
+fn file_name(path: &Path) -> String {
+    path.file_name()
+        .unwrap_or_default()
+        .to_string_lossy()
+        .to_string()
+}
+
+// 依次处理全部文件，单个文件失败时继续处理其余文件
+async fn convert(
+    sources: Vec<pipeline::Source>,
+    output: PathBuf,
+    options: ProcessOptions,
+) -> Status {
+    processes::set_background_priority(options.background_priority);
+    let (mut sender, mut receiver) = mpsc::channel(100);
+    // 处理步骤和工具的实时输出写入 stderr
+    let printer = tokio::spawn(async move {
+        while let Some(message) = receiver.next().await {
+            match message {
+                Message::ProcessingStep(step) => eprintln!("{step}"),
+                Message::TerminalOutput(line) => eprintln!("{line}"),
+                _ => {}
+            }
+        }
+    });
+
+    // 工具在单独的进程组中运行，不会收到终端的 Ctrl+C；中断时先结束它们，临时文件留给下次启动时清理
+    tokio::spawn(async {
+        if tokio::signal::ctrl_c().await.is_ok() {
+            processes::kill_all();
+            std::process::exit(130);
+        }
+    });
+
+    for note in temp_files::clean_stale() {
+        println!("{note}");
+    }
+    let skip_list = skip_list::SkipList::load();
+    let total = sources.len();
+    let (mut converted, mut failed) = (0, 0);
+    for (index, source) in sources.into_iter().enumerate() {
+        let name = file_name(&source.path);
+        if let Some(reason) = skip_list.reason(&source.path) {
+            println!("🚫 Skipping {name} (skip list: {reason})");
+            continue;
+        }
+        println!("Processing file {}/{total}: {name}", index + 1);
+        // 日志已经在处理过程中写入 stderr
+        let (result, _) = pipeline::process_file_with_retries(
+            source,
+            output.clone(),
+            options.clone(),
+            &mut sender,
+            None,
+        )
+        .await;
+        match result {
+            Ok(_) => {
+                converted += 1;
+                println!("✅ {name} completed");
+            }
+            Err(e) => {
+                failed += 1;
+                println!("❌ {name} failed: {e}");
+            }
+        }
+    }
+    drop(sender);
+    let _ = printer.await;
+
+    println!("{converted} converted, {failed} failed");
+    match (converted, failed) {
+        (_, 0) => Status::Success,
+        (0, _) => Status::AllFailed,
+        _ => Status::PartialFailure,
+    }
+}
+
+// 以 --self-test 启动时运行自检，工具日志写入 stderr，每项检查的结果写入 stdout
+pub fn self_test() -> ExitCode {
+    let runtime = match tokio::runtime::Runtime::new() {
+        Ok(runtime) => runtime,
+        Err(e) => {
+            eprintln!("Error: Failed to start runtime: {e}");
+            return Status::AllFailed.into();
+        }
+    };
+    let report = runtime.block_on(async {
+        let (mut sender, mut receiver) = mpsc::channel(100);
+        let printer = tokio::spawn(async move {
+            while let Some(message) = receiver.next().await {
+                if let Message::TerminalOutput(line) = message {
+                    eprintln!("{line}");
+                }
+            }
+        });
+        let report = self_test::run_self_test(&mut sender).await;
+        drop(sender);
+        let _ = printer.await;
+        report
+    });
+    for line in report.summary() {
+        println!("{line}");
+    }
+    if report.passed() {
+        Status::Success
+    } else if report.tools_missing() {
+        Status::ToolsMissing
+    } else {
+        Status::AllFailed
+    }
+    .into()
+}
+
+// 以 --cli 启动时在不打开窗口的情况下处理文件并返回退出码
+pub fn run(args: &[String]) -> ExitCode {
+    if args.iter().any(|arg| arg == "--help" || arg == "-h") {
+        println!("{USAGE}");
+        return Status::Success.into();
+    }
+    let prepared = parse_args(args).and_then(prepare);
+    let (sources, output, options) = match prepared {
+        Ok(prepared) => prepared,
+        Err(e) => {
+            eprintln!("Error: {e}\n\n{USAGE}");
+            return Status::ConfigError.into();
+        }
+    };
+
+    let missing = required_tools(&options, &sources)
+        .into_iter()
+        .filter(|tool| !find_tool(tool))
+        .collect::<Vec<_>>();
+    if !missing.is_empty() {
+        eprintln!(
+            "Error: required tools not found in PATH: {}",
+            missing.join(", ")
+        );
+        return Status::ToolsMissing.into();
+    }
+
+    match tokio::runtime::Runtime::new() {
+        Ok(runtime) => runtime.block_on(convert(sources, output, options)).into(),
+        Err(e) => {
+            eprintln!("Error: Failed to start runtime: {e}");
+            Status::AllFailed.into()
+        }
+    }
+}
@@ -0,0 +1,560 @@
+use std::path::{Path, PathBuf};
+use std::process::Command;
+
+// 外部工具的启动和命令行的转义。程序和参数始终分开保存，直接交给操作系统启动，不经过 shell
+// 或 cmd.exe，文件名、命名队列、上传地址等用户提供的内容原样到达工具。日志中的命令行
+// 按平台转义，可以直接复制到终端运行，`; rm -rf`、`&& del` 这样的文件名不会改变命令结构
+
+// 创建外部命令：程序按 resolve() 查找，每个参数单独传递
+pub fn command(program: &str, args: &[&str]) -> Command {
+    let mut command = Command::new(resolve(program));
+    command.args(args);
+    command
+}
+
+// Windows 上按 PATH 和 PATHEXT 查找程序：Command::new 只会补全 .exe，以 .cmd/.bat 包装的工具
+// （如 PgsToSrt）需要完整路径。标准库启动 .cmd/.bat 时会按 cmd.exe 的规则转义参数，
+// 无法安全转义时拒绝启动。其他平台由系统按 PATH 查找；找不到时原样返回，由启动时报错
+pub fn resolve(program: &str) -> PathBuf {
+    if !cfg!(windows) || Path::new(program).components().count() > 1 {
+        return PathBuf::from(program);
+    }
+    find(program).unwrap_or_else(|| PathBuf::from(program))
+}
+
+// 在 PATH 中查找程序，Windows 上按 PATHEXT 补全扩展名；找不到时为 None
+pub fn find(program: &str) -> Option<PathBuf> {
+    let paths = std::env::var_os("PATH")
+        .map(|paths| std::env::split_paths(&paths).collect::<Vec<_>>())
+        .unwrap_or_default();
+    let extensions = if cfg!(windows) {
+        std::env::var("PATHEXT")
+            .unwrap_or_else(|_| ".COM;.EXE;.BAT;.CMD".to_string())
+            .split(';')
+            .filter(|extension| !extension.is_empty())
+            .map(ToString::to_string)
+            .collect::<Vec<_>>()
+    } else {
+        Vec::new()
+    };
+    find_program(program, &paths, &extensions)
+}
+
+// 依次在每个目录中查找：已带扩展名（或没有 PATHEXT，即 Windows 以外）时先按原名，
+// 再按 PATHEXT 的顺序补全扩展名
+fn find_program(program: &str, paths: &[PathBuf], extensions: &[String]) -> Option<PathBuf> {
+    let names = (Path::new(program).extension().is_some() || extensions.is_empty())
+        .then(|| program.to_string())
+        .into_iter()
+        .chain(
+            extensions
+                .iter()
+                .map(|extension| format!("{program}{}", extension.to_lowercase())),
+        )
+        .collect::<Vec<_>>();
+    paths
+        .iter()
+        .flat_map(|dir| names.iter().map(move |name| dir.join(name)))
+        .find(|candidate| candidate.is_file())
+}
+
+// 找不到程序时说明的结尾；重试不会改变这种失败
+const NOT_FOUND: &str = "was not found, install it or add it to PATH";
+
+// 启动程序失败的说明
+pub fn spawn_error(program: &str, e: &std::io::Error) -> String {
+    if e.kind() == std::io::ErrorKind::NotFound {
+        format!("{program} {NOT_FOUND}")
+    } else {
+        format!("Failed to execute command {program}: {e}")
+    }
+}
+
+// 失败是否因为找不到程序（说明可能被步骤加上前缀）
+pub fn is_not_found(error: &str) -> bool {
+    error.contains(NOT_FOUND)
+}
+
+// 不需要转义的字符
+fn is_plain(arg: &str) -> bool {
+    !arg.is_empty()
+        && arg
+            .chars()
+            .all(|c| c.is_ascii_alphanumeric() || "-_./:=,+@".contains(c))
+}
+
+// POSIX shell：用单引号包裹，单引号本身写为 '\''
+pub fn quote_posix(arg: &str) -> String {
+    if is_plain(arg) {
+        return arg.to_string();
+    }
+    format!("'{}'", arg.replace('\'', r"'\''"))
+}
+
+// cmd.exe 会解释的字符；在 cmd 中粘贴日志里的命令时，被 ^ 转义后原样传给程序
+const CMD_SPECIAL: &str = "()%!^\"<>&|";
+
+// Windows：先按 MSVC 运行库解析命令行的规则加引号（引号前的反斜杠加倍），
+// 再用 ^ 转义 cmd.exe 的特殊字符（包括引号），cmd 去掉 ^ 后程序收到原样的参数
+pub fn quote_windows(arg: &str) -> String {
+    if is_plain(arg) {
+        return arg.to_string();
+    }
+    let mut quoted = String::from("\"");
+    let mut backslashes = 0;
+    // cmd.exe 在换行处结束命令且无法转义；Windows 文件名中不会出现换行
+    for c in arg
+        .chars()
+        .map(|c| if c == '\n' || c == '\r' { ' ' } else { c })
+    {
+        if c == '\\' {
+            backslashes += 1;
+            continue;
+        }
+        let escapes = if c == '"' {
+            backslashes * 2 + 1
+        } else {
+            backslashes
+        };
+        quoted.push_str(&"\\".repeat(escapes));
+        quoted.push(c);
+        backslashes = 0;
+    }
+    // 结尾的反斜杠后面是闭合引号，同样需要加倍
+    quoted.push_str(&"\\".repeat(backslashes * 2));
+    quoted.push('"');
+
+    let mut escaped = String::with_capacity(quoted.len() * 2);
+    for c in quoted.chars() {
+        if CMD_SPECIAL.contains(c) {
+            escaped.push('^');
+        }
+        escaped.push(c);
+    }
+    escaped
+}
+
+pub fn quote(arg: &str) -> String {
+    if cfg!(windows) {
+        quote_windows(arg)
+    } else {
+        quote_posix(arg)
+    }
+}
+
+// 切分用户填写的附加参数和命令。Windows 上按系统解析命令行的规则，反斜杠是路径分隔符，
+// C:\tools\notify.exe 原样保留；其他平台按 POSIX shell 的规则
+pub fn split(line: &str) -> Result<Vec<String>, String> {
+    if cfg!(windows) {
+        split_windows(line)
+    } else {
+        split_shell(line)
+    }
+}
+
+// 按 POSIX shell 的规则切分：空白分隔，单引号内原样保留，
+// 双引号内只有 \" 和 \\ 是转义，引号外的反斜杠转义下一个字符。不做变量展开或通配
+fn split_shell(line: &str) -> Result<Vec<String>, String> {
+    let mut args = Vec::new();
+    let mut current = None::<String>;
+    let mut chars = line.chars();
+    while let Some(c) = chars.next() {
+        match c {
+            '\'' => {
+                let arg = current.get_or_insert_with(String::new);
+                loop {
+                    match chars.next() {
+                        Some('\'') => break,
+                        Some(c) => arg.push(c),
+                        None => return Err("Unterminated single quote".to_string()),
+                    }
+                }
+            }
+            '"' => {
+                let arg = current.get_or_insert_with(String::new);
+                loop {
+                    match chars.next() {
+                        Some('"') => break,
+                        Some('\\') => match chars.next() {
+                            Some(c @ ('"' | '\\')) => arg.push(c),
+                            Some(c) => {
+                                arg.push('\\');
+                                arg.push(c);
+                            }
+                            None => return Err("Unterminated double quote".to_string()),
+                        },
+                        Some(c) => arg.push(c),
+                        None => return Err("Unterminated double quote".to_string()),
+                    }
+                }
+            }
+            '\\' => match chars.next() {
+                Some(c) => current.get_or_insert_with(String::new).push(c),
+                None => return Err("Trailing backslash".to_string()),
+            },
+            c if c.is_whitespace() => args.extend(current.take()),
+            c => current.get_or_insert_with(String::new).push(c),
+        }
+    }
+    args.extend(current);
+    Ok(args)
+}
+
+// 按 CommandLineToArgvW 和 MSVC 运行库的规则切分：空白分隔，双引号括起含空格的参数，
+// 引号内的 "" 表示一个引号。反斜杠只在引号前有特殊含义：2n 个反斜杠加引号为 n 个反斜杠，
+// 引号照常起止；2n+1 个为 n 个反斜杠加一个字面引号。其余反斜杠和单引号都是普通字符
+fn split_windows(line: &str) -> Result<Vec<String>, String> {
+    let mut args = Vec::new();
+    let mut current = None::<String>;
+    let mut quoted = false;
+    let mut chars = line.chars().peekable();
+    while let Some(c) = chars.next() {
+        match c {
+            '\\' => {
+                let mut backslashes = 1;
+                while chars.next_if_eq(&'\\').is_some() {
+                    backslashes += 1;
+                }
+                let arg = current.get_or_insert_with(String::new);
+                if chars.peek() == Some(&'"') {
+                    arg.push_str(&"\\".repeat(backslashes / 2));
+                    if backslashes % 2 == 1 {
+                        arg.push('"');
+                        chars.next();
+                    }
+                } else {
+                    arg.push_str(&"\\".repeat(backslashes));
+                }
+            }
+            '"' if quoted && chars.next_if_eq(&'"').is_some() => {
+                current.get_or_insert_with(String::new).push('"');
+            }
+            '"' => {
+                quoted = !quoted;
+                current.get_or_insert_with(String::new);
+            }
+            c if c.is_whitespace() && !quoted => args.extend(current.take()),
+            c => current.get_or_insert_with(String::new).push(c),
+        }
+    }
+    if quoted {
+        return Err("Unterminated double quote".to_string());
+    }
+    args.extend(current);
+    Ok(args)
+}
+
+// 完整的命令行，用于日志
+pub fn join(command: &str, args: &[&str]) -> String {
+    std::iter::once(command)
+        .chain(args.iter().copied())
+        .map(quote)
+        .collect::<Vec<_>>()
+        .join(" ")
+}
+
+// 转义后的参数列表，用于显示
+pub fn join_args(args: &[String]) -> String {
+    args.iter()
+        .map(|arg| quote(arg))
+        .collect::<Vec<_>>()
+        .join(" ")
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    const HOSTILE: &[&str] = &[
+        "Movie; rm -rf ~.mkv",
+        "Movie && del /q C:\\*.mkv",
+        "a | b > c < d",
+        "$(reboot) `id` $HOME",
+        "%PATH% !USERPROFILE! ^caret",
+        "it's \"quoted\"",
+        "trailing backslash\\",
+        "C:\\Media\\Film (2019)\\Film.mkv",
+        "back\\\\\"slash quote",
+        "line\nbreak",
+        "",
+        "   ",
+    ];
+
+    // 按 POSIX shell 的规则切分只包含单引号、反斜杠和空格的命令行
+    fn split_posix(line: &str) -> Vec<String> {
+        let mut args = Vec::new();
+        let mut current = None::<String>;
+        let mut quoted = false;
+        let mut chars = line.chars();
+        while let Some(c) = chars.next() {
+            match c {
+                '\\' if !quoted => current
+                    .get_or_insert_with(String::new)
+                    .push(chars.next().expect("dangling backslash")),
+                '\'' => {
+                    quoted = !quoted;
+                    current.get_or_insert_with(String::new);
+                }
+                c if !quoted && c == ' ' => args.extend(current.take()),
+                c => current.get_or_insert_with(String::new).push(c),
+            }
+        }
+        assert!(!quoted, "unterminated quote in {line:?}");
+        args.extend(current);
+        args
+    }
+
+    // 模拟 cmd.exe：^ 之外不能出现未转义的特殊字符，去掉 ^ 后得到传给程序的命令行
+    fn unescape_cmd(line: &str) -> String {
+        let mut result = String::new();
+        let mut chars = line.chars();
+        while let Some(c) = chars.next() {
+            match c {
+                '^' => result.push(chars.next().expect("dangling caret")),
+                c if CMD_SPECIAL.contains(c) => panic!("unescaped {c:?} in {line:?}"),
+                c => result.push(c),
+            }
+        }
+        result
+    }
+
+    // 按 MSVC 运行库的规则切分命令行
+    fn split_msvc(line: &str) -> Vec<String> {
+        let mut args = Vec::new();
+        let mut current = None::<String>;
+        let mut quoted = false;
+        let mut chars = line.chars().peekable();
+        while let Some(c) = chars.next() {
+            match c {
+                '\\' => {
+                    let mut backslashes = 1;
+                    while chars.peek() == Some(&'\\') {
+                        chars.next();
+                        backslashes += 1;
+                    }
+                    let arg = current.get_or_insert_with(String::new);
+                    if chars.peek() == Some(&'"') {
+                        arg.push_str(&"\\".repeat(backslashes / 2));
+                        if backslashes % 2 == 1 {
+                            arg.push(chars.next().unwrap_or_default());
+                        }
+                    } else {
+                        arg.push_str(&"\\".repeat(backslashes));
+                    }
+                }
+                '"' => {
+                    quoted = !quoted;
+                    current.get_or_insert_with(String::new);
+                }
+                ' ' if !quoted => args.extend(current.take()),
+                c => current.get_or_insert_with(String::new).push(c),
+            }
+        }
+        assert!(!quoted, "unterminated quote in {line:?}");
+        args.extend(current);
+        args
+    }
+
+    fn expected(args: &[&str]) -> Vec<String> {
+        std::iter::once("ffmpeg")
+            .chain(args.iter().copied())
+            .map(ToString::to_string)
+            .collect()
+    }
+
+    #[test]
+    fn posix_quoting_keeps_each_argument_intact() {
+        let line = std::iter::once("ffmpeg")
+            .chain(HOSTILE.iter().copied())
+            .map(quote_posix)
+            .collect::<Vec<_>>()
+            .join(" ");
+        assert_eq!(split_posix(&line), expected(HOSTILE));
+    }
+
+    #[test]
+    fn windows_quoting_survives_cmd_and_msvc_parsing() {
+        let hostile = HOSTILE
+            .iter()
+            .copied()
+            .filter(|arg| !arg.contains('\n'))
+            .collect::<Vec<_>>();
+        let line = std::iter::once("ffmpeg")
+            .chain(hostile.iter().copied())
+            .map(quote_windows)
+            .collect::<Vec<_>>()
+            .join(" ");
+        assert_eq!(split_msvc(&unescape_cmd(&line)), expected(&hostile));
+    }
+
+    #[test]
+    fn windows_line_breaks_cannot_end_the_command() {
+        let quoted = quote_windows("a\r\n& del b");
+        assert!(!quoted.contains(['\r', '\n']));
+        assert_eq!(split_msvc(&unescape_cmd(&quoted)), ["a  & del b"]);
+    }
+
+    #[test]
+    fn hostile_file_names_are_quoted() {
+        assert_eq!(quote_posix("a; rm -rf ~"), "'a; rm -rf ~'");
+        assert_eq!(quote_posix("it's"), r"'it'\''s'");
+        assert_eq!(quote_windows("a && del b"), r#"^"a ^&^& del b^""#);
+        assert_eq!(quote_windows("50%"), r#"^"50^%^""#);
+    }
+
+    #[test]
+    fn split_reverses_posix_quoting() {
+        let line = HOSTILE
+            .iter()
+            .map(|arg| quote_posix(arg))
+            .collect::<Vec<_>>()
+            .join(" ");
+        assert_eq!(split_shell(&line).unwrap(), HOSTILE);
+        assert_eq!(
+            split_shell(r#"--a "b c" d\ e "f\"g" 'h\i'"#).unwrap(),
+            ["--a", "b c", "d e", "f\"g", "h\\i"]
+        );
+        assert!(split_shell("'open").is_err());
+        assert!(split_shell("\"open").is_err());
+        assert!(split_shell("trailing\\").is_err());
+        assert!(split_shell("  ").unwrap().is_empty());
+    }
+
+    #[test]
+    fn split_keeps_windows_paths() {
+        assert_eq!(
+            split_windows(r#"C:\tools\notify.exe --title "Done" {output}"#).unwrap(),
+            [r"C:\tools\notify.exe", "--title", "Done", "{output}"]
+        );
+        assert_eq!(
+            split_windows(r#""C:\Program Files\Notify\notify.exe" "D:\Media\\" it's"#).unwrap(),
+            [r"C:\Program Files\Notify\notify.exe", r"D:\Media\", "it's"]
+        );
+        assert_eq!(
+            split_windows(r#"a\\\"b "c""d" \\server\share"#).unwrap(),
+            [r#"a\"b"#, r#"c"d"#, r"\\server\share"]
+        );
+        assert!(split_windows("\"open").is_err());
+        assert!(split_windows("  ").unwrap().is_empty());
+    }
+
+    #[test]
+    fn split_reverses_windows_quoting() {
+        let hostile = HOSTILE
+            .iter()
+            .copied()
+            .filter(|arg| !arg.contains('\n'))
+            .collect::<Vec<_>>();
+        let line = hostile
+            .iter()
+            .map(|arg| unescape_cmd(&quote_windows(arg)))
+            .collect::<Vec<_>>()
+            .join(" ");
+        assert_eq!(split_windows(&line).unwrap(), hostile);
+    }
+
+    // 每个测试使用单独的临时目录
+    fn scratch_dir(name: &str) -> PathBuf {
+        let dir = std::env::temp_dir().join(format!(
+            "rebottle-command-line-{name}-{}",
+            std::process::id()
+        ));
+        let _ = std::fs::remove_dir_all(&dir);
+        std::fs::create_dir_all(&dir).unwrap();
+        dir
+    }
+
+    // 各平台都可能出现的文件名：空格、shell 和 cmd.exe 的特殊字符、引号、Unicode
+    const EXOTIC_NAMES: &[&str] = &[
+        "Movie; rm -rf ~.mkv",
+        "Movie && del q.mkv",
+        "a | b > c < d.mkv",
+        "$(reboot) `id` $HOME.mkv",
+        "%PATH% !USERPROFILE! ^caret.mkv",
+        "it's \"quoted\".mkv",
+        "Film (2019) [2160p].mkv",
+        "电影 – Été 🎬.mkv",
+        "  leading and trailing spaces  .mkv",
+        "-i dash first.mkv",
+    ];
+
+    #[cfg(unix)]
+    #[test]
+    fn arguments_reach_the_program_unchanged() {
+        let args = HOSTILE
+            .iter()
+            .chain(EXOTIC_NAMES)
+            .copied()
+            .collect::<Vec<_>>();
+        let mut printf_args = vec!["%s\\0"];
+        printf_args.extend(&args);
+        let output = command("printf", &printf_args).output().unwrap();
+        assert!(output.status.success());
+        let received = String::from_utf8(output.stdout).unwrap();
+        assert_eq!(received.split_terminator('\0').collect::<Vec<_>>(), args);
+    }
+
+    #[cfg(unix)]
+    #[test]
+    fn exotic_file_names_are_opened_directly() {
+        let dir = scratch_dir("names");
+        for name in EXOTIC_NAMES {
+            let path = dir.join(name);
+            std::fs::write(&path, name).unwrap();
+            let path = path.to_string_lossy();
+            let output = command("cat", &["--", &path]).output().unwrap();
+            assert!(output.status.success(), "cat failed for {name:?}");
+            assert_eq!(String::from_utf8_lossy(&output.stdout), *name);
+        }
+        let _ = std::fs::remove_dir_all(dir);
+    }
+
+    #[test]
+    fn wrapped_tools_are_found_by_path_extension() {
+        let dir = scratch_dir("pathext");
+        let extensions = [".EXE".to_string(), ".CMD".to_string()];
+        let paths = [dir.join("missing"), dir.clone()];
+        std::fs::write(dir.join("PgsToSrt.cmd"), "").unwrap();
+        assert_eq!(
+            find_program("PgsToSrt", &paths, &extensions),
+            Some(dir.join("PgsToSrt.cmd"))
+        );
+        // PATHEXT 中靠前的扩展名优先
+        std::fs::write(dir.join("PgsToSrt.exe"), "").unwrap();
+        assert_eq!(
+            find_program("PgsToSrt", &paths, &extensions),
+            Some(dir.join("PgsToSrt.exe"))
+        );
+        std::fs::write(dir.join("tool.v2.bat"), "").unwrap();
+        assert_eq!(
+            find_program("tool.v2.bat", &paths, &extensions),
+            Some(dir.join("tool.v2.bat"))
+        );
+        assert_eq!(find_program("dovi_tool", &paths, &extensions), None);
+        // Windows 以外没有 PATHEXT，按原名查找
+        std::fs::write(dir.join("dovi_tool"), "").unwrap();
+        assert_eq!(
+            find_program("dovi_tool", &paths, &[]),
+            Some(dir.join("dovi_tool"))
+        );
+        let _ = std::fs::remove_dir_all(dir);
+    }
+
+    #[test]
+    fn programs_with_a_directory_are_not_searched() {
+        let program = Path::new("tools").join("ffmpeg");
+        let program = program.to_string_lossy();
+        assert_eq!(resolve(&program), PathBuf::from(program.as_ref()));
+    }
+
+    #[test]
+    fn plain_arguments_are_unchanged() {
+        let args = ["-i", "/tmp/movie.mkv", "-map", "0:a:1", "-b:a", "768k"];
+        assert_eq!(
+            join("ffmpeg", &args),
+            "ffmpeg -i /tmp/movie.mkv -map 0:a:1 -b:a 768k"
+        );
+        assert_eq!(quote_windows("0:v:0"), "0:v:0");
+        assert_eq!(quote_posix(""), "''");
+    }
+}
@@ -0,0 +1,197 @@
+use serde::Serialize;
+use serde::de::DeserializeOwned;
+use serde_json::{Value, json};
+use std::path::Path;
+use std::sync::Mutex;
+
+use crate::session;
+
+// 迁移函数：把版本 N 的数据转换为版本 N + 1
+pub type Migration = fn(Value) -> Result<Value, String>;
+
+// 配置文件的结构版本；文件保存为 {"version": N, "data": ...}
+// 没有版本号的旧文件视为版本 1，migrations[i] 把版本 i + 1 升级为 i + 2
+pub struct Schema {
+    pub file: &'static str,
+    // 用于错误信息，如 "session"、"queues"
+    pub name: &'static str,
+    pub migrations: &'static [Migration],
+}
+
+impl Schema {
+    pub fn version(&self) -> u64 {
+        self.migrations.len() as u64 + 1
+    }
+}
+
+// 加载配置时的提示（迁移、备份、无法读取），启动后显示在处理日志中
+static NOTES: Mutex<Vec<String>> = Mutex::new(Vec::new());
+
+fn note(message: String) {
+    if let Ok(mut notes) = NOTES.lock() {
+        notes.push(message);
+    }
+}
+
+pub fn take_notes() -> Vec<String> {
+    NOTES
+        .lock()
+        .map(|mut notes| std::mem::take(&mut *notes))
+        .unwrap_or_default()
+}
+
+// 在修改前保留原文件，如 session.json.v1.bak；返回用于提示的说明
+fn backup(path: &Path, suffix: &str) -> String {
+    let name = format!(
+        "{}.{suffix}.bak",
+        path.file_name().unwrap_or_default().to_string_lossy()
+    );
+    match std::fs::copy(path, path.with_file_name(&name)) {
+        Ok(_) => format!("backup: {name}"),
+        Err(e) => format!("backup failed: {e}"),
+    }
+}
+
+// 拆出版本号和数据
+fn unwrap_versioned(value: Value) -> (u64, Value) {
+    match value {
+        Value::Object(mut object) if object.len() == 2 && object.contains_key("data") => {
+            match object.get("version").and_then(Value::as_u64) {
+                Some(version) => (version, object.remove("data").unwrap_or_default()),
+                None => (1, Value::Object(object)),
+            }
+        }
+        value => (1, value),
+    }
+}
+
+fn migrate(schema: &Schema, version: u64, mut data: Value) -> Result<Value, String> {
+    for (from, migration) in schema
+        .migrations
+        .iter()
+        .enumerate()
+        .skip(version as usize - 1)
+    {
+        data = migration(data)
+            .map_err(|e| format!("migration from version {} failed: {e}", from + 1))?;
+    }
+    Ok(data)
+}
+
+// 读取并按需迁移配置；迁移前、以及文件无法读取或来自更新的版本时，先备份原文件，
+// 避免之后保存时静默丢失设置。文件不存在或无法读取时使用默认值
+pub fn load<T: DeserializeOwned + Default>(schema: &Schema) -> T {
+    let Some(path) = session::config_dir().map(|dir| dir.join(schema.file)) else {
+        return T::default();
+    };
+    let Ok(text) = std::fs::read_to_string(&path) else {
+        return T::default();
+    };
+
+    let result = serde_json::from_str::<Value>(&text)
+        .map_err(|e| e.to_string())
+        .and_then(|value| {
+            let (version, data) = unwrap_versioned(value);
+            let current = schema.version();
+            let data = if version < current {
+                let backup = backup(&path, &format!("v{version}"));
+                note(format!(
+                    "⚙️ Upgraded {} settings from version {version} to {current} ({backup})",
+                    schema.name
+                ));
+                migrate(schema, version.max(1), data)?
+            } else {
+                if version > current {
+                    let backup = backup(&path, &format!("v{version}"));
+                    note(format!(
+                        "⚠️ {} settings were saved by a newer version of Rebottle, unknown settings will be lost ({backup})",
+                        schema.name
+                    ));
+                }
+                data
+            };
+            serde_json::from_value(data).map_err(|e| e.to_string())
+        });
+
+    result.unwrap_or_else(|e| {
+        let backup = backup(&path, "invalid");
+        note(format!(
+            "⚠️ Could not read {} settings, using defaults: {e} ({backup})",
+            schema.name
+        ));
+        T::default()
+    })
+}
+
+// 保存为当前版本
+pub fn save<T: Serialize>(schema: &Schema, value: &T) -> Result<(), String> {
+    let dir = session::config_dir().ok_or("Config directory not available")?;
+    std::fs::create_dir_all(&dir).map_err(|e| format!("Failed to create config directory: {e}"))?;
+    let data = serde_json::to_value(value)
+        .and_then(|data| {
+            serde_json::to_string_pretty(&json!({ "version": schema.version(), "data": data }))
+        })
+        .map_err(|e| format!("Failed to serialize {}: {e}", schema.name))?;
+    std::fs::write(dir.join(schema.file), data)
+        .map_err(|e| format!("Failed to save {}: {e}", schema.name))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn append(data: Value, value: u64) -> Result<Value, String> {
+        let mut data = data;
+        data.as_array_mut()
+            .ok_or("not an array")?
+            .push(Value::from(value));
+        Ok(data)
+    }
+
+    fn to_version_2(data: Value) -> Result<Value, String> {
+        append(data, 2)
+    }
+
+    fn to_version_3(data: Value) -> Result<Value, String> {
+        append(data, 3)
+    }
+
+    const SCHEMA: Schema = Schema {
+        file: "test.json",
+        name: "test",
+        migrations: &[to_version_2, to_version_3],
+    };
+
+    #[test]
+    fn versions_are_read_from_the_envelope() {
+        assert_eq!(SCHEMA.version(), 3);
+        assert_eq!(
+            unwrap_versioned(json!({ "version": 2, "data": { "a": 1 } })),
+            (2, json!({ "a": 1 }))
+        );
+        // 没有版本号的旧文件、格式不符的版本号或多余的键都按版本 1 的数据处理
+        for value in [
+            json!({ "a": 1 }),
+            json!({ "version": "2", "data": 1 }),
+            json!({ "version": 2, "data": 1, "extra": true }),
+            json!({ "data": 1, "other": 2 }),
+            json!([1, 2]),
+            Value::Null,
+        ] {
+            assert_eq!(unwrap_versioned(value.clone()), (1, value));
+        }
+    }
+
+    #[test]
+    fn migrations_run_from_the_saved_version() {
+        assert_eq!(migrate(&SCHEMA, 1, json!([1])), Ok(json!([1, 2, 3])));
+        assert_eq!(migrate(&SCHEMA, 2, json!([1, 2])), Ok(json!([1, 2, 3])));
+        // 当前版本和更新的版本不需要迁移
+        assert_eq!(migrate(&SCHEMA, 3, json!([1, 2, 3])), Ok(json!([1, 2, 3])));
+        assert_eq!(migrate(&SCHEMA, 4, json!({})), Ok(json!({})));
+        assert_eq!(
+            migrate(&SCHEMA, 2, json!({})),
+            Err("migration from version 2 failed: not an array".to_string())
+        );
+    }
+}
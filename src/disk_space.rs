@@ -0,0 +1,61 @@
+use std::path::Path;
+
+use crate::execute_command;
+
+// 文件夹本身或最近的已存在的上级目录
+fn existing(folder: &Path) -> Option<&Path> {
+    folder.ancestors().find(|path| path.exists())
+}
+
+// 文件夹所在磁盘的可用空间（字节）。文件夹还不存在时查询最近的已存在的上级目录；
+// 无法查询时（工具缺失、网络路径等）返回 None
+pub fn available(folder: &Path) -> Option<u64> {
+    let path = existing(folder)?.to_string_lossy();
+    if cfg!(windows) {
+        // PowerShell 的单引号字符串中 ' 需要写两次
+        let script = format!(
+            "[System.IO.DriveInfo]::new([System.IO.Path]::GetPathRoot('{}')).AvailableFreeSpace",
+            path.replace('\'', "''")
+        );
+        let output = execute_command("powershell", &["-NoProfile", "-Command", &script]).ok()?;
+        String::from_utf8_lossy(&output.stdout).trim().parse().ok()
+    } else {
+        // -P 保证每个文件系统只占一行，-k 以 1024 字节为单位；第 4 列为可用空间
+        let output = execute_command("df", &["-Pk", &path]).ok()?;
+        let stdout = String::from_utf8_lossy(&output.stdout);
+        let kilobytes = stdout
+            .lines()
+            .nth(1)?
+            .split_whitespace()
+            .nth(3)?
+            .parse::<u64>()
+            .ok()?;
+        Some(kilobytes * 1024)
+    }
+}
+
+// 两个文件夹是否在同一个磁盘上，这时需要的空间要合计
+pub fn same_volume(a: &Path, b: &Path) -> bool {
+    let (Some(a), Some(b)) = (existing(a), existing(b)) else {
+        return false;
+    };
+    #[cfg(unix)]
+    {
+        use std::os::unix::fs::MetadataExt;
+        match (a.metadata(), b.metadata()) {
+            (Ok(a), Ok(b)) => a.dev() == b.dev(),
+            _ => false,
+        }
+    }
+
+    // 按盘符或 UNC 共享比较
+    #[cfg(not(unix))]
+    {
+        let root = |path: &Path| {
+            path.components()
+                .next()
+                .map(|root| root.as_os_str().to_string_lossy().to_lowercase())
+        };
+        root(a) == root(b)
+    }
+}
@@ -0,0 +1,101 @@
+use std::collections::HashMap;
+use std::path::{Path, PathBuf};
+use std::time::{Duration, Instant};
+
+// 批处理的计时：按已完成文件的源文件大小和用时测量吞吐量（字节/秒），
+// 估计队列中每个待处理文件还需多久完成
+#[derive(Debug, Clone, Default)]
+pub struct BatchTiming {
+    // 本批次待处理的文件（按处理顺序），完成或失败后移除
+    pending: Vec<PathBuf>,
+    // 正在处理的文件及开始时间，同时处理多个文件时有多项
+    current: Vec<(PathBuf, Instant)>,
+    // 同时处理的文件数
+    jobs: usize,
+    // 本批次已完成文件的源文件字节数和用时（秒）
+    bytes_done: u64,
+    seconds_done: f64,
+}
+
+impl BatchTiming {
+    pub fn new(files: Vec<PathBuf>, jobs: usize) -> Self {
+        Self {
+            pending: files,
+            jobs: jobs.max(1),
+            ..Self::default()
+        }
+    }
+
+    pub fn start(&mut self, path: PathBuf) {
+        self.current.push((path, Instant::now()));
+    }
+
+    pub fn is_current(&self, path: &Path) -> bool {
+        self.current.iter().any(|(current, _)| current == path)
+    }
+
+    // 文件处理成功，计入吞吐量；返回更新后的吞吐量
+    pub fn finish(&mut self, path: &Path, size: u64) -> Option<f64> {
+        if let Some(index) = self.current.iter().position(|(current, _)| current == path) {
+            let (current, started) = self.current.remove(index);
+            self.bytes_done += size;
+            self.seconds_done += started.elapsed().as_secs_f64();
+            self.pending.retain(|pending| *pending != current);
+        }
+        self.measured_rate()
+    }
+
+    // 失败的文件不计入吞吐量
+    pub fn remove(&mut self, path: &Path) {
+        self.current.retain(|(current, _)| current != path);
+        self.pending.retain(|pending| pending != path);
+    }
+
+    fn measured_rate(&self) -> Option<f64> {
+        (self.seconds_done > 0.0 && self.bytes_done > 0)
+            .then(|| self.bytes_done as f64 / self.seconds_done)
+    }
+
+    // 本批次测量的吞吐量，还没有文件完成时使用上一次批处理的结果
+    pub fn rate(&self, previous: Option<f64>) -> Option<f64> {
+        self.measured_rate().or(previous).filter(|rate| *rate > 0.0)
+    }
+
+    // 每个待处理文件距离完成的预计时间：文件按顺序交给最先空闲的处理位置（同时处理的文件数），
+    // 正在处理的文件减去已用的时间
+    pub fn estimates(
+        &self,
+        rate: f64,
+        size_of: impl Fn(&Path) -> u64,
+    ) -> HashMap<PathBuf, Duration> {
+        let mut slots = vec![0.0_f64; self.jobs.max(1)];
+        self.pending
+            .iter()
+            .map(|path| {
+                let mut seconds = size_of(path) as f64 / rate;
+                if let Some((_, started)) = self.current.iter().find(|(current, _)| current == path)
+                {
+                    seconds = (seconds - started.elapsed().as_secs_f64()).max(0.0);
+                }
+                let slot = (0..slots.len())
+                    .min_by(|a, b| slots[*a].total_cmp(&slots[*b]))
+                    .unwrap_or(0);
+                slots[slot] += seconds;
+                (path.clone(), Duration::from_secs_f64(slots[slot]))
+            })
+            .collect()
+    }
+}
+
+// 例如 "2h 05m"、"12m"、"< 1m"
+pub fn format_duration(duration: Duration) -> String {
+    if duration.as_secs() < 60 {
+        return "< 1m".to_string();
+    }
+    let minutes = duration.as_secs().div_ceil(60);
+    if minutes < 60 {
+        format!("{minutes}m")
+    } else {
+        format!("{}h {:02}m", minutes / 60, minutes % 60)
+    }
+}
@@ -0,0 +1,261 @@
+use serde::{Deserialize, Serialize};
+use std::collections::HashMap;
+use std::path::{Path, PathBuf};
+use std::sync::Mutex;
+
+use crate::pipeline::normalize_path;
+use crate::{config, format_utc_now, probe};
+
+const SCHEMA: config::Schema = config::Schema {
+    file: "history.json",
+    name: "conversion history",
+    migrations: &[],
+};
+
+// 输出时长与预期相差超过这个秒数（或时长的 1%，取较大者）时报告为不一致；
+// 裁剪起点会向前对齐到关键帧，输出通常比预期略长
+const DURATION_TOLERANCE: f64 = 5.0;
+
+// 一次成功的转换：源文件、写出的文件和输出应有的时长（裁剪或拼接后的长度）
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct Conversion {
+    pub source: PathBuf,
+    pub outputs: Vec<PathBuf>,
+    pub duration: Option<f64>,
+    pub converted_at: String,
+}
+
+// 转换历史，保存在配置目录的 history.json 中，用于把输出文件夹中的 MP4 对应回源文件
+#[derive(Debug, Clone, Default, Serialize, Deserialize)]
+#[serde(default)]
+pub struct History {
+    conversions: Vec<Conversion>,
+}
+
+// 同时处理多个文件时按顺序读写
+static LOCK: Mutex<()> = Mutex::new(());
+
+impl History {
+    pub fn load() -> Self {
+        config::load(&SCHEMA)
+    }
+}
+
+// 记录一次成功的转换
+pub fn record(source: &Path, outputs: &[PathBuf], duration: Option<f64>) -> Result<(), String> {
+    let _guard = LOCK.lock().unwrap_or_else(|poisoned| poisoned.into_inner());
+    let mut history = History::load();
+    history.conversions.push(Conversion {
+        source: normalize_path(source),
+        outputs: outputs
+            .iter()
+            .map(|output| normalize_path(output))
+            .collect(),
+        duration,
+        converted_at: format_utc_now(),
+    });
+    config::save(&SCHEMA, &history)
+}
+
+// 输出文件夹中一个需要注意的 MP4
+#[derive(Debug, Clone, PartialEq)]
+pub enum Finding {
+    // 历史和队列中都找不到对应的源文件
+    Orphan,
+    // 源文件已不存在
+    SourceMissing(PathBuf),
+    // 同一源文件在文件夹中还有更新的转换结果
+    Duplicate {
+        source: PathBuf,
+        newer: PathBuf,
+    },
+    DurationMismatch {
+        source: PathBuf,
+        expected: f64,
+        actual: f64,
+    },
+    Unreadable(String),
+}
+
+fn file_name(path: &Path) -> String {
+    path.file_name()
+        .unwrap_or_default()
+        .to_string_lossy()
+        .to_string()
+}
+
+impl std::fmt::Display for Finding {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self {
+            Finding::Orphan => write!(f, "orphan, no known source"),
+            Finding::SourceMissing(source) => {
+                write!(f, "source {} no longer exists", source.to_string_lossy())
+            }
+            Finding::Duplicate { source, newer } => write!(
+                f,
+                "older conversion of {}, {} is newer",
+                file_name(source),
+                file_name(newer)
+            ),
+            Finding::DurationMismatch {
+                source,
+                expected,
+                actual,
+            } => write!(
+                f,
+                "{actual:.1}s long, {} should give {expected:.1}s",
+                file_name(source)
+            ),
+            Finding::Unreadable(error) => write!(f, "could not be read: {error}"),
+        }
+    }
+}
+
+// 对一个输出文件夹的核对结果
+#[derive(Debug, Clone, Default)]
+pub struct Reconciliation {
+    pub folder: PathBuf,
+    // 文件夹（含子文件夹）中的 MP4 数量，以及其中对应到源文件且没有问题的数量
+    pub total: usize,
+    pub matched: usize,
+    pub findings: Vec<(PathBuf, Finding)>,
+}
+
+impl Reconciliation {
+    pub fn count(&self, kind: fn(&Finding) -> bool) -> usize {
+        self.findings
+            .iter()
+            .filter(|(_, finding)| kind(finding))
+            .count()
+    }
+}
+
+// 递归列出文件夹中的 MP4；无法读取的子文件夹跳过
+fn scan_mp4_files(folder: &Path, files: &mut Vec<PathBuf>) -> Result<(), String> {
+    let entries = std::fs::read_dir(folder).map_err(|e| e.to_string())?;
+    for entry in entries.flatten() {
+        let path = entry.path();
+        if path.is_dir() {
+            let _ = scan_mp4_files(&path, files);
+        } else if path
+            .extension()
+            .is_some_and(|extension| extension.eq_ignore_ascii_case("mp4"))
+        {
+            files.push(path);
+        }
+    }
+    Ok(())
+}
+
+// 历史中没有记录的输出按文件名对应到队列中的源文件：输出名为 "<源文件名>_…" 或 "<源文件名>.vN_…"，
+// 多个源文件都符合时取文件名最长的一个
+fn queued_source<'a>(
+    output: &Path,
+    queued: &'a [(PathBuf, Option<f64>)],
+) -> Option<&'a (PathBuf, Option<f64>)> {
+    let stem = output.file_stem()?.to_string_lossy().to_string();
+    queued
+        .iter()
+        .filter(|(source, _)| {
+            let source_stem = source.file_stem().unwrap_or_default().to_string_lossy();
+            stem.strip_prefix(source_stem.as_ref())
+                .is_some_and(|rest| rest.starts_with(['_', '.']))
+        })
+        .max_by_key(|(source, _)| source.file_stem().unwrap_or_default().len())
+}
+
+// 扫描输出文件夹，按转换历史（其次按队列中的文件名）把每个 MP4 对应回源文件，
+// 找出没有源文件的孤立文件、同一源文件的重复转换和时长与预期不一致的输出。
+// queued 为队列中的源文件及其时长（裁剪或拼接的文件没有可比较的时长）
+pub fn reconcile(
+    folder: &Path,
+    queued: &[(PathBuf, Option<f64>)],
+) -> Result<Reconciliation, String> {
+    let mut files = Vec::new();
+    scan_mp4_files(folder, &mut files)?;
+    files.sort();
+
+    let history = History::load();
+    // 输出文件 → 最近一次写出它的转换
+    let mut by_output = HashMap::new();
+    for (index, conversion) in history.conversions.iter().enumerate() {
+        for output in &conversion.outputs {
+            by_output.insert(output.clone(), index);
+        }
+    }
+    let known = files
+        .iter()
+        .map(|file| by_output.get(&normalize_path(file)).copied())
+        .collect::<Vec<_>>();
+    // 每个源文件在这个文件夹中最近一次的转换
+    let mut latest: HashMap<&Path, usize> = HashMap::new();
+    for index in known.iter().flatten() {
+        let source = history.conversions[*index].source.as_path();
+        let entry = latest.entry(source).or_insert(*index);
+        *entry = (*entry).max(*index);
+    }
+
+    let mut reconciliation = Reconciliation {
+        folder: folder.to_path_buf(),
+        total: files.len(),
+        ..Reconciliation::default()
+    };
+    for (file, index) in files.into_iter().zip(known) {
+        let (source, expected) = match index {
+            Some(index) => {
+                let conversion = &history.conversions[index];
+                let newest = latest[conversion.source.as_path()];
+                if newest != index {
+                    let newer = history.conversions[newest]
+                        .outputs
+                        .first()
+                        .cloned()
+                        .unwrap_or_default();
+                    reconciliation.findings.push((
+                        file,
+                        Finding::Duplicate {
+                            source: conversion.source.clone(),
+                            newer,
+                        },
+                    ));
+                    continue;
+                }
+                (conversion.source.clone(), conversion.duration)
+            }
+            None => match queued_source(&file, queued) {
+                Some((source, duration)) => (source.clone(), *duration),
+                None => {
+                    reconciliation.findings.push((file, Finding::Orphan));
+                    continue;
+                }
+            },
+        };
+        if !source.exists() {
+            reconciliation
+                .findings
+                .push((file, Finding::SourceMissing(source)));
+            continue;
+        }
+        let Some(expected) = expected else {
+            reconciliation.matched += 1;
+            continue;
+        };
+        match probe::quick_probe(&file).map(|info| info.duration) {
+            Ok(Some(actual))
+                if (actual - expected).abs() > DURATION_TOLERANCE.max(expected * 0.01) =>
+            {
+                reconciliation.findings.push((
+                    file,
+                    Finding::DurationMismatch {
+                        source,
+                        expected,
+                        actual,
+                    },
+                ));
+            }
+            Ok(_) => reconciliation.matched += 1,
+            Err(e) => reconciliation.findings.push((file, Finding::Unreadable(e))),
+        }
+    }
+    Ok(reconciliation)
+}
@@ -0,0 +1,138 @@
+use std::path::{Path, PathBuf};
+use std::process::Stdio;
+use std::time::Duration;
+use tokio::io::AsyncWriteExt;
+
+use crate::{build_async_command, processes, session};
+
+const HOOKS_DIR: &str = "hooks.d";
+// 单个脚本的最长运行时间
+const HOOK_TIMEOUT: Duration = Duration::from_secs(120);
+
+// 处理过程中的生命周期事件，对应 hooks.d 中的脚本名
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum HookEvent {
+    PreFile,
+    PostFile,
+    PostBatch,
+}
+
+impl HookEvent {
+    fn name(&self) -> &'static str {
+        match self {
+            HookEvent::PreFile => "pre-file",
+            HookEvent::PostFile => "post-file",
+            HookEvent::PostBatch => "post-batch",
+        }
+    }
+}
+
+fn hooks_dir() -> Option<PathBuf> {
+    session::config_dir().map(|dir| dir.join(HOOKS_DIR))
+}
+
+#[cfg(unix)]
+fn is_executable(path: &Path) -> bool {
+    use std::os::unix::fs::PermissionsExt;
+    std::fs::metadata(path)
+        .is_ok_and(|meta| meta.is_file() && meta.permissions().mode() & 0o111 != 0)
+}
+
+#[cfg(not(unix))]
+fn is_executable(path: &Path) -> bool {
+    path.is_file()
+        && path.extension().is_some_and(|extension| {
+            ["exe", "bat", "cmd"]
+                .iter()
+                .any(|known| extension.eq_ignore_ascii_case(known))
+        })
+}
+
+// 事件对应的脚本：文件名为事件名本身，或以 "事件名." / "事件名-" 开头（如 post-file.sh、post-file-10-notify），按名称排序
+fn scripts_for(event: HookEvent) -> Vec<PathBuf> {
+    let Some(entries) = hooks_dir().and_then(|dir| std::fs::read_dir(dir).ok()) else {
+        return Vec::new();
+    };
+    let name = event.name();
+    let mut scripts = entries
+        .filter_map(|entry| entry.ok().map(|entry| entry.path()))
+        .filter(|path| {
+            let file_name = path.file_name().unwrap_or_default().to_string_lossy();
+            file_name == name
+                || file_name.starts_with(&format!("{name}."))
+                || file_name.starts_with(&format!("{name}-"))
+        })
+        .filter(|path| is_executable(path))
+        .collect::<Vec<_>>();
+    scripts.sort();
+    scripts
+}
+
+// 依次运行事件对应的脚本，JSON 负载写入 stdin，返回要记录的日志
+pub async fn run(event: HookEvent, payload: serde_json::Value) -> Vec<String> {
+    let mut logs = Vec::new();
+    let mut payload = payload;
+    payload["event"] = serde_json::json!(event.name());
+    let input = payload.to_string();
+
+    for script in scripts_for(event) {
+        let script_name = script
+            .file_name()
+            .unwrap_or_default()
+            .to_string_lossy()
+            .to_string();
+        logs.push(format!("$ hook {script_name} ({})", event.name()));
+
+        // 与其他外部工具一样启动和登记：取消处理或 Ctrl+C 时一起结束
+        let child = build_async_command(&script.to_string_lossy(), &[])
+            .stdin(Stdio::piped())
+            .stdout(Stdio::piped())
+            .stderr(Stdio::piped())
+            .spawn();
+        let mut child = match child {
+            Ok(child) => child,
+            Err(e) => {
+                logs.push(format!("Error: Failed to run hook {script_name}: {e}"));
+                continue;
+            }
+        };
+
+        let running = processes::register(&child);
+
+        // 写入 stdin 也计入超时：不读取 stdin 的脚本不会让写入一直等待
+        let input = &input;
+        let finished = async move {
+            if let Some(mut stdin) = child.stdin.take() {
+                // 脚本可能不读取 stdin，写入失败不影响运行
+                let _ = stdin.write_all(input.as_bytes()).await;
+            }
+            child.wait_with_output().await
+        };
+        match tokio::time::timeout(HOOK_TIMEOUT, finished).await {
+            Ok(Ok(output)) => {
+                for line in String::from_utf8_lossy(&output.stdout).lines() {
+                    logs.push(format!("[{script_name}] {line}"));
+                }
+                for line in String::from_utf8_lossy(&output.stderr).lines() {
+                    logs.push(format!("[{script_name}] Error: {line}"));
+                }
+                if !output.status.success() {
+                    logs.push(format!(
+                        "Error: Hook {script_name} exited with {}",
+                        output.status
+                    ));
+                }
+            }
+            Ok(Err(e)) => logs.push(format!("Error: Hook {script_name} failed: {e}")),
+            // 超时后结束脚本及其启动的子进程，避免阻塞批处理
+            Err(_) => {
+                running.kill();
+                logs.push(format!(
+                    "Error: Hook {script_name} timed out after {}s and was stopped",
+                    HOOK_TIMEOUT.as_secs()
+                ));
+            }
+        }
+    }
+    logs
+}
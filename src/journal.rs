@@ -0,0 +1,146 @@
+use serde::{Deserialize, Serialize};
+use std::path::{Path, PathBuf};
+use std::sync::Mutex;
+
+use crate::projects::Project;
+use crate::{config, session, temp_files};
+
+// 批处理日志（journal），保存在配置目录的 journal.json 中：开始批处理时写入整批文件及其设置，
+// 处理过程中记录每个文件的状态和到达的步骤，批处理结束（完成、失败、取消或暂停）时删除。
+// 启动时仍然存在、且写入它的进程已经结束，说明上次的批处理因崩溃、断电或强制退出而中断
+const SCHEMA: config::Schema = config::Schema {
+    file: "journal.json",
+    name: "batch journal",
+    migrations: &[],
+};
+
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq, Serialize, Deserialize)]
+pub enum FileState {
+    #[default]
+    Pending,
+    Running,
+    Done,
+    Failed,
+}
+
+#[derive(Debug, Clone, Default, Serialize, Deserialize)]
+#[serde(default)]
+pub struct FileEntry {
+    pub path: PathBuf,
+    pub state: FileState,
+    // 最近开始的处理步骤
+    pub step: Option<String>,
+}
+
+#[derive(Debug, Clone, Default, Serialize, Deserialize)]
+#[serde(default)]
+pub struct Journal {
+    // 批处理开始时的文件及其设置、输出文件夹和选项
+    pub batch: Project,
+    pub files: Vec<FileEntry>,
+    pub process: u32,
+}
+
+impl Journal {
+    // 尚未完成的文件（包括失败的文件），保持批处理中的顺序
+    pub fn remaining(&self) -> Project {
+        let mut batch = self.batch.clone();
+        batch.files.retain(|file| {
+            self.files
+                .iter()
+                .all(|entry| entry.path != file.path || entry.state != FileState::Done)
+        });
+        batch
+    }
+
+    // 中断时正在处理的文件及其步骤，用于日志
+    pub fn interrupted_files(&self) -> Vec<String> {
+        self.files
+            .iter()
+            .filter(|entry| entry.state == FileState::Running)
+            .map(|entry| {
+                let name = entry.path.file_name().unwrap_or_default().to_string_lossy();
+                match &entry.step {
+                    Some(step) => format!("{name} ({step})"),
+                    None => name.to_string(),
+                }
+            })
+            .collect()
+    }
+}
+
+static LOCK: Mutex<()> = Mutex::new(());
+
+fn file() -> Option<PathBuf> {
+    session::config_dir().map(|dir| dir.join(SCHEMA.file))
+}
+
+// 只修改本进程开始的批处理的日志；--cli 和自检同样调用处理流程，但不写日志
+fn update(path: &Path, f: impl FnOnce(&mut FileEntry)) {
+    let _guard = LOCK.lock().unwrap_or_else(|poisoned| poisoned.into_inner());
+    if !file().is_some_and(|file| file.exists()) {
+        return;
+    }
+    let mut journal: Journal = config::load(&SCHEMA);
+    if journal.process != std::process::id() {
+        return;
+    }
+    if let Some(entry) = journal.files.iter_mut().find(|entry| entry.path == path) {
+        f(entry);
+        let _ = config::save(&SCHEMA, &journal);
+    }
+}
+
+// 开始批处理：batch 中的文件即为本批要处理的文件
+pub fn start(batch: &Project) -> Result<(), String> {
+    let _guard = LOCK.lock().unwrap_or_else(|poisoned| poisoned.into_inner());
+    let journal = Journal {
+        batch: batch.clone(),
+        files: batch
+            .files
+            .iter()
+            .map(|file| FileEntry {
+                path: file.path.clone(),
+                ..FileEntry::default()
+            })
+            .collect(),
+        process: std::process::id(),
+    };
+    config::save(&SCHEMA, &journal)
+}
+
+pub fn set_state(path: &Path, state: FileState) {
+    update(path, |entry| entry.state = state);
+}
+
+pub fn set_step(path: &Path, step: &str) {
+    update(path, |entry| {
+        entry.state = FileState::Running;
+        entry.step = Some(step.to_string());
+    });
+}
+
+// 批处理正常结束、失败、取消或暂停
+pub fn finish() {
+    let _guard = LOCK.lock().unwrap_or_else(|poisoned| poisoned.into_inner());
+    if let Some(file) = file() {
+        let _ = std::fs::remove_file(file);
+    }
+}
+
+// 启动时检查上次的批处理是否被中断；返回中断的日志并删除它
+pub fn take_interrupted() -> Option<Journal> {
+    if !file().is_some_and(|file| file.exists()) {
+        return None;
+    }
+    let journal: Journal = {
+        let _guard = LOCK.lock().unwrap_or_else(|poisoned| poisoned.into_inner());
+        config::load(&SCHEMA)
+    };
+    // 另一个实例仍在处理这个批次
+    if temp_files::process_running(journal.process) {
+        return None;
+    }
+    finish();
+    Some(journal)
+}
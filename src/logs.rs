@@ -0,0 +1,94 @@
+use iced::futures::channel::mpsc;
+
+use crate::Message;
+
+// 处理日志：记录的行在处理结束后返回（失败原因、自检报告），同时立即发送到终端面板（--cli 时写入 stderr）。
+// 外部工具的输出在运行过程中逐行显示，不必等整批处理结束
+pub struct Logs {
+    lines: Vec<String>,
+    terminal: Terminal,
+}
+
+// 终端面板的发送端。同时处理多个文件时每行带上文件名标记，各文件的进度不再单独显示在进度条上
+#[derive(Clone)]
+pub struct Terminal {
+    sender: mpsc::Sender<Message>,
+    tag: Option<String>,
+}
+
+impl Terminal {
+    // 发送一行到终端。每次用新的 Sender 副本发送：futures 的 mpsc 为每个发送者保留一个位置，
+    // 通道已满时 try_send 也不会丢掉日志，行的顺序与发送顺序一致
+    pub fn show(&self, line: String) {
+        let line = match &self.tag {
+            Some(tag) => format!("[{tag}] {line}"),
+            None => line,
+        };
+        let _ = self.sender.clone().try_send(Message::TerminalOutput(line));
+    }
+
+    pub fn progress(&self, fraction: f32) {
+        if self.tag.is_none() {
+            let _ = self
+                .sender
+                .clone()
+                .try_send(Message::ProcessingProgress(fraction));
+        }
+    }
+}
+
+impl Logs {
+    pub fn new(sender: &mpsc::Sender<Message>) -> Self {
+        Self::tagged(sender, None)
+    }
+
+    // 显示时每行带上 [tag] 前缀；记录的行不带前缀
+    pub fn tagged(sender: &mpsc::Sender<Message>, tag: Option<String>) -> Self {
+        Self {
+            lines: Vec::new(),
+            terminal: Terminal {
+                sender: sender.clone(),
+                tag,
+            },
+        }
+    }
+
+    // 记录并显示
+    pub fn push(&mut self, line: String) {
+        self.terminal.show(line.clone());
+        self.lines.push(line);
+    }
+
+    pub fn extend(&mut self, lines: impl IntoIterator<Item = String>) {
+        for line in lines {
+            self.push(line);
+        }
+    }
+
+    pub fn append(&mut self, lines: &mut Vec<String>) {
+        self.extend(lines.drain(..));
+    }
+
+    // 只显示、不记录：外部工具的实时输出
+    pub fn show(&self, line: String) {
+        self.terminal.show(line);
+    }
+
+    // 只记录：已经实时显示过的行
+    pub fn record(&mut self, line: String) {
+        self.lines.push(line);
+    }
+
+    // 在单独的任务中读取工具输出时使用
+    pub fn terminal(&self) -> Terminal {
+        self.terminal.clone()
+    }
+
+    pub fn progress(&self, fraction: f32) {
+        self.terminal.progress(fraction);
+    }
+
+    pub fn into_lines(self) -> Vec<String> {
+        self.lines
+    }
+}
@@ -1,247 +1,4249 @@
 use iced::event::{self, Event};
+use iced::futures::channel::mpsc;
+use iced::futures::stream::FuturesUnordered;
+use iced::futures::{SinkExt, StreamExt};
+use iced::keyboard::{self, Key, key::Named};
 use iced::widget::{
     Space, button, checkbox, column, container, pick_list, progress_bar, row, scrollable, text,
+    text_input, tooltip,
 };
-use iced::{Alignment, Element, Length, Task, Theme};
+use iced::{Alignment, Element, Length, Point, Size, Task, Theme};
 use resvg::usvg;
 use rfd::FileDialog;
-use std::path::PathBuf;
-use std::process::Command;
+use serde::{Deserialize, Serialize};
+use std::collections::HashMap;
+use std::path::{Path, PathBuf};
+use std::process::Stdio;
+use std::sync::Arc;
+use std::sync::atomic::{AtomicBool, Ordering};
+use std::time::Duration;
 use tiny_skia::Pixmap;
+use tokio::io::{AsyncBufReadExt, BufReader};
+
+mod active_area;
+mod ass;
+mod charset;
+mod cli;
+mod command_line;
+mod config;
+mod disk_space;
+mod eta;
+mod history;
+mod hooks;
+mod journal;
+mod logs;
+mod media_server;
+mod mp4;
+mod parts;
+mod pipeline;
+mod power;
+mod probe;
+mod processes;
+mod projects;
+mod report;
+mod rpu_analysis;
+mod self_test;
+mod session;
+mod skip_list;
+mod stats;
+mod temp_files;
+mod verbosity;
+mod watch;
 
 #[derive(Debug, Clone)]
 pub struct App {
     // 文件队列
-    file_queue: Vec<PathBuf>,
+    file_queue: Vec<QueueItem>,
+    selected_file: Option<usize>,
     output_folder: Option<PathBuf>,
     // 处理选项
-    include_subtitles: bool,
-    frame_rate: FrameRate,
+    options: ProcessOptions,
     // 状态
     processing: bool,
+    // 正在运行的批处理任务，取消时中止
+    processing_task: Option<iced::task::Handle>,
+    // 当前文件完成后暂停批处理
+    pause_requested: Arc<AtomicBool>,
     current_file_index: usize,
     progress: f32,
+    // 当前批处理的计时，用于显示每个待处理文件的预计剩余时间
+    timing: Option<eta::BatchTiming>,
     log_messages: Vec<String>,
     // 新增：终端日志
     terminal_logs: Vec<String>,
+    // 界面状态（标签页、过滤、排序、窗口位置），退出时保存
+    session: session::Session,
+    next_sequence: u64,
+    // 用户已确认使用与源不一致的帧率
+    frame_rate_confirmed: bool,
+    // 正在输入的自定义帧率，有效时写入选项
+    custom_frame_rate: String,
+    // 监视文件夹状态，None 表示尚未完成第一次扫描
+    watch_health: Option<watch::WatchHealth>,
+    stats: stats::Stats,
+    skip_list: skip_list::SkipList,
+    // 监视文件夹中被替换的文件的输出版本号，第一次替换为 2
+    output_versions: HashMap<PathBuf, u32>,
+    // 命名队列，以及保存队列时输入的名称
+    projects: projects::Projects,
+    project_name: String,
+    // 杜比视界 L1 亮度分析结果，以及是否有分析正在进行
+    analyses: Vec<rpu_analysis::Analysis>,
+    analyzing: bool,
+    // 从 Jellyfin/Plex 导入队列的设置，是否显示设置面板，以及是否有导入正在进行
+    media_server: media_server::ServerConfig,
+    show_importer: bool,
+    importing: bool,
+    // 自检正在运行
+    self_testing: bool,
+    // 开始处理前正在对队列进行深度分析，完成后自动开始
+    deep_probe_pending: bool,
+    // 快速模式确认转换后等待分析完成再开始的文件
+    quick_convert: Option<PathBuf>,
+    // 预设对比中选择的两个预设（当前设置或命名队列）
+    compare_presets: [Option<String>; 2],
+    // 输出文件夹核对的结果，以及是否有核对正在进行
+    reconciliation: Option<Result<history::Reconciliation, String>>,
+    reconciling: bool,
+    // 临时文件夹所在磁盘的可用空间，None 表示无法查询
+    temp_space: Option<u64>,
+    // 最近一次自动保存的队列状态，有变化时才写入磁盘
+    autosaved: Option<serde_json::Value>,
+    // 批处理结束后的操作（睡眠、休眠、关机），以及执行前剩余的倒计时秒数
+    when_finished: power::PowerAction,
+    power_countdown: Option<u32>,
+}
+
+// 底部面板的标签页
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+pub enum Tab {
+    Log,
+    Terminal,
+    Stats,
+    Metadata,
+    Pipeline,
+    Compare,
+    Library,
+}
+
+// 队列排序方式，同时决定处理顺序
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+pub enum QueueSort {
+    Added,
+    Name,
+    Size,
+}
+
+impl std::fmt::Display for QueueSort {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        f.write_str(match self {
+            QueueSort::Added => "Date Added",
+            QueueSort::Name => "Name",
+            QueueSort::Size => "Size",
+        })
+    }
+}
+
+// 转换选项，处理时整体传递给处理流程；随命名队列一起保存
+#[derive(Debug, Clone, Serialize, Deserialize)]
+#[serde(default)]
+pub struct ProcessOptions {
+    frame_rate: FrameRate,
+    dv_profile: DvProfile,
+    sample_entry: SampleEntry,
+    enhancement_layer: EnhancementLayer,
+    subtitle_mode: SubtitleMode,
+    // 自动选择字幕时对听障字幕（SDH）的偏好
+    sdh_subtitles: SdhPreference,
+    // 源文件只有图形字幕时，用基于 Tesseract 的 PgsToSrt 把 PGS 字幕识别为文本
+    ocr_subtitles: bool,
+    // 合并字幕后仍保留没有字幕的 MP4；默认带字幕的 MP4 代替它，只输出一个文件
+    keep_unsubtitled: bool,
+    // 未手动选择音轨时封装全部音轨，否则只封装默认音轨
+    all_audio_tracks: bool,
+    // 封装全部音轨时跳过评论音轨
+    skip_commentary: bool,
+    // TrueHD 音轨改用源文件中对应的 AC-3 核心轨道
+    truehd_ac3_core: bool,
+    // 将 TrueHD 音轨有损转码为 E-AC-3
+    truehd_to_eac3: bool,
+    // 另外添加第一条音轨下混的 AAC 立体声兼容音轨
+    aac_stereo: bool,
+    // 不封装任何音频，只输出杜比视界视频
+    video_only: bool,
+    // 高级设置：DTS 和 TrueHD 转码为 E-AC-3 时的码率（kb/s）
+    eac3_bitrate: u32,
+    // 高级设置：PCM 和 FLAC 音轨转码的目标编码
+    lossless_audio: LosslessAudio,
+    // 转码的音轨（E-AC-3 和 AAC 立体声）做 EBU R128 响度标准化
+    loudnorm: bool,
+    // 高级设置：响度标准化的目标响度（LUFS）
+    loudnorm_target: i32,
+    // 源文件没有语言标签时写入的音频/字幕语言（ISO 639-2），为空时保持 und
+    default_language: String,
+    // 转换成功后在源 MKV 中写入标记
+    annotate_source: bool,
+    // 处理前先扫描源文件的解码错误
+    verify_source: bool,
+    // 封装后逐包比较原样复制的音轨与源文件
+    verify_audio: bool,
+    // 提取后检查每条音频的数据包数和时长，避免输出没有声音的 MP4
+    check_audio: bool,
+    // 另外输出去掉杜比视界的 HDR10 MP4
+    hdr10_fallback: bool,
+    // 在输出旁边保存色调映射后的截图
+    export_poster: bool,
+    // 没有杜比视界元数据的文件如何处理
+    non_dv_mode: NonDvMode,
+    // 输出文件夹中已有同名输出时如何处理
+    existing_outputs: ExistingOutputs,
+    // 文件成功转换（输出已校验）后如何处理源文件
+    source_after_success: SourceAfterSuccess,
+    // 高级设置：MP4 品牌
+    device_preset: DevicePreset,
+    major_brand: String,
+    compatible_brands: String,
+    // 高级设置：附加到 mp4muxer 命令行末尾的参数，按 shell 规则切分
+    mux_extra_args: String,
+    // 高级设置：提取 RPU，可选用 dovi_tool editor 编辑后重新注入
+    rpu_workflow: bool,
+    rpu_edit_config: Option<PathBuf>,
+    keep_rpu: bool,
+    // 高级设置：把选择的第一条字幕烧录进视频，需要重新编码视频并重新注入 RPU
+    burn_subtitles: bool,
+    // 高级设置：开始下一个文件前等待的秒数（另加随机抖动），减轻 NAS 上的 I/O 突发
+    stagger_seconds: u32,
+    // 高级设置：同时处理的文件数，适用于 NVMe 等读写足够快的磁盘
+    max_concurrent_jobs: u32,
+    // 高级设置：文件处理失败后重试的次数，应对网络共享短暂断开、文件被占用等临时故障
+    retry_count: u32,
+    // 高级设置：以后台优先级运行处理工具（Unix 上为 nice/ionice，Windows 上为低于正常）
+    background_priority: bool,
+    // 高级设置：减少临时空间占用，能用管道的地方不写中间文件，中间文件用完立即删除
+    low_temp_space: bool,
+    // 高级设置：存放中间文件的文件夹，None 时使用系统临时目录（通常在容量较小的系统盘上）
+    temp_folder: Option<PathBuf>,
+    // 高级设置：处理完成后上传到的目录（SFTP/SMB 地址或 rclone 的 remote:path），为空时不上传
+    upload_backend: UploadBackend,
+    upload_destination: String,
+    remove_after_upload: bool,
+    // 高级设置：每个文件成功后对每个输出运行的命令，{output} 和 {source} 替换为输出和源文件的路径，为空时不运行
+    post_command: String,
+    // 高级设置：各外部工具的输出详细程度
+    tool_verbosity: verbosity::ToolVerbosity,
+}
+
+impl Default for ProcessOptions {
+    fn default() -> Self {
+        let (major_brand, compatible_brands) = DevicePreset::AppleTv.brands().unwrap_or_default();
+        Self {
+            frame_rate: FrameRate::FILM_23976,
+            dv_profile: DvProfile::Auto,
+            sample_entry: SampleEntry::Dvh1,
+            enhancement_layer: EnhancementLayer::Discard,
+            subtitle_mode: SubtitleMode::None,
+            sdh_subtitles: SdhPreference::Any,
+            ocr_subtitles: false,
+            keep_unsubtitled: false,
+            all_audio_tracks: false,
+            skip_commentary: false,
+            truehd_ac3_core: false,
+            truehd_to_eac3: false,
+            aac_stereo: false,
+            video_only: false,
+            eac3_bitrate: 768,
+            lossless_audio: LosslessAudio::Eac3,
+            loudnorm: false,
+            loudnorm_target: -16,
+            default_language: String::new(),
+            annotate_source: false,
+            verify_source: false,
+            verify_audio: false,
+            check_audio: true,
+            hdr10_fallback: false,
+            export_poster: false,
+            non_dv_mode: NonDvMode::Skip,
+            existing_outputs: ExistingOutputs::Overwrite,
+            source_after_success: SourceAfterSuccess::Keep,
+            device_preset: DevicePreset::AppleTv,
+            major_brand: major_brand.to_string(),
+            compatible_brands: compatible_brands.to_string(),
+            mux_extra_args: String::new(),
+            rpu_workflow: false,
+            burn_subtitles: false,
+            rpu_edit_config: None,
+            keep_rpu: false,
+            stagger_seconds: 0,
+            max_concurrent_jobs: 1,
+            retry_count: 0,
+            background_priority: false,
+            low_temp_space: false,
+            temp_folder: None,
+            upload_backend: UploadBackend::Curl,
+            upload_destination: String::new(),
+            remove_after_upload: false,
+            post_command: String::new(),
+            tool_verbosity: verbosity::ToolVerbosity::default(),
+        }
+    }
+}
+
+impl ProcessOptions {
+    fn compatible_brand_list(&self) -> Vec<&str> {
+        self.compatible_brands
+            .split(',')
+            .map(str::trim)
+            .filter(|brand| !brand.is_empty())
+            .collect()
+    }
+
+    // MP4 品牌必须是 4 个 ASCII 字符
+    fn brand_error(&self) -> Option<String> {
+        let invalid = std::iter::once(self.major_brand.as_str())
+            .filter(|brand| !brand.is_empty())
+            .chain(self.compatible_brand_list())
+            .find(|brand| brand.len() != 4 || !brand.is_ascii())?;
+        Some(format!(
+            "Invalid brand \"{invalid}\": brands must be exactly 4 ASCII characters"
+        ))
+    }
+
+    fn mux_extra_args(&self) -> Result<Vec<String>, String> {
+        command_line::split(&self.mux_extra_args)
+            .map_err(|e| format!("Invalid mp4muxer arguments: {e}"))
+    }
+
+    // 附加参数不能覆盖处理流程自己设置的 mp4muxer 参数，否则输出与选项不一致
+    fn mux_args_error(&self) -> Option<String> {
+        let args = match self.mux_extra_args() {
+            Ok(args) => args,
+            Err(e) => return Some(e),
+        };
+        let reserved = args.iter().find(|arg| {
+            let name = arg.split('=').next().unwrap_or_default();
+            MUX_RESERVED_ARGS.contains(&name)
+        })?;
+        Some(format!(
+            "mp4muxer argument {reserved} is set by Rebottle, use the corresponding option instead"
+        ))
+    }
+
+    // 输出的文本字幕轨道：selected 为手动选择的轨道（0:s:N），None 时按字幕模式选择，
+    // 优先语言为第一条输出音轨的语言，没有时为设置的默认语言
+    fn subtitle_tracks<'a>(
+        &self,
+        info: &'a probe::MediaInfo,
+        audio_tracks: &[usize],
+        selected: Option<&[usize]>,
+    ) -> Vec<&'a probe::SubtitleStream> {
+        let text = info
+            .subtitles
+            .iter()
+            .filter(|subtitle| self.converts_subtitle(info, subtitle))
+            .collect::<Vec<_>>();
+        if let Some(selected) = selected {
+            return text
+                .into_iter()
+                .filter(|subtitle| selected.contains(&subtitle.index))
+                .collect();
+        }
+        let text = text
+            .iter()
+            .copied()
+            .filter(|subtitle| self.sdh_subtitles.keeps(subtitle, &text))
+            .collect::<Vec<_>>();
+        match self.subtitle_mode {
+            SubtitleMode::None => Vec::new(),
+            SubtitleMode::All => text,
+            SubtitleMode::Preferred => {
+                let language = info
+                    .audio_tracks(audio_tracks, self.auto_audio())
+                    .first()
+                    .and_then(|audio| audio.language.clone())
+                    .or_else(|| self.default_language().map(ToString::to_string));
+                text.iter()
+                    .find(|subtitle| language.is_some() && subtitle.language == language)
+                    .or(text.first())
+                    .into_iter()
+                    .copied()
+                    .collect()
+            }
+        }
+    }
+
+    // 可以转换为 mov_text 的字幕：文本字幕，开启 OCR 且源文件只有图形字幕时还有 PGS 字幕
+    fn converts_subtitle(&self, info: &probe::MediaInfo, subtitle: &probe::SubtitleStream) -> bool {
+        subtitle.is_text()
+            || (self.ocr_subtitles && subtitle.is_pgs() && info.has_only_image_subtitles())
+    }
+
+    // 没有为文件选择音轨时自动选择的轨道
+    fn auto_audio(&self) -> probe::AutoAudio {
+        match (self.all_audio_tracks, self.skip_commentary) {
+            (false, _) => probe::AutoAudio::Preferred,
+            (true, false) => probe::AutoAudio::All,
+            (true, true) => probe::AutoAudio::AllExceptCommentary,
+        }
+    }
+
+    fn default_language(&self) -> Option<&str> {
+        Some(self.default_language.trim()).filter(|language| !language.is_empty())
+    }
+
+    // 默认语言必须是 3 个小写字母的 ISO 639-2 代码
+    fn language_error(&self) -> Option<String> {
+        let language = self.default_language()?;
+        (language.len() != 3 || !language.chars().all(|c| c.is_ascii_lowercase())).then(|| {
+            format!(
+                "Invalid default language \"{language}\": use a 3-letter ISO 639-2 code such as eng"
+            )
+        })
+    }
+
+    // 封装使用的帧率：HLG 杜比视界（profile 8.4，常见于 iPhone 拍摄）通常是 30/60 fps，
+    // 检测到的帧率是已知帧率时直接使用，不沿用为电影设置的帧率
+    fn frame_rate_for(&self, media_info: &probe::MediaInfo) -> FrameRate {
+        media_info
+            .video
+            .as_ref()
+            .filter(|video| video.is_hlg() && video.dolby_vision.is_some())
+            .and_then(|video| FrameRate::from_fps(video.frame_rate?))
+            .unwrap_or(self.frame_rate)
+    }
+
+    fn upload_destination(&self) -> Option<&str> {
+        Some(self.upload_destination.trim()).filter(|destination| !destination.is_empty())
+    }
+
+    // curl 的上传目标必须是 sftp:// 或 smb:// 地址，rclone 的目标必须是 remote:path
+    fn upload_error(&self) -> Option<String> {
+        let destination = self.upload_destination()?.to_lowercase();
+        match self.upload_backend {
+            UploadBackend::Curl => (!destination.starts_with("sftp://")
+                && !destination.starts_with("smb://"))
+            .then(|| {
+                "Invalid upload destination: use sftp://host/path/ or smb://host/share/path/"
+                    .to_string()
+            }),
+            UploadBackend::Rclone => (!destination.contains(':')).then(|| {
+                "Invalid rclone destination: use a configured remote such as gdrive:Movies"
+                    .to_string()
+            }),
+        }
+    }
+
+    // 处理后命令按平台的规则切分（见 command_line::split），没有设置时为 None
+    fn post_command(&self) -> Result<Option<Vec<String>>, String> {
+        let args = command_line::split(&self.post_command)
+            .map_err(|e| format!("Invalid post-processing command: {e}"))?;
+        Ok((!args.is_empty()).then_some(args))
+    }
+
+    // 存放中间文件的文件夹
+    fn temp_dir(&self) -> PathBuf {
+        self.temp_folder.clone().unwrap_or_else(std::env::temp_dir)
+    }
+
+    fn temp_folder_error(&self) -> Option<String> {
+        let folder = self.temp_folder.as_ref()?;
+        (!folder.is_dir()).then(|| format!("Temp folder {} does not exist", folder.display()))
+    }
+
+    // 开始处理前必须通过的检查
+    fn validation_error(&self) -> Option<String> {
+        self.brand_error()
+            .or_else(|| self.mux_args_error())
+            .or_else(|| self.language_error())
+            .or_else(|| self.upload_error())
+            .or_else(|| self.temp_folder_error())
+            .or_else(|| self.post_command().err())
+    }
+}
+
+// 处理流程传给 mp4muxer 的参数，不能通过附加参数重复设置
+const MUX_RESERVED_ARGS: [&str; 10] = [
+    "-o",
+    "--output-file",
+    "-i",
+    "--input-file",
+    "--input-video-frame-rate",
+    "--dv-profile",
+    "--dv-bl-compatible-id",
+    "--dvh1flag",
+    "--mpeg4-brand",
+    "--mpeg4-comp-brand",
+];
+
+// 封装时写入的杜比视界 profile；Auto 根据源文件推荐
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+pub enum DvProfile {
+    Auto,
+    P5,
+    P81,
+    P84,
+    P9,
+}
+
+impl DvProfile {
+    const ALL: [DvProfile; 5] = [
+        DvProfile::Auto,
+        DvProfile::P5,
+        DvProfile::P81,
+        DvProfile::P84,
+        DvProfile::P9,
+    ];
+
+    // 根据源文件推荐 profile；profile 7 会先转换为 8.1。
+    // 兼容 ID 缺失的 profile 8 源按基础层的传输特性区分 8.1 (PQ) 和 8.4 (HLG)，
+    // 不能按 profile 5 封装，否则部分播放器会显示绿色/紫色画面
+    fn suggest(dv: &probe::DolbyVision, hlg: bool) -> DvProfile {
+        match (dv.profile, dv.bl_compatibility_id) {
+            (8, 4) => DvProfile::P84,
+            (8, _) if hlg => DvProfile::P84,
+            (7, _) | (8, _) => DvProfile::P81,
+            (9, _) => DvProfile::P9,
+            _ => DvProfile::P5,
+        }
+    }
+
+    // Auto 时使用推荐值，无法分析源文件时沿用 profile 5
+    fn resolve(self, media_info: &probe::MediaInfo) -> DvProfile {
+        match self {
+            DvProfile::Auto => media_info
+                .video
+                .as_ref()
+                .and_then(|video| {
+                    Some(DvProfile::suggest(
+                        video.dolby_vision.as_ref()?,
+                        video.is_hlg(),
+                    ))
+                })
+                .unwrap_or(DvProfile::P5),
+            profile => profile,
+        }
+    }
+
+    // mp4muxer 的 --dv-profile 和 --dv-bl-compatible-id
+    fn mux_flags(&self) -> (&'static str, Option<&'static str>) {
+        match self {
+            DvProfile::Auto | DvProfile::P5 => ("5", None),
+            DvProfile::P81 => ("8", Some("1")),
+            DvProfile::P84 => ("8", Some("4")),
+            DvProfile::P9 => ("9", Some("2")),
+        }
+    }
+}
+
+impl std::fmt::Display for DvProfile {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        f.write_str(match self {
+            DvProfile::Auto => "Auto (from source)",
+            DvProfile::P5 => "5",
+            DvProfile::P81 => "8.1",
+            DvProfile::P84 => "8.4",
+            DvProfile::P9 => "9",
+        })
+    }
+}
+
+// 杜比视界视频轨道的 sample entry：Apple 设备需要 dvh1，部分播放器只识别 dvhe
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+pub enum SampleEntry {
+    Dvh1,
+    Dvhe,
+}
+
+impl SampleEntry {
+    const ALL: [SampleEntry; 2] = [SampleEntry::Dvh1, SampleEntry::Dvhe];
+
+    // 同时用作输出文件名后缀
+    fn tag(&self) -> &'static str {
+        match self {
+            SampleEntry::Dvh1 => "dvh1",
+            SampleEntry::Dvhe => "dvhe",
+        }
+    }
+
+    // mp4muxer 的 --dvh1flag
+    fn mux_flag(&self) -> &'static str {
+        match self {
+            SampleEntry::Dvh1 => "1",
+            SampleEntry::Dvhe => "0",
+        }
+    }
+}
+
+impl std::fmt::Display for SampleEntry {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        f.write_str(match self {
+            SampleEntry::Dvh1 => "dvh1 (Apple / QuickTime)",
+            SampleEntry::Dvhe => "dvhe",
+        })
+    }
+}
+
+// profile 7 双层源的增强层（FEL/MEL）处理方式
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+pub enum EnhancementLayer {
+    Discard,
+    KeepSeparate,
+}
+
+impl EnhancementLayer {
+    const ALL: [EnhancementLayer; 2] = [EnhancementLayer::Discard, EnhancementLayer::KeepSeparate];
+
+    fn description(&self) -> &'static str {
+        match self {
+            EnhancementLayer::Discard => {
+                "The EL is dropped and the RPU converted to profile 8.1. Lossless for MEL sources; \
+                 for FEL sources the 12-bit reconstruction is lost and only the 10-bit base layer \
+                 with dynamic metadata remains."
+            }
+            EnhancementLayer::KeepSeparate => {
+                "The MP4 is still profile 8.1 (MP4 players cannot use a second layer), but the EL \
+                 is saved next to it as _EL.hevc so a full dual-layer file can be rebuilt later. \
+                 FEL layers can be several GB."
+            }
+        }
+    }
+}
+
+impl std::fmt::Display for EnhancementLayer {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        f.write_str(match self {
+            EnhancementLayer::Discard => "Discard (MEL-style 8.1)",
+            EnhancementLayer::KeepSeparate => "Keep as Separate File",
+        })
+    }
+}
+
+// 上传输出使用的工具
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+pub enum UploadBackend {
+    // curl 上传到 SFTP/SMB
+    Curl,
+    // rclone 复制或移动到已配置的 remote（云存储等）
+    Rclone,
+}
+
+impl UploadBackend {
+    const ALL: [UploadBackend; 2] = [UploadBackend::Curl, UploadBackend::Rclone];
+}
+
+impl std::fmt::Display for UploadBackend {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        f.write_str(match self {
+            UploadBackend::Curl => "SFTP/SMB (curl)",
+            UploadBackend::Rclone => "rclone",
+        })
+    }
+}
+
+// 转换为 mov_text 的文本字幕轨道
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq, Serialize, Deserialize)]
+pub enum SubtitleMode {
+    #[default]
+    None,
+    // 与第一条输出音轨语言相同的一条，没有时为第一条
+    Preferred,
+    All,
+}
+
+impl SubtitleMode {
+    const ALL: [SubtitleMode; 3] = [
+        SubtitleMode::None,
+        SubtitleMode::Preferred,
+        SubtitleMode::All,
+    ];
 }
 
-#[derive(Debug, Clone, PartialEq)]
-pub enum FrameRate {
-    Film23976, // 24000/1001
-    Film24,    // 24
-    Tv29970,   // 30000/1001
-    Tv25,      // 25
-    Hfr60,     // 60
-    Hfr59940,  // 60000/1001
-}
+impl std::fmt::Display for SubtitleMode {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        f.write_str(match self {
+            SubtitleMode::None => "None",
+            SubtitleMode::Preferred => "Preferred Language",
+            SubtitleMode::All => "All Text Subtitles",
+        })
+    }
+}
+
+// 自动选择字幕时如何对待听障字幕（SDH）；手动选择的轨道不受影响
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq, Serialize, Deserialize)]
+pub enum SdhPreference {
+    #[default]
+    Any,
+    // 同一语言同时有 SDH 和普通字幕时选择 SDH
+    Prefer,
+    Exclude,
+}
+
+impl SdhPreference {
+    const ALL: [SdhPreference; 3] = [
+        SdhPreference::Any,
+        SdhPreference::Prefer,
+        SdhPreference::Exclude,
+    ];
+
+    // 自动选择时是否保留这条字幕：Exclude 去掉全部 SDH 轨道；Prefer 在同一语言有 SDH 轨道时
+    // 去掉该语言的普通字幕，只翻译外语对白的强制字幕始终保留
+    fn keeps(self, subtitle: &probe::SubtitleStream, text: &[&probe::SubtitleStream]) -> bool {
+        match self {
+            SdhPreference::Any => true,
+            SdhPreference::Exclude => !subtitle.is_sdh(),
+            SdhPreference::Prefer => {
+                subtitle.is_sdh()
+                    || subtitle.forced
+                    || !text
+                        .iter()
+                        .any(|other| other.is_sdh() && other.language == subtitle.language)
+            }
+        }
+    }
+}
+
+impl std::fmt::Display for SdhPreference {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        f.write_str(match self {
+            SdhPreference::Any => "Any",
+            SdhPreference::Prefer => "Prefer SDH",
+            SdhPreference::Exclude => "Exclude SDH",
+        })
+    }
+}
+
+// mp4muxer 不能封装 PCM 和 FLAC，这类音轨转码的目标编码
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq, Serialize, Deserialize)]
+pub enum LosslessAudio {
+    #[default]
+    Eac3,
+    Aac,
+}
+
+impl LosslessAudio {
+    const ALL: [LosslessAudio; 2] = [LosslessAudio::Eac3, LosslessAudio::Aac];
+}
+
+impl std::fmt::Display for LosslessAudio {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        f.write_str(match self {
+            LosslessAudio::Eac3 => "E-AC-3",
+            LosslessAudio::Aac => "AAC",
+        })
+    }
+}
+
+// 非杜比视界文件的处理方式
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+pub enum NonDvMode {
+    Skip,
+    Remux,
+    Abort,
+}
+
+impl NonDvMode {
+    const ALL: [NonDvMode; 3] = [NonDvMode::Skip, NonDvMode::Remux, NonDvMode::Abort];
+}
+
+impl std::fmt::Display for NonDvMode {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        f.write_str(match self {
+            NonDvMode::Skip => "Skip",
+            NonDvMode::Remux => "Plain HDR10/SDR Remux",
+            NonDvMode::Abort => "Abort Batch",
+        })
+    }
+}
+
+// 输出文件已存在时的处理方式：覆盖、跳过该文件，或在文件名后加 " (2)" 等编号另存
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+pub enum ExistingOutputs {
+    Overwrite,
+    Skip,
+    Rename,
+}
+
+impl ExistingOutputs {
+    const ALL: [ExistingOutputs; 3] = [
+        ExistingOutputs::Overwrite,
+        ExistingOutputs::Skip,
+        ExistingOutputs::Rename,
+    ];
+}
+
+impl std::fmt::Display for ExistingOutputs {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        f.write_str(match self {
+            ExistingOutputs::Overwrite => "Overwrite",
+            ExistingOutputs::Skip => "Skip File",
+            ExistingOutputs::Rename => "Add Number",
+        })
+    }
+}
+
+// 成功转换后源文件的处理方式：保留、移到源文件旁的 processed 文件夹、移到回收站或直接删除
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+pub enum SourceAfterSuccess {
+    Keep,
+    Move,
+    Recycle,
+    Delete,
+}
+
+impl SourceAfterSuccess {
+    const ALL: [SourceAfterSuccess; 4] = [
+        SourceAfterSuccess::Keep,
+        SourceAfterSuccess::Move,
+        SourceAfterSuccess::Recycle,
+        SourceAfterSuccess::Delete,
+    ];
+}
+
+impl std::fmt::Display for SourceAfterSuccess {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        f.write_str(match self {
+            SourceAfterSuccess::Keep => "Keep",
+            SourceAfterSuccess::Move => "Move to \"processed\"",
+            SourceAfterSuccess::Recycle => "Move to Recycle Bin",
+            SourceAfterSuccess::Delete => "Delete",
+        })
+    }
+}
+
+// 目标设备预设，决定默认的 MP4 品牌
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+pub enum DevicePreset {
+    AppleTv,
+    Generic,
+    Custom,
+}
+
+impl DevicePreset {
+    const ALL: [DevicePreset; 3] = [
+        DevicePreset::AppleTv,
+        DevicePreset::Generic,
+        DevicePreset::Custom,
+    ];
+
+    // (主品牌, 兼容品牌)
+    fn brands(&self) -> Option<(&'static str, &'static str)> {
+        match self {
+            DevicePreset::AppleTv => Some(("mp42", "mp42,iso6,isom,msdh,dby1")),
+            DevicePreset::Generic => Some(("isom", "isom,iso6,mp42,dby1")),
+            DevicePreset::Custom => None,
+        }
+    }
+}
+
+impl std::fmt::Display for DevicePreset {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        f.write_str(match self {
+            DevicePreset::AppleTv => "Apple TV / QuickTime",
+            DevicePreset::Generic => "Generic Player",
+            DevicePreset::Custom => "Custom",
+        })
+    }
+}
+
+// 队列中的单个文件及其分析结果
+#[derive(Debug, Clone)]
+pub struct QueueItem {
+    path: PathBuf,
+    // L5 有效画面区域的偏移输入
+    active_area: active_area::Form,
+    size: u64,
+    // 加入队列的顺序
+    sequence: u64,
+    // None 表示仍在分析中
+    info: Option<Result<probe::MediaInfo, String>>,
+    // 处理失败时的错误和失败步骤的最后几行输出
+    failure: Option<(String, Vec<String>)>,
+    // 输出版本号，大于 1 时输出文件名带 .vN
+    version: u32,
+    // 裁剪起止位置，留空表示从开头 / 到结尾
+    trim_start: String,
+    trim_end: String,
+    // 手动选择的音频轨道（0:a:N），为空时自动选择
+    audio_tracks: Vec<usize>,
+    // 手动选择的字幕轨道（0:s:N，可以为空），None 时按字幕模式选择
+    subtitle_tracks: Option<Vec<usize>>,
+    // 添加的外部字幕文件（.srt/.ass），封装在源文件的字幕之后
+    external_subtitles: Vec<PathBuf>,
+    // 音频偏移（毫秒），留空表示使用源文件容器中的延迟
+    audio_offset: String,
+    // 字幕偏移（毫秒），留空表示不调整
+    subtitle_offset: String,
+    // 拼接在 path 之后的分段（CD2、CD3…），已从队列中合并到这一项
+    parts: Vec<PathBuf>,
+    // 深度分析正在进行
+    deep_probing: bool,
+}
+
+impl QueueItem {
+    fn new(path: PathBuf, sequence: u64) -> Self {
+        let size = std::fs::metadata(&path).map(|meta| meta.len()).unwrap_or(0);
+        Self {
+            path,
+            active_area: active_area::Form::default(),
+            size,
+            sequence,
+            info: None,
+            failure: None,
+            version: 1,
+            trim_start: String::new(),
+            trim_end: String::new(),
+            audio_tracks: Vec::new(),
+            subtitle_tracks: None,
+            external_subtitles: Vec::new(),
+            audio_offset: String::new(),
+            subtitle_offset: String::new(),
+            parts: Vec::new(),
+            deep_probing: false,
+        }
+    }
+
+    // 解析 L5 偏移；分析出画面尺寸后检查偏移不超出画面
+    fn active_area(&self) -> Result<Option<active_area::Offsets>, String> {
+        self.active_area
+            .parse(self.info.as_ref().and_then(|info| info.as_ref().ok()))
+    }
+
+    // 解析裁剪范围；章节编号依赖分析结果中的章节列表
+    fn trim(&self) -> Result<Option<pipeline::Trim>, String> {
+        let chapters = match &self.info {
+            Some(Ok(info)) => info.chapters.as_slice(),
+            _ => &[],
+        };
+        pipeline::Trim::parse(&self.trim_start, &self.trim_end, chapters)
+    }
+
+    fn audio_offset(&self) -> Result<Option<i64>, String> {
+        pipeline::parse_audio_offset(&self.audio_offset)
+    }
+
+    fn subtitle_offset(&self) -> Result<Option<i64>, String> {
+        pipeline::parse_subtitle_offset(&self.subtitle_offset)
+    }
+
+    // 处理参数；裁剪范围或偏移无效时按未设置处理
+    fn source(&self) -> pipeline::Source {
+        pipeline::Source {
+            path: self.path.clone(),
+            active_area: self.active_area().ok().flatten(),
+            version: self.version,
+            trim: self.trim().ok().flatten(),
+            audio_tracks: self.audio_tracks.clone(),
+            subtitle_tracks: self.subtitle_tracks.clone(),
+            external_subtitles: self.external_subtitles.clone(),
+            audio_offset: self.audio_offset().ok().flatten(),
+            subtitle_offset: self.subtitle_offset().ok().flatten(),
+            parts: self.parts.clone(),
+        }
+    }
+}
+
+// 封装帧率，以有理数 num/den 保存（已约分），原样传给 mp4muxer 的 --input-video-frame-rate。
+// 常用帧率作为预设，也可以输入自定义值（如 48、50、120000/1001）；配置中保存为 "24000/1001" 形式的字符串
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(try_from = "String", into = "String")]
+pub struct FrameRate {
+    num: u32,
+    den: u32,
+}
+
+impl FrameRate {
+    const FILM_23976: FrameRate = FrameRate::new(24000, 1001);
+    const FILM_24: FrameRate = FrameRate::new(24, 1);
+    const TV_29970: FrameRate = FrameRate::new(30000, 1001);
+    const TV_25: FrameRate = FrameRate::new(25, 1);
+    const HFR_60: FrameRate = FrameRate::new(60, 1);
+    const HFR_59940: FrameRate = FrameRate::new(60000, 1001);
+
+    const PRESETS: [FrameRate; 6] = [
+        FrameRate::FILM_23976,
+        FrameRate::FILM_24,
+        FrameRate::TV_29970,
+        FrameRate::TV_25,
+        FrameRate::HFR_60,
+        FrameRate::HFR_59940,
+    ];
+
+    // mp4muxer 和 ffmpeg 可接受的范围
+    const MAX_FPS: f64 = 240.0;
+
+    const fn new(num: u32, den: u32) -> Self {
+        let (mut a, mut b) = (num, den);
+        while b != 0 {
+            (a, b) = (b, a % b);
+        }
+        FrameRate {
+            num: num / a,
+            den: den / a,
+        }
+    }
+
+    // 解析自定义帧率：整数（50）、分数（120000/1001）或小数；小数按整数帧率或
+    // NTSC 帧率（n×1000/1001，如 47.952）识别，其余小数请写成分数
+    fn parse(text: &str) -> Result<FrameRate, String> {
+        let text = text.trim();
+        let invalid = || {
+            format!(
+                "Invalid frame rate \"{text}\": use a whole number or a fraction, e.g. 50 or 120000/1001"
+            )
+        };
+        let frame_rate = match text.split_once('/') {
+            Some((num, den)) => {
+                let num = num.trim().parse::<u32>().map_err(|_| invalid())?;
+                let den = den.trim().parse::<u32>().map_err(|_| invalid())?;
+                if num == 0 || den == 0 {
+                    return Err(invalid());
+                }
+                FrameRate::new(num, den)
+            }
+            None => {
+                let fps = text.parse::<f64>().map_err(|_| invalid())?;
+                let whole = fps.round();
+                let ntsc = (fps * 1.001).round();
+                if fps <= 0.0 {
+                    return Err(invalid());
+                } else if (fps - whole).abs() < 0.0005 {
+                    FrameRate::new(whole as u32, 1)
+                } else if (fps - ntsc * 1000.0 / 1001.0).abs() < 0.0005 {
+                    FrameRate::new(ntsc as u32 * 1000, 1001)
+                } else {
+                    return Err(format!(
+                        "Frame rate {text} is not a whole or NTSC rate, enter it as a fraction"
+                    ));
+                }
+            }
+        };
+        if frame_rate.fps() > FrameRate::MAX_FPS {
+            return Err(format!(
+                "Frame rate {text} is above {} fps",
+                FrameRate::MAX_FPS
+            ));
+        }
+        Ok(frame_rate)
+    }
+
+    fn fps(&self) -> f64 {
+        self.num as f64 / self.den as f64
+    }
+
+    fn matches(&self, fps: f64) -> bool {
+        (self.fps() - fps).abs() < 0.01
+    }
+
+    fn from_fps(fps: f64) -> Option<FrameRate> {
+        FrameRate::PRESETS
+            .into_iter()
+            .find(|frame_rate| frame_rate.matches(fps))
+    }
+
+    fn is_preset(&self) -> bool {
+        FrameRate::PRESETS.contains(self)
+    }
+
+    fn to_value(self) -> String {
+        if self.den == 1 {
+            self.num.to_string()
+        } else {
+            format!("{}/{}", self.num, self.den)
+        }
+    }
+}
+
+impl TryFrom<String> for FrameRate {
+    type Error = String;
+
+    fn try_from(value: String) -> Result<Self, Self::Error> {
+        FrameRate::parse(&value)
+    }
+}
+
+impl From<FrameRate> for String {
+    fn from(frame_rate: FrameRate) -> Self {
+        frame_rate.to_value()
+    }
+}
+
+impl Default for App {
+    fn default() -> Self {
+        Self::with_session(session::Session::default())
+    }
+}
+
+impl App {
+    fn with_session(session: session::Session) -> Self {
+        let mut app = Self {
+            file_queue: Vec::new(),
+            selected_file: None,
+            output_folder: None,
+            options: ProcessOptions::default(),
+            processing: false,
+            processing_task: None,
+            pause_requested: Arc::new(AtomicBool::new(false)),
+            current_file_index: 0,
+            timing: None,
+            progress: 0.0,
+            log_messages: Vec::new(),
+            terminal_logs: Vec::new(),
+            session,
+            next_sequence: 0,
+            frame_rate_confirmed: false,
+            custom_frame_rate: String::new(),
+            watch_health: None,
+            stats: stats::Stats::load(),
+            skip_list: skip_list::SkipList::load(),
+            output_versions: HashMap::new(),
+            projects: projects::Projects::load(),
+            project_name: String::new(),
+            analyses: Vec::new(),
+            analyzing: false,
+            media_server: media_server::ServerConfig::load(),
+            show_importer: false,
+            importing: false,
+            self_testing: false,
+            deep_probe_pending: false,
+            quick_convert: None,
+            compare_presets: [Some(CURRENT_SETTINGS.to_string()), None],
+            reconciliation: None,
+            reconciling: false,
+            temp_space: None,
+            autosaved: None,
+            when_finished: power::PowerAction::Nothing,
+            power_countdown: None,
+        };
+        // 清理上次崩溃或强制退出时残留的临时文件，与配置迁移和备份的提示一起显示
+        let cleaned = temp_files::clean_stale();
+        app.log_messages = config::take_notes();
+        app.log_messages.extend(cleaned);
+        // 上次的批处理被中断时，把尚未完成的文件作为暂停的批处理，可以从第一个未完成的文件继续
+        if let Some(journal) = journal::take_interrupted() {
+            let batch = journal.remaining();
+            let running = journal.interrupted_files();
+            app.log_messages.push(format!(
+                "⚠️ The previous batch was interrupted{}; {} unfinished file(s) can be resumed",
+                if running.is_empty() {
+                    String::new()
+                } else {
+                    format!(" while processing {}", running.join(", "))
+                },
+                batch.files.len()
+            ));
+            if !batch.files.is_empty() {
+                app.projects.paused = Some(batch);
+                if let Err(e) = app.projects.save() {
+                    app.log_messages.push(format!("Error: {e}"));
+                }
+            }
+        }
+        app
+    }
+}
+
+#[derive(Debug, Clone)]
+pub enum Message {
+    SelectInputFiles,
+    InputFilesSelected(Vec<PathBuf>),
+    FilesDropped(Vec<PathBuf>),
+    RemoveFileFromQueue(usize),
+    SkipFile(usize),
+    UnskipFile(PathBuf),
+    ActiveAreaChanged((active_area::Edge, String)),
+    TrimStartChanged(String),
+    TrimEndChanged(String),
+    AudioOffsetChanged(String),
+    SubtitleOffsetChanged(String),
+    AudioTrackToggled((usize, bool)),
+    AudioTracksReset,
+    SubtitleTrackToggled((usize, bool)),
+    SubtitleTracksReset,
+    SubtitleFileDropped(PathBuf),
+    AddExternalSubtitles,
+    ExternalSubtitlesSelected((PathBuf, Vec<PathBuf>)),
+    RemoveExternalSubtitle(usize),
+    JoinParts,
+    SplitParts,
+    ClearQueue,
+    ProjectSelected(String),
+    ProjectNameChanged(String),
+    SaveProject,
+    DeleteProject,
+    SelectQueueItem(usize),
+    ToggleQuickMode(bool),
+    ToggleAnnotateSource(bool),
+    NonDvModeSelected(NonDvMode),
+    ExistingOutputsSelected(ExistingOutputs),
+    SourceAfterSuccessSelected(SourceAfterSuccess),
+    ToggleVerifySource(bool),
+    ToggleVerifyAudio(bool),
+    ToggleCheckAudio(bool),
+    ToggleHdr10Fallback(bool),
+    ToggleOcrSubtitles(bool),
+    ToggleKeepUnsubtitled(bool),
+    ToggleExportPoster(bool),
+    UploadBackendSelected(UploadBackend),
+    UploadDestinationChanged(String),
+    PostCommandChanged(String),
+    ToggleRemoveAfterUpload(bool),
+    ExportReport,
+    ToggleAdvancedSettings,
+    DevicePresetSelected(DevicePreset),
+    MajorBrandChanged(String),
+    CompatibleBrandsChanged(String),
+    MuxExtraArgsChanged(String),
+    ToggleRpuWorkflow(bool),
+    ToggleBurnSubtitles(bool),
+    ToggleKeepRpu(bool),
+    StaggerSecondsSelected(u32),
+    MaxConcurrentJobsSelected(u32),
+    RetryCountSelected(u32),
+    ToggleBackgroundPriority(bool),
+    ToggleLowTempSpace(bool),
+    SelectTempFolder,
+    TempFolderSelected(Option<PathBuf>),
+    ClearTempFolder,
+    TempSpaceChecked(Option<u64>),
+    Eac3BitrateSelected(u32),
+    LosslessAudioSelected(LosslessAudio),
+    LoudnormTargetSelected(i32),
+    RunSelfTest,
+    SelfTestComplete(self_test::Report),
+    SelectRpuEditConfig,
+    RpuEditConfigSelected(Option<PathBuf>),
+    ToolVerbositySelected((&'static str, verbosity::Verbosity)),
+    ReportPathSelected(Option<PathBuf>),
+    ComparePresetSelected((usize, String)),
+    SaveComparison,
+    ComparisonPathSelected(Option<PathBuf>),
+    ReconcileFolder,
+    ReconcileFolderSelected(Option<PathBuf>),
+    ReconciliationComplete(Result<history::Reconciliation, String>),
+    QuickConvertConfirmed((PathBuf, bool)),
+    // 键盘导航
+    MoveSelection(i32),
+    RemoveSelected,
+    FocusNext,
+    FocusPrevious,
+    FileProbed(Box<(PathBuf, Result<probe::MediaInfo, String>)>),
+    DeepProbe,
+    DeepProbed(Box<(PathBuf, probe::DeepProbe)>),
+    ToggleDeepProbeBeforeStart(bool),
+    AnalyzeSelected,
+    AnalyzeFile,
+    AnalyzeFileSelected(Option<PathBuf>),
+    AnalysisComplete(Box<(PathBuf, Result<rpu_analysis::Analysis, String>)>),
+    ClearAnalyses,
+    // 从媒体服务器导入
+    ToggleImporter,
+    ServerKindSelected(media_server::ServerKind),
+    ServerUrlChanged(String),
+    ServerTokenChanged(String),
+    ServerPrefixChanged(String),
+    LocalPrefixChanged(String),
+    ImportFromServer,
+    ServerImportComplete(Result<media_server::Import, String>),
+    FileStarted(PathBuf),
+    EtaTick,
+    FileSucceeded(PathBuf),
+    // 路径、错误、最后的输出行、失败时运行的工具
+    FileFailed(Box<(PathBuf, String, Vec<String>, String)>),
+    ToggleCollectStats(bool),
+    ClearStats,
+    SelectOutputFolder,
+    OutputFolderSelected(Option<PathBuf>),
+    SubtitleModeSelected(SubtitleMode),
+    SdhPreferenceSelected(SdhPreference),
+    ToggleAllAudioTracks(bool),
+    ToggleSkipCommentary(bool),
+    ToggleTruehdAc3Core(bool),
+    ToggleTruehdToEac3(bool),
+    ToggleAacStereo(bool),
+    ToggleVideoOnly(bool),
+    ToggleLoudnorm(bool),
+    DefaultLanguageChanged(String),
+    FrameRateSelected(FrameRate),
+    CustomFrameRateChanged(String),
+    FrameRateConfirmed(bool),
+    DvProfileSelected(DvProfile),
+    SampleEntrySelected(SampleEntry),
+    EnhancementLayerSelected(EnhancementLayer),
+    StartProcessing,
+    CancelProcessing,
+    TogglePause,
+    // 批处理已暂停，附带尚未处理的文件
+    BatchPaused(Vec<PathBuf>),
+    ResumeBatch,
+    ProcessingStep(String),
+    ProcessingProgress(f32),
+    ProcessingComplete(Result<(), String>),
+    ClearLog,
+    // 新增：终端日志消息
+    TerminalOutput(String),
+    ClearTerminal,
+    TabSelected(Tab),
+    TerminalFilterChanged(String),
+    TerminalErrorsOnlyToggled(bool),
+    QueueSortSelected(QueueSort),
+    WindowMoved(Point),
+    WindowResized(Size),
+    WindowCloseRequested(iced::window::Id),
+    WhenFinishedSelected(power::PowerAction),
+    PowerCountdownTick,
+    CancelPowerAction,
+    PowerActionDone(Result<(), String>),
+    // 监视文件夹
+    SelectWatchFolder,
+    WatchFolderSelected(Option<PathBuf>),
+    StopWatching,
+    WatchHealthChanged(watch::WatchHealth),
+    WatchFilesArrived(Vec<PathBuf>),
+    WatchFileReplaced(PathBuf),
+    ReconvertConfirmed((PathBuf, bool)),
+    ReplacedPolicySelected(watch::ReplacedPolicy),
+}
+
+impl App {
+    fn subscription(&self) -> iced::Subscription<Message> {
+        let window_events = event::listen_with(|event, _status, id| match event {
+            Event::Window(iced::window::Event::FileDropped(path)) => {
+                let extension = path.extension()?.to_string_lossy().to_lowercase();
+                if extension == "mkv" {
+                    return Some(Message::FilesDropped(vec![path]));
+                }
+                // 字幕文件添加到选中的队列项
+                if pipeline::SUBTITLE_EXTENSIONS.contains(&extension.as_str()) {
+                    return Some(Message::SubtitleFileDropped(path));
+                }
+                None
+            }
+            Event::Window(iced::window::Event::Moved(position)) => {
+                Some(Message::WindowMoved(position))
+            }
+            Event::Window(iced::window::Event::Resized(size)) => Some(Message::WindowResized(size)),
+            Event::Window(iced::window::Event::CloseRequested) => {
+                Some(Message::WindowCloseRequested(id))
+            }
+            _ => None,
+        });
+
+        let mut subscriptions = vec![window_events, keyboard::on_key_press(handle_key_press)];
+        if let Some(folder) = self.session.watch_folder.clone() {
+            subscriptions.push(iced::Subscription::run_with_id(
+                folder.clone(),
+                watch::watch_folder(folder),
+            ));
+        }
+        // 处理中定时刷新，外部工具没有进度输出时预计剩余时间也会更新
+        if self.timing.is_some() {
+            subscriptions
+                .push(iced::time::every(Duration::from_secs(15)).map(|_| Message::EtaTick));
+        }
+        if self.power_countdown.is_some() {
+            subscriptions.push(
+                iced::time::every(Duration::from_secs(1)).map(|_| Message::PowerCountdownTick),
+            );
+        }
+        iced::Subscription::batch(subscriptions)
+    }
+
+    fn update(&mut self, message: Message) -> Task<Message> {
+        let task = self.handle(message);
+        self.autosave_queue();
+        task
+    }
+
+    // 队列、输出文件夹或选项变化后立即保存，意外关闭窗口或程序崩溃后启动时恢复
+    fn autosave_queue(&mut self) {
+        let project = self.current_project(self.projects.active.clone().unwrap_or_default());
+        let Ok(snapshot) = serde_json::to_value(&project) else {
+            return;
+        };
+        if self.autosaved.as_ref() == Some(&snapshot) {
+            return;
+        }
+        self.autosaved = Some(snapshot);
+        self.projects.current = Some(project);
+        if let Err(e) = self.projects.save() {
+            self.log_messages
+                .push(format!("⚠️ Could not save the queue: {e}"));
+        }
+    }
+
+    fn handle(&mut self, message: Message) -> Task<Message> {
+        match message {
+            Message::SelectInputFiles => {
+                Task::perform(select_input_files(), Message::InputFilesSelected)
+            }
+            Message::InputFilesSelected(files) => self.enqueue_files(files),
+            Message::FilesDropped(files) => {
+                if self.session.quick_mode
+                    && !self.processing
+                    && files.len() == 1
+                    && self.skip_list.reason(&files[0]).is_none()
+                {
+                    Task::perform(
+                        confirm_quick_convert(files[0].clone()),
+                        Message::QuickConvertConfirmed,
+                    )
+                } else {
+                    self.enqueue_files(files)
+                }
+            }
+            Message::RemoveFileFromQueue(index) => {
+                if index < self.file_queue.len() {
+                    self.file_queue.remove(index);
+                    self.selected_file = match self.selected_file {
+                        Some(selected) if selected == index => None,
+                        Some(selected) if selected > index => Some(selected - 1),
+                        other => other,
+                    };
+                }
+                Task::none()
+            }
+            Message::SkipFile(index) => {
+                let Some(item) = self.file_queue.get(index) else {
+                    return Task::none();
+                };
+                let reason = match &item.failure {
+                    Some((error, _)) => error.lines().next().unwrap_or_default().to_string(),
+                    None => "Marked as never process".to_string(),
+                };
+                self.skip_list.insert(&item.path, reason);
+                self.log_messages.push(format!(
+                    "🚫 {} added to the skip list",
+                    item.path.file_name().unwrap_or_default().to_string_lossy()
+                ));
+                if let Err(e) = self.skip_list.save() {
+                    self.log_messages.push(format!("Error: {e}"));
+                }
+                self.update(Message::RemoveFileFromQueue(index))
+            }
+            Message::ActiveAreaChanged((edge, value)) => {
+                if let Some(item) = self
+                    .selected_file
+                    .and_then(|index| self.file_queue.get_mut(index))
+                {
+                    item.active_area.set(edge, value);
+                }
+                Task::none()
+            }
+            Message::TrimStartChanged(value) => {
+                if let Some(item) = self
+                    .selected_file
+                    .and_then(|index| self.file_queue.get_mut(index))
+                {
+                    item.trim_start = value;
+                }
+                Task::none()
+            }
+            Message::TrimEndChanged(value) => {
+                if let Some(item) = self
+                    .selected_file
+                    .and_then(|index| self.file_queue.get_mut(index))
+                {
+                    item.trim_end = value;
+                }
+                Task::none()
+            }
+            Message::AudioOffsetChanged(value) => {
+                if let Some(item) = self
+                    .selected_file
+                    .and_then(|index| self.file_queue.get_mut(index))
+                {
+                    item.audio_offset = value;
+                }
+                Task::none()
+            }
+            Message::SubtitleOffsetChanged(value) => {
+                if let Some(item) = self
+                    .selected_file
+                    .and_then(|index| self.file_queue.get_mut(index))
+                {
+                    item.subtitle_offset = value;
+                }
+                Task::none()
+            }
+            Message::AudioTrackToggled((index, enabled)) => {
+                let auto_audio = self.options.auto_audio();
+                if let Some(item) = self
+                    .selected_file
+                    .and_then(|selected| self.file_queue.get_mut(selected))
+                    && let Some(Ok(info)) = &item.info
+                {
+                    // 从当前（可能是自动的）选择开始修改，保持源文件中的轨道顺序
+                    let mut tracks = info
+                        .audio_tracks(&item.audio_tracks, auto_audio)
+                        .iter()
+                        .map(|audio| audio.index)
+                        .filter(|track| *track != index)
+                        .collect::<Vec<_>>();
+                    if enabled {
+                        tracks.push(index);
+                        tracks.sort_unstable();
+                    }
+                    // 至少保留一条音轨
+                    if !tracks.is_empty() {
+                        item.audio_tracks = tracks;
+                    }
+                }
+                Task::none()
+            }
+            Message::SubtitleTrackToggled((index, enabled)) => {
+                if let Some(item) = self
+                    .selected_file
+                    .and_then(|selected| self.file_queue.get_mut(selected))
+                    && let Some(Ok(info)) = &item.info
+                {
+                    // 从当前（可能是自动的）选择开始修改；可以一条都不选
+                    let mut tracks = self
+                        .options
+                        .subtitle_tracks(info, &item.audio_tracks, item.subtitle_tracks.as_deref())
+                        .iter()
+                        .map(|subtitle| subtitle.index)
+                        .filter(|track| *track != index)
+                        .collect::<Vec<_>>();
+                    if enabled {
+                        tracks.push(index);
+                        tracks.sort_unstable();
+                    }
+                    item.subtitle_tracks = Some(tracks);
+                }
+                Task::none()
+            }
+            Message::SubtitleTracksReset => {
+                if let Some(item) = self
+                    .selected_file
+                    .and_then(|selected| self.file_queue.get_mut(selected))
+                {
+                    item.subtitle_tracks = None;
+                }
+                Task::none()
+            }
+            Message::SubtitleFileDropped(path) => {
+                match self
+                    .selected_file
+                    .and_then(|selected| self.file_queue.get(selected))
+                {
+                    Some(item) => {
+                        let item_path = item.path.clone();
+                        self.attach_subtitles(&item_path, vec![path]);
+                    }
+                    None => self.log_messages.push(format!(
+                        "⚠️ Select a file in the queue before dropping the subtitle {}",
+                        path.file_name().unwrap_or_default().to_string_lossy()
+                    )),
+                }
+                Task::none()
+            }
+            Message::AddExternalSubtitles => {
+                match self
+                    .selected_file
+                    .and_then(|selected| self.file_queue.get(selected))
+                {
+                    Some(item) => {
+                        let item_path = item.path.clone();
+                        Task::perform(select_subtitle_files(), move |files| {
+                            Message::ExternalSubtitlesSelected((item_path.clone(), files))
+                        })
+                    }
+                    None => Task::none(),
+                }
+            }
+            Message::ExternalSubtitlesSelected((item_path, files)) => {
+                self.attach_subtitles(&item_path, files);
+                Task::none()
+            }
+            Message::RemoveExternalSubtitle(index) => {
+                if let Some(item) = self
+                    .selected_file
+                    .and_then(|selected| self.file_queue.get_mut(selected))
+                    && index < item.external_subtitles.len()
+                {
+                    item.external_subtitles.remove(index);
+                }
+                Task::none()
+            }
+            Message::AudioTracksReset => {
+                if let Some(item) = self
+                    .selected_file
+                    .and_then(|selected| self.file_queue.get_mut(selected))
+                {
+                    item.audio_tracks.clear();
+                }
+                Task::none()
+            }
+            Message::UnskipFile(path) => {
+                self.skip_list.remove(&path);
+                if let Err(e) = self.skip_list.save() {
+                    self.log_messages.push(format!("Error: {e}"));
+                }
+                Task::none()
+            }
+            Message::ClearQueue => {
+                self.file_queue.clear();
+                self.selected_file = None;
+                Task::none()
+            }
+            Message::ProjectSelected(name) => {
+                if self.processing {
+                    self.log_messages
+                        .push("❌ Cannot switch queues while processing".to_string());
+                    return Task::none();
+                }
+                if self.projects.active.as_deref() == Some(name.as_str()) {
+                    return Task::none();
+                }
+                self.store_active_project();
+                let task = self.open_project(&name);
+                if let Err(e) = self.projects.save() {
+                    self.log_messages.push(format!("Error: {e}"));
+                }
+                task
+            }
+            Message::ProjectNameChanged(name) => {
+                self.project_name = name;
+                Task::none()
+            }
+            Message::SaveProject => {
+                let name = self.project_name.trim().to_string();
+                if name.is_empty() {
+                    return Task::none();
+                }
+                let project = self.current_project(name.clone());
+                self.projects.upsert(project);
+                self.projects.active = Some(name.clone());
+                self.project_name.clear();
+                match self.projects.save() {
+                    Ok(()) => self.log_messages.push(format!("💾 Saved queue \"{name}\"")),
+                    Err(e) => self.log_messages.push(format!("Error: {e}")),
+                }
+                Task::none()
+            }
+            Message::DeleteProject => {
+                if let Some(name) = self.projects.active.clone() {
+                    self.projects.remove(&name);
+                    match self.projects.save() {
+                        Ok(()) => self
+                            .log_messages
+                            .push(format!("🗑 Deleted queue \"{name}\"")),
+                        Err(e) => self.log_messages.push(format!("Error: {e}")),
+                    }
+                }
+                Task::none()
+            }
+            Message::SelectQueueItem(index) => {
+                self.selected_file = if self.selected_file == Some(index) {
+                    None
+                } else {
+                    Some(index)
+                };
+                Task::none()
+            }
+            Message::MoveSelection(delta) => {
+                if self.file_queue.is_empty() {
+                    return Task::none();
+                }
+                let last = self.file_queue.len() - 1;
+                let index = match self.selected_file {
+                    Some(index) => (index as i64 + delta as i64).clamp(0, last as i64) as usize,
+                    None if delta < 0 => last,
+                    None => 0,
+                };
+                self.selected_file = Some(index);
+                // 滚动队列，保持选中项可见
+                let offset = if last == 0 {
+                    0.0
+                } else {
+                    index as f32 / last as f32
+                };
+                scrollable::snap_to(
+                    queue_scroll_id(),
+                    scrollable::RelativeOffset { x: 0.0, y: offset },
+                )
+            }
+            Message::JoinParts => {
+                let Some(index) = self.selected_file.filter(|_| !self.processing) else {
+                    return Task::none();
+                };
+                let joinable = self.joinable_parts(index);
+                // 已分析的分段先检查与第 1 段一致，处理时会再检查一次
+                if let Some(Ok(first)) = &self.file_queue[index].info {
+                    for &other in &joinable {
+                        let part = &self.file_queue[other];
+                        if let Some(Ok(info)) = &part.info
+                            && let Some(mismatch) = parts::mismatch(first, info)
+                        {
+                            self.log_messages.push(format!(
+                                "❌ Cannot join {}: {mismatch}",
+                                part.path.file_name().unwrap_or_default().to_string_lossy()
+                            ));
+                            return Task::none();
+                        }
+                    }
+                }
+                let (paths, size) =
+                    joinable
+                        .iter()
+                        .fold((Vec::new(), 0), |(mut paths, size), &other| {
+                            paths.push(self.file_queue[other].path.clone());
+                            (paths, size + self.file_queue[other].size)
+                        });
+                let path = self.file_queue[index].path.clone();
+                self.file_queue.retain(|item| !paths.contains(&item.path));
+                self.selected_file = self.file_queue.iter().position(|item| item.path == path);
+                if let Some(item) = self
+                    .selected_file
+                    .and_then(|index| self.file_queue.get_mut(index))
+                {
+                    item.size += size;
+                    item.parts = paths;
+                    self.log_messages.push(format!(
+                        "🔗 {} will be joined from {} parts",
+                        path.file_name().unwrap_or_default().to_string_lossy(),
+                        item.parts.len() + 1
+                    ));
+                }
+                Task::none()
+            }
+            Message::SplitParts => {
+                let Some(item) = self
+                    .selected_file
+                    .filter(|_| !self.processing)
+                    .and_then(|index| self.file_queue.get_mut(index))
+                else {
+                    return Task::none();
+                };
+                let parts = std::mem::take(&mut item.parts);
+                item.size = std::fs::metadata(&item.path)
+                    .map(|meta| meta.len())
+                    .unwrap_or(0);
+                self.enqueue_files(parts)
+            }
+            Message::RemoveSelected => match self.selected_file {
+                Some(index) if !self.processing => self.update(Message::RemoveFileFromQueue(index)),
+                _ => Task::none(),
+            },
+            Message::FocusNext => iced::widget::focus_next(),
+            Message::FocusPrevious => iced::widget::focus_previous(),
+            Message::FileProbed(probed) => {
+                let (path, result) = *probed;
+                for item in self.file_queue.iter_mut().filter(|item| item.path == path) {
+                    item.info = Some(result.clone());
+                }
+                if self.quick_convert.as_ref() == Some(&path) {
+                    self.quick_convert = None;
+                    return self.start_quick_convert(path, result.is_ok());
+                }
+                Task::none()
+            }
+            Message::DeepProbe => match self.selected_file {
+                Some(index) => self.deep_probe(&[index]),
+                None => Task::none(),
+            },
+            Message::DeepProbed(probed) => {
+                let (path, deep) = *probed;
+                if let Some(variable) = deep.variable_frame_rate() {
+                    self.log_messages.push(format!(
+                        "⚠ {}: variable frame rate detected ({:.3}-{:.3} fps)",
+                        path.file_name().unwrap_or_default().to_string_lossy(),
+                        variable.0,
+                        variable.1
+                    ));
+                    // 新的帧率警告需要重新确认
+                    self.frame_rate_confirmed = false;
+                }
+                for item in self.file_queue.iter_mut().filter(|item| item.path == path) {
+                    item.deep_probing = false;
+                    if let Some(Ok(info)) = &mut item.info {
+                        info.deep = Some(deep.clone());
+                    }
+                }
+                // 开始前的深度分析全部完成后继续开始处理；出现新的警告时由用户确认后再开始
+                if self.deep_probe_pending && !self.file_queue.iter().any(|item| item.deep_probing)
+                {
+                    self.deep_probe_pending = false;
+                    return self.update(Message::StartProcessing);
+                }
+                Task::none()
+            }
+            Message::ToggleDeepProbeBeforeStart(enabled) => {
+                self.session.deep_probe_before_start = enabled;
+                Task::none()
+            }
+            Message::AnalyzeSelected => {
+                match self
+                    .selected_file
+                    .and_then(|index| self.file_queue.get(index))
+                {
+                    Some(item) => self.analyze(item.path.clone()),
+                    None => Task::none(),
+                }
+            }
+            Message::AnalyzeFile => {
+                Task::perform(select_analysis_file(), Message::AnalyzeFileSelected)
+            }
+            Message::AnalyzeFileSelected(Some(path)) => self.analyze(path),
+            Message::AnalyzeFileSelected(None) => Task::none(),
+            Message::AnalysisComplete(analyzed) => {
+                let (path, result) = *analyzed;
+                self.analyzing = false;
+                match result {
+                    Ok(analysis) => {
+                        self.log_messages.push(format!(
+                            "📈 Analyzed {} scenes in {}",
+                            analysis.scenes.len(),
+                            path.file_name().unwrap_or_default().to_string_lossy()
+                        ));
+                        self.analyses.push(analysis);
+                    }
+                    Err(e) => self.log_messages.push(format!(
+                        "❌ Failed to analyze {}: {e}",
+                        path.file_name().unwrap_or_default().to_string_lossy()
+                    )),
+                }
+                Task::none()
+            }
+            Message::ClearAnalyses => {
+                self.analyses.clear();
+                Task::none()
+            }
+            Message::ToggleImporter => {
+                self.show_importer = !self.show_importer;
+                Task::none()
+            }
+            Message::ServerKindSelected(kind) => {
+                self.media_server.kind = kind;
+                Task::none()
+            }
+            Message::ServerUrlChanged(url) => {
+                self.media_server.url = url;
+                Task::none()
+            }
+            Message::ServerTokenChanged(token) => {
+                self.media_server.token = token;
+                Task::none()
+            }
+            Message::ServerPrefixChanged(prefix) => {
+                self.media_server.server_prefix = prefix;
+                Task::none()
+            }
+            Message::LocalPrefixChanged(prefix) => {
+                self.media_server.local_prefix = prefix;
+                Task::none()
+            }
+            Message::ImportFromServer => {
+                if self.importing || self.media_server.validation_error().is_some() {
+                    return Task::none();
+                }
+                if let Err(e) = self.media_server.save() {
+                    self.log_messages.push(format!("Error: {e}"));
+                }
+                self.importing = true;
+                self.log_messages.push(format!(
+                    "📚 Querying {} for Dolby Vision MKV files without an MP4 version...",
+                    self.media_server.kind
+                ));
+                Task::perform(
+                    import_from_server(self.media_server.clone()),
+                    Message::ServerImportComplete,
+                )
+            }
+            Message::ServerImportComplete(result) => {
+                self.importing = false;
+                match result {
+                    Ok(import) => {
+                        for path in &import.missing {
+                            self.log_messages.push(format!(
+                                "⚠️ Not found locally, check the path mapping: {}",
+                                path.to_string_lossy()
+                            ));
+                        }
+                        let files = import
+                            .files
+                            .into_iter()
+                            .filter(|path| !self.file_queue.iter().any(|item| &item.path == path))
+                            .collect::<Vec<_>>();
+                        self.log_messages.push(format!(
+                            "📚 Found {} file(s) to convert ({} already have an MP4, {} not found locally)",
+                            files.len(),
+                            import.converted,
+                            import.missing.len()
+                        ));
+                        self.enqueue_files(files)
+                    }
+                    Err(e) => {
+                        self.log_messages
+                            .push(format!("❌ Failed to import from media server: {e}"));
+                        Task::none()
+                    }
+                }
+            }
+            Message::FileStarted(path) => {
+                if let Some(timing) = &mut self.timing {
+                    timing.start(path);
+                }
+                Task::none()
+            }
+            Message::EtaTick => Task::none(),
+            Message::FileSucceeded(path) => {
+                let size = self
+                    .file_queue
+                    .iter()
+                    .find(|item| item.path == path)
+                    .map_or(0, |item| item.size);
+                if let Some(rate) = self
+                    .timing
+                    .as_mut()
+                    .and_then(|timing| timing.finish(&path, size))
+                {
+                    self.session.throughput = Some(rate);
+                }
+                self.record_stats(&path, None);
+                Task::none()
+            }
+            Message::FileFailed(failed) => {
+                let (path, error, tail, tool) = *failed;
+                if let Some(timing) = &mut self.timing {
+                    timing.remove(&path);
+                }
+                for item in self.file_queue.iter_mut().filter(|item| item.path == path) {
+                    item.failure = Some((error.clone(), tail.clone()));
+                }
+                self.record_stats(&path, Some(&tool));
+                Task::none()
+            }
+            Message::ToggleCollectStats(enabled) => {
+                self.session.collect_stats = enabled;
+                Task::none()
+            }
+            Message::ClearStats => {
+                self.stats = stats::Stats::default();
+                if let Err(e) = self.stats.save() {
+                    self.log_messages.push(format!("Error: {e}"));
+                }
+                Task::none()
+            }
+            Message::SelectOutputFolder => {
+                Task::perform(select_output_folder(), Message::OutputFolderSelected)
+            }
+            Message::OutputFolderSelected(path) => {
+                self.output_folder = path;
+                Task::none()
+            }
+            Message::ToggleAllAudioTracks(enabled) => {
+                self.options.all_audio_tracks = enabled;
+                Task::none()
+            }
+            Message::ToggleSkipCommentary(enabled) => {
+                self.options.skip_commentary = enabled;
+                Task::none()
+            }
+            Message::ToggleTruehdAc3Core(enabled) => {
+                self.options.truehd_ac3_core = enabled;
+                Task::none()
+            }
+            Message::ToggleAacStereo(enabled) => {
+                self.options.aac_stereo = enabled;
+                Task::none()
+            }
+            Message::ToggleVideoOnly(enabled) => {
+                self.options.video_only = enabled;
+                Task::none()
+            }
+            Message::ToggleLoudnorm(enabled) => {
+                self.options.loudnorm = enabled;
+                Task::none()
+            }
+            Message::ToggleTruehdToEac3(enabled) => {
+                self.options.truehd_to_eac3 = enabled;
+                Task::none()
+            }
+            Message::SubtitleModeSelected(mode) => {
+                self.options.subtitle_mode = mode;
+                Task::none()
+            }
+            Message::SdhPreferenceSelected(preference) => {
+                self.options.sdh_subtitles = preference;
+                Task::none()
+            }
+            Message::DefaultLanguageChanged(language) => {
+                self.options.default_language = language;
+                Task::none()
+            }
+            Message::FrameRateSelected(frame_rate) => {
+                self.options.frame_rate = frame_rate;
+                self.custom_frame_rate.clear();
+                self.frame_rate_confirmed = false;
+                Task::none()
+            }
+            Message::CustomFrameRateChanged(value) => {
+                if let Ok(frame_rate) = FrameRate::parse(&value) {
+                    self.options.frame_rate = frame_rate;
+                    self.frame_rate_confirmed = false;
+                }
+                self.custom_frame_rate = value;
+                Task::none()
+            }
+            Message::DvProfileSelected(profile) => {
+                self.options.dv_profile = profile;
+                Task::none()
+            }
+            Message::SampleEntrySelected(entry) => {
+                self.options.sample_entry = entry;
+                Task::none()
+            }
+            Message::EnhancementLayerSelected(layer) => {
+                self.options.enhancement_layer = layer;
+                Task::none()
+            }
+            Message::FrameRateConfirmed(confirmed) => {
+                self.frame_rate_confirmed = confirmed;
+                Task::none()
+            }
+            Message::StartProcessing => {
+                if self.deep_probe_pending {
+                    return Task::none();
+                }
+                let unprobed = self.deep_probe_candidates();
+                if self.session.deep_probe_before_start && !unprobed.is_empty() && self.can_start()
+                {
+                    self.deep_probe_pending = true;
+                    self.log_messages.push(format!(
+                        "🔍 Deep probing {} file(s) before starting...",
+                        unprobed.len()
+                    ));
+                    self.deep_probe(&unprobed)
+                } else if let Some(output) = self.output_folder.clone()
+                    && self.can_start()
+                {
+                    let files = self
+                        .file_queue
+                        .iter()
+                        .map(|item| item.path.clone())
+                        .collect();
+                    self.start_processing(files, output, self.options.clone())
+                } else {
+                    Task::none()
+                }
+            }
+            Message::ToggleAnnotateSource(enabled) => {
+                self.options.annotate_source = enabled;
+                Task::none()
+            }
+            Message::ToggleVerifySource(enabled) => {
+                self.options.verify_source = enabled;
+                Task::none()
+            }
+            Message::ToggleVerifyAudio(enabled) => {
+                self.options.verify_audio = enabled;
+                Task::none()
+            }
+            Message::ToggleCheckAudio(enabled) => {
+                self.options.check_audio = enabled;
+                Task::none()
+            }
+            Message::ToggleHdr10Fallback(enabled) => {
+                self.options.hdr10_fallback = enabled;
+                Task::none()
+            }
+            Message::ToggleOcrSubtitles(enabled) => {
+                self.options.ocr_subtitles = enabled;
+                Task::none()
+            }
+            Message::ToggleKeepUnsubtitled(enabled) => {
+                self.options.keep_unsubtitled = enabled;
+                Task::none()
+            }
+            Message::UploadBackendSelected(backend) => {
+                self.options.upload_backend = backend;
+                Task::none()
+            }
+            Message::UploadDestinationChanged(destination) => {
+                self.options.upload_destination = destination;
+                Task::none()
+            }
+            Message::PostCommandChanged(command) => {
+                self.options.post_command = command;
+                Task::none()
+            }
+            Message::ToggleRemoveAfterUpload(enabled) => {
+                self.options.remove_after_upload = enabled;
+                Task::none()
+            }
+            Message::ToggleExportPoster(enabled) => {
+                self.options.export_poster = enabled;
+                Task::none()
+            }
+            Message::NonDvModeSelected(mode) => {
+                self.options.non_dv_mode = mode;
+                Task::none()
+            }
+            Message::ExistingOutputsSelected(policy) => {
+                self.options.existing_outputs = policy;
+                Task::none()
+            }
+            Message::SourceAfterSuccessSelected(policy) => {
+                self.options.source_after_success = policy;
+                Task::none()
+            }
+            Message::ToggleAdvancedSettings => {
+                self.session.show_advanced = !self.session.show_advanced;
+                Task::none()
+            }
+            Message::DevicePresetSelected(preset) => {
+                self.options.device_preset = preset;
+                if let Some((major, compatible)) = preset.brands() {
+                    self.options.major_brand = major.to_string();
+                    self.options.compatible_brands = compatible.to_string();
+                }
+                Task::none()
+            }
+            Message::MajorBrandChanged(brand) => {
+                self.options.major_brand = brand;
+                self.options.device_preset = DevicePreset::Custom;
+                Task::none()
+            }
+            Message::CompatibleBrandsChanged(brands) => {
+                self.options.compatible_brands = brands;
+                self.options.device_preset = DevicePreset::Custom;
+                Task::none()
+            }
+            Message::MuxExtraArgsChanged(args) => {
+                self.options.mux_extra_args = args;
+                Task::none()
+            }
+            Message::ToggleRpuWorkflow(enabled) => {
+                self.options.rpu_workflow = enabled;
+                Task::none()
+            }
+            Message::ToggleBurnSubtitles(enabled) => {
+                self.options.burn_subtitles = enabled;
+                Task::none()
+            }
+            Message::ToggleKeepRpu(enabled) => {
+                self.options.keep_rpu = enabled;
+                Task::none()
+            }
+            Message::RunSelfTest => {
+                if self.processing || self.self_testing {
+                    return Task::none();
+                }
+                self.self_testing = true;
+                self.progress = 0.0;
+                self.log_messages.clear();
+                self.terminal_logs.clear();
+                self.log_messages
+                    .push("🩺 Running self-test on a generated sample...".to_string());
+                Task::run(
+                    iced::stream::channel(100, |mut sender| async move {
+                        let report = self_test::run_self_test(&mut sender).await;
+                        let _ = sender.send(Message::SelfTestComplete(report)).await;
+                    }),
+                    std::convert::identity,
+                )
+            }
+            Message::SelfTestComplete(report) => {
+                self.self_testing = false;
+                self.progress = if report.passed() { 1.0 } else { 0.0 };
+                self.log_messages.extend(report.logs.iter().cloned());
+                self.log_messages.extend(report.summary());
+                Task::none()
+            }
+            Message::Eac3BitrateSelected(bitrate) => {
+                self.options.eac3_bitrate = bitrate;
+                Task::none()
+            }
+            Message::LosslessAudioSelected(target) => {
+                self.options.lossless_audio = target;
+                Task::none()
+            }
+            Message::LoudnormTargetSelected(target) => {
+                self.options.loudnorm_target = target;
+                Task::none()
+            }
+            Message::StaggerSecondsSelected(seconds) => {
+                self.options.stagger_seconds = seconds;
+                Task::none()
+            }
+            Message::MaxConcurrentJobsSelected(jobs) => {
+                self.options.max_concurrent_jobs = jobs;
+                Task::none()
+            }
+            Message::RetryCountSelected(count) => {
+                self.options.retry_count = count;
+                Task::none()
+            }
+            Message::ToggleBackgroundPriority(enabled) => {
+                self.options.background_priority = enabled;
+                Task::none()
+            }
+            Message::ToggleLowTempSpace(enabled) => {
+                self.options.low_temp_space = enabled;
+                Task::none()
+            }
+            Message::SelectTempFolder => {
+                Task::perform(select_temp_folder(), Message::TempFolderSelected)
+            }
+            Message::TempFolderSelected(None) => Task::none(),
+            Message::TempFolderSelected(folder) => {
+                self.options.temp_folder = folder;
+                self.check_temp_space()
+            }
+            Message::ClearTempFolder => {
+                self.options.temp_folder = None;
+                self.check_temp_space()
+            }
+            Message::TempSpaceChecked(space) => {
+                self.temp_space = space;
+                Task::none()
+            }
+            Message::SelectRpuEditConfig => {
+                Task::perform(select_rpu_edit_config(), Message::RpuEditConfigSelected)
+            }
+            Message::RpuEditConfigSelected(path) => {
+                self.options.rpu_edit_config = path;
+                Task::none()
+            }
+            Message::ToolVerbositySelected((tool, level)) => {
+                self.options.tool_verbosity.set(tool, level);
+                Task::none()
+            }
+            Message::ExportReport => {
+                Task::perform(select_report_path(), Message::ReportPathSelected)
+            }
+            Message::ReportPathSelected(Some(path)) => {
+                let is_markdown = path
+                    .extension()
+                    .is_some_and(|extension| extension.eq_ignore_ascii_case("md"));
+                let report = if is_markdown {
+                    report::markdown_report(&self.file_queue, &self.options)
+                } else {
+                    report::json_report(&self.file_queue, &self.options)
+                };
+                match std::fs::write(&path, report) {
+                    Ok(()) => self
+                        .log_messages
+                        .push(format!("📄 Report saved to {}", path.to_string_lossy())),
+                    Err(e) => self
+                        .log_messages
+                        .push(format!("❌ Failed to save report: {e}")),
+                }
+                Task::none()
+            }
+            Message::ReportPathSelected(None) => Task::none(),
+            Message::ComparePresetSelected((slot, name)) => {
+                self.compare_presets[slot] = Some(name);
+                Task::none()
+            }
+            Message::SaveComparison => {
+                Task::perform(select_comparison_path(), Message::ComparisonPathSelected)
+            }
+            Message::ComparisonPathSelected(Some(path)) => {
+                let Some([a, b]) = self.compared_presets() else {
+                    return Task::none();
+                };
+                let report =
+                    report::comparison_report(&self.file_queue, (&a.0, &a.1), (&b.0, &b.1));
+                match std::fs::write(&path, report) {
+                    Ok(()) => self.log_messages.push(format!(
+                        "📄 Preset comparison saved to {}",
+                        path.to_string_lossy()
+                    )),
+                    Err(e) => self
+                        .log_messages
+                        .push(format!("❌ Failed to save preset comparison: {e}")),
+                }
+                Task::none()
+            }
+            Message::ComparisonPathSelected(None) => Task::none(),
+            Message::ReconcileFolder => {
+                Task::perform(select_library_folder(), Message::ReconcileFolderSelected)
+            }
+            Message::ReconcileFolderSelected(Some(folder)) => {
+                if self.reconciling {
+                    return Task::none();
+                }
+                self.reconciling = true;
+                // 队列中裁剪或拼接的文件输出时长与源文件不同，不比较时长
+                let queued = self
+                    .file_queue
+                    .iter()
+                    .map(|item| {
+                        let whole = item.trim_start.trim().is_empty()
+                            && item.trim_end.trim().is_empty()
+                            && item.parts.is_empty();
+                        let duration = match &item.info {
+                            Some(Ok(info)) if whole => info.duration,
+                            _ => None,
+                        };
+                        (item.path.clone(), duration)
+                    })
+                    .collect();
+                Task::perform(
+                    reconcile_folder(folder, queued),
+                    Message::ReconciliationComplete,
+                )
+            }
+            Message::ReconcileFolderSelected(None) => Task::none(),
+            Message::ReconciliationComplete(result) => {
+                self.reconciling = false;
+                self.reconciliation = Some(result);
+                Task::none()
+            }
+            Message::ToggleQuickMode(enabled) => {
+                self.session.quick_mode = enabled;
+                Task::none()
+            }
+            Message::QuickConvertConfirmed((path, confirmed)) => {
+                if !confirmed {
+                    return Task::none();
+                }
+                // 分析完成后才能检查帧率，收到 FileProbed 时再开始
+                let task = self.enqueue_files(vec![path.clone()]);
+                if self.file_queue.iter().any(|item| item.path == path) {
+                    self.quick_convert = Some(path);
+                }
+                task
+            }
+            Message::ProcessingStep(step) => {
+                self.log_messages.push(step);
+                Task::none()
+            }
+            Message::ProcessingProgress(progress) => {
+                self.progress = progress;
+                Task::none()
+            }
+            Message::ProcessingComplete(result) => {
+                journal::finish();
+                self.processing = false;
+                self.processing_task = None;
+                self.timing = None;
+                match result {
+                    Ok(_) => {
+                        self.log_messages
+                            .push("✅ Processing completed successfully!".to_string());
+                        self.progress = 1.0;
+                    }
+                    Err(err) => {
+                        self.log_messages
+                            .push(format!("❌ Processing failed: {err}"));
+                        self.progress = 0.0;
+                    }
+                }
+                // 执行或取消后恢复为 Do Nothing，下一个批次需要重新选择
+                if self.when_finished != power::PowerAction::Nothing {
+                    self.power_countdown = Some(power::COUNTDOWN_SECONDS);
+                    self.log_messages.push(format!(
+                        "⏻ {} in {}s, click Cancel to stay awake",
+                        self.when_finished,
+                        power::COUNTDOWN_SECONDS
+                    ));
+                }
+                self.check_temp_space()
+            }
+            Message::WhenFinishedSelected(action) => {
+                self.when_finished = action;
+                Task::none()
+            }
+            Message::PowerCountdownTick => {
+                let Some(remaining) = self.power_countdown else {
+                    return Task::none();
+                };
+                if remaining > 1 {
+                    self.power_countdown = Some(remaining - 1);
+                    return Task::none();
+                }
+                self.power_countdown = None;
+                let action = std::mem::take(&mut self.when_finished);
+                // 关机时程序可能来不及处理关闭窗口的事件，先保存界面状态
+                if let Err(e) = self.session.save() {
+                    self.log_messages.push(format!("Error: {e}"));
+                }
+                self.log_messages.push(format!("⏻ {}...", action.verb()));
+                Task::perform(
+                    async move {
+                        tokio::task::spawn_blocking(move || power::run(action))
+                            .await
+                            .map_err(|e| e.to_string())
+                            .and_then(|result| result)
+                    },
+                    Message::PowerActionDone,
+                )
+            }
+            Message::CancelPowerAction => {
+                if self.power_countdown.take().is_some() {
+                    let action = std::mem::take(&mut self.when_finished);
+                    self.log_messages.push(format!("⏻ {action} cancelled"));
+                }
+                Task::none()
+            }
+            Message::PowerActionDone(result) => {
+                if let Err(e) = result {
+                    self.log_messages
+                        .push(format!("❌ Could not change the power state: {e}"));
+                }
+                Task::none()
+            }
+            Message::CancelProcessing => {
+                if !self.processing {
+                    return Task::none();
+                }
+                self.pause_requested.store(false, Ordering::Relaxed);
+                journal::finish();
+                // 先中止任务，不再启动后续步骤和文件，再结束正在运行的工具
+                if let Some(task) = self.processing_task.take() {
+                    task.abort();
+                }
+                let stopped = processes::kill_all();
+                let removed = self
+                    .file_queue
+                    .iter()
+                    .map(|item| temp_files::remove_job(&item.path))
+                    .sum::<usize>();
+                self.processing = false;
+                self.timing = None;
+                self.progress = 0.0;
+                self.log_messages.push(format!(
+                    "⏹ Processing cancelled: stopped {stopped} running tool(s), removed {removed} temporary file(s)"
+                ));
+                Task::none()
+            }
+            Message::TogglePause => {
+                let paused = !self.pause_requested.load(Ordering::Relaxed);
+                self.pause_requested.store(paused, Ordering::Relaxed);
+                self.log_messages.push(if paused {
+                    "⏸ The batch will pause after the current file".to_string()
+                } else {
+                    "▶ Pause cancelled, the batch continues".to_string()
+                });
+                Task::none()
+            }
+            Message::BatchPaused(remaining) => {
+                journal::finish();
+                self.processing = false;
+                self.processing_task = None;
+                self.timing = None;
+                // 保存剩余文件及其设置，退出程序后也可以继续
+                let mut batch = self.current_project(String::new());
+                batch.files.retain(|file| remaining.contains(&file.path));
+                self.log_messages.push(format!(
+                    "⏸ Batch paused, {} file(s) remaining",
+                    batch.files.len()
+                ));
+                self.projects.paused = Some(batch);
+                if let Err(e) = self.projects.save() {
+                    self.log_messages.push(format!("Error: {e}"));
+                }
+                Task::none()
+            }
+            Message::ResumeBatch => {
+                if self.processing {
+                    return Task::none();
+                }
+                let Some(batch) = self.projects.paused.clone() else {
+                    return Task::none();
+                };
+                // 队列中已没有这些文件时（例如重新启动了程序），先恢复暂停时保存的队列
+                let load = if batch
+                    .files
+                    .iter()
+                    .all(|file| self.file_queue.iter().any(|item| item.path == file.path))
+                {
+                    Task::none()
+                } else {
+                    self.store_active_project();
+                    self.projects.active = None;
+                    self.load_project(batch.clone())
+                };
+                let files = batch
+                    .files
+                    .iter()
+                    .map(|file| file.path.clone())
+                    .filter(|path| self.file_queue.iter().any(|item| &item.path == path))
+                    .collect::<Vec<_>>();
+                let Some(output) = self.output_folder.clone() else {
+                    return load;
+                };
+                self.log_messages.push(format!(
+                    "▶ Resuming paused batch with {} file(s)",
+                    files.len()
+                ));
+                Task::batch([
+                    load,
+                    self.start_processing(files, output, self.options.clone()),
+                ])
+            }
+            Message::ClearLog => {
+                self.log_messages.clear();
+                Task::none()
+            }
+            Message::TerminalOutput(output) => {
+                self.terminal_logs.push(output);
+                Task::none()
+            }
+            Message::ClearTerminal => {
+                self.terminal_logs.clear();
+                Task::none()
+            }
+            Message::TabSelected(tab) => {
+                self.session.active_tab = tab;
+                Task::none()
+            }
+            Message::TerminalFilterChanged(filter) => {
+                self.session.terminal_filter = filter;
+                Task::none()
+            }
+            Message::TerminalErrorsOnlyToggled(enabled) => {
+                self.session.terminal_errors_only = enabled;
+                Task::none()
+            }
+            Message::QueueSortSelected(sort) => {
+                self.session.queue_sort = sort;
+                self.sort_queue();
+                Task::none()
+            }
+            Message::WindowMoved(position) => {
+                self.session.window_x = Some(position.x);
+                self.session.window_y = Some(position.y);
+                Task::none()
+            }
+            Message::WindowResized(size) => {
+                self.session.window_width = size.width;
+                self.session.window_height = size.height;
+                Task::none()
+            }
+            Message::WindowCloseRequested(id) => {
+                // 工具在单独的进程组中运行，不会随窗口一起退出
+                processes::kill_all();
+                // 退出前保存界面状态和当前打开的队列
+                if let Err(e) = self.session.save() {
+                    eprintln!("{e}");
+                }
+                if self.projects.active.is_some() {
+                    self.store_active_project();
+                    if let Err(e) = self.projects.save() {
+                        eprintln!("{e}");
+                    }
+                }
+                iced::window::close(id)
+            }
+            Message::SelectWatchFolder => {
+                Task::perform(select_watch_folder(), Message::WatchFolderSelected)
+            }
+            Message::WatchFolderSelected(Some(folder)) => {
+                self.session.watch_folder = Some(folder);
+                self.watch_health = None;
+                Task::none()
+            }
+            Message::WatchFolderSelected(None) => Task::none(),
+            Message::StopWatching => {
+                self.session.watch_folder = None;
+                self.watch_health = None;
+                Task::none()
+            }
+            Message::WatchHealthChanged(health) => {
+                let folder = self
+                    .session
+                    .watch_folder
+                    .as_ref()
+                    .map(|folder| folder.to_string_lossy().to_string())
+                    .unwrap_or_default();
+                match &health {
+                    watch::WatchHealth::Watching if self.watch_health.is_some() => self
+                        .log_messages
+                        .push(format!("🔌 Watch folder {folder} is reachable again")),
+                    watch::WatchHealth::Unreachable { error, .. }
+                        if !matches!(
+                            self.watch_health,
+                            Some(watch::WatchHealth::Unreachable { .. })
+                        ) =>
+                    {
+                        self.log_messages
+                            .push(format!("⚠️ Watch folder {folder} is unreachable: {error}"))
+                    }
+                    _ => {}
+                }
+                self.watch_health = Some(health);
+                Task::none()
+            }
+            Message::WatchFilesArrived(files) => {
+                let files = self.without_skipped(files);
+                if files.is_empty() {
+                    return Task::none();
+                }
+                for file in &files {
+                    self.log_messages.push(format!(
+                        "👀 New file in watch folder: {}",
+                        file.file_name().unwrap_or_default().to_string_lossy()
+                    ));
+                }
+                self.enqueue_and_start(files)
+            }
+            Message::WatchFileReplaced(path) => {
+                let name = path.file_name().unwrap_or_default().to_string_lossy();
+                match self.session.replaced_policy {
+                    watch::ReplacedPolicy::Ignore => {
+                        self.log_messages.push(format!(
+                            "♻️ {name} was replaced in the watch folder, ignored"
+                        ));
+                        Task::none()
+                    }
+                    watch::ReplacedPolicy::Ask => {
+                        Task::perform(confirm_reconvert(path.clone()), Message::ReconvertConfirmed)
+                    }
+                    watch::ReplacedPolicy::Reconvert => {
+                        self.update(Message::ReconvertConfirmed((path.clone(), true)))
+                    }
+                }
+            }
+            Message::ReconvertConfirmed((path, confirmed)) => {
+                if !confirmed || self.skip_list.reason(&path).is_some() {
+                    return Task::none();
+                }
+                let version = self.output_versions.get(&path).copied().unwrap_or(1) + 1;
+                self.output_versions.insert(path.clone(), version);
+                self.log_messages.push(format!(
+                    "♻️ {} was replaced in the watch folder, reconverting as v{version}",
+                    path.file_name().unwrap_or_default().to_string_lossy()
+                ));
+                // 队列中尚未处理的旧条目分析结果已过期
+                if !self.processing {
+                    self.file_queue.retain(|item| item.path != path);
+                    self.selected_file = None;
+                }
+                self.enqueue_and_start(vec![path])
+            }
+            Message::ReplacedPolicySelected(policy) => {
+                self.session.replaced_policy = policy;
+                Task::none()
+            }
+        }
+    }
+
+    // 开始批量处理指定的文件
+    fn start_processing(
+        &mut self,
+        files: Vec<PathBuf>,
+        output: PathBuf,
+        options: ProcessOptions,
+    ) -> Task<Message> {
+        if self.processing || files.is_empty() {
+            return Task::none();
+        }
+        if let Some(error) = options.validation_error() {
+            self.log_messages.push(format!("❌ {error}"));
+            return Task::none();
+        }
+        if let Some(error) = self.file_settings_error() {
+            self.log_messages.push(format!("❌ {error}"));
+            return Task::none();
+        }
+
+        self.processing = true;
+        self.pause_requested = Arc::new(AtomicBool::new(false));
+        // 开始新的批处理后不再继续之前暂停的批处理
+        if self.projects.paused.take().is_some()
+            && let Err(e) = self.projects.save()
+        {
+            self.log_messages.push(format!("Error: {e}"));
+        }
+        self.current_file_index = 0;
+        self.progress = 0.0;
+        self.log_messages.clear();
+        self.terminal_logs.clear();
+        for item in &mut self.file_queue {
+            item.failure = None;
+        }
+
+        self.timing = Some(eta::BatchTiming::new(
+            files.clone(),
+            options.max_concurrent_jobs as usize,
+        ));
+        // 记录本批的文件及其设置，程序崩溃后可以从第一个未完成的文件继续
+        let mut batch = self.current_project(String::new());
+        batch.output_folder = Some(output.clone());
+        batch.options = options.clone();
+        batch.files.retain(|file| files.contains(&file.path));
+        if let Err(e) = journal::start(&batch) {
+            self.log_messages.push(format!("⚠️ {e}"));
+        }
+
+        let files = files
+            .into_iter()
+            .map(
+                |path| match self.file_queue.iter().find(|item| item.path == path) {
+                    Some(item) => item.source(),
+                    None => pipeline::Source {
+                        path,
+                        version: 1,
+                        ..pipeline::Source::default()
+                    },
+                },
+            )
+            .collect();
+
+        // 通过通道把处理过程中的进度实时发送给界面
+        let pause = self.pause_requested.clone();
+        let (task, handle) = Task::run(
+            iced::stream::channel(100, move |mut sender| async move {
+                let message =
+                    match process_video_queue(files, output, options, pause, &mut sender).await {
+                        Ok(remaining) if !remaining.is_empty() => Message::BatchPaused(remaining),
+                        result => Message::ProcessingComplete(result.map(|_| ())),
+                    };
+                let _ = sender.send(message).await;
+            }),
+            std::convert::identity,
+        )
+        .abortable();
+        self.processing_task = Some(handle);
+        task
+    }
+
+    // 去掉跳过列表中的文件并记录原因
+    fn without_skipped(&mut self, files: Vec<PathBuf>) -> Vec<PathBuf> {
+        files
+            .into_iter()
+            .filter(|path| {
+                let Some(reason) = self.skip_list.reason(path) else {
+                    return true;
+                };
+                self.log_messages.push(format!(
+                    "🚫 Skipping {} (skip list: {reason})",
+                    path.file_name().unwrap_or_default().to_string_lossy()
+                ));
+                false
+            })
+            .collect()
+    }
+
+    // 队列中可以拼接在第 index 项之后的分段（第 index 项为第 1 段）
+    fn joinable_parts(&self, index: usize) -> Vec<usize> {
+        let Some(item) = self
+            .file_queue
+            .get(index)
+            .filter(|item| item.parts.is_empty())
+        else {
+            return Vec::new();
+        };
+        let candidates = self
+            .file_queue
+            .iter()
+            .filter(|other| other.parts.is_empty())
+            .map(|other| other.path.as_path());
+        parts::following_parts(&item.path, candidates)
+            .iter()
+            .filter_map(|path| self.file_queue.iter().position(|other| &other.path == path))
+            .collect()
+    }
+
+    // 将文件加入队列，并在后台分析每个文件
+    fn enqueue_files(&mut self, files: Vec<PathBuf>) -> Task<Message> {
+        let files = self.without_skipped(files);
+        let tasks = files
+            .iter()
+            .map(|path| Task::perform(probe_file(path.clone()), Message::FileProbed))
+            .collect::<Vec<_>>();
+        for path in files {
+            let mut item = QueueItem::new(path, self.next_sequence);
+            item.version = self.output_versions.get(&item.path).copied().unwrap_or(1);
+            self.file_queue.push(item);
+            self.next_sequence += 1;
+        }
+        // 新文件可能带来新的帧率警告，需要重新确认
+        self.frame_rate_confirmed = false;
+        self.sort_queue();
+        Task::batch(tasks)
+    }
+
+    // 监视文件夹发现的文件：加入队列，空闲时自动开始处理
+    fn enqueue_and_start(&mut self, files: Vec<PathBuf>) -> Task<Message> {
+        let task = self.enqueue_files(files);
+        match self.output_folder.clone() {
+            // 批处理暂停时只加入队列，继续时一起处理
+            Some(output)
+                if !self.processing && self.projects.paused.is_none() && self.can_start() =>
+            {
+                let files = self
+                    .file_queue
+                    .iter()
+                    .map(|item| item.path.clone())
+                    .collect();
+                Task::batch([
+                    task,
+                    self.start_processing(files, output, self.options.clone()),
+                ])
+            }
+            _ => task,
+        }
+    }
+
+    // 快速模式：用默认选项转换单个文件，输出到源文件所在目录
+    fn start_quick_convert(&mut self, path: PathBuf, probed: bool) -> Task<Message> {
+        let options = ProcessOptions::default();
+        let name = path
+            .file_name()
+            .unwrap_or_default()
+            .to_string_lossy()
+            .to_string();
+        match path.parent() {
+            Some(folder) if probed && !self.processing && self.can_start_with(&options) => {
+                let folder = folder.to_path_buf();
+                self.start_processing(vec![path], folder, options)
+            }
+            _ => {
+                self.log_messages.push(format!(
+                    "⚠ {name} was added to the queue but not converted, check it and start manually"
+                ));
+                Task::none()
+            }
+        }
+    }
+
+    // 为队列项添加外部字幕，同一文件只添加一次
+    fn attach_subtitles(&mut self, item_path: &Path, files: Vec<PathBuf>) {
+        let Some(item) = self
+            .file_queue
+            .iter_mut()
+            .find(|item| item.path == item_path)
+        else {
+            return;
+        };
+        for file in files {
+            if !item.external_subtitles.contains(&file) {
+                self.log_messages.push(format!(
+                    "📝 Added subtitle {} to {}",
+                    file.file_name().unwrap_or_default().to_string_lossy(),
+                    item.path.file_name().unwrap_or_default().to_string_lossy()
+                ));
+                item.external_subtitles.push(file);
+            }
+        }
+    }
+
+    // 分析完成、尚未进行深度分析的文件
+    fn deep_probe_candidates(&self) -> Vec<usize> {
+        self.file_queue
+            .iter()
+            .enumerate()
+            .filter(|(_, item)| {
+                matches!(&item.info, Some(Ok(info)) if info.deep.is_none()) && !item.deep_probing
+            })
+            .map(|(index, _)| index)
+            .collect()
+    }
+
+    // 在后台对文件进行深度分析（可变帧率、黑边、RPU 摘要）
+    fn deep_probe(&mut self, indices: &[usize]) -> Task<Message> {
+        let tasks = indices
+            .iter()
+            .filter_map(|index| {
+                let item = self.file_queue.get_mut(*index)?;
+                let Some(Ok(info)) = &item.info else {
+                    return None;
+                };
+                if item.deep_probing {
+                    return None;
+                }
+                item.deep_probing = true;
+                Some(Task::perform(
+                    deep_probe_file(item.path.clone(), info.clone()),
+                    Message::DeepProbed,
+                ))
+            })
+            .collect::<Vec<_>>();
+        Task::batch(tasks)
+    }
+
+    // 在后台提取 RPU 并分析 L1 亮度
+    fn analyze(&mut self, path: PathBuf) -> Task<Message> {
+        if self.analyzing {
+            return Task::none();
+        }
+        self.analyzing = true;
+        self.session.active_tab = Tab::Metadata;
+        self.log_messages.push(format!(
+            "📈 Analyzing Dolby Vision metadata of {}...",
+            path.file_name().unwrap_or_default().to_string_lossy()
+        ));
+        Task::perform(analyze_file(path), Message::AnalysisComplete)
+    }
+
+    // 流程标签页：每条分支在当前选项下的步骤依次排成节点，标出使用的工具；
+    // 只在源文件满足条件时执行的步骤用橙色边框和 "if ..." 标出
+    fn view_pipeline_graph(&self) -> Vec<Element<'_, Message>> {
+        let node = |step: pipeline::Step| {
+            let condition = step.condition();
+            let border_color = if condition.is_some() {
+                iced::Color::from_rgb(0.9, 0.6, 0.2)
+            } else {
+                iced::Color::from_rgb(0.4, 0.4, 0.4)
+            };
+            let mut content = column![
+                text(step.to_string()).size(12),
+                text(step.tool(&self.options))
+                    .size(10)
+                    .font(iced::Font::MONOSPACE)
+                    .style(|_theme: &Theme| text::Style {
+                        color: Some(iced::Color::from_rgb(0.6, 0.6, 0.6)),
+                    }),
+            ]
+            .spacing(2);
+            if let Some(condition) = condition {
+                content = content.push(text(format!("if {condition}")).size(10).style(
+                    |_theme: &Theme| text::Style {
+                        color: Some(iced::Color::from_rgb(0.9, 0.6, 0.2)),
+                    },
+                ));
+            }
+            container(content)
+                .padding(6)
+                .style(move |_theme: &Theme| container::Style {
+                    background: Some(iced::Background::Color(iced::Color::from_rgb(
+                        0.15, 0.15, 0.15,
+                    ))),
+                    border: iced::Border {
+                        color: border_color,
+                        width: 1.0,
+                        radius: 4.0.into(),
+                    },
+                    ..Default::default()
+                })
+        };
+
+        let mut graph = Vec::new();
+        for (branch, steps) in pipeline::preset_graph(&self.options) {
+            graph.push(text(format!("{branch}:")).size(12).into());
+            // 非杜比视界文件按设置跳过或中止时没有步骤
+            if steps.is_empty() {
+                let outcome = match self.options.non_dv_mode {
+                    NonDvMode::Abort => "the batch stops",
+                    _ => "skipped",
+                };
+                graph.push(text(format!("  {outcome}")).size(12).into());
+                continue;
+            }
+            let mut nodes = row![].spacing(6).align_y(Alignment::Center);
+            for (index, step) in steps.into_iter().enumerate() {
+                if index > 0 {
+                    nodes = nodes.push(text("→").size(16));
+                }
+                nodes = nodes.push(node(step));
+            }
+            graph.push(nodes.wrap().into());
+        }
+        graph
+    }
+
+    // 预设对比中选择的两个预设的名称和选项；命名队列已被删除时为 None
+    fn compared_presets(&self) -> Option<[(String, ProcessOptions); 2]> {
+        let preset = |name: &Option<String>| {
+            let name = name.clone()?;
+            let options = if name == CURRENT_SETTINGS {
+                self.options.clone()
+            } else {
+                self.projects.get(&name)?.options.clone()
+            };
+            Some((name, options))
+        };
+        Some([
+            preset(&self.compare_presets[0])?,
+            preset(&self.compare_presets[1])?,
+        ])
+    }
+
+    // 预设对比标签页：两个预设在当前队列上的预演，逐个文件并排显示
+    fn view_comparison(&self) -> Vec<Element<'_, Message>> {
+        let Some([(name_a, a), (name_b, b)]) = self.compared_presets() else {
+            return vec![
+                text(
+                    "Pick two presets to compare: the current settings or a saved queue's options.",
+                )
+                .size(12)
+                .into(),
+            ];
+        };
+        if self.file_queue.is_empty() {
+            return vec![
+                text("Add files to the queue to compare the presets on them.")
+                    .size(12)
+                    .into(),
+            ];
+        }
+
+        let mut lines: Vec<Element<Message>> = Vec::new();
+        let differences = report::option_differences(&a, &b);
+        if differences.is_empty() {
+            lines.push(text("The presets have the same settings.").size(12).into());
+        }
+        for (key, value_a, value_b) in differences {
+            lines.push(
+                text(format!("{key}: {value_a} → {value_b}"))
+                    .size(11)
+                    .font(iced::Font::MONOSPACE)
+                    .into(),
+            );
+        }
+
+        let side = |lines: Vec<String>| {
+            column(
+                lines
+                    .into_iter()
+                    .map(|line| text(line).size(11).into())
+                    .collect::<Vec<Element<Message>>>(),
+            )
+            .spacing(2)
+            .width(Length::Fill)
+        };
+        for item in &self.file_queue {
+            lines.push(Space::with_height(Length::Fixed(6.0)).into());
+            lines.push(
+                text(
+                    item.path
+                        .file_name()
+                        .unwrap_or_default()
+                        .to_string_lossy()
+                        .to_string(),
+                )
+                .size(13)
+                .into(),
+            );
+            let (Some((run_a, size_a)), Some((run_b, size_b))) =
+                (report::dry_run(item, &a), report::dry_run(item, &b))
+            else {
+                lines.push(text("Not analyzed yet").size(11).into());
+                continue;
+            };
+            lines.push(
+                row![
+                    column![
+                        text(name_a.clone()).size(11),
+                        side(report::dry_run_lines(&run_a, size_a, &a))
+                    ],
+                    column![
+                        text(name_b.clone()).size(11),
+                        side(report::dry_run_lines(&run_b, size_b, &b))
+                    ]
+                ]
+                .spacing(20)
+                .into(),
+            );
+        }
+        lines
+    }
+
+    // 输出核对标签页：需要注意的 MP4 按类别列出，路径相对于扫描的文件夹
+    fn view_reconciliation(&self) -> Vec<Element<'_, Message>> {
+        let reconciliation = match &self.reconciliation {
+            _ if self.reconciling => {
+                return vec![text("Scanning the output folder...").size(12).into()];
+            }
+            None => {
+                return vec![
+                    text("Scan an output library folder to match each MP4 back to its source through the conversion history and the queue, and list orphans, duplicates and outputs whose duration does not match.")
+                        .size(12)
+                        .into(),
+                ];
+            }
+            Some(Err(e)) => {
+                return vec![
+                    text(format!("❌ Failed to scan the folder: {e}"))
+                        .size(12)
+                        .into(),
+                ];
+            }
+            Some(Ok(reconciliation)) => reconciliation,
+        };
+
+        let mut lines: Vec<Element<Message>> = vec![
+            text(format!(
+                "{}: {} MP4 file(s), {} matched, {} orphaned, {} with a missing source, {} duplicate(s), {} duration mismatch(es)",
+                reconciliation.folder.to_string_lossy(),
+                reconciliation.total,
+                reconciliation.matched,
+                reconciliation.count(|finding| matches!(finding, history::Finding::Orphan)),
+                reconciliation
+                    .count(|finding| matches!(finding, history::Finding::SourceMissing(_))),
+                reconciliation
+                    .count(|finding| matches!(finding, history::Finding::Duplicate { .. })),
+                reconciliation.count(|finding| {
+                    matches!(finding, history::Finding::DurationMismatch { .. })
+                }),
+            ))
+            .size(12)
+            .into(),
+        ];
+        for (path, finding) in &reconciliation.findings {
+            let relative = path
+                .strip_prefix(&reconciliation.folder)
+                .unwrap_or(path)
+                .to_string_lossy();
+            lines.push(
+                text(format!("{relative}: {finding}"))
+                    .size(11)
+                    .font(iced::Font::MONOSPACE)
+                    .into(),
+            );
+        }
+        lines
+    }
+
+    // 杜比视界元数据标签页：每个分析结果的摘要和逐场景亮度图
+    fn view_analyses(&self) -> Vec<Element<'_, Message>> {
+        if self.analyses.is_empty() {
+            let hint = if self.analyzing {
+                "Extracting the RPU with dovi_tool, this reads the whole video stream..."
+            } else {
+                "Select a file in the queue and click \"Analyze Selected\", or analyze any MKV/MP4 (e.g. a converted output) to compare."
+            };
+            return vec![text(hint).size(12).into()];
+        }
+
+        let mut lines = Vec::new();
+        for analysis in &self.analyses {
+            lines.push(
+                text(format!(
+                    "{} — {} frames, {} scenes, peak {:.0} nits, average {:.0} nits",
+                    analysis
+                        .path
+                        .file_name()
+                        .unwrap_or_default()
+                        .to_string_lossy(),
+                    analysis.frames,
+                    analysis.scenes.len(),
+                    rpu_analysis::pq_to_nits(analysis.peak_pq()),
+                    rpu_analysis::pq_to_nits(analysis.average_pq())
+                ))
+                .size(13)
+                .into(),
+            );
+            lines.extend(
+                analysis
+                    .summary
+                    .iter()
+                    .map(|line| text(line).size(11).font(iced::Font::MONOSPACE).into()),
+            );
+            lines.push(view_l1_chart(analysis));
+        }
+        if self.analyzing {
+            lines.push(text("Analyzing...").size(12).into());
+        }
+        lines
+    }
+
+    // 在后台查询临时文件夹的可用空间
+    fn check_temp_space(&self) -> Task<Message> {
+        let folder = self.options.temp_dir();
+        Task::perform(
+            async move {
+                tokio::task::spawn_blocking(move || disk_space::available(&folder))
+                    .await
+                    .ok()
+                    .flatten()
+            },
+            Message::TempSpaceChecked,
+        )
+    }
+
+    // 当前队列、输出文件夹和选项的快照
+    fn current_project(&self, name: String) -> projects::Project {
+        projects::Project {
+            name,
+            files: self
+                .file_queue
+                .iter()
+                .map(|item| projects::SavedFile {
+                    path: item.path.clone(),
+                    active_area: item.active_area.clone(),
+                    trim_start: item.trim_start.clone(),
+                    trim_end: item.trim_end.clone(),
+                    audio_tracks: item.audio_tracks.clone(),
+                    subtitle_tracks: item.subtitle_tracks.clone(),
+                    external_subtitles: item.external_subtitles.clone(),
+                    audio_offset: item.audio_offset.clone(),
+                    subtitle_offset: item.subtitle_offset.clone(),
+                    parts: item.parts.clone(),
+                })
+                .collect(),
+            output_folder: self.output_folder.clone(),
+            options: self.options.clone(),
+        }
+    }
+
+    // 切换前把当前打开的队列写回列表
+    fn store_active_project(&mut self) {
+        if let Some(name) = self.projects.active.clone() {
+            let project = self.current_project(name);
+            self.projects.upsert(project);
+        }
+    }
+
+    // 用命名队列替换当前队列、输出文件夹和选项
+    fn open_project(&mut self, name: &str) -> Task<Message> {
+        let Some(project) = self.projects.get(name).cloned() else {
+            return Task::none();
+        };
+        self.projects.active = Some(project.name.clone());
+        self.log_messages
+            .push(format!("📂 Opened queue \"{}\"", project.name));
+        self.load_project(project)
+    }
+
+    // 载入保存的队列、输出文件夹和选项；已不存在的文件会被忽略
+    fn load_project(&mut self, project: projects::Project) -> Task<Message> {
+        self.file_queue.clear();
+        self.selected_file = None;
+        self.output_folder = project.output_folder;
+        self.options = project.options;
+        let (existing, missing): (Vec<_>, Vec<_>) = project
+            .files
+            .into_iter()
+            .partition(|file| file.path.exists());
+        for file in missing {
+            self.log_messages.push(format!(
+                "⚠️ {} no longer exists and was removed from the queue",
+                file.path.display()
+            ));
+        }
+        let task = self.enqueue_files(existing.iter().map(|file| file.path.clone()).collect());
+        for file in existing {
+            if let Some(item) = self
+                .file_queue
+                .iter_mut()
+                .find(|item| item.path == file.path)
+            {
+                item.active_area = file.active_area;
+                item.trim_start = file.trim_start;
+                item.trim_end = file.trim_end;
+                item.audio_tracks = file.audio_tracks;
+                item.subtitle_tracks = file.subtitle_tracks;
+                item.external_subtitles = file
+                    .external_subtitles
+                    .into_iter()
+                    .filter(|path| path.exists())
+                    .collect();
+                item.audio_offset = file.audio_offset;
+                item.subtitle_offset = file.subtitle_offset;
+                item.size += file
+                    .parts
+                    .iter()
+                    .filter_map(|part| std::fs::metadata(part).ok())
+                    .map(|meta| meta.len())
+                    .sum::<u64>();
+                item.parts = file.parts;
+            }
+        }
+        Task::batch([task, self.check_temp_space()])
+    }
+
+    // 批处理结束后的操作，处理过程中也可以修改
+    fn view_when_finished(&self) -> Element<'_, Message> {
+        with_tooltip(
+            row![
+                text("When Finished:").size(14),
+                pick_list(
+                    power::PowerAction::choices(),
+                    Some(self.when_finished),
+                    Message::WhenFinishedSelected
+                )
+                .text_size(14)
+            ]
+            .spacing(10)
+            .align_y(Alignment::Center),
+            "Sleep, hibernate or shut down the computer after the batch ends, with a 60-second countdown that can be cancelled. Applies to the next batch only",
+        )
+    }
+
+    // 标题栏中的命名队列选择器
+    fn view_projects(&self) -> Element<'_, Message> {
+        let name = self.project_name.trim();
+        row![
+            text("Queue:").size(14),
+            pick_list(
+                self.projects.names(),
+                self.projects.active.clone(),
+                Message::ProjectSelected
+            )
+            .placeholder("Unsaved")
+            .text_size(14),
+            text_input("New queue name", &self.project_name)
+                .on_input(Message::ProjectNameChanged)
+                .on_submit(Message::SaveProject)
+                .size(14)
+                .width(Length::Fixed(160.0)),
+            with_tooltip(
+                button("Save").on_press_maybe((!name.is_empty()).then_some(Message::SaveProject)),
+                "Save the queue, output folder and options under this name"
+            ),
+            with_tooltip(
+                button("Delete").on_press_maybe(
+                    self.projects
+                        .active
+                        .is_some()
+                        .then_some(Message::DeleteProject)
+                ),
+                "Delete the selected saved queue (files stay in the current queue)"
+            ),
+        ]
+        .spacing(8)
+        .align_y(Alignment::Center)
+        .into()
+    }
+
+    // 按当前排序方式重排队列，并保持选中项不变
+    fn sort_queue(&mut self) {
+        let selected = self
+            .selected_file
+            .map(|index| self.file_queue[index].sequence);
+        match self.session.queue_sort {
+            QueueSort::Added => self.file_queue.sort_by_key(|item| item.sequence),
+            QueueSort::Name => self.file_queue.sort_by_key(|item| {
+                item.path
+                    .file_name()
+                    .unwrap_or_default()
+                    .to_string_lossy()
+                    .to_lowercase()
+            }),
+            QueueSort::Size => self.file_queue.sort_by_key(|item| item.size),
+        }
+        self.selected_file = selected.and_then(|sequence| {
+            self.file_queue
+                .iter()
+                .position(|item| item.sequence == sequence)
+        });
+    }
+
+    // 监视文件夹及其连接状态
+    fn view_watch_folder(&self) -> Element<'_, Message> {
+        let Some(folder) = &self.session.watch_folder else {
+            return row![
+                text("Watch Folder: off").size(12).width(Length::Fill),
+                with_tooltip(
+                    button(text("Watch Folder...").size(12)).on_press(Message::SelectWatchFolder),
+                    "New MKV files copied into this folder are queued and converted automatically"
+                )
+            ]
+            .spacing(10)
+            .align_y(Alignment::Center)
+            .into();
+        };
+
+        let (status, color) = match &self.watch_health {
+            None => (
+                "Connecting...".to_string(),
+                iced::Color::from_rgb(0.6, 0.6, 0.6),
+            ),
+            Some(health @ watch::WatchHealth::Watching) => {
+                (health.to_string(), iced::Color::from_rgb(0.3, 0.8, 0.3))
+            }
+            Some(health @ watch::WatchHealth::Unreachable { .. }) => {
+                (health.to_string(), iced::Color::from_rgb(0.9, 0.6, 0.2))
+            }
+        };
+
+        row![
+            text(format!("Watch Folder: {}", folder.to_string_lossy()))
+                .size(12)
+                .width(Length::Fill),
+            text(status)
+                .size(12)
+                .style(move |_theme: &Theme| text::Style { color: Some(color) }),
+            with_tooltip(
+                pick_list(
+                    watch::ReplacedPolicy::ALL,
+                    Some(self.session.replaced_policy),
+                    Message::ReplacedPolicySelected
+                )
+                .text_size(12),
+                "When a file with the same name reappears with a different size or checksum (e.g. a proper/repack)"
+            ),
+            button(text("Stop Watching").size(12)).on_press(Message::StopWatching)
+        ]
+        .spacing(10)
+        .align_y(Alignment::Center)
+        .into()
+    }
+
+    // 媒体服务器导入设置：服务器地址、令牌和路径映射
+    fn view_importer(&self) -> Element<'_, Message> {
+        let config = &self.media_server;
+        let error = config.validation_error();
+        let mut import_row = row![
+            with_tooltip(
+                text_input("/media (server path)", &config.server_prefix)
+                    .on_input(Message::ServerPrefixChanged)
+                    .size(12),
+                "Path prefix as the server sees it, replaced by the local prefix"
+            ),
+            text("→").size(12),
+            text_input("/Volumes/media (local path)", &config.local_prefix)
+                .on_input(Message::LocalPrefixChanged)
+                .size(12),
+            button(
+                text(if self.importing {
+                    "Importing..."
+                } else {
+                    "Import"
+                })
+                .size(12)
+            )
+            .on_press_maybe(
+                (!self.importing && error.is_none()).then_some(Message::ImportFromServer)
+            ),
+        ]
+        .spacing(6)
+        .align_y(Alignment::Center);
+        if let Some(error) = error {
+            import_row =
+                import_row.push(text(error).size(12).style(|_theme: &Theme| text::Style {
+                    color: Some(iced::Color::from_rgb(0.9, 0.3, 0.3)),
+                }));
+        }
+
+        column![
+            row![
+                pick_list(
+                    media_server::ServerKind::ALL,
+                    Some(config.kind),
+                    Message::ServerKindSelected
+                )
+                .text_size(12),
+                text_input("http://localhost:8096", &config.url)
+                    .on_input(Message::ServerUrlChanged)
+                    .size(12),
+                with_tooltip(
+                    text_input(
+                        match config.kind {
+                            media_server::ServerKind::Jellyfin => "API key",
+                            media_server::ServerKind::Plex => "X-Plex-Token",
+                        },
+                        &config.token
+                    )
+                    .on_input(Message::ServerTokenChanged)
+                    .secure(true)
+                    .size(12),
+                    "Saved in the Rebottle config folder"
+                ),
+            ]
+            .spacing(6)
+            .align_y(Alignment::Center),
+            import_row,
+        ]
+        .spacing(6)
+        .into()
+    }
+
+    // 开启统计时记录处理结果，只使用已分析的源文件特征
+    fn record_stats(&mut self, path: &Path, failed_tool: Option<&str>) {
+        if !self.session.collect_stats {
+            return;
+        }
+        let info = self
+            .file_queue
+            .iter()
+            .find(|item| item.path == path)
+            .and_then(|item| item.info.as_ref()?.as_ref().ok());
+        self.stats.record(info, failed_tool);
+        if let Err(e) = self.stats.save() {
+            self.log_messages.push(format!("Error: {e}"));
+        }
+    }
+
+    // 统计页：总数、按工具分类的失败次数、按源文件特征分类的失败率
+    fn view_stats(&self) -> Vec<Element<'_, Message>> {
+        let stats = &self.stats;
+        if stats.total.processed == 0 {
+            let hint = if self.session.collect_stats {
+                "No files processed since stats were enabled."
+            } else {
+                "Failure stats are off. Enable them to record failure categories locally; file names are never stored."
+            };
+            return vec![text(hint).size(12).into()];
+        }
+
+        let mut lines: Vec<Element<Message>> = vec![
+            text(format!(
+                "Processed: {}   Failed: {} ({:.0}%)",
+                stats.total.processed,
+                stats.total.failed,
+                stats.total.failure_rate() * 100.0
+            ))
+            .size(12)
+            .into(),
+        ];
+
+        let mut tools = stats.failures_by_tool.iter().collect::<Vec<_>>();
+        tools.sort_by(|a, b| b.1.cmp(a.1));
+        if !tools.is_empty() {
+            lines.push(text("Failures by tool:").size(12).into());
+            lines.extend(tools.into_iter().map(|(tool, count)| {
+                text(format!("  {tool}: {count}"))
+                    .size(11)
+                    .font(iced::Font::MONOSPACE)
+                    .into()
+            }));
+        }
+
+        let mut sources = stats.by_source.iter().collect::<Vec<_>>();
+        sources.sort_by(|a, b| {
+            b.1.failed
+                .cmp(&a.1.failed)
+                .then(b.1.failure_rate().total_cmp(&a.1.failure_rate()))
+        });
+        lines.push(text("By source characteristics:").size(12).into());
+        lines.extend(sources.into_iter().map(|(name, counts)| {
+            text(format!(
+                "  {name}: {} failed of {} ({:.0}%)",
+                counts.failed,
+                counts.processed,
+                counts.failure_rate() * 100.0
+            ))
+            .size(11)
+            .font(iced::Font::MONOSPACE)
+            .into()
+        }));
+        lines
+    }
+
+    // 是否可以开始批量处理
+    fn can_start(&self) -> bool {
+        self.output_folder.is_some() && self.can_start_with(&self.options)
+    }
+
+    // 使用指定选项时队列可以开始处理
+    fn can_start_with(&self, options: &ProcessOptions) -> bool {
+        !self.file_queue.is_empty()
+            && options.validation_error().is_none()
+            && self.file_settings_error().is_none()
+            && (self.frame_rate_warnings(options).is_empty() || self.frame_rate_confirmed)
+    }
+
+    // 第一个裁剪范围或偏移无效的文件
+    fn file_settings_error(&self) -> Option<String> {
+        self.file_queue.iter().find_map(|item| {
+            let error = item
+                .trim()
+                .err()
+                .or_else(|| item.active_area().err())
+                .or_else(|| item.audio_offset().err())
+                .or_else(|| item.subtitle_offset().err())?;
+            Some(format!(
+                "{}: {error}",
+                item.path.file_name().unwrap_or_default().to_string_lossy()
+            ))
+        })
+    }
+
+    // 所选帧率与源文件检测到的帧率不一致，或源文件使用了 pulldown
+    fn frame_rate_warnings(&self, options: &ProcessOptions) -> Vec<String> {
+        self.file_queue
+            .iter()
+            .filter_map(|item| {
+                let info = item.info.as_ref()?.as_ref().ok()?;
+                let video = info.video.as_ref()?;
+                let name = item.path.file_name().unwrap_or_default().to_string_lossy();
+                if let Some((min, max)) = info
+                    .deep
+                    .as_ref()
+                    .and_then(probe::DeepProbe::variable_frame_rate)
+                {
+                    Some(format!(
+                        "{name}: variable frame rate detected ({min:.3}-{max:.3} fps)"
+                    ))
+                } else if video.pulldown {
+                    Some(format!(
+                        "{name}: soft telecine (pulldown) detected, actual frame rate {:.3} fps",
+                        video.frame_rate.unwrap_or_default()
+                    ))
+                } else {
+                    let fps = video.frame_rate?;
+                    (!options.frame_rate_for(info).matches(fps))
+                        .then(|| format!("{name}: detected {fps:.3} fps"))
+                }
+            })
+            .collect()
+    }
+
+    fn view_frame_rate_warnings(&self) -> Option<Element<'_, Message>> {
+        let warnings = self.frame_rate_warnings(&self.options);
+        if warnings.is_empty() {
+            return None;
+        }
+
+        let warning_style = |_theme: &Theme| text::Style {
+            color: Some(iced::Color::from_rgb(0.9, 0.6, 0.2)),
+        };
+        let mut content = column![
+            text(format!(
+                "⚠ Frame rate check: {} is selected, but the source differs",
+                self.options.frame_rate
+            ))
+            .size(14)
+            .style(warning_style)
+        ]
+        .spacing(3);
+        for warning in warnings {
+            content = content.push(text(format!("  • {warning}")).size(12));
+        }
+        content = content
+            .push(
+                text(
+                    "The video is retimed to the selected rate while the audio is copied unchanged, \
+                     so audio drifts out of sync over the runtime (about 4% for 23.976 vs 25). \
+                     For pulldown sources, pick the actual film rate (usually 23.976).",
+                )
+                .size(12),
+            )
+            .push(
+                checkbox(
+                    "I understand, use the selected frame rate anyway",
+                    self.frame_rate_confirmed,
+                )
+                .on_toggle(Message::FrameRateConfirmed)
+                .text_size(12),
+            );
+        Some(content.into())
+    }
+
+    // 高级设置（可折叠）
+    fn view_advanced_settings(&self) -> Element<'_, Message> {
+        let toggle = button(
+            text(if self.session.show_advanced {
+                "▼ Advanced Settings"
+            } else {
+                "▶ Advanced Settings"
+            })
+            .size(14),
+        )
+        .on_press(Message::ToggleAdvancedSettings)
+        .style(button::text);
+
+        if !self.session.show_advanced {
+            return toggle.into();
+        }
+
+        let mut settings = column![
+            row![
+                text("Device Preset:").size(14),
+                pick_list(
+                    DevicePreset::ALL,
+                    Some(self.options.device_preset),
+                    Message::DevicePresetSelected
+                )
+                .text_size(14)
+            ]
+            .spacing(10)
+            .align_y(Alignment::Center),
+            row![
+                text("Major Brand:").size(14),
+                text_input("mp42", &self.options.major_brand)
+                    .on_input(Message::MajorBrandChanged)
+                    .size(14)
+                    .width(Length::Fixed(80.0)),
+                text("Compatible Brands:").size(14),
+                text_input("mp42,iso6,isom,msdh,dby1", &self.options.compatible_brands)
+                    .on_input(Message::CompatibleBrandsChanged)
+                    .size(14)
+            ]
+            .spacing(10)
+            .align_y(Alignment::Center),
+        ]
+        .spacing(10);
+
+        if let Some(error) = self.options.brand_error() {
+            settings = settings.push(text(error).size(12).style(|_theme: &Theme| text::Style {
+                color: Some(iced::Color::from_rgb(0.9, 0.3, 0.3)),
+            }));
+        }
+
+        settings = settings.push(with_tooltip(
+            row![
+                text("mp4muxer Extra Arguments:").size(14),
+                text_input("none", &self.options.mux_extra_args)
+                    .on_input(Message::MuxExtraArgsChanged)
+                    .size(14)
+            ]
+            .spacing(10)
+            .align_y(Alignment::Center),
+            "Appended to every mp4muxer command; quote arguments with spaces as in a shell. Saved with named queues and shown in each file's pipeline log",
+        ));
+        match self.options.mux_extra_args() {
+            Ok(args) if !args.is_empty() && self.options.mux_args_error().is_none() => {
+                settings = settings
+                    .push(text(format!("mp4muxer … {}", command_line::join_args(&args))).size(12));
+            }
+            _ => {}
+        }
+        if let Some(error) = self.options.mux_args_error() {
+            settings = settings.push(text(error).size(12).style(|_theme: &Theme| text::Style {
+                color: Some(iced::Color::from_rgb(0.9, 0.3, 0.3)),
+            }));
+        }
+
+        settings = settings.push(with_tooltip(
+            checkbox(
+                "Extract and Re-inject RPU (dovi_tool)",
+                self.options.rpu_workflow,
+            )
+            .on_toggle(Message::ToggleRpuWorkflow)
+            .text_size(14),
+            "Runs dovi_tool extract-rpu and inject-rpu on the extracted video before muxing",
+        ));
+        if self.options.rpu_workflow {
+            let config = self.options.rpu_edit_config.as_ref().map_or(
+                "No editor config (RPU is re-injected unchanged)".to_string(),
+                |path| path.to_string_lossy().to_string(),
+            );
+            settings = settings
+                .push(
+                    checkbox("Keep RPU Next to Output", self.options.keep_rpu)
+                        .on_toggle(Message::ToggleKeepRpu)
+                        .text_size(14),
+                )
+                .push(
+                    row![
+                        text("RPU Editor Config:").size(14),
+                        text(config).size(12).width(Length::Fill),
+                        button(text("Choose...").size(12)).on_press(Message::SelectRpuEditConfig),
+                        button(text("Clear").size(12)).on_press_maybe(
+                            self.options
+                                .rpu_edit_config
+                                .is_some()
+                                .then_some(Message::RpuEditConfigSelected(None))
+                        )
+                    ]
+                    .spacing(10)
+                    .align_y(Alignment::Center),
+                );
+        }
+
+        settings = settings.push(with_tooltip(
+            checkbox(
+                "Burn In Subtitles (re-encodes video)",
+                self.options.burn_subtitles,
+            )
+            .on_toggle(Message::ToggleBurnSubtitles)
+            .text_size(14),
+            "For devices that cannot show mov_text: draw the first selected subtitle track into the picture of Dolby Vision HEVC sources. The video is re-encoded with libx265 and the RPU is extracted and re-injected with dovi_tool",
+        ));
+        if self.options.burn_subtitles {
+            settings = settings.push(
+                text("⚠ Burning in re-encodes the video (lossy, much slower than remuxing) and adds a dovi_tool RPU re-inject step. Profile 5 sources are not burned, their base layer is not in a standard color space.")
+                    .size(12)
+                    .style(|_theme: &Theme| text::Style {
+                        color: Some(iced::Color::from_rgb(0.9, 0.6, 0.2)),
+                    }),
+            );
+        }
+
+        settings = settings.push(with_tooltip(
+            row![
+                text("Stagger Start (s):").size(14),
+                pick_list(
+                    STAGGER_CHOICES,
+                    Some(self.options.stagger_seconds),
+                    Message::StaggerSecondsSelected
+                )
+                .text_size(14)
+            ]
+            .spacing(10)
+            .align_y(Alignment::Center),
+            "Wait this long, plus up to half again at random, before starting each file after the first to spread out extraction bursts on a NAS",
+        ));
+
+        settings = settings.push(with_tooltip(
+            row![
+                text("Concurrent Jobs:").size(14),
+                pick_list(
+                    CONCURRENT_JOB_CHOICES,
+                    Some(self.options.max_concurrent_jobs),
+                    Message::MaxConcurrentJobsSelected
+                )
+                .text_size(14)
+            ]
+            .spacing(10)
+            .align_y(Alignment::Center),
+            "Convert this many files at the same time; worthwhile on fast NVMe drives. Terminal lines are tagged with the file name and the progress bar shows the whole batch",
+        ));
+
+        settings = settings.push(with_tooltip(
+            row![
+                text("Retries:").size(14),
+                pick_list(
+                    RETRY_CHOICES,
+                    Some(self.options.retry_count),
+                    Message::RetryCountSelected
+                )
+                .text_size(14)
+            ]
+            .spacing(10)
+            .align_y(Alignment::Center),
+            "Run a failed file again this many times before marking it failed, for network share hiccups and locked files",
+        ));
+
+        let temp_folder = match &self.options.temp_folder {
+            Some(folder) => folder.to_string_lossy().to_string(),
+            None => format!("System default ({})", std::env::temp_dir().display()),
+        };
+        let temp_space = self
+            .temp_space
+            .map_or("free space unknown".to_string(), |space| {
+                format!("{} free", format_size(space))
+            });
+        settings = settings.push(with_tooltip(
+            row![
+                text("Temp Folder:").size(14),
+                text(temp_folder).size(12).width(Length::Fill),
+                text(temp_space).size(12),
+                button(text("Choose...").size(12)).on_press(Message::SelectTempFolder),
+                button(text("Clear").size(12)).on_press_maybe(
+                    self.options
+                        .temp_folder
+                        .is_some()
+                        .then_some(Message::ClearTempFolder)
+                )
+            ]
+            .spacing(10)
+            .align_y(Alignment::Center),
+            "Where extracted video and audio streams are written while converting. A UHD remux needs room for its whole video stream, so pick a large drive rather than a small system SSD",
+        ));
+
+        settings = settings.push(with_tooltip(
+            checkbox("Background Priority", self.options.background_priority)
+                .on_toggle(Message::ToggleBackgroundPriority)
+                .text_size(14),
+            "Run mkvextract, ffmpeg, mp4muxer and the other tools at low CPU and disk priority (nice/ionice, or below normal on Windows) so the computer stays usable during long batches. Applies from the next batch",
+        ));
+
+        settings = settings.push(with_tooltip(
+            checkbox("Low Temp Space", self.options.low_temp_space)
+                .on_toggle(Message::ToggleLowTempSpace)
+                .text_size(14),
+            "Pipe the video of profile 7 sources from ffmpeg straight into dovi_tool instead of extracting it first, and delete each intermediate stream as soon as the next step has consumed it",
+        ));
+
+        settings = settings.push(with_tooltip(
+            row![
+                text("E-AC-3 Bitrate (kb/s):").size(14),
+                pick_list(
+                    EAC3_BITRATES,
+                    Some(self.options.eac3_bitrate),
+                    Message::Eac3BitrateSelected
+                )
+                .text_size(14)
+            ]
+            .spacing(10)
+            .align_y(Alignment::Center),
+            "Bitrate used when DTS (always, mp4muxer can't mux DTS) or TrueHD audio is transcoded to E-AC-3",
+        ));
+
+        settings = settings.push(with_tooltip(
+            row![
+                text("PCM/FLAC Audio To:").size(14),
+                pick_list(
+                    LosslessAudio::ALL,
+                    Some(self.options.lossless_audio),
+                    Message::LosslessAudioSelected
+                )
+                .text_size(14)
+            ]
+            .spacing(10)
+            .align_y(Alignment::Center),
+            "mp4muxer can't mux LPCM or FLAC, so these tracks are always transcoded: E-AC-3 at the bitrate above (downmixed to 5.1), or AAC at 96 kb/s per channel",
+        ));
+
+        settings = settings.push(with_tooltip(
+            row![
+                text("Loudness Target (LUFS):").size(14),
+                pick_list(
+                    LOUDNORM_TARGETS,
+                    Some(self.options.loudnorm_target),
+                    Message::LoudnormTargetSelected
+                )
+                .text_size(14)
+            ]
+            .spacing(10)
+            .align_y(Alignment::Center),
+            "Integrated loudness used by \"Normalize Loudness\": -16 suits phones and tablets, -23 is the EBU R128 broadcast level",
+        ));
+
+        settings = settings.push(with_tooltip(
+            row![
+                text("Upload To:").size(14),
+                pick_list(
+                    UploadBackend::ALL,
+                    Some(self.options.upload_backend),
+                    Message::UploadBackendSelected
+                )
+                .text_size(14),
+                text_input(
+                    match self.options.upload_backend {
+                        UploadBackend::Curl =>
+                            "sftp://user@nas/volume1/Movies/ or smb://nas/share/Movies/",
+                        UploadBackend::Rclone => "remote:Movies (from rclone config)",
+                    },
+                    &self.options.upload_destination
+                )
+                .on_input(Message::UploadDestinationChanged)
+                .size(14)
+                .width(Length::Fill),
+                checkbox("Remove Local Copy", self.options.remove_after_upload)
+                    .on_toggle_maybe(
+                        self.options
+                            .upload_destination()
+                            .is_some()
+                            .then_some(Message::ToggleRemoveAfterUpload)
+                    )
+                    .text_size(14)
+            ]
+            .spacing(10)
+            .align_y(Alignment::Center),
+            "Upload finished outputs after verification (3 attempts). curl reads passwords from ~/.netrc and SFTP also uses your SSH keys; rclone uses its own remotes and moves instead of copying when the local copy is removed",
+        ));
+        if let Some(error) = self.options.upload_error() {
+            settings = settings.push(text(error).size(12).style(|_theme: &Theme| text::Style {
+                color: Some(iced::Color::from_rgb(0.9, 0.3, 0.3)),
+            }));
+        }
 
-impl FrameRate {
-    fn to_string(&self) -> &'static str {
-        match self {
-            FrameRate::Film23976 => "23.976 (24000/1001)",
-            FrameRate::Film24 => "24.000 (24)",
-            FrameRate::Tv29970 => "29.970 (30000/1001)",
-            FrameRate::Tv25 => "25.000 (25)",
-            FrameRate::Hfr60 => "60.000 (60)",
-            FrameRate::Hfr59940 => "59.940 (60000/1001)",
+        settings = settings.push(with_tooltip(
+            row![
+                text("After Each File:").size(14),
+                text_input(
+                    "curl -X POST \"http://plex:32400/library/sections/1/refresh?X-Plex-Token=...\"",
+                    &self.options.post_command
+                )
+                .on_input(Message::PostCommandChanged)
+                .size(14)
+                .width(Length::Fill),
+            ]
+            .spacing(10)
+            .align_y(Alignment::Center),
+            "Command to run for every output of a successful file, split like a shell command line. {output} and {source} are replaced with the output and source paths. A failing command is logged but does not fail the file",
+        ));
+        if let Err(error) = self.options.post_command() {
+            settings = settings.push(text(error).size(12).style(|_theme: &Theme| text::Style {
+                color: Some(iced::Color::from_rgb(0.9, 0.3, 0.3)),
+            }));
         }
-    }
 
-    fn to_value(&self) -> &'static str {
-        match self {
-            FrameRate::Film23976 => "24000/1001",
-            FrameRate::Film24 => "24",
-            FrameRate::Tv29970 => "30000/1001",
-            FrameRate::Tv25 => "25",
-            FrameRate::Hfr60 => "60",
-            FrameRate::Hfr59940 => "60000/1001",
+        let mut verbosity_row = row![text("Tool Output:").size(14)]
+            .spacing(10)
+            .align_y(Alignment::Center);
+        for tool in verbosity::TOOLS {
+            verbosity_row = verbosity_row.push(text(tool).size(12)).push(
+                pick_list(
+                    verbosity::Verbosity::ALL,
+                    Some(self.options.tool_verbosity.get(tool)),
+                    move |level| Message::ToolVerbositySelected((tool, level)),
+                )
+                .text_size(12),
+            );
         }
-    }
-}
+        settings = settings.push(with_tooltip(
+            verbosity_row.wrap(),
+            "Quiet hides successful output and passes each tool's quiet flag; Verbose logs full output",
+        ));
 
-impl Default for App {
-    fn default() -> Self {
-        Self {
-            file_queue: Vec::new(),
-            output_folder: None,
-            include_subtitles: false,
-            frame_rate: FrameRate::Film23976,
-            processing: false,
-            current_file_index: 0,
-            progress: 0.0,
-            log_messages: Vec::new(),
-            terminal_logs: Vec::new(),
+        settings = settings.push(with_tooltip(
+            button(text(if self.self_testing {
+                "Running Self-Test..."
+            } else {
+                "Run Self-Test"
+            }))
+            .on_press_maybe(
+                (!self.processing && !self.self_testing).then_some(Message::RunSelfTest),
+            ),
+            "Convert a generated 2-second Dolby Vision sample with the default options and check every step, to confirm the tools are installed and working",
+        ));
+
+        // 跳过列表，可以逐个恢复
+        if self.skip_list.is_empty() {
+            settings = settings.push(text("Skip List: empty").size(14));
+        } else {
+            settings = settings.push(text("Skip List:").size(14));
+            for (path, reason) in self.skip_list.iter() {
+                settings = settings.push(
+                    row![
+                        text(format!("{} ({reason})", path.to_string_lossy()))
+                            .size(12)
+                            .width(Length::Fill),
+                        button(text("Allow").size(12)).on_press(Message::UnskipFile(path.clone()))
+                    ]
+                    .spacing(10)
+                    .align_y(Alignment::Center),
+                );
+            }
         }
+
+        column![toggle, container(settings).padding([0, 20])]
+            .spacing(5)
+            .into()
     }
-}
 
-#[derive(Debug, Clone)]
-pub enum Message {
-    SelectInputFiles,
-    InputFilesSelected(Vec<PathBuf>),
-    FilesDropped(Vec<PathBuf>),
-    RemoveFileFromQueue(usize),
-    ClearQueue,
-    SelectOutputFolder,
-    OutputFolderSelected(Option<PathBuf>),
-    ToggleSubtitles(bool),
-    FrameRateSelected(FrameRate),
-    StartProcessing,
-    ProcessingStep(String),
-    ProcessingProgress(f32),
-    ProcessingComplete(Result<(), String>),
-    ClearLog,
-    // 新增：终端日志消息
-    TerminalOutput(String),
-    ClearTerminal,
-    ProcessingCompleteWithLogs((Result<(), String>, Vec<String>)),
-}
+    // 整个队列的预计输出大小
+    fn estimated_batch_size(&self) -> Option<u64> {
+        let sizes = self
+            .file_queue
+            .iter()
+            .filter_map(|item| {
+                let info = item.info.as_ref()?.as_ref().ok()?;
+                info.estimated_output_size(
+                    &item.audio_tracks,
+                    self.options.auto_audio(),
+                    &self.options.subtitle_tracks(
+                        info,
+                        &item.audio_tracks,
+                        item.subtitle_tracks.as_deref(),
+                    ),
+                )
+            })
+            .collect::<Vec<_>>();
+        (!sizes.is_empty()).then(|| sizes.iter().sum())
+    }
 
-impl App {
-    fn subscription(&self) -> iced::Subscription<Message> {
-        event::listen().map(|event| match event {
-            Event::Window(iced::window::Event::FileDropped(path)) => {
-                if let Some(extension) = path.extension() {
-                    if extension.to_string_lossy().to_lowercase() == "mkv" {
-                        return Message::FilesDropped(vec![path]);
-                    }
-                }
-                Message::FilesDropped(vec![])
-            }
-            _ => Message::FilesDropped(vec![]),
-        })
+    // 处理中每个待处理文件距离完成的预计时间，以及使用的吞吐量
+    fn estimated_finish_times(&self) -> Option<(HashMap<PathBuf, Duration>, f64)> {
+        let timing = self.timing.as_ref()?;
+        let rate = timing.rate(self.session.throughput)?;
+        let estimates = timing.estimates(rate, |path| {
+            self.file_queue
+                .iter()
+                .find(|item| item.path == path)
+                .map_or(0, |item| item.size)
+        });
+        Some((estimates, rate))
     }
 
-    fn update(&mut self, message: Message) -> Task<Message> {
-        match message {
-            Message::SelectInputFiles => {
-                Task::perform(select_input_files(), Message::InputFilesSelected)
-            }
-            Message::InputFilesSelected(files) => {
-                self.file_queue.extend(files);
-                Task::none()
-            }
-            Message::FilesDropped(files) => {
-                self.file_queue.extend(files);
-                Task::none()
+    // 队列中的一行：文件名（点击查看详情）、标签和移除按钮
+    fn view_queue_item<'a>(
+        &self,
+        index: usize,
+        item: &'a QueueItem,
+        estimates: Option<&(HashMap<PathBuf, Duration>, f64)>,
+    ) -> Element<'a, Message> {
+        let selected = self.selected_file == Some(index);
+        let mut badges = row![].spacing(5).align_y(Alignment::Center);
+        if let Some((estimates, rate)) = estimates
+            && let Some(remaining) = estimates.get(&item.path)
+        {
+            let current = self
+                .timing
+                .as_ref()
+                .is_some_and(|timing| timing.is_current(&item.path));
+            badges = badges.push(with_tooltip(
+                text(format!(
+                    "{} {}",
+                    if current { "▶ ETA" } else { "ETA" },
+                    eta::format_duration(*remaining)
+                ))
+                .size(11)
+                .style(|theme: &Theme| text::Style {
+                    color: Some(theme.palette().primary),
+                }),
+                format!(
+                    "Estimated time until this file is done, at the measured {}/s",
+                    format_size(*rate as u64)
+                ),
+            ));
+        }
+        if !item.parts.is_empty() {
+            badges = badges.push(with_tooltip(
+                text(format!("+{} parts", item.parts.len())).size(11),
+                format!(
+                    "Joined with mkvmerge before conversion: {}",
+                    std::iter::once(&item.path)
+                        .chain(&item.parts)
+                        .map(|path| path.file_name().unwrap_or_default().to_string_lossy())
+                        .collect::<Vec<_>>()
+                        .join(" + ")
+                ),
+            ));
+        }
+        if let Some(Ok(info)) = &item.info {
+            if let Some(size) = info.estimated_output_size(
+                &item.audio_tracks,
+                self.options.auto_audio(),
+                &self.options.subtitle_tracks(
+                    info,
+                    &item.audio_tracks,
+                    item.subtitle_tracks.as_deref(),
+                ),
+            ) {
+                badges = badges.push(with_tooltip(
+                    text(format!("≈ {}", format_size(size))).size(11),
+                    "Estimated output size",
+                ));
             }
-            Message::RemoveFileFromQueue(index) => {
-                if index < self.file_queue.len() {
-                    self.file_queue.remove(index);
-                }
-                Task::none()
+            let languages = info.language_summary();
+            if !languages.is_empty() {
+                badges =
+                    badges.push(
+                        text(languages)
+                            .size(11)
+                            .style(|_theme: &Theme| text::Style {
+                                color: Some(iced::Color::from_rgb(0.6, 0.6, 0.6)),
+                            }),
+                    );
             }
-            Message::ClearQueue => {
-                self.file_queue.clear();
-                Task::none()
+            if !info.has_dolby_vision() {
+                badges = badges.push(with_tooltip(
+                    badge("NO DV"),
+                    "No Dolby Vision metadata found, handled by the Non-DV Files option",
+                ));
             }
-            Message::SelectOutputFolder => {
-                Task::perform(select_output_folder(), Message::OutputFolderSelected)
+            if info.has_dolby_vision() && info.video.as_ref().is_some_and(|video| video.is_av1()) {
+                badges = badges.push(with_tooltip(
+                    badge("AV1 DV"),
+                    "Experimental: AV1 Dolby Vision is muxed by ffmpeg (6.1 or newer) with dav1/av01 + dvvC instead of mp4muxer",
+                ));
             }
-            Message::OutputFolderSelected(path) => {
-                self.output_folder = path;
-                Task::none()
+            if info.video.as_ref().is_some_and(|video| video.hdr10_plus) {
+                badges = badges.push(with_tooltip(
+                    badge("HDR10+"),
+                    "HDR10+ dynamic metadata is kept alongside Dolby Vision (hdr10plus_tool)",
+                ));
             }
-            Message::ToggleSubtitles(enabled) => {
-                self.include_subtitles = enabled;
-                Task::none()
+            if info.has_atmos() {
+                badges = badges.push(badge("ATMOS"));
             }
-            Message::FrameRateSelected(frame_rate) => {
-                self.frame_rate = frame_rate;
-                Task::none()
+            if self.options.subtitle_mode != SubtitleMode::None && info.has_only_image_subtitles() {
+                badges = badges.push(with_tooltip(
+                    badge("IMAGE SUBS"),
+                    if self.options.ocr_subtitles {
+                        "Only PGS/VobSub subtitles: PGS tracks are recognized as text (OCR), VobSub is skipped"
+                    } else {
+                        "Only PGS/VobSub subtitles, which will be skipped"
+                    },
+                ));
             }
-            Message::StartProcessing => {
-                if !self.file_queue.is_empty() && self.output_folder.is_some() {
-                    self.processing = true;
-                    self.current_file_index = 0;
-                    self.progress = 0.0;
-                    self.log_messages.clear();
-                    self.terminal_logs.clear();
-
-                    let files = self.file_queue.clone();
-                    let output = self.output_folder.as_ref().unwrap().clone();
-                    let frame_rate = self.frame_rate.clone();
-                    let include_subtitles = self.include_subtitles;
+        }
 
-                    Task::perform(
-                        process_video_queue_with_logs(files, output, frame_rate, include_subtitles),
-                        Message::ProcessingCompleteWithLogs,
-                    )
+        let row = row![
+            button(
+                text(format!(
+                    "{}. {}",
+                    index + 1,
+                    item.path.file_name().unwrap_or_default().to_string_lossy()
+                ))
+                .size(12)
+            )
+            .on_press(Message::SelectQueueItem(index))
+            .width(Length::Fill)
+            .style(move |theme: &Theme, status| {
+                if selected {
+                    button::secondary(theme, status)
                 } else {
-                    Task::none()
-                }
-            }
-            Message::ProcessingStep(step) => {
-                self.log_messages.push(step);
-                Task::none()
-            }
-            Message::ProcessingProgress(progress) => {
-                self.progress = progress;
-                Task::none()
-            }
-            Message::ProcessingComplete(result) => {
-                self.processing = false;
-                match result {
-                    Ok(_) => {
-                        self.log_messages
-                            .push("✅ Processing completed successfully!".to_string());
-                        self.progress = 1.0;
-                    }
-                    Err(err) => {
-                        self.log_messages
-                            .push(format!("❌ Processing failed: {err}"));
-                        self.progress = 0.0;
-                    }
-                }
-                Task::none()
-            }
-            Message::ClearLog => {
-                self.log_messages.clear();
-                Task::none()
-            }
-            Message::TerminalOutput(output) => {
-                self.terminal_logs.push(output);
-                Task::none()
-            }
-            Message::ClearTerminal => {
-                self.terminal_logs.clear();
-                Task::none()
-            }
-            Message::ProcessingCompleteWithLogs((result, logs)) => {
-                self.processing = false;
-                // 将终端日志添加到terminal_logs
-                self.terminal_logs.extend(logs);
-                match result {
-                    Ok(_) => {
-                        self.log_messages
-                            .push("✅ Processing completed successfully!".to_string());
-                        self.progress = 1.0;
-                    }
-                    Err(err) => {
-                        self.log_messages
-                            .push(format!("❌ Processing failed: {err}"));
-                        self.progress = 0.0;
-                    }
+                    button::text(theme, status)
                 }
-                Task::none()
-            }
+            }),
+            badges,
+            with_tooltip(
+                button("Skip").on_press(Message::SkipFile(index)),
+                "Never process this file: remove it and ignore it when added again or seen in the watch folder"
+            ),
+            with_tooltip(
+                button("Remove")
+                    .on_press(Message::RemoveFileFromQueue(index))
+                    .style(|theme: &Theme, _status| {
+                        button::Style {
+                            background: Some(iced::Background::Color(iced::Color::from_rgb(
+                                0.8, 0.2, 0.2,
+                            ))),
+                            text_color: iced::Color::WHITE,
+                            ..button::primary(theme, _status)
+                        }
+                    }),
+                "Remove this file (select it and press Delete)"
+            )
+        ]
+        .spacing(10)
+        .align_y(Alignment::Center);
+
+        let Some((error, tail)) = &item.failure else {
+            return row.into();
+        };
+
+        // 失败的文件展开显示错误和失败步骤的输出
+        let mut failure = column![
+            text(format!("❌ {}", error.lines().next().unwrap_or_default()))
+                .size(12)
+                .style(|_theme: &Theme| text::Style {
+                    color: Some(iced::Color::from_rgb(0.9, 0.3, 0.3)),
+                })
+        ]
+        .spacing(2);
+        for line in tail {
+            failure = failure.push(text(line).size(11).font(iced::Font::MONOSPACE));
         }
+        column![
+            row,
+            container(failure)
+                .padding([5, 20])
+                .style(container::rounded_box)
+        ]
+        .spacing(5)
+        .into()
     }
 
-    fn view(&self) -> Element<Message> {
+    fn view(&self) -> Element<'_, Message> {
         let title = text("Dolby Vision MKV to MP4 Converter")
             .size(32)
             .style(|theme: &Theme| text::Style {
                 color: Some(theme.palette().primary),
             });
 
+        let keyboard_hint = text(
+            "Keyboard: ↑/↓ select file · Delete remove · Tab next field · Ctrl+O add files · Ctrl+Enter start",
+        )
+        .size(12)
+        .style(|_theme: &Theme| text::Style {
+            color: Some(iced::Color::from_rgb(0.6, 0.6, 0.6)),
+        });
+
+        let estimates = self.estimated_finish_times();
+        let mut queue_summary = match self.estimated_batch_size() {
+            Some(size) => format!(
+                "{} files · ≈ {} output",
+                self.file_queue.len(),
+                format_size(size)
+            ),
+            None => format!("{} files", self.file_queue.len()),
+        };
+        if let Some(remaining) = estimates
+            .as_ref()
+            .and_then(|(estimates, _)| estimates.values().max())
+        {
+            queue_summary.push_str(&format!(" · done in {}", eta::format_duration(*remaining)));
+        }
         let queue_header = row![
             text("File Queue:").size(16),
             Space::with_width(Length::Fill),
-            text(format!("{} files", self.file_queue.len())).size(14),
-            button("Select Files").on_press(Message::SelectInputFiles),
-            button("Clear Queue").on_press(Message::ClearQueue)
+            text(queue_summary).size(14),
+            pick_list(
+                [QueueSort::Added, QueueSort::Name, QueueSort::Size],
+                Some(self.session.queue_sort),
+                Message::QueueSortSelected
+            )
+            .text_size(14),
+            with_tooltip(
+                button("Select Files").on_press(Message::SelectInputFiles),
+                "Add MKV files to the queue (Ctrl+O)"
+            ),
+            with_tooltip(
+                button("Import from Server").on_press(Message::ToggleImporter),
+                "Queue Dolby Vision MKV files from a Jellyfin or Plex library that have no MP4 version yet"
+            ),
+            with_tooltip(
+                button("Export Report")
+                    .on_press_maybe((!self.file_queue.is_empty()).then_some(Message::ExportReport)),
+                "Save the queue's tracks, Dolby Vision profile and options as JSON or Markdown"
+            ),
+            with_tooltip(
+                button("Clear Queue").on_press(Message::ClearQueue),
+                "Remove all files from the queue"
+            )
         ]
         .spacing(10)
         .align_y(Alignment::Center);
@@ -273,216 +4275,1263 @@ impl App {
                             self.file_queue
                                 .iter()
                                 .enumerate()
-                                .map(|(index, file)| {
-                                    row![
-                                        text(format!(
-                                            "{}. {}",
-                                            index + 1,
-                                            file.file_name().unwrap_or_default().to_string_lossy()
-                                        ))
-                                        .size(12)
-                                        .width(Length::Fill),
-                                        button("Remove")
-                                            .on_press(Message::RemoveFileFromQueue(index))
-                                            .style(|theme: &Theme, _status| {
-                                                button::Style {
-                                                    background: Some(iced::Background::Color(
-                                                        iced::Color::from_rgb(0.8, 0.2, 0.2),
-                                                    )),
-                                                    text_color: iced::Color::WHITE,
-                                                    ..button::primary(theme, _status)
-                                                }
-                                            })
-                                    ]
-                                    .spacing(10)
-                                    .align_y(Alignment::Center)
-                                    .into()
+                                .map(|(index, item)| {
+                                    self.view_queue_item(index, item, estimates.as_ref())
                                 })
                                 .collect::<Vec<_>>(),
                         )
                         .spacing(5),
                     )
+                    .id(queue_scroll_id())
                     .height(Length::Fixed(150.0)),
                 )
-                .padding(10)
-                .style(|_theme: &Theme| container::Style {
-                    background: Some(iced::Background::Color(iced::Color::from_rgb(
-                        0.05, 0.05, 0.05,
-                    ))),
-                    border: iced::Border {
-                        color: iced::Color::from_rgb(0.3, 0.3, 0.3),
-                        width: 1.0,
-                        radius: 4.0.into(),
-                    },
-                    ..Default::default()
-                })
-            };
-
-        let input_section = column![queue_header, queue_list].spacing(10);
-
-        let output_section = column![
-            text("Output Folder:").size(16),
+                .padding(10)
+                .style(|_theme: &Theme| container::Style {
+                    background: Some(iced::Background::Color(iced::Color::from_rgb(
+                        0.05, 0.05, 0.05,
+                    ))),
+                    border: iced::Border {
+                        color: iced::Color::from_rgb(0.3, 0.3, 0.3),
+                        width: 1.0,
+                        radius: 4.0.into(),
+                    },
+                    ..Default::default()
+                })
+            };
+
+        let mut input_section = column![queue_header].spacing(10);
+        if self.show_importer {
+            input_section = input_section.push(self.view_importer());
+        }
+        input_section = input_section.push(queue_list);
+        if let Some(item) = self
+            .selected_file
+            .and_then(|index| self.file_queue.get(index))
+        {
+            let joinable = self
+                .selected_file
+                .map_or(0, |index| self.joinable_parts(index).len());
+            input_section = input_section.push(view_file_info(item, &self.options, joinable));
+        }
+
+        let output_section = column![
+            text("Output Folder:").size(16),
+            row![
+                text(
+                    self.output_folder
+                        .as_ref()
+                        .map(|p| p.to_string_lossy().to_string())
+                        .unwrap_or_else(|| "No folder selected".to_string())
+                )
+                .width(Length::Fill),
+                button("Select Output Folder").on_press(Message::SelectOutputFolder)
+            ]
+            .spacing(10)
+            .align_y(Alignment::Center),
+            self.view_watch_folder(),
+        ]
+        .spacing(5);
+
+        let options_section = column![
+            text("Options:").size(16),
+            with_tooltip(
+                row![
+                    text("Subtitles:").size(14),
+                    pick_list(
+                        SubtitleMode::ALL,
+                        Some(self.options.subtitle_mode),
+                        Message::SubtitleModeSelected
+                    )
+                    .text_size(14)
+                ]
+                .spacing(10)
+                .align_y(Alignment::Center),
+                "Text subtitles converted to mov_text and muxed into a _with_subs MP4: the track in the first audio track's language (or the first one), or every text track. PGS/VobSub are skipped"
+            ),
+            with_tooltip(
+                row![
+                    text("SDH Subtitles:").size(14),
+                    pick_list(
+                        SdhPreference::ALL,
+                        Some(self.options.sdh_subtitles),
+                        Message::SdhPreferenceSelected
+                    )
+                    .text_size(14)
+                ]
+                .spacing(10)
+                .align_y(Alignment::Center),
+                "How automatic subtitle selection treats SDH (hearing impaired) tracks, recognized by the container flag or an SDH/HI/CC title. Prefer: use the SDH track when a language has both. Exclude: never pick SDH tracks. Tracks chosen in the file info are always used"
+            ),
+            with_tooltip(
+                checkbox("OCR Image Subtitles", self.options.ocr_subtitles)
+                    .on_toggle(Message::ToggleOcrSubtitles),
+                "When a Dolby Vision source only has PGS subtitles, recognize them as text with PgsToSrt (Tesseract) in the track's language. Slow: several minutes per track"
+            ),
+            with_tooltip(
+                checkbox("Keep MP4 Without Subtitles", self.options.keep_unsubtitled)
+                    .on_toggle(Message::ToggleKeepUnsubtitled),
+                "Also keep the _dvh1.mp4 without subtitles next to the _with_subs MP4. Off: the subtitled MP4 replaces it, so each file is written once"
+            ),
+            with_tooltip(
+                checkbox("Include All Audio Tracks", self.options.all_audio_tracks)
+                    .on_toggle(Message::ToggleAllAudioTracks),
+                "Mux every audio track in source order with its language tag, unless tracks are chosen in the file info"
+            ),
+            with_tooltip(
+                checkbox("Skip Commentary Tracks", self.options.skip_commentary)
+                    .on_toggle_maybe(
+                        self.options
+                            .all_audio_tracks
+                            .then_some(Message::ToggleSkipCommentary)
+                    ),
+                "With \"Include All Audio Tracks\", leave out tracks flagged as commentary or titled \"Commentary\". Tracks chosen in the file info are always muxed"
+            ),
+            with_tooltip(
+                checkbox("Use AC-3 Core for TrueHD", self.options.truehd_ac3_core)
+                    .on_toggle(Message::ToggleTruehdAc3Core),
+                "Use the Blu-ray AC-3 core (the AC-3 track with the same language that follows the TrueHD track) instead of TrueHD. Falls back to the option below if there is none"
+            ),
+            with_tooltip(
+                checkbox("Transcode TrueHD to E-AC-3", self.options.truehd_to_eac3)
+                    .on_toggle(Message::ToggleTruehdToEac3),
+                "Most players can't play TrueHD from an MP4. Lossy E-AC-3 at the bitrate in the advanced settings, downmixed to 5.1; Atmos objects are not preserved"
+            ),
+            with_tooltip(
+                checkbox("Add AAC Stereo Track", self.options.aac_stereo)
+                    .on_toggle(Message::ToggleAacStereo),
+                "Also add a 192 kb/s AAC 2.0 downmix of the first audio track for browsers and TVs that can't decode E-AC-3"
+            ),
+            with_tooltip(
+                checkbox("Video Only (No Audio)", self.options.video_only)
+                    .on_toggle(Message::ToggleVideoOnly),
+                "Skip audio entirely and write a Dolby Vision video-only MP4, e.g. to pair with an external audio workflow or when audio extraction fails"
+            ),
+            with_tooltip(
+                checkbox("Normalize Loudness", self.options.loudnorm)
+                    .on_toggle(Message::ToggleLoudnorm),
+                "Apply EBU R128 loudness normalization (ffmpeg loudnorm) to transcoded audio, at the target in the advanced settings, for quieter dialog-heavy mixes on phones and tablets. Copied tracks are not changed"
+            ),
+            with_tooltip(
+                checkbox("Also Create HDR10 MP4", self.options.hdr10_fallback)
+                    .on_toggle(Message::ToggleHdr10Fallback),
+                "Write a second _hdr10.mp4 with the Dolby Vision metadata removed (dovi_tool remove) for devices without Dolby Vision"
+            ),
+            with_tooltip(
+                checkbox("Export Poster Thumbnail", self.options.export_poster)
+                    .on_toggle(Message::ToggleExportPoster),
+                "Save a tone-mapped frame from 20% into the movie as name-poster.jpg next to the output"
+            ),
+            with_tooltip(
+                checkbox("Verify Source First", self.options.verify_source)
+                    .on_toggle(Message::ToggleVerifySource),
+                "Decode each file once (ffmpeg -v error -f null) and stop on corrupt or truncated sources before extracting"
+            ),
+            with_tooltip(
+                checkbox("Deep Probe Before Start", self.session.deep_probe_before_start)
+                    .on_toggle(Message::ToggleDeepProbeBeforeStart),
+                "Before a batch starts, sample each file for a variable frame rate, black bars and a Dolby Vision RPU summary. Adding files only reads the container so slow network sources stay responsive"
+            ),
+            with_tooltip(
+                checkbox("Verify Copied Audio", self.options.verify_audio)
+                    .on_toggle(Message::ToggleVerifyAudio),
+                "After muxing, hash every packet of each copied audio track in the source and the output (ffmpeg framemd5) and fail if the copy was altered or lost the last seconds"
+            ),
+            with_tooltip(
+                checkbox("Check Extracted Audio", self.options.check_audio)
+                    .on_toggle(Message::ToggleCheckAudio),
+                "After extraction, count the packets and read the duration of each audio file (ffprobe) and stop if a track is empty or much shorter than the video instead of producing a silent MP4"
+            ),
+            with_tooltip(
+                checkbox("Annotate Source MKV", self.options.annotate_source)
+                    .on_toggle(Message::ToggleAnnotateSource),
+                "After a successful conversion, tag the source MKV with the date and output path (mkvpropedit)"
+            ),
+            with_tooltip(
+                checkbox(
+                    "Quick Mode (dropping a single file converts it next to the source)",
+                    self.session.quick_mode
+                )
+                .on_toggle(Message::ToggleQuickMode),
+                "Uses the current options and asks once before starting"
+            ),
+            row![
+                text("Dolby Vision Profile:"),
+                with_tooltip(
+                    pick_list(
+                        DvProfile::ALL,
+                        Some(self.options.dv_profile),
+                        Message::DvProfileSelected
+                    ),
+                    "Profile written by mp4muxer; Auto uses the profile suggested for each source"
+                )
+            ]
+            .spacing(10)
+            .align_y(Alignment::Center),
+            row![
+                text("Sample Entry:"),
+                with_tooltip(
+                    pick_list(
+                        SampleEntry::ALL,
+                        Some(self.options.sample_entry),
+                        Message::SampleEntrySelected
+                    ),
+                    "Apple devices need dvh1; some other players only recognize dvhe. Also used as the output file suffix"
+                )
+            ]
+            .spacing(10)
+            .align_y(Alignment::Center),
+            column![
+                row![
+                    text("Profile 7 Enhancement Layer:"),
+                    pick_list(
+                        EnhancementLayer::ALL,
+                        Some(self.options.enhancement_layer),
+                        Message::EnhancementLayerSelected
+                    )
+                ]
+                .spacing(10)
+                .align_y(Alignment::Center),
+                text(self.options.enhancement_layer.description())
+                    .size(12)
+                    .style(|_theme: &Theme| text::Style {
+                        color: Some(iced::Color::from_rgb(0.9, 0.6, 0.2)),
+                    }),
+            ]
+            .spacing(3),
+            row![
+                text("Non-DV Files:"),
+                pick_list(
+                    NonDvMode::ALL,
+                    Some(self.options.non_dv_mode),
+                    Message::NonDvModeSelected
+                )
+            ]
+            .spacing(10)
+            .align_y(Alignment::Center),
+            row![
+                text("Existing Outputs:"),
+                with_tooltip(
+                    pick_list(
+                        ExistingOutputs::ALL,
+                        Some(self.options.existing_outputs),
+                        Message::ExistingOutputsSelected
+                    ),
+                    "When an output such as Movie_dvh1.mp4 is already in the output folder: replace it, skip the file, or write Movie (2)_dvh1.mp4 instead"
+                )
+            ]
+            .spacing(10)
+            .align_y(Alignment::Center),
             row![
-                text(
-                    self.output_folder
-                        .as_ref()
-                        .map(|p| p.to_string_lossy().to_string())
-                        .unwrap_or_else(|| "No folder selected".to_string())
+                text("Source After Success:"),
+                with_tooltip(
+                    pick_list(
+                        SourceAfterSuccess::ALL,
+                        Some(self.options.source_after_success),
+                        Message::SourceAfterSuccessSelected
+                    ),
+                    "What to do with the source MKV (and its other parts) once every output has been verified: leave it, move it into a \"processed\" folder next to it, send it to the recycle bin, or delete it"
                 )
-                .width(Length::Fill),
-                button("Select Output Folder").on_press(Message::SelectOutputFolder)
             ]
             .spacing(10)
             .align_y(Alignment::Center),
-        ]
-        .spacing(5);
-
-        let options_section = column![
-            text("Options:").size(16),
-            checkbox("Include Subtitles", self.include_subtitles)
-                .on_toggle(Message::ToggleSubtitles),
+            row![
+                text("Default Language:"),
+                with_tooltip(
+                    text_input("und", &self.options.default_language)
+                        .on_input(Message::DefaultLanguageChanged)
+                        .width(Length::Fixed(60.0)),
+                    "Written to audio and subtitle tracks whose source has no language tag, so players don't show \"Unknown\""
+                )
+            ]
+            .push_maybe(self.options.language_error().map(|error| {
+                text(error).size(12).style(|_theme: &Theme| text::Style {
+                    color: Some(iced::Color::from_rgb(0.9, 0.3, 0.3)),
+                })
+            }))
+            .spacing(10)
+            .align_y(Alignment::Center),
             row![
                 text("Frame Rate:"),
                 pick_list(
-                    vec![
-                        FrameRate::Film23976,
-                        FrameRate::Film24,
-                        FrameRate::Tv29970,
-                        FrameRate::Tv25,
-                        FrameRate::Hfr60,
-                        FrameRate::Hfr59940,
-                    ],
-                    Some(self.frame_rate.clone()),
+                    FrameRate::PRESETS,
+                    Some(self.options.frame_rate).filter(FrameRate::is_preset),
                     Message::FrameRateSelected
                 )
                 .text_size(14)
-                .placeholder("Select Frame Rate")
+                .placeholder("Custom"),
+                with_tooltip(
+                    text_input(
+                        &if self.options.frame_rate.is_preset() {
+                            "Custom".to_string()
+                        } else {
+                            self.options.frame_rate.to_value()
+                        },
+                        &self.custom_frame_rate
+                    )
+                    .on_input(Message::CustomFrameRateChanged)
+                    .size(14)
+                    .width(Length::Fixed(120.0)),
+                    "Another rate, e.g. 48, 50 or 120000/1001. Passed to mp4muxer --input-video-frame-rate as entered"
+                ),
             ]
             .spacing(10)
             .align_y(Alignment::Center),
+            column![].push_maybe(
+                FrameRate::parse(&self.custom_frame_rate)
+                    .err()
+                    .filter(|_| !self.custom_frame_rate.trim().is_empty())
+                    .map(|error| {
+                        text(error).size(12).style(|_theme: &Theme| text::Style {
+                            color: Some(iced::Color::from_rgb(0.9, 0.3, 0.3)),
+                        })
+                    })
+            ),
+            self.view_advanced_settings(),
         ]
         .spacing(10);
 
         let process_section = column![if self.processing {
             column![
-                text("Processing...").size(16),
+                row![
+                    text(if self.pause_requested.load(Ordering::Relaxed) {
+                        format!(
+                            "Processing... {:.0}% (pausing after this file)",
+                            self.progress * 100.0
+                        )
+                    } else {
+                        format!("Processing... {:.0}%", self.progress * 100.0)
+                    })
+                    .size(16),
+                    Space::with_width(Length::Fill),
+                    self.view_when_finished(),
+                    with_tooltip(
+                        button(
+                            text(if self.pause_requested.load(Ordering::Relaxed) {
+                                "Keep Going"
+                            } else {
+                                "Pause"
+                            })
+                            .size(14)
+                        )
+                        .on_press(Message::TogglePause),
+                        "Pause the batch after the current file completes"
+                    ),
+                    with_tooltip(
+                        button(text("Cancel").size(14)).on_press(Message::CancelProcessing),
+                        "Stop the running tools, remove temporary files and end the batch"
+                    )
+                ]
+                .spacing(10)
+                .align_y(Alignment::Center),
                 progress_bar(0.0..=1.0, self.progress)
             ]
             .spacing(5)
         } else {
             column![
-                button("Start Batch Processing")
-                    .on_press_maybe(
-                        if !self.file_queue.is_empty() && self.output_folder.is_some() {
-                            Some(Message::StartProcessing)
-                        } else {
-                            None
-                        }
+                row![with_tooltip(
+                    button("Start Batch Processing")
+                        .on_press_maybe(self.can_start().then_some(Message::StartProcessing))
+                        .style(|theme: &Theme, status| {
+                            button::Style {
+                                background: Some(iced::Background::Color(theme.palette().primary)),
+                                text_color: theme.palette().background,
+                                ..button::primary(theme, status)
+                            }
+                        }),
+                    "Convert every file in the queue (Ctrl+Enter)"
+                )]
+                .push_maybe(self.projects.paused.as_ref().map(|batch| {
+                    with_tooltip(
+                        button(text(format!(
+                            "Resume Paused Batch ({} files)",
+                            batch.files.len()
+                        )))
+                        .on_press(Message::ResumeBatch),
+                        "Continue the paused batch with the files it had not processed yet",
                     )
-                    .style(|theme: &Theme, status| {
-                        button::Style {
-                            background: Some(iced::Background::Color(theme.palette().primary)),
-                            text_color: theme.palette().background,
-                            ..button::primary(theme, status)
-                        }
-                    })
+                }))
+                .push(Space::with_width(Length::Fill))
+                .push(self.view_when_finished())
+                .spacing(10)
+                .align_y(Alignment::Center)
             ]
+            .push_maybe(self.power_countdown.map(|remaining| {
+                row![
+                    text(format!(
+                        "⏻ {} in {remaining}s...",
+                        self.when_finished.verb()
+                    ))
+                    .size(16)
+                    .style(|_theme: &Theme| text::Style {
+                        color: Some(iced::Color::from_rgb(0.9, 0.6, 0.2)),
+                    }),
+                    button(text("Cancel").size(14)).on_press(Message::CancelPowerAction)
+                ]
+                .spacing(10)
+                .align_y(Alignment::Center)
+            }))
+            .push_maybe(self.view_frame_rate_warnings())
         }];
 
-        let log_section = if !self.log_messages.is_empty() {
+        let tab_button = |label: &'static str, tab: Tab| {
+            let active = self.session.active_tab == tab;
+            button(text(label).size(14))
+                .on_press(Message::TabSelected(tab))
+                .style(move |theme: &Theme, status| {
+                    if active {
+                        button::primary(theme, status)
+                    } else {
+                        button::secondary(theme, status)
+                    }
+                })
+        };
+
+        let tab_controls: Element<Message> = match self.session.active_tab {
+            Tab::Log => button("Clear Log").on_press(Message::ClearLog).into(),
+            Tab::Metadata => row![
+                with_tooltip(
+                    button("Analyze Selected").on_press_maybe(
+                        (self.selected_file.is_some() && !self.analyzing)
+                            .then_some(Message::AnalyzeSelected)
+                    ),
+                    "Extract the RPU of the selected queue file and chart its L1 brightness"
+                ),
+                with_tooltip(
+                    button("Analyze File...")
+                        .on_press_maybe((!self.analyzing).then_some(Message::AnalyzeFile)),
+                    "Analyze another MKV or MP4, e.g. a converted output, to compare"
+                ),
+                button("Clear").on_press(Message::ClearAnalyses)
+            ]
+            .spacing(10)
+            .align_y(Alignment::Center)
+            .into(),
+            Tab::Stats => row![
+                checkbox("Collect failure stats", self.session.collect_stats)
+                    .on_toggle(Message::ToggleCollectStats)
+                    .text_size(12),
+                button("Clear Stats").on_press(Message::ClearStats)
+            ]
+            .spacing(10)
+            .align_y(Alignment::Center)
+            .into(),
+            Tab::Pipeline => text("Current settings, for a Dolby Vision HEVC source")
+                .size(12)
+                .into(),
+            Tab::Compare => {
+                let presets = std::iter::once(CURRENT_SETTINGS.to_string())
+                    .chain(self.projects.names())
+                    .collect::<Vec<_>>();
+                let picker = |slot: usize| {
+                    pick_list(
+                        presets.clone(),
+                        self.compare_presets[slot].clone(),
+                        move |name| Message::ComparePresetSelected((slot, name)),
+                    )
+                    .placeholder("Preset")
+                    .text_size(12)
+                };
+                row![
+                    picker(0),
+                    text("vs").size(12),
+                    picker(1),
+                    with_tooltip(
+                        button("Save Report...").on_press_maybe(
+                            (self.compared_presets().is_some() && !self.file_queue.is_empty())
+                                .then_some(Message::SaveComparison)
+                        ),
+                        "Save the side-by-side dry run as Markdown"
+                    )
+                ]
+                .spacing(10)
+                .align_y(Alignment::Center)
+                .into()
+            }
+            Tab::Library => with_tooltip(
+                button(if self.reconciling {
+                    "Scanning..."
+                } else {
+                    "Scan Folder..."
+                })
+                .on_press_maybe((!self.reconciling).then_some(Message::ReconcileFolder)),
+                "Pick an output library folder (subfolders included) to audit",
+            ),
+            Tab::Terminal => row![
+                text_input("Filter...", &self.session.terminal_filter)
+                    .on_input(Message::TerminalFilterChanged)
+                    .size(12)
+                    .width(Length::Fixed(200.0)),
+                checkbox("Errors only", self.session.terminal_errors_only)
+                    .on_toggle(Message::TerminalErrorsOnlyToggled)
+                    .text_size(12),
+                button("Clear Terminal").on_press(Message::ClearTerminal)
+            ]
+            .spacing(10)
+            .align_y(Alignment::Center)
+            .into(),
+        };
+
+        let tab_bar = row![
+            tab_button("Processing Log", Tab::Log),
+            tab_button("Terminal", Tab::Terminal),
+            tab_button("Stats", Tab::Stats),
+            tab_button("DV Metadata", Tab::Metadata),
+            tab_button("Pipeline", Tab::Pipeline),
+            tab_button("Compare", Tab::Compare),
+            tab_button("Library", Tab::Library),
+            Space::with_width(Length::Fill),
+            tab_controls
+        ]
+        .spacing(5)
+        .align_y(Alignment::Center);
+
+        let tab_content: Vec<Element<Message>> = match self.session.active_tab {
+            Tab::Log => self
+                .log_messages
+                .iter()
+                .map(|msg| text(msg).size(12).into())
+                .collect(),
+            // 新增：终端显示区域
+            Tab::Terminal => {
+                let filter = self.session.terminal_filter.to_lowercase();
+                self.terminal_logs
+                    .iter()
+                    .filter(|line| !self.session.terminal_errors_only || is_error_line(line))
+                    .filter(|line| filter.is_empty() || line.to_lowercase().contains(&filter))
+                    .map(|cmd| text(cmd).size(11).font(iced::Font::MONOSPACE).into())
+                    .collect()
+            }
+            Tab::Stats => self.view_stats(),
+            Tab::Metadata => self.view_analyses(),
+            Tab::Pipeline => self.view_pipeline_graph(),
+            Tab::Compare => self.view_comparison(),
+            Tab::Library => self.view_reconciliation(),
+        };
+
+        let tab_section = column![
+            tab_bar,
+            container(
+                scrollable(column(tab_content).spacing(2))
+                    .height(Length::Fixed(350.0))
+                    .width(Length::Fill)
+            )
+            .style(|_theme: &Theme| container::Style {
+                background: Some(iced::Background::Color(iced::Color::from_rgb(
+                    0.1, 0.1, 0.1
+                ))),
+                border: iced::Border {
+                    color: iced::Color::from_rgb(0.3, 0.3, 0.3),
+                    width: 1.0,
+                    radius: 4.0.into(),
+                },
+                ..Default::default()
+            })
+            .padding(10)
+            .width(Length::Fill)
+        ]
+        .spacing(5);
+
+        container(
             column![
                 row![
-                    text("Processing Log:").size(16),
+                    column![title, keyboard_hint].spacing(5),
                     Space::with_width(Length::Fill),
-                    button("Clear Log").on_press(Message::ClearLog)
+                    self.view_projects()
                 ]
                 .align_y(Alignment::Center),
-                container(
-                    scrollable(
-                        column(
-                            self.log_messages
-                                .iter()
-                                .map(|msg| text(msg).size(12).into())
-                                .collect::<Vec<_>>()
-                        )
-                        .spacing(2)
-                    )
-                    .height(Length::Fixed(150.0))
-                )
-                .style(|_theme: &Theme| container::Style {
-                    background: Some(iced::Background::Color(iced::Color::from_rgb(
-                        0.1, 0.1, 0.1
-                    ))),
-                    border: iced::Border {
-                        color: iced::Color::from_rgb(0.3, 0.3, 0.3),
-                        width: 1.0,
-                        radius: 4.0.into(),
-                    },
-                    ..Default::default()
-                })
-                .padding(10)
+                input_section,
+                output_section,
+                options_section,
+                process_section,
+                tab_section
             ]
-            .spacing(5)
-        } else {
-            column![]
-        };
+            .spacing(20)
+            .max_width(1200),
+        )
+        .padding(20)
+        .center_x(Length::Fill)
+        .width(Length::Fill)
+        .height(Length::Fill)
+        .into()
+    }
+}
+
+// 键盘快捷键；仅处理未被输入框等控件占用的按键
+fn handle_key_press(key: Key, modifiers: keyboard::Modifiers) -> Option<Message> {
+    match key.as_ref() {
+        Key::Named(Named::ArrowUp) => Some(Message::MoveSelection(-1)),
+        Key::Named(Named::ArrowDown) => Some(Message::MoveSelection(1)),
+        Key::Named(Named::Delete) => Some(Message::RemoveSelected),
+        Key::Named(Named::Tab) if modifiers.shift() => Some(Message::FocusPrevious),
+        Key::Named(Named::Tab) => Some(Message::FocusNext),
+        Key::Named(Named::Enter) if modifiers.command() => Some(Message::StartProcessing),
+        Key::Character("o") if modifiers.command() => Some(Message::SelectInputFiles),
+        _ => None,
+    }
+}
+
+fn queue_scroll_id() -> scrollable::Id {
+    scrollable::Id::new("queue")
+}
+
+// 带快捷键说明的提示
+fn with_tooltip<'a>(
+    content: impl Into<Element<'a, Message>>,
+    label: impl text::IntoFragment<'a>,
+) -> Element<'a, Message> {
+    tooltip(
+        content,
+        container(text(label).size(12))
+            .padding(5)
+            .style(container::rounded_box),
+        tooltip::Position::Bottom,
+    )
+    .into()
+}
+
+// 以 1024 为单位格式化字节数
+fn format_size(bytes: u64) -> String {
+    const UNITS: [&str; 5] = ["B", "KB", "MB", "GB", "TB"];
+    let mut size = bytes as f64;
+    let mut unit = 0;
+    while size >= 1024.0 && unit < UNITS.len() - 1 {
+        size /= 1024.0;
+        unit += 1;
+    }
+    if unit == 0 {
+        format!("{bytes} B")
+    } else {
+        format!("{size:.1} {}", UNITS[unit])
+    }
+}
+
+// 队列中的小标签
+fn badge(label: &str) -> Element<'_, Message> {
+    container(text(label).size(10))
+        .padding([2, 6])
+        .style(|theme: &Theme| container::Style {
+            background: Some(iced::Background::Color(theme.palette().primary)),
+            text_color: Some(theme.palette().background),
+            border: iced::Border {
+                radius: 4.0.into(),
+                ..Default::default()
+            },
+            ..Default::default()
+        })
+        .into()
+}
+
+// 终端中的错误输出行
+fn is_error_line(line: &str) -> bool {
+    line.starts_with("Error") || line.contains("failed") || line.contains('❌')
+}
+
+// 选中文件的详细信息区域
+// joinable：队列中可以拼接在这个文件之后的分段数
+fn view_file_info<'a>(
+    item: &'a QueueItem,
+    options: &ProcessOptions,
+    joinable: usize,
+) -> Element<'a, Message> {
+    let mut details = column![
+        text(format!(
+            "File Info: {}",
+            item.path.file_name().unwrap_or_default().to_string_lossy()
+        ))
+        .size(14)
+    ]
+    .spacing(4);
+
+    match &item.info {
+        None => details = details.push(text("Analyzing...").size(12)),
+        Some(Err(err)) => details = details.push(text(format!("Analysis failed: {err}")).size(12)),
+        Some(Ok(info)) => {
+            if let Some(duration) = info.duration {
+                details = details.push(text(format!("Duration: {duration:.1}s")).size(12));
+            }
+            if let Some(size) = info.estimated_output_size(
+                &item.audio_tracks,
+                options.auto_audio(),
+                &options.subtitle_tracks(info, &item.audio_tracks, item.subtitle_tracks.as_deref()),
+            ) {
+                details = details
+                    .push(text(format!("Estimated output size: {}", format_size(size))).size(12));
+            }
+            match &info.video {
+                Some(video) => {
+                    details = details.push(
+                        text(format!(
+                            "Video: {} {}x{}{}{}{}",
+                            video.codec,
+                            video.width,
+                            video.height,
+                            video
+                                .frame_rate
+                                .map_or(String::new(), |fps| format!(" @ {fps:.3} fps")),
+                            if video.pulldown { " (pulldown)" } else { "" },
+                            if video.is_hlg() { " HLG" } else { "" }
+                        ))
+                        .size(12),
+                    );
+                    details = details.push(
+                        text(format!(
+                            "Dolby Vision: {}",
+                            video
+                                .dolby_vision
+                                .as_ref()
+                                .map_or("none".to_string(), ToString::to_string)
+                        ))
+                        .size(12),
+                    );
+                    if let Some(dv) = &video.dolby_vision {
+                        let suggested = DvProfile::suggest(dv, video.is_hlg());
+                        let note = match options.dv_profile {
+                            DvProfile::Auto => String::new(),
+                            selected if selected == suggested => String::new(),
+                            selected => format!(" (profile {selected} is selected)"),
+                        };
+                        details = details.push(
+                            text(format!("Suggested output profile: {suggested}{note}")).size(12),
+                        );
+                    }
+                    if video.hdr10_plus {
+                        details = details.push(text("HDR10+: dynamic metadata present").size(12));
+                    }
+                    if video.hdr.is_empty() {
+                        details = details.push(text("HDR10 metadata: none").size(12));
+                    } else {
+                        if let Some(display) = &video.hdr.mastering_display {
+                            details = details
+                                .push(text(format!("Mastering display: {display}")).size(12));
+                        }
+                        details = details.push(
+                            text(format!(
+                                "MaxCLL: {} cd/m², MaxFALL: {} cd/m²",
+                                video
+                                    .hdr
+                                    .max_cll
+                                    .map_or("-".to_string(), |value| value.to_string()),
+                                video
+                                    .hdr
+                                    .max_fall
+                                    .map_or("-".to_string(), |value| value.to_string()),
+                            ))
+                            .size(12),
+                        );
+                    }
+                }
+                None => details = details.push(text("Video: none").size(12)),
+            }
+            if info.audio.is_empty() {
+                details =
+                    details.push(text("Audio: none, a video-only MP4 will be written").size(12));
+            }
+            if options.video_only && !info.audio.is_empty() {
+                details = details.push(
+                    text("Video only: no audio tracks will be included")
+                        .size(12)
+                        .style(|_theme: &Theme| text::Style {
+                            color: Some(iced::Color::from_rgb(0.9, 0.6, 0.2)),
+                        }),
+                );
+            } else if info.audio.len() > 1 {
+                // 多条音轨时可以勾选要封装的轨道
+                let selected = info.audio_tracks(&item.audio_tracks, options.auto_audio());
+                let mut header = row![text("Output audio:").size(12)]
+                    .spacing(6)
+                    .align_y(Alignment::Center);
+                if !item.audio_tracks.is_empty() {
+                    header = header.push(
+                        button(text("Automatic").size(11)).on_press(Message::AudioTracksReset),
+                    );
+                }
+                details = details.push(with_tooltip(
+                    header,
+                    "Audio tracks muxed into the MP4 in source order. Automatic uses every track with \"Include All Audio Tracks\" (except commentary with \"Skip Commentary Tracks\"), otherwise the E-AC-3 Atmos track or the first",
+                ));
+                for audio in &info.audio {
+                    let atmos = if audio.is_atmos() { " [Atmos]" } else { "" };
+                    let checked = selected.contains(&audio);
+                    let index = audio.index;
+                    details = details.push(
+                        checkbox(format!("Audio: {audio}{atmos}"), checked)
+                            .on_toggle_maybe((!checked || selected.len() > 1).then_some(
+                                move |enabled| Message::AudioTrackToggled((index, enabled)),
+                            ))
+                            .text_size(12),
+                    );
+                }
+            } else {
+                for audio in &info.audio {
+                    let atmos = if audio.is_atmos() { " [Atmos]" } else { "" };
+                    details = details.push(text(format!("Audio: {audio}{atmos}")).size(12));
+                }
+            }
+            if !options.truehd_to_eac3
+                && !options.truehd_ac3_core
+                && info
+                    .audio_tracks(&item.audio_tracks, options.auto_audio())
+                    .iter()
+                    .any(|audio| audio.codec == "truehd")
+            {
+                details = details.push(
+                    text("⚠ TrueHD can't be played from MP4 by most players, use the AC-3 core or transcode to E-AC-3 (lossy)")
+                        .size(12)
+                        .style(|_theme: &Theme| text::Style {
+                            color: Some(iced::Color::from_rgb(0.9, 0.6, 0.2)),
+                        }),
+                );
+            }
+            if info
+                .audio_tracks(&item.audio_tracks, options.auto_audio())
+                .iter()
+                .any(|audio| audio.is_pcm_or_flac())
+            {
+                details = details.push(
+                    text(format!(
+                        "⚠ PCM/FLAC can't be muxed by mp4muxer and will be transcoded to {} (lossy)",
+                        options.lossless_audio
+                    ))
+                    .size(12)
+                    .style(|_theme: &Theme| text::Style {
+                        color: Some(iced::Color::from_rgb(0.9, 0.6, 0.2)),
+                    }),
+                );
+            }
+            if info
+                .audio_tracks(&item.audio_tracks, options.auto_audio())
+                .iter()
+                .any(|audio| audio.codec == "dts")
+            {
+                details = details.push(
+                    text(format!(
+                        "⚠ DTS can't be muxed into MP4 and will be transcoded to E-AC-3 {} kb/s (lossy)",
+                        options.eac3_bitrate
+                    ))
+                    .size(12)
+                    .style(|_theme: &Theme| text::Style {
+                        color: Some(iced::Color::from_rgb(0.9, 0.6, 0.2)),
+                    }),
+                );
+            }
+            if info
+                .subtitles
+                .iter()
+                .any(|subtitle| options.converts_subtitle(info, subtitle))
+            {
+                // 文本字幕（以及开启 OCR 时的 PGS 字幕）可以勾选要转换为 mov_text 的轨道
+                let included = options.subtitle_tracks(
+                    info,
+                    &item.audio_tracks,
+                    item.subtitle_tracks.as_deref(),
+                );
+                let mut header = row![text("Output subtitles:").size(12)]
+                    .spacing(6)
+                    .align_y(Alignment::Center);
+                if item.subtitle_tracks.is_some() {
+                    header = header.push(
+                        button(text("Automatic").size(11)).on_press(Message::SubtitleTracksReset),
+                    );
+                }
+                details = details.push(with_tooltip(
+                    header,
+                    "Text subtitle tracks converted to mov_text and muxed in source order. Automatic follows the Subtitles setting",
+                ));
+                for subtitle in &info.subtitles {
+                    if options.converts_subtitle(info, subtitle) {
+                        let index = subtitle.index;
+                        let ocr = if subtitle.is_text() { "" } else { " (OCR)" };
+                        details = details.push(
+                            checkbox(
+                                format!("Subtitle: {subtitle}{ocr}"),
+                                included.contains(&subtitle),
+                            )
+                            .on_toggle(move |enabled| {
+                                Message::SubtitleTrackToggled((index, enabled))
+                            })
+                            .text_size(12),
+                        );
+                    } else {
+                        details = details.push(
+                            text(format!(
+                                "Subtitle: {subtitle} (image-based, cannot be converted)"
+                            ))
+                            .size(12),
+                        );
+                    }
+                }
+            } else {
+                for subtitle in &info.subtitles {
+                    details = details.push(
+                        text(format!(
+                            "Subtitle: {subtitle} (image-based, cannot be converted)"
+                        ))
+                        .size(12),
+                    );
+                }
+            }
+            if !info.chapters.is_empty() {
+                details = details.push(text(format!("Chapters: {}", info.chapters.len())).size(12));
+            }
+            details = details.push(view_deep_probe(item, info));
+        }
+    }
 
-        // 新增：终端显示区域
-        let terminal_section = column![
+    for (index, path) in item.external_subtitles.iter().enumerate() {
+        let subtitle = pipeline::external_subtitle(path);
+        details = details.push(
             row![
-                text("Terminal:").size(16),
-                Space::with_width(Length::Fill),
-                button("Clear Terminal").on_press(Message::ClearTerminal)
+                text(format!(
+                    "External subtitle: {} [{}]",
+                    path.file_name().unwrap_or_default().to_string_lossy(),
+                    subtitle.language.as_deref().unwrap_or("und")
+                ))
+                .size(12),
+                button(text("Remove").size(11)).on_press(Message::RemoveExternalSubtitle(index)),
             ]
+            .spacing(6)
             .align_y(Alignment::Center),
-            container(
-                scrollable(
-                    column(
-                        self.terminal_logs
-                            .iter()
-                            .map(|cmd| text(cmd).size(11).font(iced::Font::MONOSPACE).into())
-                            .collect::<Vec<_>>()
-                    )
-                    .spacing(2)
+        );
+    }
+    details = details.push(with_tooltip(
+        button(text("Add Subtitle File...").size(11)).on_press(Message::AddExternalSubtitles),
+        "Add a separate .srt or .ass file (or drop it on the window). It is converted to mov_text and muxed after the source's subtitles; a language code before the extension (Movie.eng.srt) is used as its language",
+    ));
+
+    if !item.parts.is_empty() {
+        let names = item
+            .parts
+            .iter()
+            .map(|path| path.file_name().unwrap_or_default().to_string_lossy())
+            .collect::<Vec<_>>()
+            .join(" + ");
+        details = details.push(
+            row![
+                text(format!("Joined parts: + {names}")).size(12),
+                button(text("Split Parts").size(11)).on_press(Message::SplitParts),
+            ]
+            .spacing(6)
+            .align_y(Alignment::Center),
+        );
+    } else if joinable > 0 {
+        details = details.push(with_tooltip(
+            row![
+                text(format!(
+                    "Multi-part source: {joinable} more part(s) in the queue"
+                ))
+                .size(12),
+                button(text("Join Parts").size(11)).on_press(Message::JoinParts),
+            ]
+            .spacing(6)
+            .align_y(Alignment::Center),
+            "Append the following parts (CD2, Part 2…) with mkvmerge and convert them into one output named without the part marker. Every part must have the same video format, frame rate and audio tracks",
+        ));
+    }
+
+    let trim_row = row![
+        text("Trim:").size(12),
+        text_input("0:00:00 or ch1", &item.trim_start)
+            .on_input(Message::TrimStartChanged)
+            .size(12)
+            .width(Length::Fixed(120.0)),
+        text("to").size(12),
+        text_input("end or ch3", &item.trim_end)
+            .on_input(Message::TrimEndChanged)
+            .size(12)
+            .width(Length::Fixed(120.0)),
+    ]
+    .spacing(6)
+    .align_y(Alignment::Center);
+    details = details.push(with_tooltip(
+        trim_row,
+        "Seconds, MM:SS, HH:MM:SS or a chapter (ch1). The start snaps to the previous keyframe and the Dolby Vision RPU is re-aligned.",
+    ));
+    match item.trim() {
+        Ok(Some(trim)) => details = details.push(text(format!("Trim range: {trim}")).size(12)),
+        Ok(None) => {}
+        Err(e) => {
+            details = details.push(text(e).size(12).style(|_theme: &Theme| text::Style {
+                color: Some(iced::Color::from_rgb(0.9, 0.3, 0.3)),
+            }))
+        }
+    }
+
+    let offset_row = row![
+        text("Audio offset:").size(12),
+        text_input("from source", &item.audio_offset)
+            .on_input(Message::AudioOffsetChanged)
+            .size(12)
+            .width(Length::Fixed(120.0)),
+        text("ms").size(12),
+    ]
+    .spacing(6)
+    .align_y(Alignment::Center);
+    details = details.push(with_tooltip(
+        offset_row,
+        "Leave empty to keep each track's delay from the source. A value in milliseconds replaces it for all output tracks; positive delays the audio, negative starts it earlier.",
+    ));
+    if let Err(e) = item.audio_offset() {
+        details = details.push(text(e).size(12).style(|_theme: &Theme| text::Style {
+            color: Some(iced::Color::from_rgb(0.9, 0.3, 0.3)),
+        }));
+    }
+
+    let subtitle_offset_row = row![
+        text("Subtitle offset:").size(12),
+        text_input("0", &item.subtitle_offset)
+            .on_input(Message::SubtitleOffsetChanged)
+            .size(12)
+            .width(Length::Fixed(120.0)),
+        text("ms").size(12),
+    ]
+    .spacing(6)
+    .align_y(Alignment::Center);
+    details = details.push(with_tooltip(
+        subtitle_offset_row,
+        "Shifts every subtitle track of this file, including added external subtitles, by the given milliseconds; positive shows them later, negative earlier. Leave empty to keep the source timing.",
+    ));
+    if let Err(e) = item.subtitle_offset() {
+        details = details.push(text(e).size(12).style(|_theme: &Theme| text::Style {
+            color: Some(iced::Color::from_rgb(0.9, 0.3, 0.3)),
+        }));
+    }
+
+    let mut active_area_row = row![text("L5 Active Area:").size(12)]
+        .spacing(6)
+        .align_y(Alignment::Center);
+    for edge in active_area::Edge::ALL {
+        active_area_row = active_area_row.push(
+            text_input(&edge.to_string(), item.active_area.value(edge))
+                .on_input(move |value| Message::ActiveAreaChanged((edge, value)))
+                .size(12)
+                .width(Length::Fixed(70.0)),
+        );
+    }
+    details = details.push(with_tooltip(
+        active_area_row,
+        "Letterbox offsets in pixels, written into the Dolby Vision L5 metadata of every frame. Leave all empty to keep the source's L5, or enter 0 to clear it.",
+    ));
+    match item.active_area() {
+        Ok(Some(offsets)) => {
+            details = details.push(text(format!("L5 active area: {offsets}")).size(12))
+        }
+        Ok(None) => {}
+        Err(e) => {
+            details = details.push(text(e).size(12).style(|_theme: &Theme| text::Style {
+                color: Some(iced::Color::from_rgb(0.9, 0.3, 0.3)),
+            }))
+        }
+    }
+
+    container(details)
+        .padding(10)
+        .width(Length::Fill)
+        .style(|_theme: &Theme| container::Style {
+            background: Some(iced::Background::Color(iced::Color::from_rgb(
+                0.05, 0.05, 0.05,
+            ))),
+            border: iced::Border {
+                color: iced::Color::from_rgb(0.3, 0.3, 0.3),
+                width: 1.0,
+                radius: 4.0.into(),
+            },
+            ..Default::default()
+        })
+        .into()
+}
+
+// 深度分析的结果，尚未进行时显示按钮
+fn view_deep_probe<'a>(item: &QueueItem, info: &'a probe::MediaInfo) -> Element<'a, Message> {
+    let Some(deep) = &info.deep else {
+        return with_tooltip(
+            row![
+                text("Deep probe: not run yet").size(12),
+                button(
+                    text(if item.deep_probing {
+                        "Probing..."
+                    } else {
+                        "Deep Probe"
+                    })
+                    .size(11)
                 )
-                .height(Length::Fixed(350.0))
-                .width(Length::Fill)
-            )
+                .on_press_maybe((!item.deep_probing).then_some(Message::DeepProbe)),
+            ]
+            .spacing(6)
+            .align_y(Alignment::Center),
+            "Sample the video for a variable frame rate, black bars and a Dolby Vision RPU summary. This reads and decodes parts of the file and can take a while on network sources",
+        );
+    };
+    let mut lines = column![].spacing(4);
+    match deep.frame_rate_range {
+        Some((min, max)) if deep.variable_frame_rate().is_some() => {
+            lines = lines.push(
+                text(format!(
+                    "⚠ Variable frame rate: {min:.3}-{max:.3} fps, the output is retimed to a constant rate"
+                ))
+                .size(12)
+                .style(|_theme: &Theme| text::Style {
+                    color: Some(iced::Color::from_rgb(0.9, 0.6, 0.2)),
+                }),
+            );
+        }
+        Some((min, _)) => {
+            lines = lines.push(text(format!("Frame rate: constant ({min:.3} fps)")).size(12));
+        }
+        None => {}
+    }
+    lines = lines.push(
+        text(match deep.crop {
+            Some(crop) => format!("Black bars: picture is {crop}"),
+            None => "Black bars: none".to_string(),
+        })
+        .size(12),
+    );
+    if !deep.rpu_summary.is_empty() {
+        lines = lines.push(text("RPU summary (first minute):").size(12));
+        for line in &deep.rpu_summary {
+            lines = lines.push(text(line).size(11).font(iced::Font::MONOSPACE));
+        }
+    }
+    for error in &deep.errors {
+        lines = lines.push(text(format!("Deep probe: {error}")).size(12).style(
+            |_theme: &Theme| text::Style {
+                color: Some(iced::Color::from_rgb(0.9, 0.6, 0.2)),
+            },
+        ));
+    }
+    lines.into()
+}
+
+// 逐场景的 L1 亮度柱状图：浅色为最大亮度，深色为平均亮度，高度按 PQ（感知亮度）比例
+fn view_l1_chart(analysis: &rpu_analysis::Analysis) -> Element<'_, Message> {
+    const HEIGHT: f32 = 140.0;
+    let bar_height = |pq: f64| (pq / rpu_analysis::PQ_MAX) as f32 * HEIGHT;
+    let bars = analysis.bars().into_iter().map(|bar| {
+        let average = container(Space::new(
+            Length::Fill,
+            Length::Fixed(bar_height(bar.avg_pq)),
+        ))
+        .style(|theme: &Theme| container::Style {
+            background: Some(iced::Background::Color(theme.palette().primary)),
+            ..Default::default()
+        });
+        let maximum = container(average)
+            .width(Length::Fill)
+            .height(Length::Fixed(bar_height(bar.max_pq).max(1.0)))
+            .align_bottom(Length::Fixed(bar_height(bar.max_pq).max(1.0)))
             .style(|_theme: &Theme| container::Style {
                 background: Some(iced::Background::Color(iced::Color::from_rgb(
-                    0.1, 0.1, 0.1
+                    0.45, 0.45, 0.55,
                 ))),
-                border: iced::Border {
-                    color: iced::Color::from_rgb(0.3, 0.3, 0.3),
-                    width: 1.0,
-                    radius: 4.0.into(),
-                },
                 ..Default::default()
-            })
-            .padding(10)
-            .width(Length::Fill)
-        ]
-        .spacing(5);
-
-        container(
-            column![
-                title,
-                input_section,
-                output_section,
-                options_section,
-                process_section,
-                log_section,
-                terminal_section
-            ]
-            .spacing(20)
-            .max_width(1200),
+            });
+        tooltip(
+            maximum,
+            container(
+                text(format!(
+                    "Frames {}–{}\nMax {:.0} nits · Avg {:.0} nits",
+                    bar.first_frame,
+                    bar.first_frame + bar.frames - 1,
+                    rpu_analysis::pq_to_nits(bar.max_pq),
+                    rpu_analysis::pq_to_nits(bar.avg_pq)
+                ))
+                .size(12),
+            )
+            .padding(6)
+            .style(container::rounded_box),
+            tooltip::Position::Top,
         )
-        .padding(20)
-        .center_x(Length::Fill)
-        .width(Length::Fill)
-        .height(Length::Fill)
         .into()
-    }
+    });
+
+    let axis = column![
+        text(format!(
+            "{:.0} nits",
+            rpu_analysis::pq_to_nits(rpu_analysis::PQ_MAX)
+        ))
+        .size(10),
+        Space::with_height(Length::Fill),
+        text(format!(
+            "{:.0}",
+            rpu_analysis::pq_to_nits(rpu_analysis::PQ_MAX / 2.0)
+        ))
+        .size(10),
+        Space::with_height(Length::Fill),
+        text("0").size(10)
+    ]
+    .height(Length::Fixed(HEIGHT))
+    .width(Length::Fixed(70.0));
+
+    row![
+        axis,
+        row(bars)
+            .spacing(1)
+            .align_y(Alignment::End)
+            .height(Length::Fixed(HEIGHT))
+            .width(Length::Fill)
+    ]
+    .spacing(5)
+    .into()
+}
+
+// 在后台线程中提取和分析 RPU
+async fn analyze_file(path: PathBuf) -> Box<(PathBuf, Result<rpu_analysis::Analysis, String>)> {
+    let analysis_path = path.clone();
+    let result = tokio::task::spawn_blocking(move || rpu_analysis::analyze(&analysis_path))
+        .await
+        .unwrap_or_else(|e| Err(format!("Analysis task failed: {e}")));
+    Box::new((path, result))
+}
+
+// 在后台线程中查询媒体服务器，大型媒体库可能需要较长时间
+async fn import_from_server(
+    config: media_server::ServerConfig,
+) -> Result<media_server::Import, String> {
+    tokio::task::spawn_blocking(move || media_server::import(&config))
+        .await
+        .unwrap_or_else(|e| Err(format!("Import task failed: {e}")))
+}
+
+// 在后台线程中核对输出文件夹，大型媒体库需要逐个读取 MP4 的时长
+async fn reconcile_folder(
+    folder: PathBuf,
+    queued: Vec<(PathBuf, Option<f64>)>,
+) -> Result<history::Reconciliation, String> {
+    tokio::task::spawn_blocking(move || history::reconcile(&folder, &queued))
+        .await
+        .unwrap_or_else(|e| Err(format!("Scan task failed: {e}")))
+}
+
+// 在后台线程中快速分析文件，避免阻塞界面
+async fn probe_file(path: PathBuf) -> Box<(PathBuf, Result<probe::MediaInfo, String>)> {
+    let probe_path = path.clone();
+    let result = tokio::task::spawn_blocking(move || probe::quick_probe(&probe_path))
+        .await
+        .unwrap_or_else(|e| Err(format!("Probe task failed: {e}")));
+    Box::new((path, result))
+}
+
+// 在后台线程中深度分析文件
+async fn deep_probe_file(
+    path: PathBuf,
+    info: probe::MediaInfo,
+) -> Box<(PathBuf, probe::DeepProbe)> {
+    let probe_path = path.clone();
+    let deep = tokio::task::spawn_blocking(move || probe::deep_probe(&probe_path, &info))
+        .await
+        .unwrap_or_else(|e| probe::DeepProbe {
+            errors: vec![format!("Deep probe task failed: {e}")],
+            ..probe::DeepProbe::default()
+        });
+    Box::new((path, deep))
+}
+
+async fn select_subtitle_files() -> Vec<PathBuf> {
+    FileDialog::new()
+        .add_filter("Subtitles", &pipeline::SUBTITLE_EXTENSIONS)
+        .set_title("Select Subtitle Files")
+        .pick_files()
+        .unwrap_or_default()
 }
 
 async fn select_input_files() -> Vec<PathBuf> {
@@ -493,52 +5542,148 @@ async fn select_input_files() -> Vec<PathBuf> {
         .unwrap_or_default()
 }
 
+// 快速模式下开始转换前的确认
+async fn confirm_reconvert(path: PathBuf) -> (PathBuf, bool) {
+    let result = rfd::AsyncMessageDialog::new()
+        .set_title("Source file was replaced")
+        .set_description(format!(
+            "{} changed in the watch folder (different size or checksum).\n\nConvert it again with a versioned output name?",
+            path.file_name().unwrap_or_default().to_string_lossy()
+        ))
+        .set_buttons(rfd::MessageButtons::YesNo)
+        .show()
+        .await;
+    (path, result == rfd::MessageDialogResult::Yes)
+}
+
+async fn confirm_quick_convert(path: PathBuf) -> (PathBuf, bool) {
+    let folder = path
+        .parent()
+        .map(|folder| folder.to_string_lossy().to_string())
+        .unwrap_or_default();
+    let result = rfd::AsyncMessageDialog::new()
+        .set_title("Convert this file now?")
+        .set_description(format!(
+            "{}\n\nOutput folder: {folder}",
+            path.file_name().unwrap_or_default().to_string_lossy()
+        ))
+        .set_buttons(rfd::MessageButtons::OkCancel)
+        .show()
+        .await;
+    (path, result == rfd::MessageDialogResult::Ok)
+}
+
+async fn select_report_path() -> Option<PathBuf> {
+    FileDialog::new()
+        .add_filter("JSON Report", &["json"])
+        .add_filter("Markdown Report", &["md"])
+        .set_title("Export Queue Report")
+        .set_file_name("rebottle-report.json")
+        .save_file()
+}
+
+async fn select_comparison_path() -> Option<PathBuf> {
+    FileDialog::new()
+        .add_filter("Markdown Report", &["md"])
+        .set_title("Save Preset Comparison")
+        .set_file_name("rebottle-comparison.md")
+        .save_file()
+}
+
+async fn select_library_folder() -> Option<PathBuf> {
+    FileDialog::new()
+        .set_title("Select Output Library Folder")
+        .pick_folder()
+}
+
 async fn select_output_folder() -> Option<PathBuf> {
     FileDialog::new()
         .set_title("Select Output Folder")
         .pick_folder()
 }
 
-// 跨平台命令执行函数
-fn execute_command(command: &str, args: &[&str]) -> Result<std::process::Output, String> {
-    #[cfg(windows)]
-    {
-        let full_command = format!("{} {}", command, args.join(" "));
-        Command::new("cmd")
-            .args(["/C", &full_command])
-            .output()
-            .map_err(|e| format!("Failed to execute command: {e}"))
-    }
+async fn select_temp_folder() -> Option<PathBuf> {
+    FileDialog::new()
+        .set_title("Select Temp Folder")
+        .pick_folder()
+}
 
-    #[cfg(not(windows))]
-    {
-        Command::new(command)
-            .args(args)
-            .output()
-            .map_err(|e| format!("Failed to execute command {command}: {e}"))
-    }
+async fn select_rpu_edit_config() -> Option<PathBuf> {
+    FileDialog::new()
+        .add_filter("dovi_tool Editor Config", &["json"])
+        .set_title("Select RPU Editor Config")
+        .pick_file()
+}
+
+async fn select_analysis_file() -> Option<PathBuf> {
+    FileDialog::new()
+        .add_filter("Dolby Vision Video", &["mkv", "mp4", "m4v", "hevc"])
+        .set_title("Select File to Analyze")
+        .pick_file()
+}
+
+async fn select_watch_folder() -> Option<PathBuf> {
+    FileDialog::new()
+        .set_title("Select Watch Folder")
+        .pick_folder()
 }
 
-// 新增：带有终端日志记录的命令执行函数
+// 跨平台命令执行函数，参数原样传给程序
+fn execute_command(command: &str, args: &[&str]) -> Result<std::process::Output, String> {
+    command_line::command(command, args)
+        .output()
+        .map_err(|e| command_line::spawn_error(command, &e))
+}
+
+// 执行外部命令：命令行和结果写入日志，stdout 和 stderr 在运行过程中逐行显示在终端面板
 async fn execute_command_with_logging(
     command: &str,
     args: &[&str],
-) -> (Result<std::process::Output, String>, Vec<String>) {
-    let mut logs = Vec::new();
-
-    // 记录要执行的命令
-    let full_command = if args.is_empty() {
-        format!("$ {command}")
-    } else {
-        format!("$ {command} {}", args.join(" "))
-    };
+    verbosity: &verbosity::ToolVerbosity,
+    logs: &mut logs::Logs,
+) -> Result<std::process::Output, String> {
+    let level = verbosity.get(command);
+    let mut full_args = verbosity.flags(command).to_vec();
+    full_args.extend_from_slice(args);
+    let args = full_args.as_slice();
 
-    logs.push(full_command);
+    // 记录要执行的命令，参数已转义，可以直接复制到终端运行
+    logs.push(format!("$ {}", command_line::join(command, args)));
 
-    // 执行命令
-    let result = execute_command(command, args);
+    let result = match build_async_command(command, args)
+        .stdin(Stdio::null())
+        .stdout(Stdio::piped())
+        .stderr(Stdio::piped())
+        .spawn()
+    {
+        Ok(mut child) => {
+            let _running = processes::register(&child);
+            // stdout 和 stderr 同时读取，避免其中一个管道写满导致命令阻塞
+            let stderr_task = child.stderr.take().map(|stderr| {
+                tokio::spawn(stream_output(stderr, command.to_string(), logs.terminal()))
+            });
+            let stdout = match child.stdout.take() {
+                Some(stdout) => stream_output(stdout, command.to_string(), logs.terminal()).await,
+                None => Vec::new(),
+            };
+            let stderr = match stderr_task {
+                Some(task) => task.await.unwrap_or_default(),
+                None => Vec::new(),
+            };
+            child
+                .wait()
+                .await
+                .map(|status| std::process::Output {
+                    status,
+                    stdout,
+                    stderr,
+                })
+                .map_err(|e| format!("Failed to execute command {command}: {e}"))
+        }
+        Err(e) => Err(command_line::spawn_error(command, &e)),
+    };
 
-    // 记录执行结果
+    // 记录执行结果；输出已经实时显示，详细级别下只记录到日志中
     match &result {
         Ok(output) => {
             if !output.status.success() {
@@ -547,7 +5692,17 @@ async fn execute_command_with_logging(
                     logs.push(format!("Error: {}", stderr.trim()));
                 }
             } else {
-                logs.push("✓ Command completed successfully".to_string());
+                if level == verbosity::Verbosity::Verbose {
+                    for line in output_lines(&output.stdout)
+                        .into_iter()
+                        .chain(output_lines(&output.stderr))
+                    {
+                        logs.record(line);
+                    }
+                }
+                if level != verbosity::Verbosity::Quiet {
+                    logs.push("✓ Command completed successfully".to_string());
+                }
             }
         }
         Err(e) => {
@@ -555,274 +5710,557 @@ async fn execute_command_with_logging(
         }
     }
 
-    (result, logs)
+    result
 }
 
-// 新增：带有日志收集的视频处理函数
-async fn process_video_with_logs(
-    input_file: PathBuf,
-    output_folder: PathBuf,
-    frame_rate: FrameRate,
-    include_subtitles: bool,
-) -> (Result<(), String>, Vec<String>) {
-    let input_stem = input_file.file_stem().unwrap().to_string_lossy();
-    let temp_dir = std::env::temp_dir();
-    let mut all_logs = Vec::new();
-
-    // Step 1: Extract video stream
-    all_logs.push("Extracting video stream...".to_string());
-    let video_file = temp_dir.join(format!("{input_stem}_DV.hevc"));
-
-    let (output, mut logs) = execute_command_with_logging(
-        "mkvextract",
-        &[
-            "tracks",
-            &input_file.to_string_lossy(),
-            &format!("0:{}", video_file.to_string_lossy()),
-        ],
-    )
-    .await;
-    all_logs.append(&mut logs);
-
-    match output {
-        Ok(out) if !out.status.success() => {
-            return (
-                Err(format!(
-                    "Video extraction failed: {}",
-                    String::from_utf8_lossy(&out.stderr)
-                )),
-                all_logs,
-            );
-        }
-        Err(e) => return (Err(e), all_logs),
-        _ => {}
-    }
-
-    // Step 2: Extract audio
-    all_logs.push("Extracting audio stream...".to_string());
-    let audio_file = temp_dir.join(format!("{input_stem}_audio.ec3"));
-
-    let (output, mut logs) = execute_command_with_logging(
-        "ffmpeg",
-        &[
-            "-i",
-            &input_file.to_string_lossy(),
-            "-map",
-            "0:a:0",
-            "-c",
-            "copy",
-            &audio_file.to_string_lossy(),
-            "-y",
-        ],
-    )
-    .await;
-    all_logs.append(&mut logs);
-
-    match output {
-        Ok(out) if !out.status.success() => {
-            return (
-                Err(format!(
-                    "Audio extraction failed: {}",
-                    String::from_utf8_lossy(&out.stderr)
-                )),
-                all_logs,
-            );
-        }
-        Err(e) => return (Err(e), all_logs),
-        _ => {}
-    }
-
-    // Step 3: Extract subtitles (if needed)
-    let subtitle_file = if include_subtitles {
-        all_logs.push("Extracting subtitles...".to_string());
-        let subs = temp_dir.join(format!("{input_stem}_subs.srt"));
-
-        let (output, mut logs) = execute_command_with_logging(
-            "ffmpeg",
-            &[
-                "-i",
-                &input_file.to_string_lossy(),
-                "-map",
-                "0:s:0",
-                "-c",
-                "copy",
-                &subs.to_string_lossy(),
-                "-y",
-            ],
-        )
-        .await;
-        all_logs.append(&mut logs);
+// 执行 producer | consumer：producer 的 stdout 通过管道直接交给 consumer，不写入中间文件。
+// 命令行和结果写入日志，两个命令的 stderr 实时显示在终端面板；任一命令失败都视为失败
+async fn execute_pipe_with_logging(
+    (producer, producer_args): (&str, &[&str]),
+    (consumer, consumer_args): (&str, &[&str]),
+    verbosity: &verbosity::ToolVerbosity,
+    logs: &mut logs::Logs,
+) -> Result<std::process::Output, String> {
+    let level = verbosity.get(consumer);
+    let mut first_args = verbosity.flags(producer).to_vec();
+    first_args.extend_from_slice(producer_args);
+    let mut second_args = verbosity.flags(consumer).to_vec();
+    second_args.extend_from_slice(consumer_args);
+    logs.push(format!(
+        "$ {} | {}",
+        command_line::join(producer, &first_args),
+        command_line::join(consumer, &second_args)
+    ));
 
-        match output {
-            Ok(out) if out.status.success() => Some(subs),
-            _ => {
-                all_logs.push("Subtitle extraction failed, continuing...".to_string());
-                None
-            }
+    let failed = |logs: &mut logs::Logs, error: String| {
+        logs.push(format!("Error: {error}"));
+        error
+    };
+    let mut first = build_async_command(producer, &first_args)
+        .stdin(Stdio::null())
+        .stdout(Stdio::piped())
+        .stderr(Stdio::piped())
+        .spawn()
+        .map_err(|e| failed(logs, command_line::spawn_error(producer, &e)))?;
+    let _first_running = processes::register(&first);
+    let stream: Stdio = first
+        .stdout
+        .take()
+        .ok_or_else(|| "no output stream".to_string())
+        .and_then(|stdout| stdout.try_into().map_err(|e: std::io::Error| e.to_string()))
+        .map_err(|e| failed(logs, format!("Failed to execute command {producer}: {e}")))?;
+    let mut second = build_async_command(consumer, &second_args)
+        .stdin(stream)
+        .stdout(Stdio::piped())
+        .stderr(Stdio::piped())
+        .spawn()
+        .map_err(|e| failed(logs, command_line::spawn_error(consumer, &e)))?;
+    let _second_running = processes::register(&second);
+
+    let stderr_tasks = [
+        (producer, first.stderr.take()),
+        (consumer, second.stderr.take()),
+    ]
+    .map(|(command, stderr)| {
+        stderr
+            .map(|stderr| tokio::spawn(stream_output(stderr, command.to_string(), logs.terminal())))
+    });
+    let stdout = match second.stdout.take() {
+        Some(stdout) => stream_output(stdout, consumer.to_string(), logs.terminal()).await,
+        None => Vec::new(),
+    };
+    // consumer 提前退出时 producer 会因管道关闭而结束
+    let second_status = second.wait().await;
+    let first_status = first.wait().await;
+    let mut stderr = Vec::new();
+    for task in stderr_tasks.into_iter().flatten() {
+        stderr.extend(task.await.unwrap_or_default());
+    }
+    let (first_status, second_status) = match (first_status, second_status) {
+        (Ok(first), Ok(second)) => (first, second),
+        (Err(e), _) => {
+            return Err(failed(
+                logs,
+                format!("Failed to execute command {producer}: {e}"),
+            ));
         }
+        (_, Err(e)) => {
+            return Err(failed(
+                logs,
+                format!("Failed to execute command {consumer}: {e}"),
+            ));
+        }
+    };
+    // producer 出错时 consumer 读到的流不完整，即使 consumer 正常退出也按失败处理
+    let status = if second_status.success() {
+        first_status
     } else {
-        None
+        second_status
     };
 
-    // Step 4: Remux using mp4muxer
-    all_logs.push("Remuxing to MP4...".to_string());
-    let output_file = output_folder.join(format!("{input_stem}_dvh1.mp4"));
-
-    let (output, mut logs) = execute_command_with_logging(
-        "mp4muxer",
-        &[
-            "-o",
-            &output_file.to_string_lossy(),
-            "-i",
-            &video_file.to_string_lossy(),
-            "--input-video-frame-rate",
-            frame_rate.to_value(),
-            "-i",
-            &audio_file.to_string_lossy(),
-            "--dv-profile",
-            "5",
-            "--dvh1flag",
-            "0",
-        ],
-    )
-    .await;
-    all_logs.append(&mut logs);
-
-    match output {
-        Ok(out) if !out.status.success() => {
-            return (
-                Err(format!(
-                    "MP4 muxing failed: {}",
-                    String::from_utf8_lossy(&out.stderr)
-                )),
-                all_logs,
-            );
+    if !status.success() {
+        let message = String::from_utf8_lossy(&stderr);
+        if !message.trim().is_empty() {
+            logs.push(format!("Error: {}", message.trim()));
         }
-        Err(e) => return (Err(e), all_logs),
-        _ => {}
-    }
-
-    // Step 5: Process subtitles (if available)
-    if let Some(ref subtitle_file) = subtitle_file {
-        all_logs.push("Processing subtitles...".to_string());
-        let subs_mp4 = temp_dir.join(format!("{input_stem}_subs.mp4"));
-        let final_output = output_folder.join(format!("{input_stem}_dvh1_with_subs.mp4"));
-
-        // Convert subtitle format
-        let (output, mut logs) = execute_command_with_logging(
-            "ffmpeg",
-            &[
-                "-i",
-                &subtitle_file.to_string_lossy(),
-                "-c:s",
-                "mov_text",
-                &subs_mp4.to_string_lossy(),
-                "-y",
-            ],
-        )
-        .await;
-        all_logs.append(&mut logs);
-
-        if let Ok(out) = output {
-            if out.status.success() {
-                // Merge subtitles
-                let (output, mut logs) = execute_command_with_logging(
-                    "MP4Box",
-                    &[
-                        "-add",
-                        &output_file.to_string_lossy(),
-                        "-add",
-                        &subs_mp4.to_string_lossy(),
-                        "-new",
-                        &final_output.to_string_lossy(),
-                    ],
-                )
-                .await;
-                all_logs.append(&mut logs);
-
-                if let Ok(out) = output {
-                    if !out.status.success() {
-                        return (
-                            Err(format!(
-                                "Subtitle merging failed: {}",
-                                String::from_utf8_lossy(&out.stderr)
-                            )),
-                            all_logs,
-                        );
+    } else {
+        if level == verbosity::Verbosity::Verbose {
+            for line in output_lines(&stdout)
+                .into_iter()
+                .chain(output_lines(&stderr))
+            {
+                logs.record(line);
+            }
+        }
+        if level != verbosity::Verbosity::Quiet {
+            logs.push("✓ Command completed successfully".to_string());
+        }
+    }
+
+    Ok(std::process::Output {
+        status,
+        stdout,
+        stderr,
+    })
+}
+
+// 逐行读取工具的输出并实时显示在终端面板（进度条用 \r 刷新的行同样拆开），返回读取到的全部内容。
+// mkvextract / mkvmerge 的进度行用于推进进度条，不逐条显示
+async fn stream_output(
+    output: impl tokio::io::AsyncRead + Unpin,
+    tool: String,
+    terminal: logs::Terminal,
+) -> Vec<u8> {
+    let mut reader = BufReader::new(output);
+    let mut buffer = Vec::new();
+    let mut line = Vec::new();
+    loop {
+        line.clear();
+        match reader.read_until(b'\n', &mut line).await {
+            Ok(0) | Err(_) => break,
+            Ok(_) => {
+                buffer.extend_from_slice(&line);
+                for segment in String::from_utf8_lossy(&line).split(['\r', '\n']) {
+                    if let Some(percent) = parse_progress_percent(segment) {
+                        terminal.progress(percent / 100.0);
+                    } else if !segment.trim().is_empty() {
+                        terminal.show(format!("[{tool}] {}", segment.trim_end()));
                     }
                 }
             }
         }
     }
+    buffer
+}
+
+// 命令输出中的非空行
+fn output_lines(output: &[u8]) -> Vec<String> {
+    String::from_utf8_lossy(output)
+        .lines()
+        .map(str::trim_end)
+        .filter(|line| !line.trim().is_empty())
+        .map(ToString::to_string)
+        .collect()
+}
+
+// 解析 ffmpeg -progress 输出中的当前处理时间（秒）
+fn parse_progress_time(line: &str) -> Option<f64> {
+    let (key, value) = line.trim().split_once('=')?;
+    match key {
+        // ffmpeg 的 out_time_ms 实际单位同样是微秒
+        "out_time_us" | "out_time_ms" => {
+            value.parse::<f64>().ok().map(|micros| micros / 1_000_000.0)
+        }
+        _ => None,
+    }
+}
+
+// 解析 mkvtoolnix 输出的 "Progress: 42%"
+fn parse_progress_percent(line: &str) -> Option<f32> {
+    let percent = line.trim().strip_prefix("Progress:")?.strip_suffix('%')?;
+    percent
+        .trim()
+        .parse::<f32>()
+        .ok()
+        .map(|percent| percent.clamp(0.0, 100.0))
+}
+
+// 跨平台的异步命令构建，与 execute_command 保持一致。处理被取消（任务中止）时结束命令；
+// Unix 上在单独的进程组中启动，取消时可以连同工具启动的子进程一起结束
+fn build_async_command(command: &str, args: &[&str]) -> tokio::process::Command {
+    #[cfg(windows)]
+    {
+        let mut cmd = tokio::process::Command::from(command_line::command(command, args));
+        cmd.kill_on_drop(true);
+        // 低于正常（BELOW_NORMAL_PRIORITY_CLASS），工具启动的子进程同样继承
+        if processes::background_priority() {
+            cmd.creation_flags(0x0000_4000);
+        }
+        cmd
+    }
+
+    // nice 和 ionice 设置优先级后直接执行工具，进程号不变，仍可按进程组结束
+    #[cfg(not(windows))]
+    {
+        let mut cmd = if processes::background_priority() {
+            let prefix = processes::priority_prefix();
+            let mut wrapped = prefix[1..].to_vec();
+            wrapped.push(command);
+            wrapped.extend_from_slice(args);
+            tokio::process::Command::from(command_line::command(prefix[0], &wrapped))
+        } else {
+            tokio::process::Command::from(command_line::command(command, args))
+        };
+        cmd.process_group(0).kill_on_drop(true);
+        cmd
+    }
+}
+
+// 执行 ffmpeg 并解析 -progress 输出，实时上报当前步骤的进度；stderr 逐行显示在终端面板
+async fn execute_ffmpeg_with_progress(
+    args: &[&str],
+    duration: Option<f64>,
+    verbosity: &verbosity::ToolVerbosity,
+    logs: &mut logs::Logs,
+) -> Result<std::process::Output, String> {
+    let level = verbosity.get("ffmpeg");
+    let mut full_args = vec!["-progress", "pipe:1", "-nostats"];
+    full_args.extend_from_slice(verbosity.flags("ffmpeg"));
+    full_args.extend_from_slice(args);
+
+    logs.push(format!("$ {}", command_line::join("ffmpeg", &full_args)));
+    logs.progress(0.0);
+
+    let mut child = match build_async_command("ffmpeg", &full_args)
+        .stdin(Stdio::null())
+        .stdout(Stdio::piped())
+        .stderr(Stdio::piped())
+        .spawn()
+    {
+        Ok(child) => child,
+        Err(e) => {
+            let error = command_line::spawn_error("ffmpeg", &e);
+            logs.push(format!("Error: {error}"));
+            return Err(error);
+        }
+    };
+    let _running = processes::register(&child);
+
+    // stderr 需要单独读取，避免管道写满导致 ffmpeg 阻塞
+    let stderr_task = child
+        .stderr
+        .take()
+        .map(|stderr| tokio::spawn(stream_output(stderr, "ffmpeg".to_string(), logs.terminal())));
+
+    if let Some(stdout) = child.stdout.take() {
+        let mut lines = BufReader::new(stdout).lines();
+        while let Ok(Some(line)) = lines.next_line().await {
+            if let (Some(current), Some(total)) = (parse_progress_time(&line), duration) {
+                logs.progress((current / total).clamp(0.0, 1.0) as f32);
+            }
+        }
+    }
+
+    let status = match child.wait().await {
+        Ok(status) => status,
+        Err(e) => {
+            let error = format!("Failed to execute command ffmpeg: {e}");
+            logs.push(format!("Error: {error}"));
+            return Err(error);
+        }
+    };
+
+    let stderr = match stderr_task {
+        Some(task) => task.await.unwrap_or_default(),
+        None => Vec::new(),
+    };
 
-    // Clean up temporary files
-    all_logs.push("Cleaning up temporary files...".to_string());
-    let _ = std::fs::remove_file(video_file);
-    let _ = std::fs::remove_file(audio_file);
-    if let Some(subtitle_file) = subtitle_file {
-        let _ = std::fs::remove_file(subtitle_file);
+    if status.success() {
+        logs.progress(1.0);
+        if level == verbosity::Verbosity::Verbose {
+            for line in output_lines(&stderr) {
+                logs.record(line);
+            }
+        }
+        if level != verbosity::Verbosity::Quiet {
+            logs.push("✓ Command completed successfully".to_string());
+        }
+    } else {
+        let message = String::from_utf8_lossy(&stderr);
+        if !message.trim().is_empty() {
+            logs.push(format!("Error: {}", message.trim()));
+        }
     }
 
-    all_logs.push("Processing completed!".to_string());
-    (Ok(()), all_logs)
+    Ok(std::process::Output {
+        status,
+        stdout: Vec::new(),
+        stderr,
+    })
+}
+
+// 当前 UTC 时间，格式为 "2025-01-31 08:00 UTC"
+fn format_utc_now() -> String {
+    let seconds = std::time::SystemTime::now()
+        .duration_since(std::time::UNIX_EPOCH)
+        .map(|duration| duration.as_secs() as i64)
+        .unwrap_or(0);
+    let (days, rest) = (seconds.div_euclid(86400), seconds.rem_euclid(86400));
+
+    // 由天数换算公历日期（Howard Hinnant 的 civil_from_days 算法）
+    let z = days + 719468;
+    let era = z.div_euclid(146097);
+    let doe = z.rem_euclid(146097);
+    let yoe = (doe - doe / 1460 + doe / 36524 - doe / 146096) / 365;
+    let doy = doe - (365 * yoe + yoe / 4 - yoe / 100);
+    let mp = (5 * doy + 2) / 153;
+    let day = doy - (153 * mp + 2) / 5 + 1;
+    let month = if mp < 10 { mp + 3 } else { mp - 9 };
+    let year = yoe + era * 400 + i64::from(month <= 2);
+
+    format!(
+        "{year:04}-{month:02}-{day:02} {:02}:{:02} UTC",
+        rest / 3600,
+        rest % 3600 / 60
+    )
+}
+
+// 最后一条命令的最后 10 行输出，用于在失败的队列行中显示
+fn stderr_tail(logs: &[String]) -> Vec<String> {
+    let start = logs
+        .iter()
+        .rposition(|line| line.starts_with("$ "))
+        .map_or(0, |index| index + 1);
+    let lines = logs[start..]
+        .iter()
+        .flat_map(|line| line.lines())
+        .map(|line| line.strip_prefix("Error: ").unwrap_or(line).trim_end())
+        .filter(|line| !line.trim().is_empty())
+        .map(ToString::to_string)
+        .collect::<Vec<_>>();
+    lines[lines.len().saturating_sub(10)..].to_vec()
+}
+
+// 失败时最后运行的工具名，没有运行外部工具时为 rebottle 自身
+fn failed_tool(logs: &[String]) -> String {
+    logs.iter()
+        .rev()
+        .find_map(|line| line.strip_prefix("$ "))
+        .and_then(|command| command.split_whitespace().next())
+        .map(|tool| {
+            Path::new(tool)
+                .file_stem()
+                .unwrap_or_default()
+                .to_string_lossy()
+                .to_string()
+        })
+        .unwrap_or_else(|| "rebottle".to_string())
+}
+
+// 错开开始时间的可选秒数，0 表示不等待
+const STAGGER_CHOICES: [u32; 6] = [0, 5, 10, 30, 60, 120];
+
+// 可选的同时处理文件数
+const CONCURRENT_JOB_CHOICES: [u32; 4] = [1, 2, 3, 4];
+
+// 可选的失败重试次数，0 表示不重试
+const RETRY_CHOICES: [u32; 4] = [0, 1, 2, 3];
+
+// DTS 和 TrueHD 转码为 E-AC-3 时可选的码率（kb/s）
+const EAC3_BITRATES: [u32; 3] = [768, 1024, 1536];
+
+// 预设对比中代表当前设置的名称
+const CURRENT_SETTINGS: &str = "Current Settings";
+
+// 响度标准化的目标响度（LUFS）
+const LOUDNORM_TARGETS: [i32; 4] = [-14, -16, -18, -23];
+
+// 错开开始的等待时间：设定值加上最多一半的随机抖动，避免多个任务同时开始读取
+fn stagger_delay(seconds: u32) -> std::time::Duration {
+    let base = u64::from(seconds) * 1000;
+    let nanos = std::time::SystemTime::now()
+        .duration_since(std::time::UNIX_EPOCH)
+        .map_or(0, |elapsed| u64::from(elapsed.subsec_nanos()));
+    std::time::Duration::from_millis(base + nanos % (base / 2 + 1))
 }
 
-// 新增：批量处理视频队列的函数
-async fn process_video_queue_with_logs(
-    files: Vec<PathBuf>,
+// 批量处理视频队列；每个文件带有输出版本号和裁剪范围，日志在处理过程中实时发送到终端面板。
+// 最多同时处理 max_concurrent_jobs 个文件；请求暂停时不再开始新的文件，返回尚未处理的文件
+async fn process_video_queue(
+    files: Vec<pipeline::Source>,
     output_folder: PathBuf,
-    frame_rate: FrameRate,
-    include_subtitles: bool,
-) -> (Result<(), String>, Vec<String>) {
-    let mut all_logs = Vec::new();
+    options: ProcessOptions,
+    pause: Arc<AtomicBool>,
+    sender: &mut mpsc::Sender<Message>,
+) -> Result<Vec<PathBuf>, String> {
+    let mut all_logs = logs::Logs::new(sender);
     let total_files = files.len();
 
     all_logs.push(format!(
         "Starting batch processing of {total_files} files..."
     ));
 
-    for (index, file) in files.iter().enumerate() {
-        all_logs.push(format!(
-            "Processing file {}/{}: {}",
-            index + 1,
-            total_files,
-            file.file_name().unwrap_or_default().to_string_lossy()
-        ));
-
-        let (result, mut logs) = process_video_with_logs(
-            file.clone(),
-            output_folder.clone(),
-            frame_rate.clone(),
-            include_subtitles,
-        )
-        .await;
+    let jobs = options.max_concurrent_jobs.max(1) as usize;
+    processes::set_background_priority(options.background_priority);
+    let mut batch_result = Ok(());
+    let mut batch_outputs = Vec::new();
+    let mut running = FuturesUnordered::new();
+    let (mut next, mut finished) = (0, 0);
+    // 最近一个文件的开始时间；错开开始的等待放在各文件自己的任务中，等待期间正在处理的文件照常推进
+    let mut start = tokio::time::Instant::now();
+    loop {
+        // 有空闲位置时开始下一个文件；有文件失败或请求暂停后不再开始新的文件，等待正在处理的文件完成
+        while running.len() < jobs && next < total_files && batch_result.is_ok() {
+            let index = next;
+            let source = &files[index];
+            let file = &source.path;
+            if index > 0 && pause.load(Ordering::Relaxed) {
+                break;
+            }
+            let now = tokio::time::Instant::now();
+            start = start.max(now);
+            if index > 0 && options.stagger_seconds > 0 {
+                start += stagger_delay(options.stagger_seconds);
+                all_logs.push(format!(
+                    "Waiting {:.1}s before starting the next file (stagger start)",
+                    (start - now).as_secs_f32()
+                ));
+            }
+            let name = file
+                .file_name()
+                .unwrap_or_default()
+                .to_string_lossy()
+                .to_string();
 
-        all_logs.append(&mut logs);
+            let file_payload = serde_json::json!({
+                "input": file.to_string_lossy(),
+                "output_folder": output_folder.to_string_lossy(),
+                "index": index + 1,
+                "total": total_files,
+                "version": source.version,
+                "trim": source.trim.map(|trim| trim.to_string()),
+                "active_area": source.active_area.map(|offsets| offsets.to_string()),
+            });
 
-        if let Err(e) = result {
-            all_logs.push(format!("File processing failed: {e}"));
-            return (
-                Err(format!(
-                    "Batch processing failed at file {}: {}",
+            // 同时处理多个文件时，终端中的每一行带上文件名
+            let tag = (jobs > 1).then(|| name.clone());
+            let (source, output_folder, options) =
+                (source.clone(), output_folder.clone(), options.clone());
+            let mut sender = sender.clone();
+            running.push(async move {
+                tokio::time::sleep_until(start).await;
+                let mut file_logs = logs::Logs::new(&sender);
+                file_logs.push(format!(
+                    "Processing file {}/{}: {name}",
                     index + 1,
-                    e
-                )),
-                all_logs,
-            );
+                    total_files
+                ));
+                file_logs.extend(hooks::run(hooks::HookEvent::PreFile, file_payload.clone()).await);
+                let _ = sender.try_send(Message::FileStarted(source.path.clone()));
+                journal::set_state(&source.path, journal::FileState::Running);
+
+                let (result, logs) = pipeline::process_file_with_retries(
+                    source,
+                    output_folder,
+                    options,
+                    &mut sender,
+                    tag,
+                )
+                .await;
+                (index, file_payload, file_logs.into_lines(), result, logs)
+            });
+            next += 1;
+        }
+
+        let Some((index, file_payload, file_lines, result, logs)) = running.next().await else {
+            break;
+        };
+        // 开始时的说明和 pre-file 钩子的输出已经显示过，只记录
+        for line in file_lines {
+            all_logs.record(line);
         }
+        let file = &files[index].path;
+        finished += 1;
+
+        let mut post_payload = file_payload;
+        post_payload["success"] = serde_json::json!(result.is_ok());
+        post_payload["error"] = serde_json::json!(result.as_ref().err());
+        post_payload["outputs"] = serde_json::json!(
+            result
+                .as_ref()
+                .map(|outputs| outputs
+                    .iter()
+                    .map(|output| output.to_string_lossy().to_string())
+                    .collect::<Vec<_>>())
+                .unwrap_or_default()
+        );
 
-        all_logs.push(format!("✅ File {}/{} completed", index + 1, total_files));
+        match result {
+            Ok(outputs) => {
+                let _ = sender.try_send(Message::FileSucceeded(file.clone()));
+                journal::set_state(file, journal::FileState::Done);
+                all_logs.extend(hooks::run(hooks::HookEvent::PostFile, post_payload).await);
+                all_logs.push(format!("✅ File {}/{} completed", index + 1, total_files));
+                batch_outputs.extend(outputs);
+            }
+            Err(e) => {
+                journal::set_state(file, journal::FileState::Failed);
+                let _ = sender.try_send(Message::FileFailed(Box::new((
+                    file.clone(),
+                    e.clone(),
+                    stderr_tail(&logs),
+                    failed_tool(&logs),
+                ))));
+                all_logs.push(format!("File processing failed: {e}"));
+                all_logs.extend(hooks::run(hooks::HookEvent::PostFile, post_payload).await);
+                if batch_result.is_ok() {
+                    batch_result = Err(format!(
+                        "Batch processing failed at file {}: {}",
+                        index + 1,
+                        e
+                    ));
+                }
+            }
+        }
+        // 同时处理多个文件时进度条显示整批的进度
+        if jobs > 1 {
+            let _ = sender.try_send(Message::ProcessingProgress(
+                finished as f32 / total_files as f32,
+            ));
+        }
     }
 
-    all_logs.push(format!(
-        "🎉 All {total_files} files processed successfully!"
-    ));
-    (Ok(()), all_logs)
+    if batch_result.is_ok() && next < total_files {
+        all_logs.push(format!(
+            "⏸ Batch paused before file {}/{total_files}",
+            next + 1
+        ));
+        return Ok(files[next..]
+            .iter()
+            .map(|source| source.path.clone())
+            .collect());
+    }
+
+    let batch_payload = serde_json::json!({
+        "output_folder": output_folder.to_string_lossy(),
+        "files": files.iter().map(|source| source.path.to_string_lossy()).collect::<Vec<_>>(),
+        "outputs": batch_outputs.iter().map(|output| output.to_string_lossy()).collect::<Vec<_>>(),
+        "success": batch_result.is_ok(),
+        "error": batch_result.as_ref().err(),
+    });
+    all_logs.extend(hooks::run(hooks::HookEvent::PostBatch, batch_payload).await);
+
+    if batch_result.is_ok() {
+        all_logs.push(format!(
+            "🎉 All {total_files} files processed successfully!"
+        ));
+    }
+    batch_result.map(|_| Vec::new())
 }
 
 impl std::fmt::Display for FrameRate {
     fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
-        write!(f, "{}", self.to_string())
+        write!(f, "{:.3} ({})", self.fps(), self.to_value())
     }
 }
 
@@ -850,13 +6288,127 @@ fn load_svg_icon() -> Option<iced::window::Icon> {
     iced::window::icon::from_rgba(rgba_data, size, size).ok()
 }
 
-fn main() -> iced::Result {
+fn main() -> std::process::ExitCode {
+    // --cli：不打开窗口，处理完成后以退出码报告结果
+    let args = std::env::args().skip(1).collect::<Vec<_>>();
+    if args.first().is_some_and(|arg| arg == "--cli") {
+        return cli::run(&args[1..]);
+    }
+    // --self-test：转换生成的样本，检查环境
+    if args.first().is_some_and(|arg| arg == "--self-test") {
+        return cli::self_test();
+    }
+    match run_gui() {
+        Ok(()) => std::process::ExitCode::SUCCESS,
+        Err(e) => {
+            eprintln!("{e}");
+            std::process::ExitCode::FAILURE
+        }
+    }
+}
+
+fn run_gui() -> iced::Result {
+    let session = session::Session::load();
+    let position = match (session.window_x, session.window_y) {
+        (Some(x), Some(y)) => iced::window::Position::Specific(Point::new(x, y)),
+        _ => iced::window::Position::Default,
+    };
+
     iced::application("Dolby Vision Converter", App::update, App::view)
         .subscription(App::subscription)
         .theme(|_| Theme::CatppuccinMocha)
         .window(iced::window::Settings {
             icon: load_svg_icon(),
+            size: Size::new(session.window_width, session.window_height),
+            position,
+            // 关闭窗口前需要先保存界面状态
+            exit_on_close_request: false,
             ..Default::default()
         })
-        .run()
+        .run_with(move || {
+            let mut app = App::with_session(session);
+            // 恢复上次自动保存的队列；没有时重新打开上次使用的命名队列
+            let task = match (app.projects.current.clone(), app.projects.active.clone()) {
+                (Some(project), _) => {
+                    if !project.files.is_empty() {
+                        app.log_messages.push(format!(
+                            "📂 Restored the previous queue ({} files)",
+                            project.files.len()
+                        ));
+                    }
+                    app.load_project(project)
+                }
+                (None, Some(name)) => app.open_project(&name),
+                (None, None) => app.check_temp_space(),
+            };
+            (app, task)
+        })
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn frame_rates_parse_from_fractions_and_decimals() {
+        for (text, expected) in [
+            ("24000/1001", FrameRate::FILM_23976),
+            // 分数按最简形式保存
+            ("48000/2002", FrameRate::FILM_23976),
+            (" 30000 / 1001 ", FrameRate::TV_29970),
+            ("24", FrameRate::FILM_24),
+            ("25.0", FrameRate::TV_25),
+            ("23.976", FrameRate::FILM_23976),
+            ("59.94", FrameRate::HFR_59940),
+            ("47.952", FrameRate::new(48000, 1001)),
+            ("50", FrameRate::new(50, 1)),
+        ] {
+            assert_eq!(FrameRate::parse(text), Ok(expected), "{text}");
+        }
+    }
+
+    #[test]
+    fn malformed_frame_rates_are_rejected() {
+        for text in [
+            "",
+            "fast",
+            "24/",
+            "/1001",
+            "24/0",
+            "0/1001",
+            "0",
+            "-24",
+            "24000/1001/2",
+            "4294967296/1",
+            // 既不是整数也不是 NTSC 帧率的小数需要写成分数
+            "23.5",
+        ] {
+            assert!(FrameRate::parse(text).is_err(), "{text} was accepted");
+        }
+    }
+
+    #[test]
+    fn frame_rates_are_limited_to_240_fps() {
+        assert_eq!(FrameRate::parse("240"), Ok(FrameRate::new(240, 1)));
+        assert_eq!(FrameRate::parse("240000/1000"), Ok(FrameRate::new(240, 1)));
+        assert!(FrameRate::parse("240001/1000").is_err());
+        assert!(FrameRate::parse("241").is_err());
+        assert_eq!(FrameRate::parse("1/1"), Ok(FrameRate::new(1, 1)));
+    }
+
+    #[test]
+    fn frame_rate_values_round_trip() {
+        assert_eq!(FrameRate::FILM_23976.to_value(), "24000/1001");
+        assert_eq!(FrameRate::FILM_24.to_value(), "24");
+        assert_eq!(FrameRate::new(120000, 1001).to_value(), "120000/1001");
+        for frame_rate in FrameRate::PRESETS {
+            assert_eq!(FrameRate::parse(&frame_rate.to_value()), Ok(frame_rate));
+        }
+        // 配置中保存为字符串，无效的值无法读取
+        assert_eq!(
+            serde_json::to_string(&FrameRate::HFR_59940).unwrap(),
+            "\"60000/1001\""
+        );
+        assert!(serde_json::from_str::<FrameRate>("\"24/0\"").is_err());
+    }
 }
@@ -0,0 +1,315 @@
+use serde::{Deserialize, Serialize};
+use serde_json::Value;
+use std::io::Write;
+use std::path::{Path, PathBuf};
+use std::process::{Command, Stdio};
+
+use crate::{command_line, config};
+
+const SCHEMA: config::Schema = config::Schema {
+    file: "media_server.json",
+    name: "media server settings",
+    migrations: &[],
+};
+// 单个请求的超时（秒），大型媒体库的列表请求可能较慢
+const REQUEST_TIMEOUT: &str = "120";
+
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq, Serialize, Deserialize)]
+pub enum ServerKind {
+    #[default]
+    Jellyfin,
+    Plex,
+}
+
+impl ServerKind {
+    pub const ALL: [ServerKind; 2] = [ServerKind::Jellyfin, ServerKind::Plex];
+}
+
+impl std::fmt::Display for ServerKind {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        f.write_str(match self {
+            ServerKind::Jellyfin => "Jellyfin",
+            ServerKind::Plex => "Plex",
+        })
+    }
+}
+
+// 媒体服务器连接设置和路径映射；令牌以明文保存在配置目录中
+#[derive(Debug, Clone, Default, PartialEq, Serialize, Deserialize)]
+#[serde(default)]
+pub struct ServerConfig {
+    pub kind: ServerKind,
+    pub url: String,
+    pub token: String,
+    // 服务器上看到的路径前缀（如 /media）替换为本机路径前缀（如 /Volumes/media）
+    pub server_prefix: String,
+    pub local_prefix: String,
+}
+
+// 一次导入的结果
+#[derive(Debug, Clone, Default)]
+pub struct Import {
+    // 本机存在、尚无 MP4 版本的杜比视界 MKV
+    pub files: Vec<PathBuf>,
+    // 已有 MP4 版本而跳过的条目数
+    pub converted: usize,
+    // 映射后本机找不到的路径
+    pub missing: Vec<PathBuf>,
+}
+
+// 服务器上的一个媒体条目：杜比视界 MKV 版本的路径，以及是否已有 MP4 版本
+struct Item {
+    mkv: Option<String>,
+    has_mp4: bool,
+}
+
+impl ServerConfig {
+    pub fn load() -> Self {
+        config::load(&SCHEMA)
+    }
+
+    pub fn save(&self) -> Result<(), String> {
+        config::save(&SCHEMA, self)
+    }
+
+    pub fn validation_error(&self) -> Option<String> {
+        let url = self.url.trim();
+        if url.is_empty() {
+            return Some("Enter the server URL".to_string());
+        }
+        if !url.starts_with("http://") && !url.starts_with("https://") {
+            return Some("Server URL must start with http:// or https://".to_string());
+        }
+        if self.token.trim().is_empty() {
+            return Some(match self.kind {
+                ServerKind::Jellyfin => "Enter a Jellyfin API key".to_string(),
+                ServerKind::Plex => "Enter a Plex token".to_string(),
+            });
+        }
+        if self.server_prefix.trim().is_empty() != self.local_prefix.trim().is_empty() {
+            return Some("Enter both the server and the local path prefix, or neither".to_string());
+        }
+        None
+    }
+
+    // 服务器路径转换为本机路径；不匹配前缀的路径保持不变
+    pub fn map_path(&self, server_path: &str) -> PathBuf {
+        let from = self.server_prefix.trim().trim_end_matches(['/', '\\']);
+        let to = self.local_prefix.trim().trim_end_matches(['/', '\\']);
+        if from.is_empty() {
+            return PathBuf::from(server_path);
+        }
+        match server_path.strip_prefix(from) {
+            Some(rest) if rest.is_empty() || rest.starts_with(['/', '\\']) => {
+                let rest = rest.trim_start_matches(['/', '\\']);
+                // Windows 服务器的路径使用反斜杠
+                rest.split(['/', '\\'])
+                    .filter(|part| !part.is_empty())
+                    .fold(PathBuf::from(to), |path, part| path.join(part))
+            }
+            _ => PathBuf::from(server_path),
+        }
+    }
+
+    // 令牌通过标准输入传给 curl（-H @-），不出现在进程列表中
+    fn get(&self, endpoint: &str) -> Result<Value, String> {
+        let url = format!("{}{endpoint}", self.url.trim().trim_end_matches('/'));
+        let header = match self.kind {
+            ServerKind::Jellyfin => format!("X-Emby-Token: {}", self.token.trim()),
+            ServerKind::Plex => format!("X-Plex-Token: {}", self.token.trim()),
+        };
+        let mut child = Command::new(command_line::resolve("curl"))
+            .args([
+                "--fail",
+                "--silent",
+                "--show-error",
+                "--location",
+                "--max-time",
+                REQUEST_TIMEOUT,
+                "-H",
+                "Accept: application/json",
+                "-H",
+                "@-",
+                &url,
+            ])
+            .stdin(Stdio::piped())
+            .stdout(Stdio::piped())
+            .stderr(Stdio::piped())
+            .spawn()
+            .map_err(|e| format!("Failed to execute command curl: {e}"))?;
+        if let Some(mut stdin) = child.stdin.take() {
+            stdin
+                .write_all(format!("{header}\n").as_bytes())
+                .map_err(|e| format!("Failed to send request headers: {e}"))?;
+        }
+        let output = child
+            .wait_with_output()
+            .map_err(|e| format!("Failed to execute command curl: {e}"))?;
+        if !output.status.success() {
+            return Err(format!(
+                "{} request failed: {}",
+                self.kind,
+                String::from_utf8_lossy(&output.stderr).trim()
+            ));
+        }
+        serde_json::from_slice(&output.stdout)
+            .map_err(|e| format!("Failed to parse {} response: {e}", self.kind))
+    }
+
+    // Jellyfin：一个条目的多个版本都在 MediaSources 中，VideoRangeType 以 DOVI 开头表示杜比视界
+    fn jellyfin_items(&self) -> Result<Vec<Item>, String> {
+        let response = self
+            .get("/Items?Recursive=true&IncludeItemTypes=Movie,Episode&Fields=Path,MediaSources")?;
+        let items = response
+            .get("Items")
+            .and_then(Value::as_array)
+            .ok_or("Unexpected Jellyfin response")?;
+        Ok(items
+            .iter()
+            .map(|item| {
+                let sources = array(item.get("MediaSources"));
+                let mkv = sources.iter().find_map(|source| {
+                    let path = source.get("Path").and_then(Value::as_str)?;
+                    let dolby_vision = array(source.get("MediaStreams")).iter().any(|stream| {
+                        stream.get("Type").and_then(Value::as_str) == Some("Video")
+                            && stream
+                                .get("VideoRangeType")
+                                .and_then(Value::as_str)
+                                .is_some_and(|range| range.starts_with("DOVI"))
+                    });
+                    (has_extension(path, "mkv") && dolby_vision).then(|| path.to_string())
+                });
+                let has_mp4 = sources.iter().any(|source| {
+                    source
+                        .get("Path")
+                        .and_then(Value::as_str)
+                        .is_some_and(|path| has_extension(path, "mp4"))
+                });
+                Item { mkv, has_mp4 }
+            })
+            .collect())
+    }
+
+    // Plex：列表中没有杜比视界信息，只对 MKV 条目再读取详细的流信息（DOVIPresent）
+    fn plex_items(&self) -> Result<Vec<Item>, String> {
+        let sections = self.get("/library/sections")?;
+        let sections = sections
+            .pointer("/MediaContainer/Directory")
+            .and_then(Value::as_array)
+            .ok_or("Unexpected Plex response")?;
+        let mut items = Vec::new();
+        for section in sections {
+            // type=1 为电影，type=4 为剧集的单集
+            let item_type = match section.get("type").and_then(Value::as_str) {
+                Some("movie") => 1,
+                Some("show") => 4,
+                _ => continue,
+            };
+            let Some(key) = section.get("key").and_then(Value::as_str) else {
+                continue;
+            };
+            let listing = self.get(&format!("/library/sections/{key}/all?type={item_type}"))?;
+            for entry in array(listing.pointer("/MediaContainer/Metadata")) {
+                let files = plex_files(entry);
+                let has_mp4 = files.iter().any(|(file, _)| has_extension(file, "mp4"));
+                if has_mp4 || !files.iter().any(|(file, _)| has_extension(file, "mkv")) {
+                    items.push(Item { mkv: None, has_mp4 });
+                    continue;
+                }
+                let Some(rating_key) = entry.get("ratingKey").and_then(Value::as_str) else {
+                    continue;
+                };
+                let details = self.get(&format!("/library/metadata/{rating_key}"))?;
+                let mkv = details
+                    .pointer("/MediaContainer/Metadata/0")
+                    .map(plex_files)
+                    .unwrap_or_default()
+                    .into_iter()
+                    .find(|(file, dolby_vision)| *dolby_vision && has_extension(file, "mkv"))
+                    .map(|(file, _)| file);
+                items.push(Item {
+                    mkv,
+                    has_mp4: false,
+                });
+            }
+        }
+        Ok(items)
+    }
+}
+
+// Plex 条目中各版本的文件路径，以及视频流是否带有杜比视界
+fn plex_files(entry: &Value) -> Vec<(String, bool)> {
+    array(entry.get("Media"))
+        .iter()
+        .flat_map(|media| array(media.get("Part")))
+        .filter_map(|part| {
+            let file = part.get("file").and_then(Value::as_str)?.to_string();
+            let dolby_vision = array(part.get("Stream")).iter().any(|stream| {
+                stream.get("streamType").and_then(Value::as_i64) == Some(1)
+                    && stream
+                        .get("DOVIPresent")
+                        .is_some_and(|present| present.as_bool() == Some(true) || *present == "1")
+            });
+            Some((file, dolby_vision))
+        })
+        .collect()
+}
+
+fn array(value: Option<&Value>) -> &[Value] {
+    value
+        .and_then(Value::as_array)
+        .map(Vec::as_slice)
+        .unwrap_or_default()
+}
+
+fn has_extension(path: &str, extension: &str) -> bool {
+    Path::new(path)
+        .extension()
+        .is_some_and(|ext| ext.eq_ignore_ascii_case(extension))
+}
+
+// 同一文件夹中已有同名的 MP4（如 Movie.mp4 或 Movie_dvh1.mp4）时视为已转换
+fn has_local_mp4(mkv: &Path) -> bool {
+    let (Some(folder), Some(stem)) = (mkv.parent(), mkv.file_stem()) else {
+        return false;
+    };
+    let stem = stem.to_string_lossy();
+    std::fs::read_dir(folder).is_ok_and(|entries| {
+        entries.flatten().any(|entry| {
+            let name = entry.file_name().to_string_lossy().to_string();
+            name.starts_with(stem.as_ref()) && has_extension(&name, "mp4")
+        })
+    })
+}
+
+// 查询媒体服务器中没有 MP4 版本的杜比视界 MKV，并映射为本机路径
+pub fn import(config: &ServerConfig) -> Result<Import, String> {
+    if let Some(error) = config.validation_error() {
+        return Err(error);
+    }
+    let items = match config.kind {
+        ServerKind::Jellyfin => config.jellyfin_items()?,
+        ServerKind::Plex => config.plex_items()?,
+    };
+
+    let mut result = Import::default();
+    for item in items {
+        if item.has_mp4 {
+            result.converted += 1;
+            continue;
+        }
+        let Some(mkv) = item.mkv else {
+            continue;
+        };
+        let path = config.map_path(&mkv);
+        if !path.exists() {
+            result.missing.push(path);
+        } else if has_local_mp4(&path) {
+            result.converted += 1;
+        } else if !result.files.contains(&path) {
+            result.files.push(path);
+        }
+    }
+    result.files.sort();
+    Ok(result)
+}
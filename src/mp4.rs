@@ -0,0 +1,155 @@
+use std::fs::File;
+use std::io::{Read, Seek, SeekFrom};
+use std::path::Path;
+
+use crate::probe::DolbyVision;
+
+// 读取 moov 时的上限，防止损坏的文件导致分配过大的内存
+const MAX_MOOV_SIZE: u64 = 256 * 1024 * 1024;
+
+// 视频轨道的 sample entry，例如 dvh1 / hvc1，以及其中的杜比视界配置盒（dvcC 或 dvvC）
+#[derive(Debug, Clone)]
+pub struct VideoEntry {
+    pub format: String,
+    pub dv_config: Option<(String, DolbyVision)>,
+}
+
+// 一个 box 的类型和内容（不含头部）
+struct Mp4Box<'a> {
+    kind: [u8; 4],
+    body: &'a [u8],
+}
+
+// 解析连续的 box；长度无效时停止
+fn boxes(mut data: &[u8]) -> Vec<Mp4Box<'_>> {
+    let mut result = Vec::new();
+    while data.len() >= 8 {
+        let size = u32::from_be_bytes([data[0], data[1], data[2], data[3]]) as u64;
+        let kind = [data[4], data[5], data[6], data[7]];
+        let (header, size) = match size {
+            0 => (8, data.len() as u64),
+            1 if data.len() >= 16 => {
+                let mut large = [0u8; 8];
+                large.copy_from_slice(&data[8..16]);
+                (16, u64::from_be_bytes(large))
+            }
+            _ => (8, size),
+        };
+        if size < header as u64 || size > data.len() as u64 {
+            break;
+        }
+        result.push(Mp4Box {
+            kind,
+            body: &data[header..size as usize],
+        });
+        data = &data[size as usize..];
+    }
+    result
+}
+
+fn child<'a>(body: &'a [u8], kind: &[u8; 4]) -> Option<&'a [u8]> {
+    boxes(body)
+        .into_iter()
+        .find(|b| &b.kind == kind)
+        .map(|b| b.body)
+}
+
+// dvcC / dvvC：版本号 2 字节，随后为 profile(7) level(6) rpu(1) el(1) bl(1)，再是兼容 ID(4)
+fn parse_dv_config(body: &[u8]) -> Option<DolbyVision> {
+    if body.len() < 5 {
+        return None;
+    }
+    let bits = u16::from_be_bytes([body[2], body[3]]);
+    Some(DolbyVision {
+        profile: (bits >> 9) as u8,
+        level: ((bits >> 3) & 0x3f) as u8,
+        rpu_present: bits & 0x4 != 0,
+        el_present: bits & 0x2 != 0,
+        bl_present: bits & 0x1 != 0,
+        bl_compatibility_id: body[4] >> 4,
+    })
+}
+
+// 读取顶层的 moov box；只读取其他顶层 box 的头部
+fn read_moov(path: &Path) -> Result<Vec<u8>, String> {
+    let mut file = File::open(path).map_err(|e| format!("Failed to open MP4: {e}"))?;
+    let file_size = file
+        .metadata()
+        .map_err(|e| format!("Failed to read MP4: {e}"))?
+        .len();
+    let mut offset = 0u64;
+    while offset + 8 <= file_size {
+        file.seek(SeekFrom::Start(offset))
+            .map_err(|e| format!("Failed to read MP4: {e}"))?;
+        let mut header = [0u8; 16];
+        file.read_exact(&mut header[..8])
+            .map_err(|e| format!("Failed to read MP4: {e}"))?;
+        let mut size = u32::from_be_bytes([header[0], header[1], header[2], header[3]]) as u64;
+        let mut header_size = 8;
+        if size == 1 {
+            file.read_exact(&mut header[8..16])
+                .map_err(|e| format!("Failed to read MP4: {e}"))?;
+            let mut large = [0u8; 8];
+            large.copy_from_slice(&header[8..16]);
+            size = u64::from_be_bytes(large);
+            header_size = 16;
+        } else if size == 0 {
+            size = file_size - offset;
+        }
+        if size < header_size {
+            return Err("MP4 box structure is corrupt".to_string());
+        }
+        if &header[4..8] == b"moov" {
+            let body_size = size - header_size;
+            if body_size > MAX_MOOV_SIZE {
+                return Err("MP4 moov box is too large".to_string());
+            }
+            let mut moov = vec![0u8; body_size as usize];
+            file.read_exact(&mut moov)
+                .map_err(|e| format!("Failed to read MP4 moov box: {e}"))?;
+            return Ok(moov);
+        }
+        offset += size;
+    }
+    Err("MP4 has no moov box".to_string())
+}
+
+// 列出所有视频轨道的 sample entry
+pub fn video_entries(path: &Path) -> Result<Vec<VideoEntry>, String> {
+    let moov = read_moov(path)?;
+    let mut entries = Vec::new();
+    for trak in boxes(&moov).into_iter().filter(|b| &b.kind == b"trak") {
+        let Some(mdia) = child(trak.body, b"mdia") else {
+            continue;
+        };
+        // hdlr: version/flags 4 字节、pre_defined 4 字节，随后是 handler 类型
+        let is_video = child(mdia, b"hdlr").is_some_and(|hdlr| hdlr.get(8..12) == Some(b"vide"));
+        if !is_video {
+            continue;
+        }
+        let Some(stsd) = child(mdia, b"minf")
+            .and_then(|minf| child(minf, b"stbl"))
+            .and_then(|stbl| child(stbl, b"stsd"))
+        else {
+            continue;
+        };
+        // stsd: version/flags 4 字节、entry_count 4 字节
+        for entry in boxes(stsd.get(8..).unwrap_or_default()) {
+            // VisualSampleEntry 的固定字段共 78 字节，之后是子 box
+            let dv_config = boxes(entry.body.get(78..).unwrap_or_default())
+                .into_iter()
+                .filter(|b| &b.kind == b"dvcC" || &b.kind == b"dvvC")
+                .find_map(|b| {
+                    Some((
+                        String::from_utf8_lossy(&b.kind).to_string(),
+                        parse_dv_config(b.body)?,
+                    ))
+                });
+            entries.push(VideoEntry {
+                format: String::from_utf8_lossy(&entry.kind).to_string(),
+                dv_config,
+            });
+        }
+    }
+    Ok(entries)
+}
@@ -0,0 +1,105 @@
+use std::path::{Path, PathBuf};
+
+use crate::probe::MediaInfo;
+
+// 多段源文件（如 "Movie CD1.mkv"、"Movie - Part 2.mkv"）的识别和拼接前的一致性检查
+
+// 文件名末尾的分段标记，较长的写在前面
+const MARKERS: [&str; 5] = ["part", "disc", "disk", "cd", "pt"];
+
+const SEPARATORS: [char; 4] = [' ', '.', '_', '-'];
+
+// 文件名中的分段信息：去掉标记后的标题和段号
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct PartName {
+    pub title: String,
+    pub number: u32,
+}
+
+// 识别文件名末尾的 "<分隔符><标记>[分隔符]<数字>"，例如 CD1、Part 2、pt.3
+pub fn part_name(path: &Path) -> Option<PartName> {
+    let stem = path.file_stem()?.to_string_lossy().to_string();
+    // 只转换 ASCII 字母，字节位置与原文件名一致
+    let lower = stem.to_ascii_lowercase();
+    let without_number = lower.trim_end_matches(|c: char| c.is_ascii_digit());
+    let number = lower[without_number.len()..].parse().ok()?;
+    let before_number = without_number.trim_end_matches(SEPARATORS);
+    let marker = MARKERS
+        .iter()
+        .find(|marker| before_number.ends_with(*marker))?;
+    let title_end = before_number.len() - marker.len();
+    // 标记前必须是分隔符，避免把 "Concept 2" 当作分段
+    if !lower[..title_end].ends_with(SEPARATORS) {
+        return None;
+    }
+    let title = stem[..title_end].trim_end_matches(SEPARATORS).to_string();
+    (number > 0 && !title.is_empty()).then_some(PartName { title, number })
+}
+
+// first 为第 1 段时，在 candidates 中按段号顺序找出同一文件夹、同一标题的后续分段；
+// 段号必须从 2 开始连续，否则不视为一组
+pub fn following_parts<'a>(
+    first: &Path,
+    candidates: impl Iterator<Item = &'a Path>,
+) -> Vec<PathBuf> {
+    let Some(name) = part_name(first).filter(|name| name.number == 1) else {
+        return Vec::new();
+    };
+    let mut parts = candidates
+        .filter(|path| *path != first && path.parent() == first.parent())
+        .filter_map(|path| {
+            let part = part_name(path)?;
+            part.title
+                .eq_ignore_ascii_case(&name.title)
+                .then_some((part.number, path.to_path_buf()))
+        })
+        .collect::<Vec<_>>();
+    parts.sort();
+    parts
+        .into_iter()
+        .enumerate()
+        .take_while(|(index, (number, _))| *number as usize == index + 2)
+        .map(|(_, (_, path))| path)
+        .collect()
+}
+
+// 拼接要求各段的视频和音频一致，返回 part 与第 1 段不一致之处
+pub fn mismatch(first: &MediaInfo, part: &MediaInfo) -> Option<String> {
+    let (Some(a), Some(b)) = (&first.video, &part.video) else {
+        return Some("no video stream".to_string());
+    };
+    if a.codec != b.codec || a.width != b.width || a.height != b.height {
+        return Some(format!(
+            "video is {} {}x{} instead of {} {}x{}",
+            b.codec, b.width, b.height, a.codec, a.width, a.height
+        ));
+    }
+    match (a.frame_rate, b.frame_rate) {
+        (Some(x), Some(y)) if (x - y).abs() > 0.001 => {
+            return Some(format!("frame rate is {y:.3} fps instead of {x:.3} fps"));
+        }
+        _ => {}
+    }
+    if a.pulldown != b.pulldown {
+        return Some("pulldown differs from the first part".to_string());
+    }
+    let profile = |info: &MediaInfo| {
+        info.video
+            .as_ref()
+            .and_then(|video| video.dolby_vision.as_ref())
+            .map(|dv| dv.profile)
+    };
+    if profile(first) != profile(part) {
+        return Some("Dolby Vision profile differs from the first part".to_string());
+    }
+    let codecs = |info: &MediaInfo| {
+        info.audio
+            .iter()
+            .map(|audio| (audio.codec.clone(), audio.channels))
+            .collect::<Vec<_>>()
+    };
+    if codecs(first) != codecs(part) {
+        return Some("audio tracks differ from the first part".to_string());
+    }
+    None
+}
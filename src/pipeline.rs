@@ -0,0 +1,4925 @@
+use iced::futures::channel::mpsc;
+use std::path::{Path, PathBuf};
+use std::process::Stdio;
+use std::sync::atomic::{AtomicU32, Ordering};
+use std::time::Duration;
+use tokio::io::{AsyncBufReadExt, AsyncReadExt, BufReader};
+
+use crate::active_area::Offsets;
+use crate::ass;
+use crate::charset::{self, Encoding};
+use crate::command_line;
+use crate::disk_space;
+use crate::history;
+use crate::journal;
+use crate::logs::Logs;
+use crate::mp4;
+use crate::parts;
+use crate::probe::{self, AudioStream, AutoAudio, MediaInfo, SubtitleStream};
+use crate::processes;
+use crate::temp_files;
+use crate::verbosity::ToolVerbosity;
+use crate::watch;
+use crate::{
+    DvProfile, EnhancementLayer, ExistingOutputs, LosslessAudio, Message, NonDvMode,
+    ProcessOptions, SourceAfterSuccess, SubtitleMode, UploadBackend, build_async_command,
+    execute_command_with_logging, execute_ffmpeg_with_progress, execute_pipe_with_logging,
+    format_size, format_utc_now, verbosity,
+};
+
+// 处理流程中的单个步骤；plan() 根据选项和源文件组合出每个文件的步骤序列
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Step {
+    JoinParts,
+    VerifySource,
+    TrimSource,
+    RemuxWithoutDv,
+    MuxAv1,
+    ExtractVideo,
+    DemuxEnhancementLayer,
+    ConvertProfile7,
+    ExtractHdr10Plus,
+    ExtractRpu,
+    EditRpu,
+    EditActiveArea,
+    BurnSubtitles,
+    InjectRpu,
+    InjectHdr10Plus,
+    ExtractAudio,
+    ExtractSubtitles,
+    OcrSubtitles,
+    Mux,
+    AddAacStereo,
+    ApplyAudioDelay,
+    WriteHdrMetadata,
+    WriteHlgColorInfo,
+    TagAudioLanguage,
+    VerifyAudio,
+    VerifyDvConfig,
+    MergeSubtitles,
+    Hdr10Fallback,
+    VerifyOutput,
+    Finalize,
+    ExportPoster,
+    AnnotateSource,
+    PostCommand,
+    Upload,
+    HandleSource,
+}
+
+impl std::fmt::Display for Step {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        f.write_str(match self {
+            Step::JoinParts => "Join parts",
+            Step::VerifySource => "Verify source",
+            Step::TrimSource => "Trim source",
+            Step::RemuxWithoutDv => "Remux without Dolby Vision",
+            Step::MuxAv1 => "Mux AV1 Dolby Vision MP4 (experimental)",
+            Step::ExtractVideo => "Extract video",
+            Step::DemuxEnhancementLayer => "Save enhancement layer",
+            Step::ConvertProfile7 => "Convert profile 7 to 8.1",
+            Step::ExtractHdr10Plus => "Extract HDR10+ metadata",
+            Step::ExtractRpu => "Extract RPU",
+            Step::EditRpu => "Edit RPU",
+            Step::EditActiveArea => "Set L5 active area",
+            Step::BurnSubtitles => "Burn in subtitles",
+            Step::InjectRpu => "Inject RPU",
+            Step::InjectHdr10Plus => "Restore HDR10+ metadata",
+            Step::ExtractAudio => "Extract audio",
+            Step::ExtractSubtitles => "Extract subtitles",
+            Step::OcrSubtitles => "OCR image subtitles",
+            Step::Mux => "Mux MP4",
+            Step::AddAacStereo => "Add AAC stereo track",
+            Step::ApplyAudioDelay => "Apply audio delay",
+            Step::WriteHdrMetadata => "Write HDR10 metadata",
+            Step::WriteHlgColorInfo => "Write HLG color info",
+            Step::TagAudioLanguage => "Tag audio language",
+            Step::VerifyAudio => "Verify copied audio",
+            Step::VerifyDvConfig => "Verify Dolby Vision configuration",
+            Step::MergeSubtitles => "Merge subtitles",
+            Step::Hdr10Fallback => "HDR10 fallback MP4",
+            Step::VerifyOutput => "Verify output",
+            Step::Finalize => "Finalize output",
+            Step::ExportPoster => "Export poster",
+            Step::AnnotateSource => "Annotate source",
+            Step::Upload => "Upload to remote destination",
+            Step::PostCommand => "Run post-processing command",
+            Step::HandleSource => "Tidy up source",
+        })
+    }
+}
+
+impl Step {
+    // 执行该步骤的外部工具，"built-in" 表示由 Rebottle 自己完成
+    pub fn tool(&self, options: &ProcessOptions) -> &'static str {
+        match self {
+            Step::JoinParts => "mkvmerge",
+            Step::VerifySource
+            | Step::TrimSource
+            | Step::RemuxWithoutDv
+            | Step::MuxAv1
+            | Step::ExtractAudio
+            | Step::ExtractSubtitles
+            | Step::VerifyAudio
+            | Step::ExportPoster => "ffmpeg",
+            Step::VerifyOutput => "ffprobe",
+            Step::ExtractVideo => "mkvextract",
+            Step::DemuxEnhancementLayer
+            | Step::ConvertProfile7
+            | Step::ExtractRpu
+            | Step::EditRpu
+            | Step::EditActiveArea
+            | Step::InjectRpu => "dovi_tool",
+            Step::ExtractHdr10Plus | Step::InjectHdr10Plus => "hdr10plus_tool",
+            Step::Mux => "mp4muxer",
+            Step::AddAacStereo
+            | Step::ApplyAudioDelay
+            | Step::WriteHdrMetadata
+            | Step::WriteHlgColorInfo
+            | Step::TagAudioLanguage => "MP4Box",
+            Step::OcrSubtitles => "ffmpeg + PgsToSrt",
+            Step::BurnSubtitles => "ffmpeg (libx265)",
+            Step::MergeSubtitles => "ffmpeg + MP4Box",
+            Step::Hdr10Fallback => "dovi_tool + ffmpeg",
+            Step::VerifyDvConfig | Step::Finalize => "built-in",
+            Step::PostCommand => "custom command",
+            Step::HandleSource => match options.source_after_success {
+                SourceAfterSuccess::Recycle if cfg!(windows) => "PowerShell",
+                SourceAfterSuccess::Recycle if cfg!(target_os = "macos") => "Finder",
+                SourceAfterSuccess::Recycle => "gio",
+                _ => "built-in",
+            },
+            Step::AnnotateSource => "mkvpropedit",
+            Step::Upload => match options.upload_backend {
+                UploadBackend::Curl => "curl",
+                UploadBackend::Rclone => "rclone",
+            },
+        }
+    }
+
+    // 步骤启动的外部程序，用于在处理前检查工具是否齐全；处理后命令由用户填写，不在其中
+    pub fn programs(&self, options: &ProcessOptions) -> &'static [&'static str] {
+        match self {
+            Step::OcrSubtitles => &["ffmpeg", "PgsToSrt"],
+            Step::BurnSubtitles => &["ffmpeg"],
+            Step::MergeSubtitles => &["ffmpeg", "MP4Box"],
+            // 低临时空间模式下 profile 7 源由 ffmpeg 通过管道交给 dovi_tool
+            Step::ConvertProfile7 | Step::Hdr10Fallback => &["ffmpeg", "dovi_tool"],
+            Step::AnnotateSource => &["mkvextract", "mkvpropedit"],
+            Step::VerifyDvConfig | Step::Finalize | Step::PostCommand => &[],
+            Step::HandleSource => match options.source_after_success {
+                SourceAfterSuccess::Recycle if cfg!(windows) => &["powershell"],
+                SourceAfterSuccess::Recycle if cfg!(target_os = "macos") => &["osascript"],
+                SourceAfterSuccess::Recycle => &["gio"],
+                _ => &[],
+            },
+            Step::Upload => match options.upload_backend {
+                UploadBackend::Curl => &["curl"],
+                UploadBackend::Rclone => &["rclone"],
+            },
+            Step::JoinParts => &["mkvmerge"],
+            Step::VerifySource
+            | Step::TrimSource
+            | Step::RemuxWithoutDv
+            | Step::MuxAv1
+            | Step::ExtractAudio
+            | Step::ExtractSubtitles
+            | Step::VerifyAudio
+            | Step::ExportPoster => &["ffmpeg"],
+            Step::VerifyOutput => &["ffprobe"],
+            Step::ExtractVideo => &["mkvextract"],
+            Step::DemuxEnhancementLayer
+            | Step::ExtractRpu
+            | Step::EditRpu
+            | Step::EditActiveArea
+            | Step::InjectRpu => &["dovi_tool"],
+            Step::ExtractHdr10Plus | Step::InjectHdr10Plus => &["hdr10plus_tool"],
+            Step::Mux => &["mp4muxer"],
+            Step::AddAacStereo
+            | Step::ApplyAudioDelay
+            | Step::WriteHdrMetadata
+            | Step::WriteHlgColorInfo
+            | Step::TagAudioLanguage => &["MP4Box"],
+        }
+    }
+
+    // 由源文件决定是否执行的步骤及其条件；其余步骤只取决于选项
+    pub fn condition(&self) -> Option<&'static str> {
+        match self {
+            Step::DemuxEnhancementLayer | Step::ConvertProfile7 => Some("profile 7 source"),
+            Step::ExtractHdr10Plus | Step::InjectHdr10Plus => Some("HDR10+ source"),
+            Step::ExtractSubtitles | Step::MergeSubtitles => Some("text subtitles"),
+            Step::OcrSubtitles => Some("PGS subtitles only"),
+            Step::BurnSubtitles => Some("subtitles, not profile 5"),
+            Step::ApplyAudioDelay => Some("audio delay"),
+            Step::WriteHdrMetadata => Some("HDR10 metadata"),
+            Step::TagAudioLanguage => Some("language tags"),
+            Step::VerifyAudio => Some("copied audio"),
+            Step::Hdr10Fallback => Some("not profile 5"),
+            Step::JoinParts => Some("multi-part source"),
+            Step::TrimSource => Some("trim range"),
+            Step::EditActiveArea => Some("L5 active area"),
+            _ => None,
+        }
+    }
+}
+
+// 流程标签页的各条分支：每条分支用具备相应特征的虚拟源文件调用 plan()，
+// 与实际处理使用同一套规则；条件步骤由 Step::condition() 标注。
+// 杜比视界 HEVC 源文件同时具备 profile 7、HDR10+、音频延迟等特征，显示可能经过的全部步骤；
+// 非杜比视界文件按设置跳过或中止时该分支没有步骤
+pub fn preset_graph(options: &ProcessOptions) -> Vec<(&'static str, Vec<Step>)> {
+    let video = |codec: &str, dolby_vision: Option<probe::DolbyVision>| MediaInfo {
+        video: Some(probe::VideoStream {
+            codec: codec.to_string(),
+            hdr: probe::HdrMetadata {
+                max_cll: Some(1000),
+                ..probe::HdrMetadata::default()
+            },
+            hdr10_plus: codec == "hevc",
+            dolby_vision,
+            ..probe::VideoStream::default()
+        }),
+        ..MediaInfo::default()
+    };
+    let profile = |profile: u8| probe::DolbyVision {
+        profile,
+        level: 6,
+        rpu_present: true,
+        el_present: profile == 7,
+        bl_present: true,
+        bl_compatibility_id: if profile == 7 { 6 } else { 1 },
+    };
+    let hevc = video("hevc", Some(profile(7)));
+    let audio = AudioStream {
+        codec: "eac3".to_string(),
+        channels: 6,
+        language: Some("eng".to_string()),
+        delay_ms: 1,
+        ..AudioStream::default()
+    };
+    let audio_tracks = if options.video_only {
+        Vec::new()
+    } else {
+        vec![&audio]
+    };
+    // 非杜比视界和 AV1 源文件由 ffmpeg 保留容器中的延迟，只有手动设置的偏移需要改写
+    let audio_offset = Source {
+        audio_offset: Some(1),
+        ..Source::default()
+    };
+    // 拼接、裁剪和 L5 有效画面区域取决于单个文件的设置
+    let per_file = Source {
+        parts: vec![PathBuf::new()],
+        trim: Some(Trim {
+            start: 1.0,
+            end: None,
+        }),
+        active_area: Some(Offsets::default()),
+        ..Source::default()
+    };
+    let non_dv = match options.non_dv_mode {
+        NonDvMode::Remux => plan(
+            options,
+            &video("hevc", None),
+            &audio_tracks,
+            false,
+            &audio_offset,
+        ),
+        NonDvMode::Skip | NonDvMode::Abort => Vec::new(),
+    };
+    vec![
+        (
+            "Dolby Vision HEVC",
+            plan(options, &hevc, &audio_tracks, true, &Source::default()),
+        ),
+        ("No Dolby Vision", non_dv),
+        (
+            "AV1 Dolby Vision",
+            plan(
+                options,
+                &video("av1", Some(profile(10))),
+                &audio_tracks,
+                true,
+                &audio_offset,
+            ),
+        ),
+        (
+            "Multi-part, trimmed or L5 active area",
+            plan(options, &hevc, &audio_tracks, true, &per_file),
+        ),
+    ]
+}
+
+// 预演（dry run）的结果：不执行任何命令，按 process_file() 的规则得出文件会经过的步骤、
+// 输出的轨道和输出文件
+#[derive(Debug, Clone, Default)]
+pub struct DryRun {
+    // 按非杜比视界文件的设置跳过或中止时的说明
+    pub skipped: Option<String>,
+    pub steps: Vec<Step>,
+    // 输出音轨及其转码目标，None 表示原样复制
+    pub audio: Vec<(AudioStream, Option<String>)>,
+    pub subtitles: Vec<SubtitleStream>,
+    pub outputs: Vec<String>,
+}
+
+// 用已分析的源文件信息预演一个文件的处理
+pub fn dry_run(source: &Source, media_info: &MediaInfo, options: &ProcessOptions) -> DryRun {
+    let dolby_vision = media_info.has_dolby_vision();
+    if !dolby_vision {
+        match options.non_dv_mode {
+            NonDvMode::Skip => {
+                return DryRun {
+                    skipped: Some("No Dolby Vision metadata, skipped".to_string()),
+                    ..DryRun::default()
+                };
+            }
+            NonDvMode::Abort => {
+                return DryRun {
+                    skipped: Some("No Dolby Vision metadata, the batch stops".to_string()),
+                    ..DryRun::default()
+                };
+            }
+            NonDvMode::Remux => {}
+        }
+    }
+
+    let mut options = options.clone();
+    if media_info.audio.is_empty() {
+        options.video_only = true;
+    }
+    let subtitles = options
+        .subtitle_tracks(
+            media_info,
+            &source.audio_tracks,
+            source.subtitle_tracks.as_deref(),
+        )
+        .into_iter()
+        .cloned()
+        .chain(
+            source
+                .external_subtitles
+                .iter()
+                .map(|path| external_subtitle(path)),
+        )
+        .collect::<Vec<_>>();
+    if source.subtitle_tracks.is_some() || !source.external_subtitles.is_empty() {
+        options.subtitle_mode = match options.subtitle_mode {
+            _ if subtitles.is_empty() => SubtitleMode::None,
+            SubtitleMode::None => SubtitleMode::All,
+            mode => mode,
+        };
+    }
+    let mut audio_tracks = if options.video_only {
+        Vec::new()
+    } else {
+        media_info
+            .audio_tracks(&source.audio_tracks, options.auto_audio())
+            .into_iter()
+            .map(|audio| match media_info.ac3_core(audio) {
+                Some(core) if options.truehd_ac3_core && audio.codec == "truehd" => core.clone(),
+                _ => audio.clone(),
+            })
+            .collect::<Vec<_>>()
+    };
+    if options.truehd_ac3_core {
+        audio_tracks.sort_by_key(|audio| audio.index);
+        audio_tracks.dedup_by_key(|audio| audio.index);
+    }
+
+    let steps = plan(
+        &options,
+        media_info,
+        &audio_tracks.iter().collect::<Vec<_>>(),
+        dolby_vision,
+        source,
+    );
+    let stem = input_stem(&source.path, &source.parts, source.version);
+    let burned = steps.contains(&Step::BurnSubtitles);
+    let outputs = output_suffixes(
+        &steps,
+        &options,
+        media_info,
+        !source.external_subtitles.is_empty(),
+    )
+    .into_iter()
+    .map(|suffix| format!("{stem}{suffix}"))
+    .collect();
+    DryRun {
+        skipped: None,
+        audio: audio_tracks
+            .into_iter()
+            .map(|audio| {
+                let target = AudioConversion::for_track(&options, &audio).target();
+                (audio, target)
+            })
+            .collect(),
+        subtitles: if options.subtitle_mode == SubtitleMode::None {
+            Vec::new()
+        } else if burned {
+            subtitles.into_iter().take(1).collect()
+        } else if steps.contains(&Step::OcrSubtitles) {
+            subtitles
+        } else {
+            subtitles
+                .into_iter()
+                .filter(SubtitleStream::is_text)
+                .collect()
+        },
+        steps,
+        outputs,
+    }
+}
+
+// 按处理步骤得出的输出文件名后缀（接在输出文件名主干之后）
+fn output_suffixes(
+    steps: &[Step],
+    options: &ProcessOptions,
+    media_info: &MediaInfo,
+    external_subtitles: bool,
+) -> Vec<String> {
+    let tag = options.sample_entry.tag();
+    // 烧录字幕后只有外部字幕作为软字幕封装
+    let burned = steps.contains(&Step::BurnSubtitles);
+    let merged = steps.contains(&Step::MergeSubtitles) && (!burned || external_subtitles);
+    steps
+        .iter()
+        .filter_map(|step| match step {
+            Step::RemuxWithoutDv => Some("_remux.mp4".to_string()),
+            Step::MuxAv1 => Some("_av1dv.mp4".to_string()),
+            Step::Mux if merged && !options.keep_unsubtitled => None,
+            Step::Mux => Some(format!("_{tag}.mp4")),
+            Step::MergeSubtitles if !merged => None,
+            Step::MergeSubtitles => Some(format!("_{tag}_with_subs.mp4")),
+            Step::Hdr10Fallback
+                if media_info
+                    .video
+                    .as_ref()
+                    .is_some_and(|video| video.is_hlg()) =>
+            {
+                Some("_hlg.mp4".to_string())
+            }
+            Step::Hdr10Fallback => Some("_hdr10.mp4".to_string()),
+            _ => None,
+        })
+        .collect()
+}
+
+// 临时文件名中的任务编号：同时处理的文件可能同名（来自不同文件夹），加上进程号和编号后互不覆盖
+static NEXT_JOB: AtomicU32 = AtomicU32::new(0);
+
+fn temp_stem(input_stem: &str) -> String {
+    let job = NEXT_JOB.fetch_add(1, Ordering::Relaxed);
+    format!("{input_stem}.{}-{job}", std::process::id())
+}
+
+// 输出文件名的主干：拼接多段源文件时去掉分段标记（"Movie CD1" → "Movie"），重新转换时带 .vN
+fn input_stem(path: &Path, parts: &[PathBuf], version: u32) -> String {
+    let mut stem = parts::part_name(path)
+        .filter(|_| !parts.is_empty())
+        .map(|name| name.title)
+        .unwrap_or_else(|| {
+            path.file_stem()
+                .unwrap_or_default()
+                .to_string_lossy()
+                .to_string()
+        });
+    if version > 1 {
+        stem.push_str(&format!(".v{version}"));
+    }
+    stem
+}
+
+// 队列中的一个文件及其处理参数
+#[derive(Debug, Clone, Default)]
+pub struct Source {
+    pub path: PathBuf,
+    // 手动设置的 L5 有效画面区域，None 时保留 RPU 中原有的 L5
+    pub active_area: Option<Offsets>,
+    // 大于 1 时（源文件被替换后重新转换）输出文件名带 .vN
+    pub version: u32,
+    pub trim: Option<Trim>,
+    // 手动选择的音频轨道（0:a:N），为空时自动选择
+    pub audio_tracks: Vec<usize>,
+    // 手动选择的字幕轨道（0:s:N，可以为空），None 时按字幕模式选择
+    pub subtitle_tracks: Option<Vec<usize>>,
+    // 随文件添加的外部字幕文件（.srt/.ass）
+    pub external_subtitles: Vec<PathBuf>,
+    // 手动设置的音频偏移（毫秒），代替容器中的延迟；None 时使用源文件的延迟
+    pub audio_offset: Option<i64>,
+    // 字幕时间偏移（毫秒），源文件和外部字幕的所有字幕轨道一起移动；None 时不调整
+    pub subtitle_offset: Option<i64>,
+    // 多段源文件（CD1/CD2）的后续分段，按顺序拼接在 path 之后
+    pub parts: Vec<PathBuf>,
+}
+
+// 解析音频偏移：毫秒数，可带正负号和 ms 后缀；留空表示使用源文件的延迟
+pub fn parse_audio_offset(text: &str) -> Result<Option<i64>, String> {
+    parse_offset(text, "audio offset")
+}
+
+// 解析字幕偏移，格式与音频偏移相同；留空表示不调整
+pub fn parse_subtitle_offset(text: &str) -> Result<Option<i64>, String> {
+    parse_offset(text, "subtitle offset")
+}
+
+fn parse_offset(text: &str, what: &str) -> Result<Option<i64>, String> {
+    let text = text.trim();
+    if text.is_empty() {
+        return Ok(None);
+    }
+    let number = text.strip_suffix("ms").unwrap_or(text).trim();
+    number
+        .strip_prefix('+')
+        .unwrap_or(number)
+        .parse::<i64>()
+        .map(Some)
+        .map_err(|_| format!("Invalid {what} \"{text}\": use milliseconds, e.g. -120"))
+}
+
+// 裁剪范围（秒）；end 为 None 表示到结尾
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct Trim {
+    pub start: f64,
+    pub end: Option<f64>,
+}
+
+impl Trim {
+    // 解析起止位置：秒数、MM:SS、HH:MM:SS(.fff)，或章节 chN（起点取章节开头，终点取章节结尾）
+    pub fn parse(
+        start: &str,
+        end: &str,
+        chapters: &[probe::Chapter],
+    ) -> Result<Option<Trim>, String> {
+        let position = |text: &str, is_end: bool| -> Result<Option<f64>, String> {
+            let text = text.trim();
+            if text.is_empty() {
+                return Ok(None);
+            }
+            if let Some(number) = text.strip_prefix("ch").or_else(|| text.strip_prefix("Ch")) {
+                let chapter = number
+                    .parse::<usize>()
+                    .ok()
+                    .and_then(|number| chapters.get(number.checked_sub(1)?))
+                    .ok_or_else(|| {
+                        format!("Chapter \"{text}\" not found ({} chapters)", chapters.len())
+                    })?;
+                return Ok(Some(if is_end { chapter.end } else { chapter.start }));
+            }
+            text.split(':')
+                .try_fold(0.0, |total, part| {
+                    part.parse::<f64>()
+                        .ok()
+                        .filter(|value| *value >= 0.0)
+                        .map(|value| total * 60.0 + value)
+                })
+                .map(Some)
+                .ok_or_else(|| format!("Invalid position \"{text}\": use seconds, HH:MM:SS or chN"))
+        };
+        let start = position(start, false)?;
+        let end = position(end, true)?;
+        if start.is_none() && end.is_none() {
+            return Ok(None);
+        }
+        let start = start.unwrap_or(0.0);
+        if end.is_some_and(|end| end <= start) {
+            return Err("Trim end must be after the start".to_string());
+        }
+        Ok(Some(Trim { start, end }))
+    }
+}
+
+impl std::fmt::Display for Trim {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        write!(f, "{:.3}s → ", self.start)?;
+        match self.end {
+            Some(end) => write!(f, "{end:.3}s"),
+            None => f.write_str("end"),
+        }
+    }
+}
+
+// 单个文件的处理状态，在各步骤之间传递
+struct Job<'a> {
+    input_file: PathBuf,
+    active_area: Option<Offsets>,
+    // 拼接在 input_file 之后的分段
+    parts: Vec<PathBuf>,
+    // 各提取步骤读取的文件：通常是源文件，拼接或裁剪后为临时 MKV
+    source_file: PathBuf,
+    trim: Option<Trim>,
+    // 输出的音频轨道（按源文件顺序），以及是否为手动选择
+    audio_tracks: Vec<AudioStream>,
+    audio_chosen: bool,
+    audio_offset: Option<i64>,
+    subtitle_offset: Option<i64>,
+    input_stem: String,
+    // 临时目录中的文件名前缀，每个任务不同
+    temp_stem: String,
+    output_folder: PathBuf,
+    temp_dir: PathBuf,
+    options: ProcessOptions,
+    media_info: MediaInfo,
+    probed: bool,
+    // AV1 杜比视界源（实验性），由 ffmpeg 直接封装
+    av1: bool,
+    // 各步骤产生的中间文件
+    video_file: PathBuf,
+    // 与 audio_tracks 一一对应
+    audio_files: Vec<PathBuf>,
+    // 第一条音轨下混的 AAC 立体声兼容音轨
+    aac_file: Option<PathBuf>,
+    // 按字幕模式选择的文本字幕轨道，以及提取成功后与之一一对应的 SRT 文件
+    subtitle_tracks: Vec<SubtitleStream>,
+    subtitle_files: Vec<PathBuf>,
+    subtitles_chosen: bool,
+    // 外部字幕文件及其格式和语言，在源文件的字幕之后封装
+    external_subtitles: Vec<(PathBuf, SubtitleStream)>,
+    // 裁剪后源文件的起点（秒），外部字幕需要相应提前
+    trim_start: f64,
+    rpu_file: Option<PathBuf>,
+    hdr10_plus_file: Option<PathBuf>,
+    // 封装时写入的杜比视界 profile（已解析 Auto）
+    dv_profile: DvProfile,
+    // 主输出的 .part 文件（mp4muxer 或 remux 的输出），后续步骤在其上继续处理
+    mp4_part: Option<PathBuf>,
+    // 待校验并重命名的 (.part, 最终文件)
+    outputs: Vec<(PathBuf, PathBuf)>,
+    finished: Vec<PathBuf>,
+    logs: Logs,
+    sender: &'a mut mpsc::Sender<Message>,
+}
+
+impl Job<'_> {
+    fn temp_file(&mut self, suffix: &str) -> PathBuf {
+        let path = self.temp_dir.join(format!("{}{suffix}", self.temp_stem));
+        temp_files::register(&self.input_file, &path);
+        path
+    }
+
+    // 输出写入过程中的 .part 文件，在登记表中登记到重命名为最终文件为止
+    fn output_part(&self, output: &Path) -> PathBuf {
+        let part = part_path(output);
+        temp_files::register(&self.input_file, &part);
+        part
+    }
+
+    // MP4Box 改写主输出时写入的新文件，如 name.mp4.hdr.part。与主输出放在同一目录，
+    // 便于直接重命名替换；作为 .part 文件登记，中断后会被清理
+    fn rewrite_part(&self, main_part: &Path, what: &str) -> PathBuf {
+        self.output_part(&main_part.with_extension(what))
+    }
+
+    fn main_part(&self) -> Result<PathBuf, String> {
+        self.mp4_part
+            .clone()
+            .ok_or_else(|| "No MP4 has been muxed yet".to_string())
+    }
+
+    // 输出轨道的语言：源文件的语言标签，没有时使用设置的默认语言
+    fn audio_language(&self, audio: &AudioStream) -> Option<String> {
+        audio
+            .language
+            .as_deref()
+            .or(self.options.default_language())
+            .map(ToString::to_string)
+    }
+
+    // 外部字幕的 ffmpeg 输入参数；源文件被裁剪时字幕按新的起点提前，再加上字幕偏移
+    fn external_subtitle_input(&self, path: &Path) -> Vec<String> {
+        let shift = self.subtitle_offset.unwrap_or(0) as f64 / 1000.0 - self.trim_start;
+        let mut args = Vec::new();
+        if shift != 0.0 {
+            args.extend(["-itsoffset".to_string(), format!("{shift:.3}")]);
+        }
+        args.extend(["-i".to_string(), path.to_string_lossy().to_string()]);
+        args
+    }
+
+    // 读取源文件字幕轨道的 ffmpeg 输入参数，设置了字幕偏移时整体移动时间戳
+    fn source_subtitle_input(&self) -> Vec<String> {
+        let mut args = Vec::new();
+        if let Some(offset) = self.subtitle_offset.filter(|offset| *offset != 0) {
+            args.extend([
+                "-itsoffset".to_string(),
+                format!("{:.3}", offset as f64 / 1000.0),
+            ]);
+        }
+        args.extend([
+            "-i".to_string(),
+            self.source_file.to_string_lossy().to_string(),
+        ]);
+        args
+    }
+
+    // 输出音轨的延迟（毫秒）：手动设置的偏移，否则为源文件容器中的延迟
+    fn audio_delay(&self, audio: &AudioStream) -> i64 {
+        self.audio_offset.unwrap_or(audio.delay_ms)
+    }
+
+    // AAC 立体声兼容音轨的来源：第一条输出音轨；本身已是 AAC 立体声时不需要
+    fn stereo_track(&self) -> Option<&AudioStream> {
+        if !self.options.aac_stereo {
+            return None;
+        }
+        self.audio_tracks
+            .first()
+            .filter(|audio| audio.codec != "aac" || audio.channels > 2)
+    }
+
+    fn subtitle_language(&self, subtitle: &SubtitleStream) -> Option<String> {
+        subtitle
+            .language
+            .as_deref()
+            .or(self.options.default_language())
+            .map(ToString::to_string)
+    }
+
+    fn dolby_vision_profile(&self) -> Option<u8> {
+        self.media_info
+            .video
+            .as_ref()
+            .and_then(|video| video.dolby_vision.as_ref())
+            .map(|dv| dv.profile)
+    }
+
+    // 视频流由新的中间文件取代；低临时空间模式下立即删除不再需要的上一个中间文件
+    fn replace_video(&mut self, file: PathBuf) {
+        let previous = std::mem::replace(&mut self.video_file, file);
+        if self.options.low_temp_space {
+            temp_files::remove(&previous);
+        }
+    }
+
+    // 低临时空间模式下删除封装完成后不再需要的视频和音频中间文件
+    fn release_streams(&mut self) {
+        if self.options.low_temp_space {
+            for file in self.audio_files.drain(..) {
+                temp_files::remove(&file);
+            }
+            temp_files::remove(&self.video_file);
+        }
+    }
+
+    // 删除登记表中该任务的中间文件，以及失败时未完成的 .part 输出
+    fn cleanup(&self) {
+        temp_files::remove_job(&self.input_file);
+    }
+}
+
+// 检查外部命令的结果，失败时返回带有 stderr 的错误信息
+fn check(
+    output: Result<std::process::Output, String>,
+    what: &str,
+) -> Result<std::process::Output, String> {
+    match output {
+        Ok(out) if out.status.success() => Ok(out),
+        Ok(out) => Err(format!(
+            "{what} failed: {}",
+            String::from_utf8_lossy(&out.stderr)
+        )),
+        Err(e) => Err(e),
+    }
+}
+
+// 低临时空间模式下 profile 7 源不先提取视频，由 ffmpeg 通过管道把视频流直接交给 dovi_tool 转换。
+// HDR10+ 提取和单独保存增强层需要原始视频流，这时仍然先提取
+fn pipes_profile_7(options: &ProcessOptions, media_info: &MediaInfo) -> bool {
+    let Some(video) = &media_info.video else {
+        return false;
+    };
+    options.low_temp_space
+        && video.dolby_vision.as_ref().map(|dv| dv.profile) == Some(7)
+        && !video.hdr10_plus
+        && options.enhancement_layer != EnhancementLayer::KeepSeparate
+}
+
+// 按探测到的轨道大小粗略估算临时文件夹和输出文件夹分别需要的空间（字节），视频大小未知时返回 None。
+// 中间视频流按需要同时存在的份数计算；封装后用 MP4Box 改写输出时会临时多出一份输出
+fn space_needed(
+    steps: &[Step],
+    options: &ProcessOptions,
+    media_info: &MediaInfo,
+    audio_tracks: &[AudioStream],
+    source_bytes: u64,
+) -> Option<(u64, u64)> {
+    let video = media_info.video.as_ref()?.size?;
+    let audio = audio_tracks
+        .iter()
+        .filter_map(|audio| audio.size)
+        .sum::<u64>();
+    let count = |candidates: &[Step]| {
+        steps
+            .iter()
+            .filter(|step| candidates.contains(step))
+            .count() as u64
+    };
+
+    let mut copies = count(&[
+        Step::ExtractVideo,
+        Step::ConvertProfile7,
+        Step::BurnSubtitles,
+        Step::InjectRpu,
+        Step::InjectHdr10Plus,
+        Step::Hdr10Fallback,
+    ]);
+    // 低临时空间模式下上一份视频流在下一步完成后删除，最多同时存在两份
+    if options.low_temp_space {
+        copies = copies.min(2);
+    }
+    let mut temp = video * copies;
+    if steps.contains(&Step::ExtractAudio) {
+        temp += audio;
+    }
+    // 合并分段和裁剪都会在临时文件夹中写出一份完整的源文件
+    temp += source_bytes * count(&[Step::JoinParts, Step::TrimSource]);
+
+    let rewrites = count(&[
+        Step::AddAacStereo,
+        Step::ApplyAudioDelay,
+        Step::WriteHdrMetadata,
+        Step::WriteHlgColorInfo,
+        Step::TagAudioLanguage,
+    ])
+    .min(1);
+    let output =
+        (video + audio) * (1 + count(&[Step::MergeSubtitles, Step::Hdr10Fallback]) + rewrites);
+    Some((temp, output))
+}
+
+// 开始处理前确认临时文件夹和输出文件夹所在的磁盘有足够的空间，不足时拒绝处理该文件，
+// 避免工具写到一半才因磁盘已满失败；无法估计或查询时只记录日志
+fn check_disk_space(
+    temp_dir: &Path,
+    output_folder: &Path,
+    needed: Option<(u64, u64)>,
+    logs: &mut Logs,
+) -> Result<(), String> {
+    let Some((temp, output)) = needed else {
+        logs.push("Stream sizes unknown, skipping the disk space check".to_string());
+        return Ok(());
+    };
+    let checks = if disk_space::same_volume(temp_dir, output_folder) {
+        vec![("Temp and output folders", output_folder, temp + output)]
+    } else {
+        vec![
+            ("Temp folder", temp_dir, temp),
+            ("Output folder", output_folder, output),
+        ]
+    };
+    for (label, folder, needed) in checks.into_iter().filter(|(_, _, needed)| *needed > 0) {
+        let Some(free) = disk_space::available(folder) else {
+            logs.push(format!(
+                "Could not check free space for {}, continuing",
+                folder.display()
+            ));
+            continue;
+        };
+        if free < needed {
+            return Err(format!(
+                "Not enough disk space: {label} ({}) need about {}, only {} free",
+                folder.display(),
+                format_size(needed),
+                format_size(free)
+            ));
+        }
+        // 估算只是近似值，余量不到一成时提醒
+        logs.push(if free < needed + needed / 10 {
+            format!(
+                "Warning: {label} ({}) need about {}, only {} free, the disk may fill up",
+                folder.display(),
+                format_size(needed),
+                format_size(free)
+            )
+        } else {
+            format!(
+                "Disk space: {label} need about {}, {} free",
+                format_size(needed),
+                format_size(free)
+            )
+        });
+    }
+    Ok(())
+}
+
+// 根据选项和源文件信息组合处理步骤
+pub fn plan(
+    options: &ProcessOptions,
+    media_info: &MediaInfo,
+    audio_tracks: &[&AudioStream],
+    dolby_vision: bool,
+    source: &Source,
+) -> Vec<Step> {
+    let audio_offset = source.audio_offset;
+    let joined = !source.parts.is_empty();
+    let trimmed = source.trim.is_some();
+    let active_area = source.active_area.is_some();
+    // 原样复制的音轨在封装后与源文件逐包比较
+    let verify_audio = options.verify_audio
+        && audio_tracks
+            .iter()
+            .any(|audio| AudioConversion::for_track(options, audio) == AudioConversion::Copy);
+    let mut steps = Vec::new();
+    if joined {
+        steps.push(Step::JoinParts);
+    }
+    if options.verify_source {
+        steps.push(Step::VerifySource);
+    }
+    if trimmed {
+        steps.push(Step::TrimSource);
+    }
+
+    if !dolby_vision {
+        steps.push(Step::RemuxWithoutDv);
+        // ffmpeg remux 时保留容器中的延迟，只有手动设置的偏移需要改写
+        if audio_offset.is_some() && !audio_tracks.is_empty() {
+            steps.push(Step::ApplyAudioDelay);
+        }
+        if verify_audio {
+            steps.push(Step::VerifyAudio);
+        }
+        steps.extend([Step::VerifyOutput, Step::Finalize]);
+    } else if media_info
+        .video
+        .as_ref()
+        .is_some_and(|video| video.is_av1())
+    {
+        // 实验性：mp4muxer 和 dovi_tool 只支持 HEVC，AV1 杜比视界（profile 10）由 ffmpeg
+        // 直接复制视频并写入 dvvC，再检查 sample entry 和配置
+        steps.push(Step::MuxAv1);
+        if audio_offset.is_some() && !audio_tracks.is_empty() {
+            steps.push(Step::ApplyAudioDelay);
+        }
+        if verify_audio {
+            steps.push(Step::VerifyAudio);
+        }
+        steps.extend([Step::VerifyDvConfig, Step::VerifyOutput, Step::Finalize]);
+    } else {
+        if !pipes_profile_7(options, media_info) {
+            steps.push(Step::ExtractVideo);
+        }
+        let profile = media_info
+            .video
+            .as_ref()
+            .and_then(|video| video.dolby_vision.as_ref())
+            .map(|dv| dv.profile);
+        let hdr10_plus = media_info
+            .video
+            .as_ref()
+            .is_some_and(|video| video.hdr10_plus);
+        // 混合 DV + HDR10+ 源：在 dovi_tool 改写视频流之前保存 HDR10+ 元数据，改写后再注入
+        if hdr10_plus {
+            steps.push(Step::ExtractHdr10Plus);
+        }
+        if profile == Some(7) {
+            if options.enhancement_layer == EnhancementLayer::KeepSeparate {
+                steps.push(Step::DemuxEnhancementLayer);
+            }
+            steps.push(Step::ConvertProfile7);
+        }
+        // 只有图形字幕时改为 OCR 识别 PGS 字幕
+        let subtitle_step = if options.ocr_subtitles && media_info.has_only_image_subtitles() {
+            Step::OcrSubtitles
+        } else {
+            Step::ExtractSubtitles
+        };
+        // 烧录字幕需要重新编码视频，编码器不保留 RPU：先提取 RPU，烧录后重新注入
+        let burn = options.burn_subtitles && options.subtitle_mode != SubtitleMode::None;
+        // 裁剪后用 dovi_tool 重新提取并注入 RPU，保证每一帧都有对应的 RPU；
+        // 设置 L5 时同样需要提取、编辑并注入 RPU
+        if options.rpu_workflow || trimmed || burn || active_area {
+            steps.push(Step::ExtractRpu);
+            if options.rpu_edit_config.is_some() {
+                steps.push(Step::EditRpu);
+            }
+            if active_area {
+                steps.push(Step::EditActiveArea);
+            }
+            if burn {
+                steps.extend([subtitle_step, Step::BurnSubtitles]);
+            }
+            steps.push(Step::InjectRpu);
+        }
+        if hdr10_plus {
+            steps.push(Step::InjectHdr10Plus);
+        }
+        if !options.video_only {
+            steps.push(Step::ExtractAudio);
+        }
+        if options.subtitle_mode != SubtitleMode::None && !burn {
+            steps.push(subtitle_step);
+        }
+        steps.push(Step::Mux);
+        if options.aac_stereo && !audio_tracks.is_empty() {
+            steps.push(Step::AddAacStereo);
+        }
+        // 提取的裸音频流没有起始时间，容器中的延迟需要在封装后写回
+        if audio_tracks
+            .iter()
+            .any(|audio| audio_offset.unwrap_or(audio.delay_ms) != 0)
+        {
+            steps.push(Step::ApplyAudioDelay);
+        }
+        // HLG 基础层（profile 8.4）不写入 PQ 的静态元数据，改为写入 HLG 的颜色信息
+        match &media_info.video {
+            Some(video) if video.is_hlg() => steps.push(Step::WriteHlgColorInfo),
+            Some(video) if !video.hdr.is_empty() => steps.push(Step::WriteHdrMetadata),
+            _ => {}
+        }
+        if audio_tracks
+            .iter()
+            .any(|audio| audio.language.is_some() || options.default_language().is_some())
+        {
+            steps.push(Step::TagAudioLanguage);
+        }
+        // 在对主输出的所有改写之后检查，字幕版本由主输出复制而来
+        if verify_audio {
+            steps.push(Step::VerifyAudio);
+        }
+        steps.push(Step::VerifyDvConfig);
+        if options.subtitle_mode != SubtitleMode::None {
+            steps.push(Step::MergeSubtitles);
+        }
+        if options.hdr10_fallback {
+            steps.push(Step::Hdr10Fallback);
+        }
+        steps.extend([Step::VerifyOutput, Step::Finalize]);
+    }
+
+    if options.export_poster {
+        steps.push(Step::ExportPoster);
+    }
+    if options.annotate_source {
+        steps.push(Step::AnnotateSource);
+    }
+    // 处理后命令在上传之前运行：上传后可能删除本地副本（或由 rclone 移走），{output} 会指向不存在的文件
+    if matches!(options.post_command(), Ok(Some(_))) {
+        steps.push(Step::PostCommand);
+    }
+    if options.upload_destination().is_some() {
+        steps.push(Step::Upload);
+    }
+    if options.source_after_success != SourceAfterSuccess::Keep {
+        steps.push(Step::HandleSource);
+    }
+    steps
+}
+
+// 成功转换后源文件移入的文件夹，位于源文件所在的文件夹中
+const PROCESSED_FOLDER: &str = "processed";
+
+// 两次处理同一文件之间的等待时间
+const FILE_RETRY_DELAY: Duration = Duration::from_secs(15);
+
+// 失败能否通过重新处理解决
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+enum Failure {
+    // 工具异常退出、读写错误：网络共享短暂断开、文件被占用等，稍后重试可能恢复
+    Transient,
+    // 找不到工具、选项错误、输出与源文件冲突、磁盘空间不足、校验发现不一致：重试结果相同
+    Permanent,
+}
+
+impl Failure {
+    // 开始执行步骤之前的失败（选项、冲突、磁盘空间）都是 Permanent。
+    // Finalize 之后输出已经重命名为最终文件，重新处理会重新转换或按已有输出的设置跳过、
+    // 改名，因此之后的步骤失败也不重新处理整个文件；上传在步骤内部自行重试
+    fn of_step(step: Step, error: &str) -> Self {
+        match step {
+            Step::VerifySource | Step::VerifyAudio | Step::VerifyDvConfig | Step::VerifyOutput => {
+                Failure::Permanent
+            }
+            Step::ExportPoster
+            | Step::AnnotateSource
+            | Step::Upload
+            | Step::PostCommand
+            | Step::HandleSource => Failure::Permanent,
+            _ if command_line::is_not_found(error) => Failure::Permanent,
+            _ => Failure::Transient,
+        }
+    }
+}
+
+// 处理失败时按设置的次数重新处理整个文件（失败的中间文件已经清理），网络共享短暂断开、
+// 文件被占用等临时故障通常在稍后重试时恢复；重试不会改变结果的失败直接返回。
+// 返回的日志包含各次失败的说明和最后一次尝试的日志
+pub async fn process_file_with_retries(
+    source: Source,
+    output_folder: PathBuf,
+    options: ProcessOptions,
+    sender: &mut mpsc::Sender<Message>,
+    tag: Option<String>,
+) -> (Result<Vec<PathBuf>, String>, Vec<String>) {
+    let logs = Logs::tagged(sender, tag.clone());
+    retry_transient(options.retry_count, FILE_RETRY_DELAY, logs, || {
+        let (source, output_folder, options, tag) = (
+            source.clone(),
+            output_folder.clone(),
+            options.clone(),
+            tag.clone(),
+        );
+        let mut sender = sender.clone();
+        async move { attempt_file(source, output_folder, options, &mut sender, tag).await }
+    })
+    .await
+}
+
+// 依次尝试，临时故障最多重试 retry_count 次，每次之前等待 delay
+async fn retry_transient<T, F, Fut>(
+    retry_count: u32,
+    delay: Duration,
+    mut logs: Logs,
+    mut attempt: F,
+) -> (Result<T, String>, Vec<String>)
+where
+    F: FnMut() -> Fut,
+    Fut: std::future::Future<Output = (Result<T, (String, Failure)>, Vec<String>)>,
+{
+    let mut number = 1;
+    loop {
+        let (result, mut lines) = attempt().await;
+        match result {
+            Err((e, Failure::Transient)) if number <= retry_count => {
+                logs.push(format!(
+                    "Attempt {number} failed: {e}. Retrying in {}s (retry {number}/{retry_count})",
+                    delay.as_secs()
+                ));
+                tokio::time::sleep(delay).await;
+                number += 1;
+            }
+            result => {
+                if let Err((_, Failure::Permanent)) = &result
+                    && number <= retry_count
+                {
+                    logs.push("Not retrying: the same error would happen again".to_string());
+                }
+                let mut retries = logs.into_lines();
+                retries.append(&mut lines);
+                return (result.map_err(|(e, _)| e), retries);
+            }
+        }
+    }
+}
+
+// 处理单个文件：分析源文件、组合步骤并依次执行，成功时返回写入的输出文件。
+// 同时处理多个文件时用 tag（文件名）标记终端中的每一行
+pub async fn process_file(
+    source: Source,
+    output_folder: PathBuf,
+    options: ProcessOptions,
+    sender: &mut mpsc::Sender<Message>,
+    tag: Option<String>,
+) -> (Result<Vec<PathBuf>, String>, Vec<String>) {
+    let (result, lines) = attempt_file(source, output_folder, options, sender, tag).await;
+    (result.map_err(|(e, _)| e), lines)
+}
+
+// 处理一次，失败时同时返回能否通过重试解决
+async fn attempt_file(
+    source: Source,
+    output_folder: PathBuf,
+    options: ProcessOptions,
+    sender: &mut mpsc::Sender<Message>,
+    tag: Option<String>,
+) -> (Result<Vec<PathBuf>, (String, Failure)>, Vec<String>) {
+    let Source {
+        path: input_file,
+        active_area,
+        version,
+        trim,
+        audio_tracks,
+        subtitle_tracks,
+        external_subtitles,
+        audio_offset,
+        subtitle_offset,
+        parts,
+    } = source.clone();
+    let mut input_stem = input_stem(&input_file, &parts, version);
+    let mut all_logs = Logs::tagged(sender, tag);
+
+    // 输出文件不能覆盖源文件
+    let tag = options.sample_entry.tag();
+    if let Some(collision) = [
+        format!("_{tag}.mp4"),
+        format!("_{tag}_with_subs.mp4"),
+        "_remux.mp4".to_string(),
+        "_av1dv.mp4".to_string(),
+        "_hdr10.mp4".to_string(),
+        "_hlg.mp4".to_string(),
+    ]
+    .into_iter()
+    .map(|suffix| output_folder.join(format!("{input_stem}{suffix}")))
+    .find(|output| paths_collide(&input_file, output))
+    {
+        return (
+            Err((
+                format!(
+                    "Output path {} is the same as the source file",
+                    collision.to_string_lossy()
+                ),
+                Failure::Permanent,
+            )),
+            all_logs.into_lines(),
+        );
+    }
+
+    // 分析源文件：时长用于计算 ffmpeg 步骤的进度，HDR 元数据用于写入输出
+    let path = input_file.clone();
+    let (media_info, probed) = match tokio::task::spawn_blocking(move || probe::probe_media(&path))
+        .await
+        .map_err(|e| e.to_string())
+        .and_then(|result| result)
+    {
+        Ok(info) => (info, true),
+        Err(e) => {
+            all_logs.push(format!("Could not probe source: {e}"));
+            (MediaInfo::default(), false)
+        }
+    };
+    match media_info.duration {
+        Some(seconds) => all_logs.push(format!("Source duration: {seconds:.1}s")),
+        None => all_logs.push("Could not probe source duration, progress unavailable".to_string()),
+    }
+    if !parts.is_empty() {
+        all_logs.push(format!(
+            "Multi-part source: joining {} parts into {input_stem}",
+            parts.len() + 1
+        ));
+    }
+
+    if let Some(video) = &media_info.video
+        && let Some(fps) = video.frame_rate
+        && (video.pulldown || !options.frame_rate.matches(fps))
+    {
+        all_logs.push(format!(
+            "Warning: muxing at {} but source is {fps:.3} fps{}, audio may drift out of sync",
+            options.frame_rate,
+            if video.pulldown { " (pulldown)" } else { "" }
+        ));
+    }
+
+    // 没有杜比视界元数据时不能走 DV 流程，否则会输出错误标记的 dvh1 文件
+    let dolby_vision = !probed || media_info.has_dolby_vision();
+    if !dolby_vision {
+        all_logs.push("No Dolby Vision metadata found in source".to_string());
+        match options.non_dv_mode {
+            NonDvMode::Skip => {
+                all_logs.push("Skipping file (Non-DV Files: Skip)".to_string());
+                return (Ok(Vec::new()), all_logs.into_lines());
+            }
+            NonDvMode::Abort => {
+                return (
+                    Err((
+                        format!(
+                            "{} has no Dolby Vision metadata",
+                            input_file.file_name().unwrap_or_default().to_string_lossy()
+                        ),
+                        Failure::Permanent,
+                    )),
+                    all_logs.into_lines(),
+                );
+            }
+            NonDvMode::Remux => {}
+        }
+    }
+
+    let mut options = options;
+    // 音乐视频和测试片源可能只有视频，没有可提取的音频时直接输出只有视频的 MP4
+    if probed && media_info.audio.is_empty() && !options.video_only {
+        all_logs.push("Source has no audio tracks, muxing a video-only MP4".to_string());
+        options.video_only = true;
+    }
+    let frame_rate = options.frame_rate_for(&media_info);
+    if frame_rate != options.frame_rate {
+        all_logs.push(format!(
+            "HLG Dolby Vision source: muxing at the source frame rate {frame_rate}"
+        ));
+        options.frame_rate = frame_rate;
+    }
+
+    let audio_chosen = !audio_tracks.is_empty();
+    let selected_subtitles = subtitle_tracks;
+    let mut subtitle_tracks = match &selected_subtitles {
+        // 没有探测到字幕信息时按编号提取手动选择的轨道，或按原来的方式提取第一条字幕
+        Some(tracks) if !probed => tracks
+            .iter()
+            .map(|&index| SubtitleStream {
+                index,
+                ..SubtitleStream::default()
+            })
+            .collect(),
+        None if !probed && options.subtitle_mode != SubtitleMode::None => {
+            vec![SubtitleStream::default()]
+        }
+        selected => options
+            .subtitle_tracks(&media_info, &audio_tracks, selected.as_deref())
+            .into_iter()
+            .cloned()
+            .collect::<Vec<_>>(),
+    };
+    let external_subtitles = external_subtitles
+        .into_iter()
+        .map(|path| {
+            let subtitle = external_subtitle(&path);
+            all_logs.push(format!(
+                "External subtitle: {} [{}]",
+                path.to_string_lossy(),
+                subtitle.language.as_deref().unwrap_or("und")
+            ));
+            (path, subtitle)
+        })
+        .collect::<Vec<_>>();
+    // 字幕步骤按实际要处理的字幕决定：手动选择的轨道和外部字幕可能与字幕模式不一致
+    if selected_subtitles.is_some() || !external_subtitles.is_empty() {
+        options.subtitle_mode = match options.subtitle_mode {
+            _ if subtitle_tracks.is_empty() && external_subtitles.is_empty() => SubtitleMode::None,
+            SubtitleMode::None => SubtitleMode::All,
+            mode => mode,
+        };
+    }
+    let mut audio_tracks = media_info
+        .audio_tracks(&audio_tracks, options.auto_audio())
+        .into_iter()
+        .cloned()
+        .collect::<Vec<_>>();
+    if options.auto_audio() == AutoAudio::AllExceptCommentary && !audio_chosen {
+        for audio in media_info
+            .audio
+            .iter()
+            .filter(|audio| !audio_tracks.contains(audio))
+        {
+            all_logs.push(format!("Skipping commentary audio track: {audio}"));
+        }
+    }
+    if options.video_only {
+        all_logs.push("Video only: audio tracks are not included".to_string());
+        audio_tracks.clear();
+    }
+    if options.truehd_ac3_core {
+        for audio in audio_tracks
+            .iter_mut()
+            .filter(|audio| audio.codec == "truehd")
+        {
+            match media_info.ac3_core(audio) {
+                Some(core) => {
+                    all_logs.push(format!(
+                        "Using AC-3 core track #{} instead of TrueHD track #{}",
+                        core.index + 1,
+                        audio.index + 1
+                    ));
+                    *audio = core.clone();
+                }
+                None => all_logs.push(format!(
+                    "No AC-3 core track found for TrueHD track #{}",
+                    audio.index + 1
+                )),
+            }
+        }
+        // AC-3 核心可能本来就在输出轨道中
+        audio_tracks.sort_by_key(|audio| audio.index);
+        audio_tracks.dedup_by_key(|audio| audio.index);
+    }
+    // 响度标准化需要重新编码，原样复制的音轨保持不变
+    if let Some(target) = loudness_target(&options) {
+        let transcoded = audio_tracks
+            .iter()
+            .filter(|audio| AudioConversion::for_track(&options, audio) != AudioConversion::Copy)
+            .count()
+            + usize::from(
+                options.aac_stereo
+                    && audio_tracks
+                        .first()
+                        .is_some_and(|audio| audio.codec != "aac" || audio.channels > 2),
+            );
+        all_logs.push(if transcoded == 0 {
+            "Loudness normalization: no audio track is transcoded, copied tracks keep their original loudness".to_string()
+        } else {
+            format!(
+                "Loudness normalization: {transcoded} transcoded track(s) normalized to {target} LUFS (EBU R128)"
+            )
+        });
+    }
+    let steps = plan(
+        &options,
+        &media_info,
+        &audio_tracks.iter().collect::<Vec<_>>(),
+        dolby_vision,
+        &source,
+    );
+    // 烧录只使用第一条选择的字幕，图形字幕直接叠加；其余情况 PGS 字幕只能在 OCR 步骤中转换
+    if steps.contains(&Step::BurnSubtitles) {
+        subtitle_tracks.truncate(1);
+    } else if probed && !steps.contains(&Step::OcrSubtitles) {
+        subtitle_tracks.retain(SubtitleStream::is_text);
+    }
+    all_logs.push(format!(
+        "Pipeline: {}",
+        steps
+            .iter()
+            .map(ToString::to_string)
+            .collect::<Vec<_>>()
+            .join(" → ")
+    ));
+    if let Some(offset) = subtitle_offset.filter(|offset| *offset != 0)
+        && options.subtitle_mode != SubtitleMode::None
+    {
+        all_logs.push(format!("Subtitle offset: {offset:+} ms"));
+    }
+
+    let suffixes = output_suffixes(
+        &steps,
+        &options,
+        &media_info,
+        !external_subtitles.is_empty(),
+    );
+    let existing = |stem: &str| {
+        suffixes
+            .iter()
+            .map(|suffix| output_folder.join(format!("{stem}{suffix}")))
+            .find(|output| output.exists())
+    };
+    if let Some(output) = existing(&input_stem) {
+        let name = output.file_name().unwrap_or_default().to_string_lossy();
+        match options.existing_outputs {
+            ExistingOutputs::Overwrite => {
+                all_logs.push(format!("{name} already exists and will be overwritten"));
+            }
+            ExistingOutputs::Skip => {
+                all_logs.push(format!(
+                    "Skipping file: {name} already exists (Existing Outputs: Skip File)"
+                ));
+                return (Ok(Vec::new()), all_logs.into_lines());
+            }
+            // 取第一个所有输出都不存在的编号
+            ExistingOutputs::Rename => {
+                if let Some(stem) = (2..)
+                    .map(|number| format!("{input_stem} ({number})"))
+                    .find(|stem| existing(stem).is_none())
+                {
+                    input_stem = stem;
+                }
+                all_logs.push(format!(
+                    "{name} already exists, writing outputs as {input_stem}*"
+                ));
+            }
+        }
+    }
+
+    let dv_profile = options.dv_profile.resolve(&media_info);
+    let av1 = dolby_vision
+        && media_info
+            .video
+            .as_ref()
+            .is_some_and(|video| video.is_av1());
+    if av1 {
+        if let Some(dv) = media_info
+            .video
+            .as_ref()
+            .and_then(|video| video.dolby_vision.as_ref())
+        {
+            all_logs.push(format!(
+                "Experimental: AV1 Dolby Vision {dv} is passed through with ffmpeg (6.1 or newer), the Dolby Vision profile, sample entry and HDR10 fallback options do not apply"
+            ));
+        }
+    } else if dolby_vision {
+        all_logs.push(format!("Output Dolby Vision profile: {dv_profile}"));
+        match options.mux_extra_args() {
+            Ok(args) if !args.is_empty() => all_logs.push(format!(
+                "mp4muxer extra arguments: {}",
+                command_line::join_args(&args)
+            )),
+            _ => {}
+        }
+    }
+
+    let temp_dir = options.temp_dir();
+    let source_bytes = std::iter::once(&input_file)
+        .chain(&parts)
+        .filter_map(|path| std::fs::metadata(path).ok())
+        .map(|meta| meta.len())
+        .sum();
+    let needed = space_needed(&steps, &options, &media_info, &audio_tracks, source_bytes);
+    if let Err(e) = check_disk_space(&temp_dir, &output_folder, needed, &mut all_logs) {
+        return (Err((e, Failure::Permanent)), all_logs.into_lines());
+    }
+    let temp_stem = temp_stem(&input_stem);
+    let mut job = Job {
+        video_file: temp_dir.join(format!("{temp_stem}_DV.hevc")),
+        audio_files: Vec::new(),
+        aac_file: None,
+        source_file: input_file.clone(),
+        trim,
+        audio_tracks,
+        audio_chosen,
+        audio_offset,
+        subtitle_offset,
+        input_file,
+        active_area,
+        parts,
+        input_stem,
+        temp_stem,
+        output_folder,
+        temp_dir,
+        options,
+        media_info,
+        probed,
+        av1,
+        subtitle_tracks,
+        subtitle_files: Vec::new(),
+        subtitles_chosen: selected_subtitles.is_some(),
+        external_subtitles,
+        trim_start: 0.0,
+        rpu_file: None,
+        hdr10_plus_file: None,
+        dv_profile,
+        mp4_part: None,
+        outputs: Vec::new(),
+        finished: Vec::new(),
+        logs: all_logs,
+        sender,
+    };
+    convert_external_encoding(&mut job).await;
+    convert_external_ass(&mut job);
+
+    let total = steps.len();
+    for (index, step) in steps.into_iter().enumerate() {
+        job.logs
+            .push(format!("Step {}/{total}: {step}...", index + 1));
+        journal::set_step(&job.input_file, &step.to_string());
+        if let Err(e) = run_step(step, &mut job).await {
+            job.cleanup();
+            let failure = Failure::of_step(step, &e);
+            return (Err((e, failure)), job.logs.into_lines());
+        }
+    }
+
+    job.logs.push("Cleaning up temporary files...".to_string());
+    job.cleanup();
+    // 记录到转换历史，供输出文件夹核对时把 MP4 对应回源文件；上传后已删除的本地副本不记录
+    let kept = job
+        .finished
+        .iter()
+        .filter(|output| output.exists())
+        .cloned()
+        .collect::<Vec<_>>();
+    if let Err(e) = history::record(&job.input_file, &kept, job.media_info.duration) {
+        job.logs
+            .push(format!("Warning: failed to record conversion history: {e}"));
+    }
+    job.logs.push("Processing completed!".to_string());
+    (Ok(job.finished), job.logs.into_lines())
+}
+
+async fn run_step(step: Step, job: &mut Job<'_>) -> Result<(), String> {
+    match step {
+        Step::JoinParts => join_parts(job).await,
+        Step::VerifySource => verify_source(job).await,
+        Step::TrimSource => trim_source(job).await,
+        Step::RemuxWithoutDv => remux_without_dv(job).await,
+        Step::MuxAv1 => mux_av1(job).await,
+        Step::ExtractVideo => extract_video(job).await,
+        Step::DemuxEnhancementLayer => demux_enhancement_layer(job).await,
+        Step::ConvertProfile7 => convert_profile_7(job).await,
+        Step::ExtractHdr10Plus => extract_hdr10_plus(job).await,
+        Step::ExtractRpu => extract_rpu(job).await,
+        Step::EditRpu => edit_rpu(job).await,
+        Step::EditActiveArea => edit_active_area(job).await,
+        Step::BurnSubtitles => burn_subtitles(job).await,
+        Step::InjectRpu => inject_rpu(job).await,
+        Step::InjectHdr10Plus => inject_hdr10_plus(job).await,
+        Step::ExtractAudio => extract_audio(job).await,
+        Step::ExtractSubtitles => extract_subtitles(job).await,
+        Step::OcrSubtitles => ocr_subtitles(job).await,
+        Step::Mux => mux(job).await,
+        Step::AddAacStereo => add_aac_stereo(job).await,
+        Step::ApplyAudioDelay => apply_audio_delay(job).await,
+        Step::WriteHdrMetadata => write_hdr_metadata(job).await,
+        Step::WriteHlgColorInfo => write_hlg_color_info(job).await,
+        Step::TagAudioLanguage => tag_audio_language(job).await,
+        Step::VerifyAudio => verify_audio(job).await,
+        Step::VerifyDvConfig => verify_dv_config(job),
+        Step::MergeSubtitles => merge_subtitles(job).await,
+        Step::Hdr10Fallback => hdr10_fallback(job).await,
+        Step::VerifyOutput => verify_output(job).await,
+        Step::Finalize => finalize(job),
+        Step::ExportPoster => export_poster(job).await,
+        Step::AnnotateSource => {
+            let output = job.finished.last().cloned();
+            let tags_file = job.temp_file("_tags.xml");
+            annotate_source(
+                &job.input_file,
+                output.as_deref(),
+                &tags_file,
+                &job.options.tool_verbosity,
+                &mut job.logs,
+            )
+            .await;
+            Ok(())
+        }
+        Step::Upload => upload(job).await,
+        Step::PostCommand => post_command(job).await,
+        Step::HandleSource => handle_source(job).await,
+    }
+}
+
+// 解码视频和音频但不输出，ffmpeg 在 error 级别下报告的任何内容都视为源文件损坏
+async fn verify_source(job: &mut Job<'_>) -> Result<(), String> {
+    let output = execute_ffmpeg_with_progress(
+        &[
+            "-v",
+            "error",
+            "-i",
+            &job.source_file.to_string_lossy(),
+            "-map",
+            "0:v:0",
+            "-map",
+            "0:a?",
+            "-f",
+            "null",
+            "-",
+        ],
+        job.media_info.duration,
+        &job.options.tool_verbosity,
+        &mut job.logs,
+    )
+    .await;
+
+    let out = output?;
+    let stderr = String::from_utf8_lossy(&out.stderr);
+    let errors = stderr
+        .lines()
+        .map(str::trim)
+        .filter(|line| !line.is_empty())
+        .collect::<Vec<_>>();
+
+    if out.status.success() && errors.is_empty() {
+        job.logs.push("✓ No decode errors found".to_string());
+        return Ok(());
+    }
+
+    if out.status.success() {
+        job.logs
+            .extend(errors.iter().map(|line| format!("Error: {line}")));
+    }
+    let summary = errors
+        .first()
+        .copied()
+        .unwrap_or("decoder exited with an error");
+    Err(format!(
+        "Source appears corrupt or truncated ({} decode errors, first: {summary})",
+        errors.len()
+    ))
+}
+
+// AAC 立体声兼容音轨的码率
+const AAC_STEREO_BITRATE: &str = "192k";
+
+// ffmpeg 的 E-AC-3 编码器最多支持 5.1 声道
+const EAC3_MAX_CHANNELS: u32 = 6;
+
+// PCM/FLAC 转码为 AAC 时每个声道的码率（kb/s），立体声与兼容音轨相同
+const AAC_BITRATE_PER_CHANNEL: u32 = 96;
+
+// 响度标准化的真峰值上限（dBTP）和响度范围（LU），使用 EBU R128 的常用值
+const LOUDNORM_TRUE_PEAK: f32 = -1.5;
+const LOUDNORM_RANGE: u32 = 11;
+
+// 一条输出音轨的处理方式
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+enum AudioConversion {
+    Copy,
+    // 有损转码为 E-AC-3（码率 kb/s）；ffmpeg 不能编码 Atmos (JOC) 或 DTS:X，对象信息会丢失
+    Eac3(u32),
+    // 有损转码为 AAC（码率 kb/s），只用于 PCM/FLAC
+    Aac(u32),
+}
+
+impl AudioConversion {
+    // mp4muxer 不能封装 DTS、PCM 和 FLAC，总是转码；大多数播放器不支持 MP4 中的 TrueHD，开启选项时转码
+    fn for_track(options: &ProcessOptions, audio: &AudioStream) -> Self {
+        if audio.is_pcm_or_flac() {
+            match options.lossless_audio {
+                LosslessAudio::Eac3 => AudioConversion::Eac3(options.eac3_bitrate),
+                LosslessAudio::Aac => {
+                    AudioConversion::Aac(AAC_BITRATE_PER_CHANNEL * audio.channels.max(1))
+                }
+            }
+        } else if audio.codec == "dts" || (audio.codec == "truehd" && options.truehd_to_eac3) {
+            AudioConversion::Eac3(options.eac3_bitrate)
+        } else {
+            AudioConversion::Copy
+        }
+    }
+
+    // 输出音轨在 ffprobe 中的编码名称
+    fn codec<'a>(&self, audio: &'a AudioStream) -> &'a str {
+        match self {
+            AudioConversion::Copy => &audio.codec,
+            AudioConversion::Eac3(_) => "eac3",
+            AudioConversion::Aac(_) => "aac",
+        }
+    }
+
+    // 转码的目标，用于日志
+    fn target(&self) -> Option<String> {
+        match self {
+            AudioConversion::Copy => None,
+            AudioConversion::Eac3(bitrate) => Some(format!("E-AC-3 {bitrate} kb/s")),
+            AudioConversion::Aac(bitrate) => Some(format!("AAC {bitrate} kb/s")),
+        }
+    }
+
+    // 提取的临时文件扩展名，ffmpeg 据此选择输出格式；mp4muxer 只接受 AC-3 和 E-AC-3
+    fn extension(&self, audio: &AudioStream) -> &'static str {
+        match self {
+            AudioConversion::Eac3(_) => "ec3",
+            AudioConversion::Aac(_) => "aac",
+            AudioConversion::Copy => match audio.codec.as_str() {
+                "eac3" => "ec3",
+                "ac3" => "ac3",
+                "truehd" => "thd",
+                "dts" => "dts",
+                "aac" => "aac",
+                "flac" => "flac",
+                _ => "mka",
+            },
+        }
+    }
+
+    // stream 为 ffmpeg 的输出流说明符，如 "a" 或 "a:1"；响度标准化只用于转码的音轨
+    fn codec_args(&self, audio: &AudioStream, stream: &str, loudness: Option<i32>) -> Vec<String> {
+        match self {
+            AudioConversion::Copy => vec![format!("-c:{stream}"), "copy".to_string()],
+            AudioConversion::Eac3(bitrate) => {
+                let mut args = vec![
+                    format!("-c:{stream}"),
+                    "eac3".to_string(),
+                    format!("-b:{stream}"),
+                    format!("{bitrate}k"),
+                ];
+                if audio.channels > EAC3_MAX_CHANNELS {
+                    args.extend([format!("-ac:{stream}"), EAC3_MAX_CHANNELS.to_string()]);
+                }
+                args.extend(loudnorm_args(stream, loudness));
+                args
+            }
+            AudioConversion::Aac(bitrate) => {
+                let mut args = vec![
+                    format!("-c:{stream}"),
+                    "aac".to_string(),
+                    format!("-b:{stream}"),
+                    format!("{bitrate}k"),
+                ];
+                args.extend(loudnorm_args(stream, loudness));
+                args
+            }
+        }
+    }
+}
+
+// 选项开启时的目标响度（LUFS）
+fn loudness_target(options: &ProcessOptions) -> Option<i32> {
+    options.loudnorm.then_some(options.loudnorm_target)
+}
+
+// EBU R128 响度标准化（ffmpeg loudnorm 单遍动态模式）；loudnorm 输出 192 kHz，需要重采样回 48 kHz
+fn loudnorm_args(stream: &str, loudness: Option<i32>) -> Vec<String> {
+    let Some(target) = loudness else {
+        return Vec::new();
+    };
+    vec![
+        format!("-filter:{stream}"),
+        format!("loudnorm=I={target}:TP={LOUDNORM_TRUE_PEAK}:LRA={LOUDNORM_RANGE}"),
+        format!("-ar:{stream}"),
+        "48000".to_string(),
+    ]
+}
+
+// 下混为 AAC 立体声的编码参数；stream 为 ffmpeg 的输出流说明符
+fn aac_stereo_args(stream: &str, loudness: Option<i32>) -> Vec<String> {
+    let mut args = vec![
+        format!("-c:{stream}"),
+        "aac".to_string(),
+        format!("-b:{stream}"),
+        AAC_STEREO_BITRATE.to_string(),
+        format!("-ac:{stream}"),
+        "2".to_string(),
+    ];
+    args.extend(loudnorm_args(stream, loudness));
+    args
+}
+
+// ffmpeg 封装时写入的轨道语言和字幕的 forced / default 标记；subtitles 为输出中的字幕轨道数
+fn track_metadata(job: &Job<'_>, subtitles: usize) -> Vec<String> {
+    let mut args = Vec::new();
+    for (index, audio) in job.audio_tracks.iter().enumerate() {
+        if let Some(language) = job.audio_language(audio) {
+            args.extend([
+                format!("-metadata:s:a:{index}"),
+                format!("language={language}"),
+            ]);
+        }
+    }
+    // AAC 立体声兼容音轨在其余音轨之后
+    if let Some(language) = job
+        .stereo_track()
+        .and_then(|audio| job.audio_language(audio))
+    {
+        args.extend([
+            format!("-metadata:s:a:{}", job.audio_tracks.len()),
+            format!("language={language}"),
+        ]);
+    }
+    // 外部字幕在源文件的字幕之后
+    let subtitle_tracks = job
+        .subtitle_tracks
+        .iter()
+        .chain(job.external_subtitles.iter().map(|(_, subtitle)| subtitle));
+    for (index, subtitle) in subtitle_tracks.take(subtitles).enumerate() {
+        if let Some(language) = job.subtitle_language(subtitle) {
+            args.extend([
+                format!("-metadata:s:s:{index}"),
+                format!("language={language}"),
+            ]);
+        }
+        // 没有探测到源文件的字幕信息时保留 ffmpeg 从源文件复制的标记
+        if job.probed || index >= job.subtitle_tracks.len() {
+            args.extend([
+                format!("-disposition:s:{index}"),
+                subtitle_disposition(subtitle).to_string(),
+            ]);
+        }
+    }
+    args
+}
+
+// ffmpeg 的字幕 disposition；mov 封装时 default 决定轨道是否启用
+fn subtitle_disposition(subtitle: &SubtitleStream) -> &'static str {
+    match (subtitle.default, subtitle.forced) {
+        (true, true) => "default+forced",
+        (true, false) => "default",
+        (false, true) => "forced",
+        (false, false) => "0",
+    }
+}
+
+// MP4Box 添加 mov_text 轨道时的选项：强制字幕写入 tx3g 的显示标记（全部 / 部分样本为强制），
+// 播放器据此自动显示外语对白的翻译；源文件标记了默认字幕时，其余字幕轨道不启用
+fn mp4box_subtitle_options(subtitle: &SubtitleStream, any_default: bool) -> String {
+    let mut options = String::new();
+    if subtitle.forced {
+        options.push_str(":txtflags=0xC0000000");
+    }
+    if any_default && !subtitle.default {
+        options.push_str(":disable");
+    }
+    options
+}
+
+// 拼接多段源文件：先检查每段的视频和音频与第 1 段一致，再用 mkvmerge 追加为一个临时 MKV
+async fn join_parts(job: &mut Job<'_>) -> Result<(), String> {
+    for part in job.parts.clone() {
+        let name = part
+            .file_name()
+            .unwrap_or_default()
+            .to_string_lossy()
+            .to_string();
+        let info = tokio::task::spawn_blocking(move || probe::probe_media(&part))
+            .await
+            .map_err(|e| e.to_string())?
+            .map_err(|e| format!("Could not probe {name}: {e}"))?;
+        if let Some(mismatch) = parts::mismatch(&job.media_info, &info) {
+            return Err(format!("Cannot join {name}: {mismatch}"));
+        }
+        job.logs.push(format!(
+            "✓ {name} matches the first part ({:.1}s)",
+            info.duration.unwrap_or(0.0)
+        ));
+    }
+
+    let joined_file = job.temp_file("_joined.mkv");
+    let mut args = vec![
+        "-o".to_string(),
+        joined_file.to_string_lossy().to_string(),
+        job.input_file.to_string_lossy().to_string(),
+    ];
+    for part in &job.parts {
+        args.extend(["+".to_string(), part.to_string_lossy().to_string()]);
+    }
+    let args = args.iter().map(String::as_str).collect::<Vec<_>>();
+    job.logs.progress(0.0);
+    let output = execute_command_with_logging(
+        "mkvmerge",
+        &args,
+        &job.options.tool_verbosity,
+        &mut job.logs,
+    )
+    .await;
+    // mkvmerge 的退出码 1 表示只有警告
+    match output {
+        Ok(out) if out.status.success() || out.status.code() == Some(1) => {}
+        output => {
+            check(output, "Joining parts")?;
+        }
+    }
+
+    let joined = joined_file.clone();
+    let info = tokio::task::spawn_blocking(move || probe::probe_media(&joined))
+        .await
+        .map_err(|e| e.to_string())??;
+    if let Some(duration) = info.duration {
+        job.logs.push(format!("Joined duration: {duration:.1}s"));
+    }
+    job.media_info.duration = info.duration;
+    job.media_info.chapters = info.chapters;
+    job.source_file = joined_file;
+    Ok(())
+}
+
+// 用 ffmpeg 流复制裁剪出临时 MKV，之后的提取步骤都读取它。
+// 流复制只能从关键帧开始，起点前移到最近的关键帧，所有轨道使用同一起点以保持音画同步；
+// RPU 随每一帧一起复制，之后再用 dovi_tool 重新提取和注入
+async fn trim_source(job: &mut Job<'_>) -> Result<(), String> {
+    let Some(trim) = job.trim else {
+        return Ok(());
+    };
+    let start = if trim.start > 0.0 {
+        let input = job.source_file.clone();
+        tokio::task::spawn_blocking(move || probe::keyframe_before(&input, trim.start))
+            .await
+            .map_err(|e| e.to_string())??
+    } else {
+        0.0
+    };
+    if (start - trim.start).abs() > 0.001 {
+        job.logs.push(format!(
+            "Trim start moved from {:.3}s to the keyframe at {start:.3}s",
+            trim.start
+        ));
+    }
+    let trimmed_file = job.temp_file("_trimmed.mkv");
+
+    let start_arg = format!("{start:.3}");
+    let end_arg = trim.end.map(|end| format!("{end:.3}"));
+    let input_arg = job.source_file.to_string_lossy();
+    let trimmed_arg = trimmed_file.to_string_lossy();
+    let mut args = vec!["-ss", &start_arg];
+    if let Some(end_arg) = &end_arg {
+        args.extend(["-to", end_arg]);
+    }
+    args.extend([
+        "-i",
+        &input_arg,
+        "-map",
+        "0",
+        "-c",
+        "copy",
+        "-avoid_negative_ts",
+        "make_zero",
+        "-f",
+        "matroska",
+        &trimmed_arg,
+        "-y",
+    ]);
+
+    let duration = trim.end.or(job.media_info.duration).map(|end| end - start);
+    let output =
+        execute_ffmpeg_with_progress(&args, duration, &job.options.tool_verbosity, &mut job.logs)
+            .await;
+    check(output, "Trimming")?;
+
+    job.media_info.duration = duration;
+    job.source_file = trimmed_file;
+    job.trim_start = start;
+    Ok(())
+}
+
+// 非杜比视界源：直接用 ffmpeg 封装为普通 HDR10/SDR MP4（HEVC 标记为 hvc1，不写入 DV 配置）
+async fn remux_without_dv(job: &mut Job<'_>) -> Result<(), String> {
+    let hevc = job
+        .media_info
+        .video
+        .as_ref()
+        .is_none_or(|video| video.codec == "hevc");
+    let video_args: &[&str] = if hevc { &["-tag:v", "hvc1"] } else { &[] };
+    remux(job, "_remux.mp4", video_args, "Remux").await
+}
+
+// AV1 杜比视界：ffmpeg 只在 -strict unofficial 时写入 dvvC。
+// 没有兼容基础层的 profile 10.0 使用 dav1 sample entry，10.1/10.4 等保留 av01 以兼容普通 AV1 播放器
+async fn mux_av1(job: &mut Job<'_>) -> Result<(), String> {
+    let video_args: &[&str] = if av1_sample_entry(job) == "dav1" {
+        &["-strict", "unofficial", "-tag:v", "dav1"]
+    } else {
+        &["-strict", "unofficial"]
+    };
+    remux(job, "_av1dv.mp4", video_args, "AV1 muxing").await
+}
+
+fn av1_sample_entry(job: &Job<'_>) -> &'static str {
+    let bl_compatible = job
+        .media_info
+        .video
+        .as_ref()
+        .and_then(|video| video.dolby_vision.as_ref())
+        .is_some_and(|dv| dv.bl_compatibility_id != 0);
+    if bl_compatible { "av01" } else { "dav1" }
+}
+
+// 用 ffmpeg 复制视频并封装选择的音轨和文本字幕
+async fn remux(
+    job: &mut Job<'_>,
+    suffix: &str,
+    video_args: &[&str],
+    what: &str,
+) -> Result<(), String> {
+    let output_file = job
+        .output_folder
+        .join(format!("{}{suffix}", job.input_stem));
+    let output_part = job.output_part(&output_file);
+    job.outputs.push((output_part.clone(), output_file));
+    job.mp4_part = Some(output_part.clone());
+
+    let mut audio_maps = job
+        .audio_tracks
+        .iter()
+        .map(|audio| format!("0:a:{}", audio.index))
+        .collect::<Vec<_>>();
+    // ASS/SSA 轨道先转换为干净的 SRT（已包含字幕偏移），转换失败的仍由 ffmpeg 直接转换
+    let ass_tracks = job
+        .subtitle_tracks
+        .iter()
+        .filter(|subtitle| subtitle.is_ass())
+        .cloned()
+        .collect::<Vec<_>>();
+    let ass_tracks = ass_tracks
+        .into_iter()
+        .map(|subtitle| {
+            let file = job.temp_file(&format!("_subs{}.srt", subtitle.index + 1));
+            (subtitle, file)
+        })
+        .collect::<Vec<_>>();
+    let converted = export_ass_tracks(job, &ass_tracks).await;
+    let clean_files = ass_tracks
+        .into_iter()
+        .zip(converted)
+        .filter_map(|((subtitle, file), ok)| ok.then_some((subtitle.index, file)))
+        .collect::<Vec<_>>();
+    let clean_file = |subtitle: &SubtitleStream| {
+        clean_files
+            .iter()
+            .position(|(index, _)| *index == subtitle.index)
+    };
+
+    // 外部字幕作为额外的输入，排在源文件的字幕之后；设置了字幕偏移时源文件再作为一个输入
+    // 移动时间戳，字幕轨道从这个输入读取；转换后的 ASS 字幕排在最后
+    let shifted_input = (job.subtitle_offset.is_some_and(|offset| offset != 0)
+        && job
+            .subtitle_tracks
+            .iter()
+            .any(|subtitle| clean_file(subtitle).is_none()))
+    .then_some(job.external_subtitles.len() + 1);
+    let first_clean_input = job.external_subtitles.len() + 1 + usize::from(shifted_input.is_some());
+    let subtitle_maps = job
+        .subtitle_tracks
+        .iter()
+        .map(|subtitle| match clean_file(subtitle) {
+            Some(number) => format!("{}:s:0", first_clean_input + number),
+            None => format!("{}:s:{}", shifted_input.unwrap_or(0), subtitle.index),
+        })
+        .chain((1..=job.external_subtitles.len()).map(|input| format!("{input}:s:0")))
+        .collect::<Vec<_>>();
+    let mut external_inputs = job
+        .external_subtitles
+        .iter()
+        .flat_map(|(path, _)| job.external_subtitle_input(path))
+        .collect::<Vec<_>>();
+    if shifted_input.is_some() {
+        external_inputs.extend(job.source_subtitle_input());
+    }
+    for (_, file) in &clean_files {
+        external_inputs.extend(["-i".to_string(), file.to_string_lossy().to_string()]);
+    }
+
+    let mut audio_codec_args = job
+        .audio_tracks
+        .iter()
+        .enumerate()
+        .flat_map(|(index, audio)| {
+            AudioConversion::for_track(&job.options, audio).codec_args(
+                audio,
+                &format!("a:{index}"),
+                loudness_target(&job.options),
+            )
+        })
+        .collect::<Vec<_>>();
+    if let Some(audio) = job.stereo_track() {
+        audio_maps.push(format!("0:a:{}", audio.index));
+        audio_codec_args.extend(aac_stereo_args(
+            &format!("a:{}", job.audio_tracks.len()),
+            loudness_target(&job.options),
+        ));
+    }
+
+    let input_arg = job.source_file.to_string_lossy();
+    let output_part_arg = output_part.to_string_lossy();
+    let mut args = vec!["-i", &input_arg];
+    args.extend(external_inputs.iter().map(String::as_str));
+    args.extend(["-map", "0:v:0"]);
+    for audio_map in &audio_maps {
+        args.extend(["-map", audio_map]);
+    }
+    for subtitle_map in &subtitle_maps {
+        args.extend(["-map", subtitle_map]);
+    }
+    if !subtitle_maps.is_empty() {
+        args.extend(["-c:s", "mov_text"]);
+    }
+    args.extend(["-c:v", "copy"]);
+    args.extend(audio_codec_args.iter().map(String::as_str));
+    args.extend(video_args);
+    let language_args = track_metadata(job, subtitle_maps.len());
+    args.extend(language_args.iter().map(String::as_str));
+    if !job.options.major_brand.is_empty() {
+        args.extend(["-brand", &job.options.major_brand]);
+    }
+    // 输出文件名以 .part 结尾，需要显式指定格式
+    args.extend(["-f", "mp4", &output_part_arg, "-y"]);
+
+    let output = execute_ffmpeg_with_progress(
+        &args,
+        job.media_info.duration,
+        &job.options.tool_verbosity,
+        &mut job.logs,
+    )
+    .await;
+    check(output, what).map(|_| ())
+}
+
+async fn extract_video(job: &mut Job<'_>) -> Result<(), String> {
+    job.logs.progress(0.0);
+    let video_file = job.temp_file("_DV.hevc");
+
+    let output = execute_command_with_logging(
+        "mkvextract",
+        &[
+            "tracks",
+            &job.source_file.to_string_lossy(),
+            &format!("0:{}", video_file.to_string_lossy()),
+        ],
+        &job.options.tool_verbosity,
+        &mut job.logs,
+    )
+    .await;
+    check(output, "Video extraction")?;
+    job.video_file = video_file;
+    Ok(())
+}
+
+// 在转换丢弃增强层之前，将 EL 单独保存到输出目录，便于日后重新合成双层文件
+async fn demux_enhancement_layer(job: &mut Job<'_>) -> Result<(), String> {
+    let el_output = job
+        .output_folder
+        .join(format!("{}_EL.hevc", job.input_stem));
+    let el_part = job.output_part(&el_output);
+    job.outputs.push((el_part.clone(), el_output));
+
+    let output = execute_command_with_logging(
+        "dovi_tool",
+        &[
+            "demux",
+            "--el-only",
+            "-e",
+            &el_part.to_string_lossy(),
+            &job.video_file.to_string_lossy(),
+        ],
+        &job.options.tool_verbosity,
+        &mut job.logs,
+    )
+    .await;
+    check(output, "Enhancement layer demux").map(|_| ())
+}
+
+// mp4muxer 无法正确处理 profile 7 的增强层，需要先用 dovi_tool 丢弃 EL 并转换 RPU
+async fn convert_profile_7(job: &mut Job<'_>) -> Result<(), String> {
+    if job.dolby_vision_profile() != Some(7) {
+        return Ok(());
+    }
+    let converted_file = job.temp_file("_DV81.hevc");
+    let converted_arg = converted_file.to_string_lossy().to_string();
+
+    let output = if pipes_profile_7(&job.options, &job.media_info) {
+        let source = job.source_file.to_string_lossy().to_string();
+        execute_pipe_with_logging(
+            (
+                "ffmpeg",
+                &[
+                    "-nostats",
+                    "-i",
+                    &source,
+                    "-map",
+                    "0:v:0",
+                    "-c:v",
+                    "copy",
+                    "-bsf:v",
+                    "hevc_mp4toannexb",
+                    "-f",
+                    "hevc",
+                    "-",
+                ],
+            ),
+            (
+                "dovi_tool",
+                &["-m", "2", "convert", "--discard", "-", "-o", &converted_arg],
+            ),
+            &job.options.tool_verbosity,
+            &mut job.logs,
+        )
+        .await
+    } else {
+        execute_command_with_logging(
+            "dovi_tool",
+            &[
+                "-m",
+                "2",
+                "convert",
+                "--discard",
+                &job.video_file.to_string_lossy(),
+                "-o",
+                &converted_arg,
+            ],
+            &job.options.tool_verbosity,
+            &mut job.logs,
+        )
+        .await
+    };
+    check(output, "Profile 7 to 8.1 conversion")?;
+
+    job.replace_video(converted_file);
+    Ok(())
+}
+
+async fn extract_hdr10_plus(job: &mut Job<'_>) -> Result<(), String> {
+    let metadata_file = job.temp_file("_HDR10Plus.json");
+
+    let output = execute_command_with_logging(
+        "hdr10plus_tool",
+        &[
+            "extract",
+            &job.video_file.to_string_lossy(),
+            "-o",
+            &metadata_file.to_string_lossy(),
+        ],
+        &job.options.tool_verbosity,
+        &mut job.logs,
+    )
+    .await;
+    check(output, "HDR10+ metadata extraction")?;
+    job.hdr10_plus_file = Some(metadata_file);
+    Ok(())
+}
+
+async fn extract_rpu(job: &mut Job<'_>) -> Result<(), String> {
+    let rpu_file = job.temp_file("_RPU.bin");
+
+    let output = execute_command_with_logging(
+        "dovi_tool",
+        &[
+            "extract-rpu",
+            &job.video_file.to_string_lossy(),
+            "-o",
+            &rpu_file.to_string_lossy(),
+        ],
+        &job.options.tool_verbosity,
+        &mut job.logs,
+    )
+    .await;
+    check(output, "RPU extraction")?;
+
+    // 保留一份 RPU，便于在其他工具中查看或编辑
+    if job.options.keep_rpu {
+        let kept = job
+            .output_folder
+            .join(format!("{}_RPU.bin", job.input_stem));
+        match std::fs::copy(&rpu_file, &kept) {
+            Ok(_) => job
+                .logs
+                .push(format!("Saved RPU to {}", kept.to_string_lossy())),
+            Err(e) => job
+                .logs
+                .push(format!("Failed to save RPU, continuing: {e}")),
+        }
+    }
+    job.rpu_file = Some(rpu_file);
+    Ok(())
+}
+
+// 使用 dovi_tool editor 和用户提供的 JSON 配置编辑 RPU
+async fn edit_rpu(job: &mut Job<'_>) -> Result<(), String> {
+    let (Some(rpu_file), Some(config)) =
+        (job.rpu_file.clone(), job.options.rpu_edit_config.clone())
+    else {
+        return Ok(());
+    };
+    let edited_file = job.temp_file("_RPU_edited.bin");
+
+    let output = execute_command_with_logging(
+        "dovi_tool",
+        &[
+            "editor",
+            "-i",
+            &rpu_file.to_string_lossy(),
+            "-j",
+            &config.to_string_lossy(),
+            "-o",
+            &edited_file.to_string_lossy(),
+        ],
+        &job.options.tool_verbosity,
+        &mut job.logs,
+    )
+    .await;
+    check(output, "RPU editing")?;
+    job.rpu_file = Some(edited_file);
+    Ok(())
+}
+
+// 用 dovi_tool editor 把队列中设置的 L5 有效画面区域写入每一帧的 RPU
+async fn edit_active_area(job: &mut Job<'_>) -> Result<(), String> {
+    let (Some(rpu_file), Some(offsets)) = (job.rpu_file.clone(), job.active_area) else {
+        return Ok(());
+    };
+    job.logs.push(format!("L5 active area: {offsets}"));
+    let config = job.temp_file("_L5.json");
+    std::fs::write(&config, offsets.editor_config().to_string())
+        .map_err(|e| format!("Failed to write L5 editor config: {e}"))?;
+    let edited_file = job.temp_file("_RPU_L5.bin");
+
+    let output = execute_command_with_logging(
+        "dovi_tool",
+        &[
+            "editor",
+            "-i",
+            &rpu_file.to_string_lossy(),
+            "-j",
+            &config.to_string_lossy(),
+            "-o",
+            &edited_file.to_string_lossy(),
+        ],
+        &job.options.tool_verbosity,
+        &mut job.logs,
+    )
+    .await;
+    check(output, "L5 active area editing")?;
+    job.rpu_file = Some(edited_file);
+    Ok(())
+}
+
+async fn inject_rpu(job: &mut Job<'_>) -> Result<(), String> {
+    let Some(rpu_file) = job.rpu_file.clone() else {
+        return Ok(());
+    };
+    let injected_file = job.temp_file("_DV_injected.hevc");
+
+    let output = execute_command_with_logging(
+        "dovi_tool",
+        &[
+            "inject-rpu",
+            "-i",
+            &job.video_file.to_string_lossy(),
+            "--rpu-in",
+            &rpu_file.to_string_lossy(),
+            "-o",
+            &injected_file.to_string_lossy(),
+        ],
+        &job.options.tool_verbosity,
+        &mut job.logs,
+    )
+    .await;
+    check(output, "RPU injection")?;
+    job.replace_video(injected_file);
+    Ok(())
+}
+
+// subtitles 滤镜的文件名参数需要两层转义：先按滤镜选项转义 \ ' :，再按滤镜图转义 \ ' [ ] , ;
+fn filter_path(path: &Path) -> String {
+    let escape = |value: &str, special: &str| {
+        value
+            .chars()
+            .flat_map(|c| {
+                let escaped = special.contains(c).then_some('\\');
+                escaped.into_iter().chain(std::iter::once(c))
+            })
+            .collect::<String>()
+    };
+    let value = escape(&path.to_string_lossy(), "\\':");
+    escape(&value, "\\'[],;")
+}
+
+// 把第一条字幕烧录进视频：文本字幕（提取或 OCR 得到的 SRT）用 subtitles 滤镜渲染，
+// 图形字幕从源文件叠加。裸 HEVC 没有时间戳，按设置的帧率读取，逐帧输出保证与 RPU 的帧数一致；
+// 重新编码会丢失 RPU，之后由 inject-rpu 写回。烧录的字幕不再作为软字幕封装
+async fn burn_subtitles(job: &mut Job<'_>) -> Result<(), String> {
+    if job.dolby_vision_profile() == Some(5) {
+        job.logs.push(
+            "Skipping subtitle burn-in: the profile 5 base layer is not in a standard color space, burned subtitles would have wrong colors".to_string(),
+        );
+        return Ok(());
+    }
+    let Some(subtitle) = job.subtitle_tracks.first().cloned() else {
+        job.logs
+            .push("No subtitle track to burn in, keeping the video unchanged".to_string());
+        return Ok(());
+    };
+    job.logs.push(format!(
+        "Warning: burning in subtitle track {subtitle}, the video is re-encoded (lossy)"
+    ));
+    let burned_file = job.temp_file("_burned.hevc");
+
+    let video_arg = job.video_file.to_string_lossy().to_string();
+    let mut args = vec![
+        "-r".to_string(),
+        job.options.frame_rate.to_value(),
+        "-i".to_string(),
+        video_arg,
+    ];
+    match job.subtitle_files.first() {
+        Some(srt_file) => args.extend([
+            "-vf".to_string(),
+            format!("subtitles=filename={}", filter_path(srt_file)),
+        ]),
+        None if subtitle.is_text() => {
+            return Err(
+                "Subtitle burn-in failed: the subtitle track could not be extracted".to_string(),
+            );
+        }
+        None => {
+            args.extend(job.source_subtitle_input());
+            args.extend([
+                "-filter_complex".to_string(),
+                format!("[0:v][1:s:{}]overlay=eof_action=pass[v]", subtitle.index),
+                "-map".to_string(),
+                "[v]".to_string(),
+            ]);
+        }
+    }
+    let video = job.media_info.video.as_ref();
+    let transfer = if video.is_some_and(|video| video.is_hlg()) {
+        "arib-std-b67"
+    } else {
+        "smpte2084"
+    };
+    let mut x265_params = vec![
+        "repeat-headers=1".to_string(),
+        "hdr10-opt=1".to_string(),
+        "colorprim=bt2020".to_string(),
+        format!("transfer={transfer}"),
+        "colormatrix=bt2020nc".to_string(),
+    ];
+    x265_params.extend(
+        video
+            .map(|video| video.hdr.to_x265_params())
+            .unwrap_or_default(),
+    );
+    args.extend(
+        [
+            "-fps_mode",
+            "passthrough",
+            "-c:v",
+            "libx265",
+            "-preset",
+            "slow",
+            "-crf",
+            "18",
+            "-pix_fmt",
+            "yuv420p10le",
+            "-x265-params",
+        ]
+        .map(ToString::to_string),
+    );
+    args.push(x265_params.join(":"));
+    args.extend(["-f", "hevc", &burned_file.to_string_lossy(), "-y"].map(ToString::to_string));
+
+    let args = args.iter().map(String::as_str).collect::<Vec<_>>();
+    let output = execute_ffmpeg_with_progress(
+        &args,
+        job.media_info.duration,
+        &job.options.tool_verbosity,
+        &mut job.logs,
+    )
+    .await;
+    check(output, "Subtitle burn-in")?;
+    job.replace_video(burned_file);
+    // 外部字幕仍作为软字幕封装
+    job.subtitle_tracks.clear();
+    job.subtitle_files.clear();
+    Ok(())
+}
+
+// 重新注入提取的 HDR10+ 元数据，hdr10plus_tool 会替换流中已有的 HDR10+ SEI
+async fn inject_hdr10_plus(job: &mut Job<'_>) -> Result<(), String> {
+    let Some(metadata_file) = job.hdr10_plus_file.clone() else {
+        return Ok(());
+    };
+    let injected_file = job.temp_file("_HDR10Plus_injected.hevc");
+
+    let output = execute_command_with_logging(
+        "hdr10plus_tool",
+        &[
+            "inject",
+            "-i",
+            &job.video_file.to_string_lossy(),
+            "-j",
+            &metadata_file.to_string_lossy(),
+            "-o",
+            &injected_file.to_string_lossy(),
+        ],
+        &job.options.tool_verbosity,
+        &mut job.logs,
+    )
+    .await;
+    check(output, "HDR10+ metadata injection")?;
+    job.logs
+        .push("HDR10+ dynamic metadata preserved".to_string());
+    job.replace_video(injected_file);
+    Ok(())
+}
+
+// 一次读取源文件，把每条输出音轨分别复制为临时文件，保持轨道顺序
+async fn extract_audio(job: &mut Job<'_>) -> Result<(), String> {
+    // 使用手动选择的轨道，否则优先选择 E-AC-3 Atmos (JOC) 轨道；原样复制时 mp4muxer 会写入 JOC 信息
+    let tracks = job.audio_tracks.clone();
+    let how = if job.audio_chosen {
+        "chosen"
+    } else {
+        "automatic"
+    };
+    for audio in &tracks {
+        job.logs
+            .push(format!("Selected audio track ({how}): {audio}"));
+        let conversion = AudioConversion::for_track(&job.options, audio);
+        if let Some(target) = conversion.target() {
+            let objects = audio.is_atmos()
+                || audio
+                    .profile
+                    .as_deref()
+                    .is_some_and(|profile| profile.contains("DTS:X"));
+            job.logs.push(format!(
+                "Warning: transcoding audio track #{} ({}) to {target} (lossy){}{}",
+                audio.index + 1,
+                audio.profile.as_deref().unwrap_or(&audio.codec),
+                if matches!(conversion, AudioConversion::Eac3(_))
+                    && audio.channels > EAC3_MAX_CHANNELS
+                {
+                    ", downmixed to 5.1"
+                } else {
+                    ""
+                },
+                if objects {
+                    ", Atmos/DTS:X objects cannot be re-encoded and will not be preserved"
+                } else {
+                    ""
+                }
+            ));
+        } else if audio.codec == "truehd" {
+            job.logs.push(format!(
+                "Warning: TrueHD track #{} cannot be played from MP4 by most players{}, enable \"Use AC-3 Core for TrueHD\" or \"Transcode TrueHD to E-AC-3\"",
+                audio.index + 1,
+                if audio.is_atmos() {
+                    " and Atmos will not be preserved"
+                } else {
+                    ""
+                }
+            ));
+        }
+    }
+
+    // 没有探测到音轨信息时按原来的方式提取第一条音轨
+    let indexes = if tracks.is_empty() {
+        vec![0]
+    } else {
+        tracks.iter().map(|audio| audio.index).collect()
+    };
+    // 按输出编码命名，避免把 AC-3 或 TrueHD 标记为 .ec3
+    let extensions = indexes
+        .iter()
+        .map(
+            |index| match tracks.iter().find(|audio| audio.index == *index) {
+                Some(audio) => AudioConversion::for_track(&job.options, audio).extension(audio),
+                None => "ec3",
+            },
+        )
+        .collect::<Vec<_>>();
+    let audio_files = extensions
+        .iter()
+        .enumerate()
+        .map(|(number, extension)| {
+            if number == 0 {
+                job.temp_file(&format!("_audio.{extension}"))
+            } else {
+                job.temp_file(&format!("_audio{}.{extension}", number + 1))
+            }
+        })
+        .collect::<Vec<_>>();
+
+    let input_arg = job.source_file.to_string_lossy().to_string();
+    let maps = indexes
+        .iter()
+        .map(|index| format!("0:a:{index}"))
+        .collect::<Vec<_>>();
+    let codec_args = indexes
+        .iter()
+        .map(
+            |index| match tracks.iter().find(|audio| audio.index == *index) {
+                Some(audio) => AudioConversion::for_track(&job.options, audio).codec_args(
+                    audio,
+                    "a",
+                    loudness_target(&job.options),
+                ),
+                None => vec!["-c".to_string(), "copy".to_string()],
+            },
+        )
+        .collect::<Vec<_>>();
+    let outputs = audio_files
+        .iter()
+        .map(|file| file.to_string_lossy().to_string())
+        .collect::<Vec<_>>();
+    let mut args = vec!["-i", input_arg.as_str()];
+    for ((map, codec_args), output) in maps.iter().zip(&codec_args).zip(&outputs) {
+        args.extend(["-map", map]);
+        args.extend(codec_args.iter().map(String::as_str));
+        args.extend([output.as_str(), "-y"]);
+    }
+    // 同一次读取中下混出 AAC 立体声兼容音轨
+    let stereo_index = job.stereo_track().map(|audio| audio.index);
+    let aac_file = stereo_index.map(|_| job.temp_file("_audio_stereo.m4a"));
+    let stereo_map = stereo_index.map(|index| format!("0:a:{index}"));
+    let stereo_args = aac_stereo_args("a", loudness_target(&job.options));
+    let aac_arg = aac_file
+        .as_ref()
+        .map(|file| file.to_string_lossy().to_string());
+    if let (Some(map), Some(aac_arg)) = (&stereo_map, &aac_arg) {
+        args.extend(["-map", map]);
+        args.extend(stereo_args.iter().map(String::as_str));
+        args.extend([aac_arg.as_str(), "-y"]);
+    }
+
+    let output = execute_ffmpeg_with_progress(
+        &args,
+        job.media_info.duration,
+        &job.options.tool_verbosity,
+        &mut job.logs,
+    )
+    .await;
+    check(output, "Audio extraction").map_err(|e| {
+        format!("{e}\nEnable \"Video Only (No Audio)\" to convert the video without audio")
+    })?;
+    if job.options.check_audio {
+        check_extracted_audio(job, &audio_files, aac_file.as_deref()).await?;
+    }
+
+    // 确认提取后的音频仍带有 Atmos 信息
+    for (audio, audio_file) in tracks.iter().zip(&audio_files) {
+        if audio.codec != "eac3" || !audio.is_atmos() {
+            continue;
+        }
+        let file = audio_file.clone();
+        match tokio::task::spawn_blocking(move || probe::probe_media(&file)).await {
+            Ok(Ok(info)) if info.has_atmos() => job.logs.push(format!(
+                "Atmos (E-AC-3 JOC) preserved in extracted audio track #{}",
+                audio.index + 1
+            )),
+            _ => job.logs.push(format!(
+                "Warning: Atmos metadata not detected in extracted audio track #{}, output may be plain EAC3",
+                audio.index + 1
+            )),
+        }
+    }
+    job.audio_files = audio_files;
+    job.aac_file = aac_file;
+    Ok(())
+}
+
+// 源文件的音频流偶尔损坏，复制后得到空文件或只有开头几秒，mux 后成为没有声音的 MP4。
+// 提取后用 ffprobe 统计数据包数和时长，空音轨或明显短于视频时直接失败
+async fn check_extracted_audio(
+    job: &mut Job<'_>,
+    audio_files: &[PathBuf],
+    aac_file: Option<&Path>,
+) -> Result<(), String> {
+    let expected = job.media_info.duration;
+    let mut problems = Vec::new();
+    for file in audio_files.iter().map(PathBuf::as_path).chain(aac_file) {
+        let name = file.file_name().unwrap_or_default().to_string_lossy();
+        let path = file.to_path_buf();
+        let (packets, duration) =
+            tokio::task::spawn_blocking(move || probe::audio_packet_count(&path))
+                .await
+                .map_err(|e| e.to_string())??;
+        match (duration, expected) {
+            _ if packets == 0 => problems.push(format!("{name} contains no audio packets")),
+            // 只看是否明显偏短：拼接的分段和音频偏移都会让时长与视频有出入
+            (Some(duration), Some(expected)) if duration < expected * 0.5 => {
+                problems.push(format!(
+                    "{name} is only {duration:.1}s long ({packets} packets) for {expected:.1}s of video"
+                ));
+            }
+            _ => job.logs.push(format!(
+                "✓ {name}: {packets} packets{}",
+                duration.map_or(String::new(), |duration| format!(", {duration:.1}s"))
+            )),
+        }
+    }
+    if problems.is_empty() {
+        Ok(())
+    } else {
+        Err(format!(
+            "Extracted audio looks broken, the MP4 would be silent:\n{}\nCheck the source audio, choose another track or enable \"Video Only (No Audio)\"",
+            problems.join("\n")
+        ))
+    }
+}
+
+// mp4muxer 不接受 AAC，用 MP4Box 把下混的立体声音轨追加到主输出，语言与第一条音轨相同
+async fn add_aac_stereo(job: &mut Job<'_>) -> Result<(), String> {
+    let Some(aac_file) = job.aac_file.clone() else {
+        if job.options.aac_stereo {
+            job.logs.push(
+                "First audio track is already AAC stereo, no compatibility track needed"
+                    .to_string(),
+            );
+        }
+        return Ok(());
+    };
+    let output_part = job.main_part()?;
+    let language = job
+        .stereo_track()
+        .and_then(|audio| job.audio_language(audio))
+        .map_or(String::new(), |language| format!(":lang={language}"));
+    let add_arg = format!("{}#audio{language}:name=Stereo", aac_file.to_string_lossy());
+    let output_part_arg = output_part.to_string_lossy();
+    let output = execute_command_with_logging(
+        "MP4Box",
+        &["-add", &add_arg, &output_part_arg],
+        &job.options.tool_verbosity,
+        &mut job.logs,
+    )
+    .await;
+    check(output, "Adding AAC stereo track").map(|_| ())
+}
+
+// 用 MP4Box 的编辑列表写入音轨的起始延迟（正数）或跳过开头（负数）。
+// 主输出的轨道 ID：视频为 1，音轨按顺序从 2 开始，AAC 立体声兼容音轨在最后，延迟与第一条音轨相同
+async fn apply_audio_delay(job: &mut Job<'_>) -> Result<(), String> {
+    let output_part = job.main_part()?;
+    let mut delays = job
+        .audio_tracks
+        .iter()
+        .map(|audio| (audio.index, job.audio_delay(audio)))
+        .collect::<Vec<_>>();
+    if job.stereo_track().is_some()
+        && let Some(&(index, delay)) = delays.first()
+    {
+        delays.push((index, delay));
+    }
+
+    let delay_args = delays
+        .iter()
+        .enumerate()
+        .map(|(number, (_, delay))| format!("{}={delay}", number + 2))
+        .collect::<Vec<_>>();
+    for (index, delay) in &delays {
+        job.logs.push(format!(
+            "Audio track #{} delay: {delay:+} ms ({})",
+            index + 1,
+            if job.audio_offset.is_some() {
+                "manual offset"
+            } else {
+                "from source"
+            }
+        ));
+    }
+    let mut args = Vec::new();
+    for delay_arg in &delay_args {
+        args.extend(["-delay", delay_arg.as_str()]);
+    }
+    let output_part_arg = output_part.to_string_lossy();
+    args.push(&output_part_arg);
+    let output =
+        execute_command_with_logging("MP4Box", &args, &job.options.tool_verbosity, &mut job.logs)
+            .await;
+    check(output, "Applying audio delay").map(|_| ())
+}
+
+// 可以作为外部字幕添加的文件
+pub const SUBTITLE_EXTENSIONS: [&str; 2] = ["srt", "ass"];
+
+// 外部字幕文件的格式取扩展名；扩展名前的标记依次为三字母语言代码和 forced / default / sdh
+// （如 Movie.eng.srt、Movie.eng.forced.srt、Movie.eng.sdh.srt）
+pub fn external_subtitle(path: &Path) -> SubtitleStream {
+    let stem = path
+        .file_stem()
+        .unwrap_or_default()
+        .to_string_lossy()
+        .to_lowercase();
+    let (mut language, mut forced, mut default, mut hearing_impaired) = (None, false, false, false);
+    // 第一段是片名，不作为标记
+    for tag in stem
+        .split('.')
+        .skip(1)
+        .collect::<Vec<_>>()
+        .into_iter()
+        .rev()
+    {
+        match tag {
+            "forced" => forced = true,
+            "default" => default = true,
+            "sdh" | "hi" | "cc" => hearing_impaired = true,
+            code if code.len() == 3 && code.chars().all(|c| c.is_ascii_lowercase()) => {
+                language = Some(code.to_string());
+                break;
+            }
+            _ => break,
+        }
+    }
+    SubtitleStream {
+        codec: path
+            .extension()
+            .unwrap_or_default()
+            .to_string_lossy()
+            .to_lowercase(),
+        language,
+        title: path
+            .file_name()
+            .map(|name| name.to_string_lossy().to_string()),
+        forced,
+        default,
+        hearing_impaired,
+        ..SubtitleStream::default()
+    }
+}
+
+// 把导出的 ASS/SSA 字幕转换为干净的 SRT，返回是否成功
+fn convert_ass(job: &mut Job<'_>, ass_file: &Path, srt_file: &Path, what: &str) -> bool {
+    let result = std::fs::read(ass_file)
+        .map_err(|e| e.to_string())
+        .and_then(|bytes| ass::to_srt(&String::from_utf8_lossy(&bytes)))
+        .and_then(|srt| std::fs::write(srt_file, srt).map_err(|e| e.to_string()));
+    match result {
+        Ok(()) => {
+            job.logs.push(format!(
+                "Converted {what} from ASS to SRT (styling and positioning removed)"
+            ));
+            true
+        }
+        Err(e) => {
+            job.logs
+                .push(format!("Converting {what} from ASS failed: {e}"));
+            false
+        }
+    }
+}
+
+// 非 UTF-8 的外部字幕在处理开始时转换为 UTF-8 的临时文件：UTF-16 直接解码，本地编码用 ffmpeg 的
+// -sub_charenc 读取后重新写出。检测或转换失败时保留原文件
+async fn convert_external_encoding(job: &mut Job<'_>) {
+    for number in 0..job.external_subtitles.len() {
+        let (path, subtitle) = job.external_subtitles[number].clone();
+        let Ok(bytes) = std::fs::read(&path) else {
+            continue;
+        };
+        let language = subtitle
+            .language
+            .as_deref()
+            .or(job.options.default_language());
+        let encoding = charset::detect(&bytes, language);
+        if encoding == Encoding::Utf8 {
+            continue;
+        }
+        let name = path
+            .file_name()
+            .unwrap_or_default()
+            .to_string_lossy()
+            .to_string();
+        let extension = if subtitle.is_ass() { "ass" } else { "srt" };
+        let utf8_file = job.temp_file(&format!("_utf8_{}.{extension}", number + 1));
+        let converted = match encoding {
+            Encoding::Utf8 => continue,
+            Encoding::Utf16(text) => std::fs::write(&utf8_file, text)
+                .map(|_| "UTF-16")
+                .map_err(|e| e.to_string()),
+            Encoding::Legacy(charset) => {
+                let input_arg = path.to_string_lossy();
+                let output_arg = utf8_file.to_string_lossy();
+                let args = [
+                    "-sub_charenc",
+                    charset,
+                    "-i",
+                    &input_arg,
+                    "-c:s",
+                    extension,
+                    &output_arg,
+                    "-y",
+                ];
+                let output = execute_ffmpeg_with_progress(
+                    &args,
+                    None,
+                    &job.options.tool_verbosity,
+                    &mut job.logs,
+                )
+                .await;
+                check(output, "ffmpeg").map(|_| charset)
+            }
+        };
+        match converted {
+            Ok(charset) => {
+                job.logs.push(format!(
+                    "Converted external subtitle {name} from {charset} to UTF-8"
+                ));
+                job.external_subtitles[number].0 = utf8_file;
+            }
+            Err(e) => job.logs.push(format!(
+                "Warning: converting external subtitle {name} to UTF-8 failed, using it as is ({e})"
+            )),
+        }
+    }
+}
+
+// 外部 ASS/SSA 字幕在处理开始时转换为 SRT，之后按普通外部字幕封装；转换失败时保留原文件，由 ffmpeg 直接转换
+fn convert_external_ass(job: &mut Job<'_>) {
+    for number in 0..job.external_subtitles.len() {
+        let (path, subtitle) = job.external_subtitles[number].clone();
+        if !subtitle.is_ass() {
+            continue;
+        }
+        let srt_file = job.temp_file(&format!("_external{}.srt", number + 1));
+        let what = format!(
+            "external subtitle {}",
+            path.file_name().unwrap_or_default().to_string_lossy()
+        );
+        if convert_ass(job, &path, &srt_file, &what) {
+            job.external_subtitles[number] = (
+                srt_file,
+                SubtitleStream {
+                    codec: "srt".to_string(),
+                    ..subtitle
+                },
+            );
+        }
+    }
+}
+
+// ffmpeg 把 ASS/SSA 直接转为 SRT 或 mov_text 时会把绘图命令当作文字、丢下定位标牌和特效的残留，
+// 因此先原样导出，再用 ass::to_srt 转换为对应的 SRT 文件。返回每条轨道是否成功
+async fn export_ass_tracks(job: &mut Job<'_>, tracks: &[(SubtitleStream, PathBuf)]) -> Vec<bool> {
+    if tracks.is_empty() {
+        return Vec::new();
+    }
+    let ass_files = tracks
+        .iter()
+        .map(|(subtitle, _)| job.temp_file(&format!("_ass{}.ass", subtitle.index + 1)))
+        .collect::<Vec<_>>();
+    let input_args = job.source_subtitle_input();
+    let maps = tracks
+        .iter()
+        .map(|(subtitle, _)| format!("0:s:{}", subtitle.index))
+        .collect::<Vec<_>>();
+    let outputs = ass_files
+        .iter()
+        .map(|file| file.to_string_lossy().to_string())
+        .collect::<Vec<_>>();
+    let mut args = input_args.iter().map(String::as_str).collect::<Vec<_>>();
+    for (map, output) in maps.iter().zip(&outputs) {
+        args.extend(["-map", map, "-c:s", "copy", output, "-y"]);
+    }
+    let output = execute_ffmpeg_with_progress(
+        &args,
+        job.media_info.duration,
+        &job.options.tool_verbosity,
+        &mut job.logs,
+    )
+    .await;
+    if !matches!(output, Ok(ref out) if out.status.success()) {
+        job.logs
+            .push("Exporting ASS subtitles failed, continuing...".to_string());
+        return vec![false; tracks.len()];
+    }
+
+    let mut converted = Vec::new();
+    for ((subtitle, srt_file), ass_file) in tracks.iter().zip(&ass_files) {
+        let what = format!("subtitle track #{}", subtitle.index + 1);
+        converted.push(convert_ass(job, ass_file, srt_file, &what));
+    }
+    converted
+}
+
+// 只处理文本字幕；图形字幕（PGS/VobSub）无法转换为 mov_text。
+// 一次读取源文件，把每条选择的字幕分别转为 SRT。字幕失败不影响主输出
+async fn extract_subtitles(job: &mut Job<'_>) -> Result<(), String> {
+    // 只有外部字幕时不需要读取源文件
+    if job.subtitle_tracks.is_empty() && !job.external_subtitles.is_empty() {
+        return Ok(());
+    }
+    // 烧录的图形字幕由 ffmpeg 直接从源文件叠加，不需要转换
+    if job.options.burn_subtitles
+        && job
+            .subtitle_tracks
+            .first()
+            .is_some_and(|subtitle| !subtitle.is_text())
+    {
+        return Ok(());
+    }
+    if job.media_info.has_only_image_subtitles() {
+        let codecs = job
+            .media_info
+            .subtitles
+            .iter()
+            .map(|subtitle| subtitle.codec.as_str())
+            .collect::<Vec<_>>()
+            .join(", ");
+        job.logs.push(format!(
+            "Skipping subtitles: only image-based tracks found ({codecs}), which cannot be converted to mov_text"
+        ));
+        return Ok(());
+    }
+    if job.subtitle_tracks.is_empty() {
+        job.logs
+            .push("No subtitle tracks found, skipping subtitles".to_string());
+        return Ok(());
+    }
+
+    let tracks = job.subtitle_tracks.clone();
+    let files = (0..tracks.len())
+        .map(|number| {
+            if number == 0 {
+                job.temp_file("_subs.srt")
+            } else {
+                job.temp_file(&format!("_subs{}.srt", number + 1))
+            }
+        })
+        .collect::<Vec<_>>();
+    if job.probed {
+        for subtitle in &tracks {
+            job.logs
+                .push(format!("Selected subtitle track: {subtitle}"));
+        }
+    }
+    // ASS/SSA 轨道单独导出并转换，其余文本字幕由 ffmpeg 直接转为 SRT
+    let (ass_tracks, other_tracks): (Vec<_>, Vec<_>) = tracks
+        .into_iter()
+        .zip(files)
+        .partition(|(subtitle, _)| subtitle.is_ass());
+    let ass_converted = export_ass_tracks(job, &ass_tracks).await;
+
+    let mut other_extracted = true;
+    if !other_tracks.is_empty() {
+        let input_args = job.source_subtitle_input();
+        let maps = other_tracks
+            .iter()
+            .map(|(subtitle, _)| format!("0:s:{}", subtitle.index))
+            .collect::<Vec<_>>();
+        let outputs = other_tracks
+            .iter()
+            .map(|(_, file)| file.to_string_lossy().to_string())
+            .collect::<Vec<_>>();
+        let mut args = input_args.iter().map(String::as_str).collect::<Vec<_>>();
+        for (map, output) in maps.iter().zip(&outputs) {
+            args.extend(["-map", map, "-c:s", "srt", output, "-y"]);
+        }
+
+        let output = execute_ffmpeg_with_progress(
+            &args,
+            job.media_info.duration,
+            &job.options.tool_verbosity,
+            &mut job.logs,
+        )
+        .await;
+        other_extracted = matches!(output, Ok(ref out) if out.status.success());
+        if !other_extracted {
+            job.logs
+                .push("Subtitle extraction failed, continuing...".to_string());
+        }
+    }
+
+    // 只保留得到 SRT 文件的轨道，按原来的顺序排列；全部失败时后续步骤只封装外部字幕
+    let mut extracted = ass_tracks
+        .into_iter()
+        .zip(ass_converted)
+        .chain(
+            other_tracks
+                .into_iter()
+                .map(|track| (track, other_extracted)),
+        )
+        .filter_map(|(track, ok)| ok.then_some(track))
+        .collect::<Vec<_>>();
+    let order = job
+        .subtitle_tracks
+        .iter()
+        .map(|subtitle| subtitle.index)
+        .collect::<Vec<_>>();
+    extracted.sort_by_key(|(subtitle, _)| order.iter().position(|index| *index == subtitle.index));
+    (job.subtitle_tracks, job.subtitle_files) = extracted.into_iter().unzip();
+    Ok(())
+}
+
+// Tesseract 的语言数据使用 ISO 639-2/T 代码，MKV 中的部分语言标签是 639-2/B 代码；
+// 没有语言标签时按英语识别
+fn tesseract_language(language: Option<&str>) -> String {
+    let language = language.unwrap_or("eng").to_ascii_lowercase();
+    match language.as_str() {
+        "fre" => "fra",
+        "ger" => "deu",
+        "chi" => "chi_sim",
+        "dut" => "nld",
+        "cze" => "ces",
+        "gre" => "ell",
+        "per" => "fas",
+        "rum" => "ron",
+        "slo" => "slk",
+        "ice" => "isl",
+        "alb" => "sqi",
+        "arm" => "hye",
+        "baq" => "eus",
+        "geo" => "kat",
+        "mac" => "mkd",
+        "may" => "msa",
+        "wel" => "cym",
+        "und" | "" => "eng",
+        other => other,
+    }
+    .to_string()
+}
+
+// 源文件只有图形字幕时的 OCR：先用 ffmpeg 一次导出所选 PGS 轨道的 .sup，再逐条用 PgsToSrt
+// （Tesseract）识别为 SRT。识别很慢，按轨道单独上报进度；失败的轨道跳过，不影响主输出
+async fn ocr_subtitles(job: &mut Job<'_>) -> Result<(), String> {
+    if job.subtitle_tracks.is_empty() && !job.external_subtitles.is_empty() {
+        return Ok(());
+    }
+    let tracks = job
+        .subtitle_tracks
+        .iter()
+        .filter(|subtitle| subtitle.is_pgs())
+        .cloned()
+        .collect::<Vec<_>>();
+    if tracks.is_empty() {
+        job.logs.push(
+            "Skipping subtitles: no PGS track to recognize, OCR does not support VobSub"
+                .to_string(),
+        );
+        job.subtitle_tracks.clear();
+        return Ok(());
+    }
+
+    let sup_files = tracks
+        .iter()
+        .map(|subtitle| job.temp_file(&format!("_ocr{}.sup", subtitle.index + 1)))
+        .collect::<Vec<_>>();
+    let input_args = job.source_subtitle_input();
+    let maps = tracks
+        .iter()
+        .map(|subtitle| format!("0:s:{}", subtitle.index))
+        .collect::<Vec<_>>();
+    let outputs = sup_files
+        .iter()
+        .map(|file| file.to_string_lossy().to_string())
+        .collect::<Vec<_>>();
+    let mut args = input_args.iter().map(String::as_str).collect::<Vec<_>>();
+    for (map, output) in maps.iter().zip(&outputs) {
+        args.extend(["-map", map, "-c:s", "copy", output, "-y"]);
+    }
+    let output = execute_ffmpeg_with_progress(
+        &args,
+        job.media_info.duration,
+        &job.options.tool_verbosity,
+        &mut job.logs,
+    )
+    .await;
+    if !matches!(output, Ok(ref out) if out.status.success()) {
+        job.logs
+            .push("Exporting PGS subtitles failed, continuing...".to_string());
+        job.subtitle_tracks.clear();
+        return Ok(());
+    }
+
+    let total = tracks.len();
+    let mut recognized = Vec::new();
+    let mut files = Vec::new();
+    for (number, (subtitle, sup_file)) in tracks.into_iter().zip(&sup_files).enumerate() {
+        let language = tesseract_language(job.subtitle_language(&subtitle).as_deref());
+        let _ = job.sender.try_send(Message::ProcessingStep(format!(
+            "🔤 OCR subtitle {}/{total}: {subtitle} ({language}), this can take several minutes...",
+            number + 1
+        )));
+        job.logs.progress(number as f32 / total as f32);
+        let srt_file = job.temp_file(&format!("_ocr{}.srt", subtitle.index + 1));
+        let result = pgs_to_srt(
+            sup_file,
+            &srt_file,
+            &language,
+            &job.options.tool_verbosity,
+            &mut job.logs,
+        )
+        .await;
+        match result {
+            Ok(()) if srt_file.is_file() => {
+                job.logs
+                    .push(format!("Recognized subtitle track: {subtitle}"));
+                recognized.push(subtitle);
+                files.push(srt_file);
+            }
+            _ => job.logs.push(format!(
+                "OCR of subtitle track #{} failed, skipping it",
+                subtitle.index + 1
+            )),
+        }
+    }
+    job.logs.progress(1.0);
+    job.subtitle_tracks = recognized;
+    job.subtitle_files = files;
+    Ok(())
+}
+
+// 用 PgsToSrt 识别一个 .sup 文件，输出实时写入终端
+async fn pgs_to_srt(
+    sup_file: &Path,
+    srt_file: &Path,
+    language: &str,
+    verbosity: &ToolVerbosity,
+    logs: &mut Logs,
+) -> Result<(), String> {
+    let sup_arg = sup_file.to_string_lossy().to_string();
+    let srt_arg = srt_file.to_string_lossy().to_string();
+    let args = [
+        "--input",
+        sup_arg.as_str(),
+        "--output",
+        srt_arg.as_str(),
+        "--tesseractlanguage",
+        language,
+    ];
+    logs.push(format!("$ {}", command_line::join("PgsToSrt", &args)));
+
+    let mut child = match build_async_command("PgsToSrt", &args)
+        .stdin(Stdio::null())
+        .stdout(Stdio::piped())
+        .stderr(Stdio::piped())
+        .spawn()
+    {
+        Ok(child) => child,
+        Err(e) => {
+            let error = command_line::spawn_error("PgsToSrt", &e);
+            logs.push(format!("Error: {error}"));
+            return Err(error);
+        }
+    };
+    let _running = processes::register(&child);
+
+    // 进度写入 stdout，错误写入 stderr；stderr 单独读取避免阻塞
+    let stderr_task = child.stderr.take().map(|mut stderr| {
+        tokio::spawn(async move {
+            let mut buffer = Vec::new();
+            let _ = stderr.read_to_end(&mut buffer).await;
+            buffer
+        })
+    });
+    if let Some(stdout) = child.stdout.take() {
+        let mut lines = BufReader::new(stdout).lines();
+        while let Ok(Some(line)) = lines.next_line().await {
+            logs.show(format!("[PgsToSrt] {line}"));
+        }
+    }
+    let stderr = match stderr_task {
+        Some(task) => task.await.unwrap_or_default(),
+        None => Vec::new(),
+    };
+
+    match child.wait().await {
+        Ok(status) if status.success() => {
+            if verbosity.get("PgsToSrt") != verbosity::Verbosity::Quiet {
+                logs.push("✓ Command completed successfully".to_string());
+            }
+            Ok(())
+        }
+        Ok(status) => {
+            let message = String::from_utf8_lossy(&stderr).trim().to_string();
+            let error = if message.is_empty() {
+                format!("PgsToSrt exited with {status}")
+            } else {
+                message
+            };
+            logs.push(format!("Error: {error}"));
+            Err(error)
+        }
+        Err(e) => {
+            let error = format!("Failed to execute command PgsToSrt: {e}");
+            logs.push(format!("Error: {error}"));
+            Err(error)
+        }
+    }
+}
+
+// 先写入 .part 文件，校验通过后再重命名，避免媒体库扫描到未写完的文件
+async fn mux(job: &mut Job<'_>) -> Result<(), String> {
+    job.logs.progress(0.0);
+    let output_file = job.output_folder.join(format!(
+        "{}_{}.mp4",
+        job.input_stem,
+        job.options.sample_entry.tag()
+    ));
+    let output_part = job.output_part(&output_file);
+    job.outputs.push((output_part.clone(), output_file));
+    job.mp4_part = Some(output_part.clone());
+
+    let (dv_profile, bl_compatible_id) = job.dv_profile.mux_flags();
+    let output_part_arg = output_part.to_string_lossy();
+    let video_arg = job.video_file.to_string_lossy();
+    let frame_rate = job.options.frame_rate.to_value();
+    let audio_args = job
+        .audio_files
+        .iter()
+        .map(|file| file.to_string_lossy().to_string())
+        .collect::<Vec<_>>();
+    let mut mux_args = vec![
+        "-o",
+        &output_part_arg,
+        "-i",
+        &video_arg,
+        "--input-video-frame-rate",
+        &frame_rate,
+    ];
+    // 每条音轨一个 -i，轨道顺序与输入顺序一致
+    for audio_arg in &audio_args {
+        mux_args.extend(["-i", audio_arg]);
+    }
+    mux_args.extend([
+        "--dv-profile",
+        dv_profile,
+        "--dvh1flag",
+        job.options.sample_entry.mux_flag(),
+    ]);
+    if let Some(id) = bl_compatible_id {
+        mux_args.extend(["--dv-bl-compatible-id", id]);
+    }
+    if !job.options.major_brand.is_empty() {
+        mux_args.extend(["--mpeg4-brand", &job.options.major_brand]);
+    }
+    if !job.options.compatible_brands.is_empty() {
+        mux_args.extend(["--mpeg4-comp-brand", &job.options.compatible_brands]);
+    }
+    let extra_args = job.options.mux_extra_args()?;
+    mux_args.extend(extra_args.iter().map(String::as_str));
+
+    let output = execute_command_with_logging(
+        "mp4muxer",
+        &mux_args,
+        &job.options.tool_verbosity,
+        &mut job.logs,
+    )
+    .await;
+    check(output, "MP4 muxing")?;
+
+    // HDR10 兼容版本还需要视频流和音频，由它在封装后删除
+    if !job.options.hdr10_fallback {
+        job.release_streams();
+    }
+    Ok(())
+}
+
+// 写入 HDR10 静态元数据；失败时继续，输出仍可播放
+async fn write_hdr_metadata(job: &mut Job<'_>) -> Result<(), String> {
+    let Some(hdr) = job
+        .media_info
+        .video
+        .as_ref()
+        .map(|video| video.hdr.clone())
+        .filter(|hdr| !hdr.is_empty())
+    else {
+        return Ok(());
+    };
+    let output_part = job.main_part()?;
+    let hdr_xml = job.temp_file("_hdr.xml");
+    let hdr_output = job.rewrite_part(&output_part, "hdr");
+
+    if let Err(e) = std::fs::write(&hdr_xml, hdr.to_gpac_xml()) {
+        job.logs.push(format!(
+            "Failed to write HDR10 metadata, continuing without it: {e}"
+        ));
+        return Ok(());
+    }
+
+    let output = execute_command_with_logging(
+        "MP4Box",
+        &[
+            "-add",
+            &format!(
+                "{}:hdr={}",
+                output_part.to_string_lossy(),
+                hdr_xml.to_string_lossy()
+            ),
+            "-new",
+            &hdr_output.to_string_lossy(),
+        ],
+        &job.options.tool_verbosity,
+        &mut job.logs,
+    )
+    .await;
+
+    match output {
+        Ok(out) if out.status.success() => {
+            if let Err(e) = std::fs::rename(&hdr_output, &output_part) {
+                job.logs.push(format!(
+                    "Failed to write HDR10 metadata, continuing without it: {e}"
+                ));
+            }
+        }
+        _ => {
+            temp_files::remove(&hdr_output);
+            job.logs
+                .push("Failed to write HDR10 metadata, continuing without it".to_string())
+        }
+    }
+    Ok(())
+}
+
+// mp4muxer 不写入 colr，部分播放器会按 PQ 解释 HLG 基础层；
+// 写入 nclx：BT.2020 原色 (9)、HLG 传输特性 (18)、BT.2020 非恒定亮度矩阵 (9)，有限范围
+async fn write_hlg_color_info(job: &mut Job<'_>) -> Result<(), String> {
+    let output_part = job.main_part()?;
+    let colr_output = job.rewrite_part(&output_part, "colr");
+
+    let output = execute_command_with_logging(
+        "MP4Box",
+        &[
+            "-add",
+            &format!("{}:colr=nclx,9,18,9,0", output_part.to_string_lossy()),
+            "-new",
+            &colr_output.to_string_lossy(),
+        ],
+        &job.options.tool_verbosity,
+        &mut job.logs,
+    )
+    .await;
+
+    match output {
+        Ok(out) if out.status.success() => {
+            if let Err(e) = std::fs::rename(&colr_output, &output_part) {
+                job.logs.push(format!(
+                    "Failed to write HLG color info, continuing without it: {e}"
+                ));
+            }
+        }
+        _ => {
+            temp_files::remove(&colr_output);
+            job.logs
+                .push("Failed to write HLG color info, continuing without it".to_string())
+        }
+    }
+    Ok(())
+}
+
+// mp4muxer 写入的音频语言为 "und"
+async fn tag_audio_language(job: &mut Job<'_>) -> Result<(), String> {
+    // mp4muxer 按输入顺序分配轨道 ID：视频为 1，音频从 2 开始
+    let languages = job
+        .audio_tracks
+        .iter()
+        .enumerate()
+        .filter_map(|(number, audio)| {
+            Some(format!("{}={}", number + 2, job.audio_language(audio)?))
+        })
+        .collect::<Vec<_>>();
+    if languages.is_empty() {
+        return Ok(());
+    }
+    let output_part = job.main_part()?;
+    job.logs.push(format!(
+        "Setting audio language to {}...",
+        languages.join(", ")
+    ));
+
+    let output_part_arg = output_part.to_string_lossy();
+    let mut args = Vec::new();
+    for language in &languages {
+        args.extend(["-lang", language.as_str()]);
+    }
+    args.push(&output_part_arg);
+    let output =
+        execute_command_with_logging("MP4Box", &args, &job.options.tool_verbosity, &mut job.logs)
+            .await;
+
+    if !matches!(output, Ok(ref out) if out.status.success()) {
+        job.logs
+            .push("Failed to set audio language, continuing...".to_string());
+    }
+    Ok(())
+}
+
+// 用 ffmpeg 的 framemd5 复制一条音轨并输出每个数据包的大小和 MD5，
+// 返回 "大小,MD5" 列表和按包时长累计的总时长（秒）
+async fn audio_packets(file: &Path, stream: &str) -> Result<(Vec<String>, f64), String> {
+    let input = file.to_string_lossy();
+    let child = build_async_command(
+        "ffmpeg",
+        &[
+            "-v", "error", "-i", &input, "-map", stream, "-c", "copy", "-f", "framemd5", "-",
+        ],
+    )
+    .stdin(Stdio::null())
+    .stdout(Stdio::piped())
+    .stderr(Stdio::piped())
+    .spawn()
+    .map_err(|e| command_line::spawn_error("ffmpeg", &e))?;
+    let _running = processes::register(&child);
+    let output = child
+        .wait_with_output()
+        .await
+        .map_err(|e| format!("Failed to run ffmpeg: {e}"))?;
+    if !output.status.success() {
+        return Err(format!(
+            "Hashing {stream} of {} failed: {}",
+            file.file_name().unwrap_or_default().to_string_lossy(),
+            String::from_utf8_lossy(&output.stderr).trim()
+        ));
+    }
+
+    // 每行为 "流, dts, pts, 时长, 大小, MD5"，时间基在 "#tb 0: 1/48000" 中
+    let mut time_base = 0.0;
+    let mut duration = 0;
+    let mut packets = Vec::new();
+    for line in String::from_utf8_lossy(&output.stdout).lines() {
+        if let Some(tb) = line.strip_prefix("#tb 0:") {
+            if let Some((num, den)) = tb.trim().split_once('/')
+                && let (Ok(num), Ok(den)) = (num.parse::<f64>(), den.parse::<f64>())
+                && den > 0.0
+            {
+                time_base = num / den;
+            }
+        } else if !line.starts_with('#') {
+            let fields = line.split(',').map(str::trim).collect::<Vec<_>>();
+            if let [_, _, _, length, size, hash] = fields.as_slice() {
+                duration += length.parse::<i64>().unwrap_or(0);
+                packets.push(format!("{size},{hash}"));
+            }
+        }
+    }
+    Ok((packets, duration as f64 * time_base))
+}
+
+// 原样复制的音轨逐包比较源文件和输出，发现被改写或截断（如丢失最后几秒）时失败
+async fn verify_audio(job: &mut Job<'_>) -> Result<(), String> {
+    let output_part = job.main_part()?;
+    let mut problems = Vec::new();
+    for (number, audio) in job.audio_tracks.clone().iter().enumerate() {
+        if AudioConversion::for_track(&job.options, audio) != AudioConversion::Copy {
+            continue;
+        }
+        let source_stream = format!("0:a:{}", audio.index);
+        let output_stream = format!("0:a:{number}");
+        job.logs.push(format!(
+            "Comparing audio track #{} with output track {}",
+            audio.index + 1,
+            number + 1
+        ));
+        let (source, output) = tokio::join!(
+            audio_packets(&job.source_file, &source_stream),
+            audio_packets(&output_part, &output_stream)
+        );
+        let ((source, source_duration), (output, output_duration)) = (source?, output?);
+
+        let track = format!("Audio track #{}", audio.index + 1);
+        if source == output {
+            job.logs.push(format!(
+                "✓ {track}: {} packets identical ({output_duration:.1}s)",
+                output.len()
+            ));
+        } else if output.len() < source.len() && source.starts_with(&output) {
+            problems.push(format!(
+                "{track} was truncated: {} of {} packets missing, output ends {:.1}s early",
+                source.len() - output.len(),
+                source.len(),
+                source_duration - output_duration
+            ));
+        } else {
+            let first = source
+                .iter()
+                .zip(&output)
+                .position(|(a, b)| a != b)
+                .unwrap_or(source.len().min(output.len()));
+            problems.push(format!(
+                "{track} was altered by the copy: packet {} of {} differs ({} packets in the output, {output_duration:.1}s vs {source_duration:.1}s)",
+                first + 1,
+                source.len(),
+                output.len()
+            ));
+        }
+    }
+    if problems.is_empty() {
+        Ok(())
+    } else {
+        Err(problems.join("\n"))
+    }
+}
+
+// 检查输出 MP4 的视频 sample entry 和 dvcC/dvvC 是否与设置一致，
+// mp4muxer 在部分输入下会静默输出没有杜比视界配置的文件
+fn verify_dv_config(job: &mut Job<'_>) -> Result<(), String> {
+    let output_part = job.main_part()?;
+    let entries = mp4::video_entries(&output_part)?;
+    let entry = entries.first().ok_or("Muxed MP4 has no video track")?;
+    let Some((config_box, config)) = &entry.dv_config else {
+        return Err(format!(
+            "Muxed MP4 has no Dolby Vision configuration box (sample entry {}){}",
+            entry.format,
+            if job.av1 {
+                ", ffmpeg 6.1 or newer is needed to write dvvC for AV1"
+            } else {
+                ""
+            }
+        ));
+    };
+    job.logs
+        .push(format!("Output {} {config_box}: {config}", entry.format));
+
+    // AV1 原样复制，配置应与源文件一致
+    let source = job
+        .media_info
+        .video
+        .as_ref()
+        .and_then(|video| video.dolby_vision.as_ref());
+    let (expected_format, expected_profile, expected_id) = match source {
+        Some(source) if job.av1 => (
+            av1_sample_entry(job),
+            source.profile,
+            source.bl_compatibility_id,
+        ),
+        _ => {
+            let (profile, bl_compatible_id) = job.dv_profile.mux_flags();
+            (
+                job.options.sample_entry.tag(),
+                profile.parse::<u8>().unwrap_or_default(),
+                bl_compatible_id.map_or(0, |id| id.parse::<u8>().unwrap_or_default()),
+            )
+        }
+    };
+    if entry.format != expected_format {
+        return Err(format!(
+            "Muxed MP4 uses sample entry {} instead of {expected_format}",
+            entry.format
+        ));
+    }
+    if config.profile != expected_profile || config.bl_compatibility_id != expected_id {
+        return Err(format!(
+            "Muxed MP4 has Dolby Vision profile {}.{} instead of {expected_profile}.{expected_id} ({config_box})",
+            config.profile, config.bl_compatibility_id
+        ));
+    }
+    if let Some(level) = job
+        .media_info
+        .video
+        .as_ref()
+        .and_then(|video| video.dolby_vision.as_ref())
+        .map(|dv| dv.level)
+        .filter(|level| *level != 0 && *level != config.level)
+    {
+        job.logs.push(format!(
+            "Warning: output Dolby Vision level {} differs from source level {level}",
+            config.level
+        ));
+    }
+    Ok(())
+}
+
+// 每条字幕转为 mov_text 后用 MP4Box 依次添加，转换失败的字幕跳过
+async fn merge_subtitles(job: &mut Job<'_>) -> Result<(), String> {
+    if job.subtitle_files.is_empty() && job.external_subtitles.is_empty() {
+        return Ok(());
+    }
+    let output_part = job.main_part()?;
+
+    // 提取的字幕在前，外部字幕在后；(ffmpeg 输入参数, 轨道信息, 日志中的名称)
+    let mut sources = job
+        .subtitle_files
+        .iter()
+        .zip(&job.subtitle_tracks)
+        .map(|(file, subtitle)| {
+            (
+                vec!["-i".to_string(), file.to_string_lossy().to_string()],
+                subtitle.clone(),
+                format!("subtitle track #{}", subtitle.index + 1),
+            )
+        })
+        .collect::<Vec<_>>();
+    sources.extend(job.external_subtitles.iter().map(|(path, subtitle)| {
+        (
+            job.external_subtitle_input(path),
+            subtitle.clone(),
+            format!(
+                "external subtitle {}",
+                path.file_name().unwrap_or_default().to_string_lossy()
+            ),
+        )
+    }));
+
+    let any_default = sources.iter().any(|(_, subtitle, _)| subtitle.default);
+    let mut subs_inputs = Vec::new();
+    for (number, (input_args, subtitle, name)) in sources.iter().enumerate() {
+        let subs_mp4 = if number == 0 {
+            job.temp_file("_subs.mp4")
+        } else {
+            job.temp_file(&format!("_subs{}.mp4", number + 1))
+        };
+        let subs_mp4_arg = subs_mp4.to_string_lossy();
+        let mut args = input_args.iter().map(String::as_str).collect::<Vec<_>>();
+        args.extend(["-c:s", "mov_text", &subs_mp4_arg, "-y"]);
+        let output = execute_ffmpeg_with_progress(
+            &args,
+            job.media_info.duration,
+            &job.options.tool_verbosity,
+            &mut job.logs,
+        )
+        .await;
+        if !matches!(output, Ok(ref out) if out.status.success()) {
+            job.logs
+                .push(format!("Converting {name} to mov_text failed, skipping it"));
+            continue;
+        }
+
+        // 带上字幕语言，避免播放器显示 "Unknown"；保留强制和默认字幕的标记
+        let mut subs_input = subs_mp4.to_string_lossy().to_string();
+        if let Some(language) = job.subtitle_language(subtitle) {
+            subs_input.push_str(&format!(":lang={language}"));
+        }
+        subs_input.push_str(&mp4box_subtitle_options(subtitle, any_default));
+        subs_inputs.push(subs_input);
+    }
+    if subs_inputs.is_empty() {
+        return Ok(());
+    }
+
+    let final_output = job.output_folder.join(format!(
+        "{}_{}_with_subs.mp4",
+        job.input_stem,
+        job.options.sample_entry.tag()
+    ));
+    let final_part = job.output_part(&final_output);
+
+    // MP4Box 新建文件时需要重新设置品牌
+    let output_part_arg = output_part.to_string_lossy();
+    let final_part_arg = final_part.to_string_lossy();
+    let mut merge_args = vec!["-add", output_part_arg.as_ref()];
+    for subs_input in &subs_inputs {
+        merge_args.extend(["-add", subs_input]);
+    }
+    if !job.options.major_brand.is_empty() {
+        merge_args.extend(["-brand", &job.options.major_brand]);
+    }
+    for brand in job.options.compatible_brand_list() {
+        merge_args.extend(["-ab", brand]);
+    }
+    merge_args.extend(["-new", &final_part_arg]);
+
+    let output = execute_command_with_logging(
+        "MP4Box",
+        &merge_args,
+        &job.options.tool_verbosity,
+        &mut job.logs,
+    )
+    .await;
+
+    match output {
+        Ok(out) if !out.status.success() => {
+            temp_files::remove(&final_part);
+            Err(format!(
+                "Subtitle merging failed: {}",
+                String::from_utf8_lossy(&out.stderr)
+            ))
+        }
+        Ok(_) if job.options.keep_unsubtitled => {
+            job.outputs.push((final_part, final_output));
+            Ok(())
+        }
+        // 带字幕的 MP4 代替没有字幕的主输出，删除已写入的 .part，磁盘上只保留一份
+        Ok(_) => {
+            match job
+                .outputs
+                .iter_mut()
+                .find(|(part, _)| *part == output_part)
+            {
+                Some(entry) => *entry = (final_part.clone(), final_output),
+                None => job.outputs.push((final_part.clone(), final_output)),
+            }
+            temp_files::remove(&output_part);
+            job.mp4_part = Some(final_part);
+            Ok(())
+        }
+        Err(_) => Ok(()),
+    }
+}
+
+// 另外输出一份去掉杜比视界 RPU 的 HDR10 MP4，供不支持杜比视界的设备播放
+async fn hdr10_fallback(job: &mut Job<'_>) -> Result<(), String> {
+    // profile 5 的基础层不是 HDR10，去掉 RPU 后颜色会错误
+    if job.dolby_vision_profile() == Some(5) {
+        job.logs.push(
+            "Skipping HDR10 fallback: profile 5 has no HDR10-compatible base layer".to_string(),
+        );
+        job.release_streams();
+        return Ok(());
+    }
+    let hdr10_video = job.temp_file("_HDR10.hevc");
+
+    let output = execute_command_with_logging(
+        "dovi_tool",
+        &[
+            "remove",
+            &job.video_file.to_string_lossy(),
+            "-o",
+            &hdr10_video.to_string_lossy(),
+        ],
+        &job.options.tool_verbosity,
+        &mut job.logs,
+    )
+    .await;
+    check(output, "Dolby Vision removal")?;
+
+    // HLG 基础层（profile 8.4）去掉 RPU 后是 HLG 而不是 HDR10
+    let hlg = job
+        .media_info
+        .video
+        .as_ref()
+        .is_some_and(|video| video.is_hlg());
+    let output_file = job.output_folder.join(format!(
+        "{}_{}.mp4",
+        job.input_stem,
+        if hlg { "hlg" } else { "hdr10" }
+    ));
+    let output_part = job.output_part(&output_file);
+
+    // 裸 HEVC 流没有时间戳，需要指定帧率；HDR10 静态元数据保留在码流的 SEI 中
+    let video_arg = hdr10_video.to_string_lossy();
+    let mut audio_args = job
+        .audio_files
+        .iter()
+        .map(|file| file.to_string_lossy().to_string())
+        .collect::<Vec<_>>();
+    let subtitle_args = job
+        .subtitle_files
+        .iter()
+        .map(|subtitle| subtitle.to_string_lossy().to_string())
+        .collect::<Vec<_>>();
+    let output_part_arg = output_part.to_string_lossy();
+    audio_args.extend(
+        job.aac_file
+            .as_ref()
+            .map(|file| file.to_string_lossy().to_string()),
+    );
+    // 提取的音频从 0 开始，用 -itsoffset 恢复延迟；AAC 立体声兼容音轨与第一条音轨相同
+    let offsets = job
+        .audio_tracks
+        .iter()
+        .map(|audio| job.audio_delay(audio))
+        .chain(job.audio_tracks.first().map(|audio| job.audio_delay(audio)))
+        .map(|delay| format!("{}", delay as f64 / 1000.0))
+        .collect::<Vec<_>>();
+    let frame_rate = job.options.frame_rate.to_value();
+    let mut args = vec!["-r", &frame_rate, "-i", &video_arg];
+    for (number, audio_arg) in audio_args.iter().enumerate() {
+        if let Some(offset) = offsets.get(number).filter(|offset| *offset != "0") {
+            args.extend(["-itsoffset", offset]);
+        }
+        args.extend(["-i", audio_arg]);
+    }
+    for subtitle_arg in &subtitle_args {
+        args.extend(["-i", subtitle_arg]);
+    }
+    let external_inputs = job
+        .external_subtitles
+        .iter()
+        .flat_map(|(path, _)| job.external_subtitle_input(path))
+        .collect::<Vec<_>>();
+    args.extend(external_inputs.iter().map(String::as_str));
+    // 输入顺序：视频、各条音轨（AAC 立体声兼容音轨在最后）、各条字幕、外部字幕
+    let audio_maps = (1..=audio_args.len())
+        .map(|input| format!("{input}:a:0"))
+        .collect::<Vec<_>>();
+    let subtitle_maps = (1..=subtitle_args.len() + job.external_subtitles.len())
+        .map(|number| format!("{}:s:0", audio_args.len() + number))
+        .collect::<Vec<_>>();
+    args.extend(["-map", "0:v:0"]);
+    for audio_map in &audio_maps {
+        args.extend(["-map", audio_map]);
+    }
+    for subtitle_map in &subtitle_maps {
+        args.extend(["-map", subtitle_map]);
+    }
+    if !subtitle_maps.is_empty() {
+        args.extend(["-c:s", "mov_text"]);
+    }
+    args.extend(["-c:v", "copy", "-c:a", "copy", "-tag:v", "hvc1"]);
+    let language_args = track_metadata(job, subtitle_maps.len());
+    args.extend(language_args.iter().map(String::as_str));
+    if !job.options.major_brand.is_empty() {
+        args.extend(["-brand", &job.options.major_brand]);
+    }
+    args.extend(["-f", "mp4", &output_part_arg, "-y"]);
+
+    let output = execute_ffmpeg_with_progress(
+        &args,
+        job.media_info.duration,
+        &job.options.tool_verbosity,
+        &mut job.logs,
+    )
+    .await;
+    let result = check(output, "HDR10 fallback muxing");
+    // 失败时也要清理未完成的 .part 文件
+    job.outputs.push((output_part, output_file));
+    job.release_streams();
+    if job.options.low_temp_space {
+        temp_files::remove(&hdr10_video);
+    }
+    result.map(|_| ())
+}
+
+// 输出比预期时长短多少秒以上视为不完整：至少 2 秒，长片按时长的 1%
+fn duration_tolerance(expected: f64) -> f64 {
+    (expected * 0.01).max(2.0)
+}
+
+// 用 ffprobe 检查每个 MP4 输出的时长、轨道数和编码是否符合预期。工具返回成功但输出被截断、
+// 缺少音轨或字幕时，在重命名为最终文件之前把文件标记为失败
+async fn verify_output(job: &mut Job<'_>) -> Result<(), String> {
+    let expected_duration = job.media_info.duration;
+    let expected_video = job
+        .media_info
+        .video
+        .as_ref()
+        .map(|video| video.codec.clone());
+    // 立体声兼容音轨排在最后，只检查选择的音轨；其他编码在 MP4 中的名称不固定，只比较常见的三种
+    let expected_audio = job
+        .audio_tracks
+        .iter()
+        .map(|audio| {
+            AudioConversion::for_track(&job.options, audio)
+                .codec(audio)
+                .to_string()
+        })
+        .collect::<Vec<_>>();
+
+    let mut problems = Vec::new();
+    for (part, output) in job.outputs.clone() {
+        if output
+            .extension()
+            .is_none_or(|extension| extension != "mp4")
+        {
+            continue;
+        }
+        let name = output
+            .file_name()
+            .unwrap_or_default()
+            .to_string_lossy()
+            .to_string();
+        let info = tokio::task::spawn_blocking(move || probe::probe_media(&part))
+            .await
+            .map_err(|e| e.to_string())?
+            .map_err(|e| format!("Could not probe output {name}: {e}"))?;
+
+        match (info.duration, expected_duration) {
+            (Some(actual), Some(expected)) if actual < expected - duration_tolerance(expected) => {
+                problems.push(format!(
+                    "{name} is only {actual:.1}s long, the source is {expected:.1}s"
+                ));
+            }
+            (None, _) => job
+                .logs
+                .push(format!("Warning: could not read the duration of {name}")),
+            _ => {}
+        }
+        match (&info.video, &expected_video) {
+            (None, _) => problems.push(format!("{name} has no video track")),
+            (Some(video), Some(expected)) if video.codec != *expected => problems.push(format!(
+                "{name} has {} video instead of {expected}",
+                video.codec
+            )),
+            _ => {}
+        }
+        if info.audio.len() < expected_audio.len() {
+            problems.push(format!(
+                "{name} has {} audio track(s) instead of {}",
+                info.audio.len(),
+                expected_audio.len()
+            ));
+        }
+        for (number, (actual, expected)) in info.audio.iter().zip(&expected_audio).enumerate() {
+            if ["eac3", "ac3", "aac"].contains(&expected.as_str()) && actual.codec != *expected {
+                problems.push(format!(
+                    "{name} audio track {} is {} instead of {expected}",
+                    number + 1,
+                    actual.codec
+                ));
+            }
+        }
+        if name.ends_with("_with_subs.mp4") && info.subtitles.is_empty() {
+            problems.push(format!("{name} has no subtitle track"));
+        }
+
+        if problems.is_empty() {
+            job.logs.push(format!(
+                "{name}: {} video, {} audio, {} subtitle track(s), {}",
+                info.video
+                    .as_ref()
+                    .map_or("no", |video| video.codec.as_str()),
+                info.audio.len(),
+                info.subtitles.len(),
+                info.duration
+                    .map_or("unknown duration".to_string(), |duration| format!(
+                        "{duration:.1}s"
+                    ))
+            ));
+        }
+    }
+    if problems.is_empty() {
+        Ok(())
+    } else {
+        Err(format!(
+            "Output verification failed: {}",
+            problems.join("; ")
+        ))
+    }
+}
+
+// 校验 .part 文件并移动到最终位置
+fn finalize(job: &mut Job<'_>) -> Result<(), String> {
+    for (part, output) in std::mem::take(&mut job.outputs) {
+        if let Err(e) = finalize_output(&part, &output) {
+            job.outputs.push((part, output));
+            return Err(e);
+        }
+        temp_files::release(&part);
+        job.logs.push(format!("Wrote {}", output.to_string_lossy()));
+        job.finished.push(output);
+    }
+    Ok(())
+}
+
+// 在输出旁边保存一帧色调映射后的 SDR 截图（name-poster.jpg），供 NAS 和文件管理器预览。
+// 截图位置为时长的 20%，跳过片头；失败不影响输出
+async fn export_poster(job: &mut Job<'_>) -> Result<(), String> {
+    let Some(output) = job.finished.first().cloned() else {
+        return Ok(());
+    };
+    let poster = output.with_file_name(format!(
+        "{}-poster.jpg",
+        output.file_stem().unwrap_or_default().to_string_lossy()
+    ));
+    let position = format!("{:.3}", job.media_info.duration.unwrap_or(0.0) * 0.2);
+    // HDR 转 SDR：线性化后用 hable 色调映射到 BT.709
+    let filter = "zscale=t=linear:npl=100,format=gbrpf32le,zscale=p=bt709,\
+                  tonemap=tonemap=hable:desat=0,zscale=t=bt709:m=bt709:r=tv,format=yuv420p";
+
+    let output = execute_ffmpeg_with_progress(
+        &[
+            "-ss",
+            &position,
+            "-i",
+            &job.source_file.to_string_lossy(),
+            "-map",
+            "0:v:0",
+            "-vf",
+            filter,
+            "-frames:v",
+            "1",
+            "-q:v",
+            "2",
+            &poster.to_string_lossy(),
+            "-y",
+        ],
+        None,
+        &job.options.tool_verbosity,
+        &mut job.logs,
+    )
+    .await;
+
+    match output {
+        Ok(out) if out.status.success() => {
+            job.logs.push(format!("Wrote {}", poster.to_string_lossy()))
+        }
+        _ => {
+            let _ = std::fs::remove_file(&poster);
+            job.logs
+                .push("Failed to export poster, continuing...".to_string());
+        }
+    }
+    Ok(())
+}
+
+// 对每个输出运行处理后命令（如触发 Plex 扫描、移动到 NAS）。模板先切分再替换占位符，
+// 路径中的空格和引号不会改变参数。输出已经完成，命令失败只记录警告，不把文件标记为失败
+async fn post_command(job: &mut Job<'_>) -> Result<(), String> {
+    let Ok(Some(template)) = job.options.post_command() else {
+        return Ok(());
+    };
+    let source = job.input_file.to_string_lossy().to_string();
+    for output in job.finished.clone() {
+        let output = output.to_string_lossy().to_string();
+        let args = template
+            .iter()
+            .map(|arg| {
+                arg.replace("{output}", &output)
+                    .replace("{source}", &source)
+            })
+            .collect::<Vec<_>>();
+        let Some((command, args)) = args.split_first() else {
+            continue;
+        };
+        let args = args.iter().map(String::as_str).collect::<Vec<_>>();
+        // 不附加任何工具的详细程度参数，命令按用户填写的原样执行
+        match execute_command_with_logging(command, &args, &ToolVerbosity::default(), &mut job.logs)
+            .await
+        {
+            Ok(out) if out.status.success() => {}
+            Ok(out) => job.logs.push(format!(
+                "Warning: post-processing command exited with {} for {output}",
+                out.status
+            )),
+            Err(e) => job
+                .logs
+                .push(format!("Warning: post-processing command failed: {e}")),
+        }
+    }
+    Ok(())
+}
+
+// 各平台把文件移到回收站的命令：Windows 用 .NET 的 FileSystem.DeleteFile，macOS 让 Finder 删除，
+// Linux 用 GLib 的 gio trash
+fn recycle_command(path: &Path) -> (&'static str, Vec<String>) {
+    let path = path.to_string_lossy();
+    if cfg!(windows) {
+        // PowerShell 的单引号字符串中 ' 需要写两次
+        let script = format!(
+            "Add-Type -AssemblyName Microsoft.VisualBasic; \
+             [Microsoft.VisualBasic.FileIO.FileSystem]::DeleteFile('{}', 'OnlyErrorDialogs', 'SendToRecycleBin')",
+            path.replace('\'', "''")
+        );
+        (
+            "powershell",
+            vec!["-NoProfile".to_string(), "-Command".to_string(), script],
+        )
+    } else if cfg!(target_os = "macos") {
+        let script = format!(
+            "tell application \"Finder\" to delete POSIX file \"{}\"",
+            path.replace('\\', "\\\\").replace('"', "\\\"")
+        );
+        ("osascript", vec!["-e".to_string(), script])
+    } else {
+        ("gio", vec!["trash".to_string(), path.to_string()])
+    }
+}
+
+// 输出不包含源文件的全部内容时的原因：裁剪了时间范围，或只选择了部分轨道。
+// 没有探测到轨道信息时无法确认，手动选择过轨道就按部分输出处理
+fn partial_output(job: &Job<'_>) -> Option<&'static str> {
+    if job.trim.is_some() {
+        return Some("the output is trimmed");
+    }
+    if job.audio_chosen && (!job.probed || job.audio_tracks.len() < job.media_info.audio.len()) {
+        return Some("only some audio tracks were selected");
+    }
+    if job.subtitles_chosen
+        && (!job.probed || job.subtitle_tracks.len() < job.media_info.subtitles.len())
+    {
+        return Some("only some subtitle tracks were selected");
+    }
+    None
+}
+
+// 所有输出校验通过并完成上传和处理后命令之后，按设置保留、移动、回收或删除源文件及其分段。
+// 输出已经完成，处理源文件失败只记录警告
+async fn handle_source(job: &mut Job<'_>) -> Result<(), String> {
+    if job.finished.is_empty() {
+        return Ok(());
+    }
+    // 输出只是源文件的一部分时删除或回收源文件会丢失内容，保留源文件
+    if matches!(
+        job.options.source_after_success,
+        SourceAfterSuccess::Recycle | SourceAfterSuccess::Delete
+    ) && let Some(reason) = partial_output(job)
+    {
+        job.logs.push(format!(
+            "Keeping the source instead of \"{}\": {reason}",
+            job.options.source_after_success
+        ));
+        return Ok(());
+    }
+    let sources = std::iter::once(job.input_file.clone())
+        .chain(job.parts.clone())
+        .collect::<Vec<_>>();
+    for source in sources {
+        let name = source
+            .file_name()
+            .unwrap_or_default()
+            .to_string_lossy()
+            .to_string();
+        let result = match job.options.source_after_success {
+            SourceAfterSuccess::Keep => Ok(()),
+            SourceAfterSuccess::Move => {
+                let folder = source
+                    .parent()
+                    .unwrap_or(Path::new("."))
+                    .join(PROCESSED_FOLDER);
+                let target = folder.join(&name);
+                if target.exists() {
+                    Err(format!("{} already exists", target.to_string_lossy()))
+                } else {
+                    std::fs::create_dir_all(&folder)
+                        .and_then(|_| std::fs::rename(&source, &target))
+                        .map(|_| {
+                            job.logs
+                                .push(format!("Moved source to {}", target.to_string_lossy()))
+                        })
+                        .map_err(|e| e.to_string())
+                }
+            }
+            SourceAfterSuccess::Recycle => {
+                let (command, args) = recycle_command(&source);
+                let args = args.iter().map(String::as_str).collect::<Vec<_>>();
+                match execute_command_with_logging(
+                    command,
+                    &args,
+                    &job.options.tool_verbosity,
+                    &mut job.logs,
+                )
+                .await
+                {
+                    Ok(out) if out.status.success() => {
+                        job.logs.push(format!("Moved {name} to the recycle bin"));
+                        Ok(())
+                    }
+                    Ok(out) => Err(format!("{command} exited with {}", out.status)),
+                    Err(e) => Err(e),
+                }
+            }
+            SourceAfterSuccess::Delete => std::fs::remove_file(&source)
+                .map(|_| job.logs.push(format!("Deleted source {name}")))
+                .map_err(|e| e.to_string()),
+        };
+        if let Err(e) = result {
+            job.logs
+                .push(format!("Warning: could not tidy up source {name}: {e}"));
+        }
+    }
+    Ok(())
+}
+
+// 上传失败时的尝试次数和两次尝试之间的等待时间
+const UPLOAD_ATTEMPTS: u32 = 3;
+const UPLOAD_RETRY_DELAY: Duration = Duration::from_secs(10);
+
+// URL 路径中的文件名：保留非保留字符，其余按 UTF-8 字节编码
+fn percent_encode(name: &str) -> String {
+    name.bytes()
+        .map(|byte| match byte {
+            b'A'..=b'Z' | b'a'..=b'z' | b'0'..=b'9' | b'-' | b'.' | b'_' | b'~' => {
+                (byte as char).to_string()
+            }
+            _ => format!("%{byte:02X}"),
+        })
+        .collect()
+}
+
+// curl 进度条（--progress-bar）的一帧，例如 "#####      23.4%"
+fn parse_curl_progress(line: &str) -> Option<f32> {
+    let percent = line
+        .split_whitespace()
+        .last()?
+        .strip_suffix('%')?
+        .parse::<f32>()
+        .ok()?;
+    Some((percent / 100.0).clamp(0.0, 1.0))
+}
+
+// 用 curl 上传单个文件，解析 stderr 中的进度条实时上报进度
+async fn curl_upload(
+    file: &Path,
+    url: &str,
+    verbosity: &ToolVerbosity,
+    logs: &mut Logs,
+) -> Result<(), String> {
+    let level = verbosity.get("curl");
+    let file_arg = file.to_string_lossy().to_string();
+    let mut args = verbosity.flags("curl").to_vec();
+    // --ftp-create-dirs 同样适用于 SFTP；密码从 ~/.netrc 读取，不写入设置
+    args.extend_from_slice(&[
+        "--fail",
+        "--show-error",
+        "--progress-bar",
+        "--ftp-create-dirs",
+        "--netrc-optional",
+        "--upload-file",
+        &file_arg,
+        url,
+    ]);
+    logs.push(format!("$ {}", command_line::join("curl", &args)));
+    logs.progress(0.0);
+
+    let mut child = match build_async_command("curl", &args)
+        .stdin(Stdio::null())
+        .stdout(Stdio::null())
+        .stderr(Stdio::piped())
+        .spawn()
+    {
+        Ok(child) => child,
+        Err(e) => {
+            let error = command_line::spawn_error("curl", &e);
+            logs.push(format!("Error: {error}"));
+            return Err(error);
+        }
+    };
+    let _running = processes::register(&child);
+
+    // 进度条用 \r 刷新，按 \r 和 \n 分段
+    let mut messages = Vec::new();
+    if let Some(mut stderr) = child.stderr.take() {
+        let mut buffer = [0u8; 4096];
+        let mut pending = Vec::new();
+        loop {
+            let read = match stderr.read(&mut buffer).await {
+                Ok(0) | Err(_) => break,
+                Ok(read) => read,
+            };
+            pending.extend_from_slice(&buffer[..read]);
+            while let Some(end) = pending.iter().position(|b| *b == b'\r' || *b == b'\n') {
+                let segment = pending.drain(..=end).collect::<Vec<_>>();
+                let line = String::from_utf8_lossy(&segment).trim().to_string();
+                match parse_curl_progress(&line) {
+                    Some(fraction) => {
+                        logs.progress(fraction);
+                    }
+                    None if !line.is_empty() => messages.push(line),
+                    None => {}
+                }
+            }
+        }
+        let line = String::from_utf8_lossy(&pending).trim().to_string();
+        if !line.is_empty() && parse_curl_progress(&line).is_none() {
+            messages.push(line);
+        }
+    }
+
+    match child.wait().await {
+        Ok(status) if status.success() => {
+            logs.progress(1.0);
+            if level == verbosity::Verbosity::Verbose {
+                logs.append(&mut messages);
+            }
+            if level != verbosity::Verbosity::Quiet {
+                logs.push("✓ Command completed successfully".to_string());
+            }
+            Ok(())
+        }
+        Ok(status) => {
+            let error = if messages.is_empty() {
+                format!("curl exited with {status}")
+            } else {
+                messages.join(" ")
+            };
+            logs.push(format!("Error: {error}"));
+            Err(error)
+        }
+        Err(e) => {
+            let error = format!("Failed to execute command curl: {e}");
+            logs.push(format!("Error: {error}"));
+            Err(error)
+        }
+    }
+}
+
+// rclone 单行统计，例如 "NOTICE: 1.2 GiB / 5 GiB, 24%, 50 MiB/s, ETA 1m"
+fn parse_rclone_progress(line: &str) -> Option<f32> {
+    let percent = line
+        .split(',')
+        .map(str::trim)
+        .find_map(|part| part.strip_suffix('%')?.parse::<f32>().ok())?;
+    Some((percent / 100.0).clamp(0.0, 1.0))
+}
+
+// 用 rclone 复制（或移动）单个文件到 remote，传输日志逐行实时发送到终端面板
+async fn rclone_upload(
+    file: &Path,
+    target: &str,
+    move_file: bool,
+    verbosity: &ToolVerbosity,
+    logs: &mut Logs,
+) -> Result<(), String> {
+    let file_arg = file.to_string_lossy().to_string();
+    let mut args = verbosity.flags("rclone").to_vec();
+    args.extend_from_slice(&[
+        if move_file { "moveto" } else { "copyto" },
+        &file_arg,
+        target,
+        "--stats",
+        "2s",
+        "--stats-one-line",
+        "--stats-log-level",
+        "NOTICE",
+    ]);
+    logs.push(format!("$ {}", command_line::join("rclone", &args)));
+    logs.progress(0.0);
+
+    let mut child = match build_async_command("rclone", &args)
+        .stdin(Stdio::null())
+        .stdout(Stdio::piped())
+        .stderr(Stdio::piped())
+        .spawn()
+    {
+        Ok(child) => child,
+        Err(e) => {
+            let error = command_line::spawn_error("rclone", &e);
+            logs.push(format!("Error: {error}"));
+            return Err(error);
+        }
+    };
+    let _running = processes::register(&child);
+
+    // rclone 的日志和统计都写入 stderr；stdout 通常为空，单独读取避免阻塞
+    let stdout_task = child.stdout.take().map(|mut stdout| {
+        tokio::spawn(async move {
+            let mut buffer = Vec::new();
+            let _ = stdout.read_to_end(&mut buffer).await;
+            buffer
+        })
+    });
+    let mut errors = Vec::new();
+    if let Some(stderr) = child.stderr.take() {
+        let mut lines = BufReader::new(stderr).lines();
+        while let Ok(Some(line)) = lines.next_line().await {
+            if let Some(fraction) = parse_rclone_progress(&line) {
+                logs.progress(fraction);
+            }
+            if line.contains("ERROR") {
+                errors.push(line.clone());
+            }
+            logs.show(format!("[rclone] {line}"));
+        }
+    }
+    if let Some(task) = stdout_task {
+        let _ = task.await;
+    }
+
+    match child.wait().await {
+        Ok(status) if status.success() => {
+            logs.progress(1.0);
+            if verbosity.get("rclone") != verbosity::Verbosity::Quiet {
+                logs.push("✓ Command completed successfully".to_string());
+            }
+            Ok(())
+        }
+        Ok(status) => {
+            let error = errors
+                .last()
+                .cloned()
+                .unwrap_or_else(|| format!("rclone exited with {status}"));
+            logs.push(format!("Error: {error}"));
+            Err(error)
+        }
+        Err(e) => {
+            let error = format!("Failed to execute command rclone: {e}");
+            logs.push(format!("Error: {error}"));
+            Err(error)
+        }
+    }
+}
+
+// 把完成的输出上传到 SFTP/SMB 目标（curl）或 rclone remote，每个文件最多尝试 UPLOAD_ATTEMPTS 次；
+// 全部上传成功后按设置删除本地副本（rclone 直接移动），失败时保留本地文件
+async fn upload(job: &mut Job<'_>) -> Result<(), String> {
+    let Some(destination) = job.options.upload_destination().map(ToString::to_string) else {
+        return Ok(());
+    };
+    let backend = job.options.upload_backend;
+    let move_file = backend == UploadBackend::Rclone && job.options.remove_after_upload;
+    let base = if destination.ends_with('/') || destination.ends_with(':') {
+        destination
+    } else {
+        format!("{destination}/")
+    };
+
+    for file in job.finished.clone() {
+        let name = file
+            .file_name()
+            .unwrap_or_default()
+            .to_string_lossy()
+            .to_string();
+        let mut attempt = 1;
+        loop {
+            let result = match backend {
+                UploadBackend::Curl => {
+                    let url = format!("{base}{}", percent_encode(&name));
+                    curl_upload(&file, &url, &job.options.tool_verbosity, &mut job.logs).await
+                }
+                UploadBackend::Rclone => {
+                    let target = format!("{base}{name}");
+                    rclone_upload(
+                        &file,
+                        &target,
+                        move_file,
+                        &job.options.tool_verbosity,
+                        &mut job.logs,
+                    )
+                    .await
+                }
+            };
+            match result {
+                Ok(()) => {
+                    job.logs.push(format!("Uploaded {name}"));
+                    break;
+                }
+                Err(e) if attempt < UPLOAD_ATTEMPTS => {
+                    job.logs.push(format!(
+                        "Upload attempt {attempt}/{UPLOAD_ATTEMPTS} failed: {e}, retrying in {}s...",
+                        UPLOAD_RETRY_DELAY.as_secs()
+                    ));
+                    tokio::time::sleep(UPLOAD_RETRY_DELAY).await;
+                    attempt += 1;
+                }
+                Err(e) => {
+                    return Err(format!(
+                        "Upload of {name} failed after {UPLOAD_ATTEMPTS} attempts: {e}"
+                    ));
+                }
+            }
+        }
+    }
+
+    if move_file {
+        job.logs
+            .push("Local copies were moved to the remote by rclone".to_string());
+    } else if job.options.remove_after_upload {
+        for file in &job.finished {
+            match std::fs::remove_file(file) {
+                Ok(()) => job
+                    .logs
+                    .push(format!("Removed local copy {}", file.to_string_lossy())),
+                Err(e) => job.logs.push(format!(
+                    "Warning: Failed to remove local copy {}: {e}",
+                    file.to_string_lossy()
+                )),
+            }
+        }
+    }
+    Ok(())
+}
+
+// 输出文件写入过程中使用的临时名称，例如 name.mp4.part
+fn part_path(output: &Path) -> PathBuf {
+    let mut name = output.as_os_str().to_owned();
+    name.push(".part");
+    PathBuf::from(name)
+}
+
+// 校验 .part 文件并重命名为最终文件名
+fn finalize_output(part: &Path, output: &Path) -> Result<(), String> {
+    let size = std::fs::metadata(part)
+        .map(|meta| meta.len())
+        .map_err(|e| format!("Output {} is missing: {e}", part.to_string_lossy()))?;
+    if size == 0 {
+        return Err(format!("Output {} is empty", part.to_string_lossy()));
+    }
+    std::fs::rename(part, output).map_err(|e| {
+        format!(
+            "Failed to rename {} to {}: {e}",
+            part.to_string_lossy(),
+            output.to_string_lossy()
+        )
+    })
+}
+
+// 规范化路径；文件尚不存在时规范化其所在目录
+pub fn normalize_path(path: &Path) -> PathBuf {
+    if let Ok(path) = path.canonicalize() {
+        return path;
+    }
+    match (path.parent(), path.file_name()) {
+        (Some(parent), Some(name)) => parent
+            .canonicalize()
+            .map(|parent| parent.join(name))
+            .unwrap_or_else(|_| path.to_path_buf()),
+        _ => path.to_path_buf(),
+    }
+}
+
+// 判断两个路径是否指向同一个文件（Windows 和 macOS 默认不区分大小写）
+fn paths_collide(a: &Path, b: &Path) -> bool {
+    let (a, b) = (normalize_path(a), normalize_path(b));
+    if cfg!(any(windows, target_os = "macos")) {
+        a.to_string_lossy().to_lowercase() == b.to_string_lossy().to_lowercase()
+    } else {
+        a == b
+    }
+}
+
+fn escape_xml(value: &str) -> String {
+    value
+        .replace('&', "&amp;")
+        .replace('<', "&lt;")
+        .replace('>', "&gt;")
+        .replace('"', "&quot;")
+}
+
+// 使用 mkvpropedit 在源 MKV 中写入全局标签，保留已有标签
+async fn annotate_source(
+    input_file: &Path,
+    output: Option<&Path>,
+    tags_file: &Path,
+    verbosity: &ToolVerbosity,
+    logs: &mut Logs,
+) {
+    let output_result = execute_command_with_logging(
+        "mkvextract",
+        &[
+            &input_file.to_string_lossy(),
+            "tags",
+            &tags_file.to_string_lossy(),
+        ],
+        verbosity,
+        logs,
+    )
+    .await;
+
+    // 没有标签时 mkvextract 不会生成内容
+    let existing = match output_result {
+        Ok(out) if out.status.success() => std::fs::read_to_string(tags_file).unwrap_or_default(),
+        _ => String::new(),
+    };
+
+    let note = format!(
+        "converted by Rebottle on {} → {}",
+        format_utc_now(),
+        output.map_or(String::new(), |output| output.to_string_lossy().to_string())
+    );
+    let tag = format!(
+        "  <Tag>\n    <Targets />\n    <Simple>\n      <Name>REBOTTLE</Name>\n      <String>{}</String>\n    </Simple>\n  </Tag>\n",
+        escape_xml(&note)
+    );
+    let tags = match existing.rfind("</Tags>") {
+        Some(end) => format!("{}{tag}{}", &existing[..end], &existing[end..]),
+        None => format!("<?xml version=\"1.0\"?>\n<Tags>\n{tag}</Tags>\n"),
+    };
+
+    if let Err(e) = std::fs::write(tags_file, tags) {
+        logs.push(format!("Failed to annotate source, continuing: {e}"));
+        return;
+    }
+
+    // all: 会替换全部标签，文件中已包含原有标签
+    let output_result = execute_command_with_logging(
+        "mkvpropedit",
+        &[
+            &input_file.to_string_lossy(),
+            "--tags",
+            &format!("all:{}", tags_file.to_string_lossy()),
+        ],
+        verbosity,
+        logs,
+    )
+    .await;
+
+    if matches!(output_result, Ok(ref out) if out.status.success()) {
+        watch::record_own_change(input_file);
+    } else {
+        logs.push("Failed to annotate source, continuing...".to_string());
+    }
+
+    temp_files::remove(tags_file);
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::cell::Cell;
+
+    // 按顺序返回给定结果的尝试，记录被调用的次数
+    async fn run_attempts(
+        retry_count: u32,
+        results: Vec<Result<u32, (String, Failure)>>,
+    ) -> (Result<u32, String>, usize) {
+        let (sender, _receiver) = mpsc::channel(64);
+        let calls = Cell::new(0);
+        let (result, _) = retry_transient(retry_count, Duration::ZERO, Logs::new(&sender), || {
+            let result = results[calls.get().min(results.len() - 1)].clone();
+            calls.set(calls.get() + 1);
+            async move { (result, Vec::new()) }
+        })
+        .await;
+        (result, calls.get())
+    }
+
+    fn permanent(error: &str) -> Result<u32, (String, Failure)> {
+        Err((error.to_string(), Failure::Permanent))
+    }
+
+    fn transient(error: &str) -> Result<u32, (String, Failure)> {
+        Err((error.to_string(), Failure::Transient))
+    }
+
+    #[tokio::test]
+    async fn deterministic_failures_are_not_retried() {
+        for error in [
+            "dovi_tool was not found, install it or add it to PATH",
+            "Output path /media/Movie.mp4 is the same as the source file",
+            "Movie.mkv has no Dolby Vision metadata",
+            "Not enough disk space in Temp folder",
+            "Output duration 5400.0s does not match source 7200.0s",
+        ] {
+            let (result, calls) = run_attempts(3, vec![permanent(error)]).await;
+            assert_eq!(result, Err(error.to_string()));
+            assert_eq!(calls, 1, "{error} was retried");
+        }
+    }
+
+    #[tokio::test]
+    async fn transient_failures_are_retried() {
+        let (result, calls) = run_attempts(
+            3,
+            vec![transient("mp4muxer crashed"), transient("I/O error"), Ok(2)],
+        )
+        .await;
+        assert_eq!(result, Ok(2));
+        assert_eq!(calls, 3);
+
+        let (result, calls) = run_attempts(2, vec![transient("mp4muxer crashed")]).await;
+        assert_eq!(result, Err("mp4muxer crashed".to_string()));
+        assert_eq!(calls, 3);
+
+        // 临时故障之后出现的确定性失败同样不再重试
+        let (_, calls) = run_attempts(
+            3,
+            vec![transient("I/O error"), permanent("Audio check failed")],
+        )
+        .await;
+        assert_eq!(calls, 2);
+    }
+
+    #[tokio::test]
+    async fn failures_after_finalize_do_not_reprocess_the_file() {
+        for step in [
+            Step::ExportPoster,
+            Step::AnnotateSource,
+            Step::Upload,
+            Step::PostCommand,
+            Step::HandleSource,
+        ] {
+            let error = "Upload of Movie_dvh1.mp4 failed after 3 attempts: curl exited with 7";
+            let failure = Failure::of_step(step, error);
+            assert_eq!(failure, Failure::Permanent, "{step:?}");
+            let (result, calls) = run_attempts(3, vec![Err((error.to_string(), failure))]).await;
+            assert_eq!(result, Err(error.to_string()));
+            assert_eq!(calls, 1, "{step:?} reprocessed the file");
+        }
+    }
+
+    #[test]
+    fn failures_are_classified_by_step_and_error() {
+        let missing = std::io::Error::from(std::io::ErrorKind::NotFound);
+        let not_found = command_line::spawn_error("mp4muxer", &missing);
+        assert_eq!(Failure::of_step(Step::Mux, &not_found), Failure::Permanent);
+        assert_eq!(
+            Failure::of_step(
+                Step::ExtractRpu,
+                &format!("RPU extraction failed: {not_found}")
+            ),
+            Failure::Permanent
+        );
+        assert_eq!(
+            Failure::of_step(Step::VerifyOutput, "Output is missing the audio track"),
+            Failure::Permanent
+        );
+        assert_eq!(
+            Failure::of_step(Step::Mux, "Muxing failed: segmentation fault"),
+            Failure::Transient
+        );
+        let denied = std::io::Error::from(std::io::ErrorKind::PermissionDenied);
+        assert_eq!(
+            Failure::of_step(
+                Step::ExtractAudio,
+                &command_line::spawn_error("ffmpeg", &denied)
+            ),
+            Failure::Transient
+        );
+    }
+
+    #[test]
+    fn trim_positions_parse_from_seconds_and_timestamps() {
+        assert_eq!(Trim::parse("", " ", &[]), Ok(None));
+        assert_eq!(
+            Trim::parse("90", "", &[]),
+            Ok(Some(Trim {
+                start: 90.0,
+                end: None
+            }))
+        );
+        assert_eq!(
+            Trim::parse("1:30", "01:00:00.5", &[]),
+            Ok(Some(Trim {
+                start: 90.0,
+                end: Some(3600.5)
+            }))
+        );
+        // 只有终点时从开头裁剪
+        assert_eq!(
+            Trim::parse("", "10", &[]),
+            Ok(Some(Trim {
+                start: 0.0,
+                end: Some(10.0)
+            }))
+        );
+    }
+
+    #[test]
+    fn trim_positions_use_chapter_bounds() {
+        let chapters = [
+            probe::Chapter {
+                start: 0.0,
+                end: 300.0,
+                title: None,
+            },
+            probe::Chapter {
+                start: 300.0,
+                end: 612.5,
+                title: Some("Second".to_string()),
+            },
+        ];
+        assert_eq!(
+            Trim::parse("ch2", "ch2", &chapters),
+            Ok(Some(Trim {
+                start: 300.0,
+                end: Some(612.5)
+            }))
+        );
+        assert_eq!(
+            Trim::parse("Ch1", "", &chapters),
+            Ok(Some(Trim {
+                start: 0.0,
+                end: None
+            }))
+        );
+        for chapter in ["ch0", "ch3", "chx"] {
+            assert!(Trim::parse(chapter, "", &chapters).is_err(), "{chapter}");
+        }
+    }
+
+    #[test]
+    fn invalid_trim_ranges_are_rejected() {
+        for (start, end) in [
+            ("abc", ""),
+            ("-5", ""),
+            ("1::30", ""),
+            ("1:30:", ""),
+            ("", "ten"),
+            // 终点必须在起点之后
+            ("10", "10"),
+            ("1:00", "59.9"),
+        ] {
+            assert!(
+                Trim::parse(start, end, &[]).is_err(),
+                "{start}..{end} was accepted"
+            );
+        }
+        assert!(Trim::parse("10", "10.001", &[]).is_ok());
+    }
+
+    #[test]
+    fn upload_progress_is_parsed_from_tool_output() {
+        assert_eq!(
+            parse_curl_progress("##########            50.0%"),
+            Some(0.5)
+        );
+        assert_eq!(
+            parse_curl_progress("#################### 100.0%"),
+            Some(1.0)
+        );
+        assert_eq!(parse_curl_progress("                      0.0%"), Some(0.0));
+        assert_eq!(parse_curl_progress("150%"), Some(1.0));
+        for line in ["", "#=#=#", "######", "##### abc%", "50.0% done"] {
+            assert_eq!(parse_curl_progress(line), None, "{line}");
+        }
+
+        assert_eq!(
+            parse_rclone_progress("NOTICE: 1.2 GiB / 4.8 GiB, 25%, 50 MiB/s, ETA 1m12s"),
+            Some(0.25)
+        );
+        assert_eq!(
+            parse_rclone_progress("NOTICE: 4.8 GiB / 4.8 GiB, 100%, 50 MiB/s, ETA 0s"),
+            Some(1.0)
+        );
+        for line in [
+            "",
+            "NOTICE: 0 B / 0 B, -, 0 B/s, ETA -",
+            "INFO  : Movie_dvh1.mp4: Copied (new)",
+            "NOTICE: 1 GiB / 2 GiB, half%, 1 MiB/s",
+        ] {
+            assert_eq!(parse_rclone_progress(line), None, "{line}");
+        }
+    }
+}
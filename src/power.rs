@@ -0,0 +1,97 @@
+use crate::execute_command;
+
+// 批处理结束后倒计时的秒数，期间可以取消
+pub const COUNTDOWN_SECONDS: u32 = 60;
+
+// Windows 睡眠：通过 PowerShell 的 P/Invoke 调用 SetSuspendState(FALSE, FALSE, FALSE)。
+// rundll32 powrprof.dll,SetSuspendState 不会解析后面的参数，开启休眠功能时会直接休眠
+const WINDOWS_SLEEP: &str = "Add-Type -Name Power -Namespace Rebottle -MemberDefinition \
+     '[DllImport(\"powrprof.dll\", SetLastError = true)] \
+     public static extern bool SetSuspendState(bool hibernate, bool forceCritical, bool disableWakeEvent);'; \
+     if (-not [Rebottle.Power]::SetSuspendState($false, $false, $false)) { exit 1 }";
+
+// 批处理结束后对电脑执行的操作；只对当前会话有效，不保存，避免忘记后第二天的批处理又关机
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq)]
+pub enum PowerAction {
+    #[default]
+    Nothing,
+    Sleep,
+    Hibernate,
+    Shutdown,
+}
+
+impl PowerAction {
+    // macOS 没有可以直接调用的休眠命令
+    pub fn choices() -> Vec<PowerAction> {
+        [
+            PowerAction::Nothing,
+            PowerAction::Sleep,
+            PowerAction::Hibernate,
+            PowerAction::Shutdown,
+        ]
+        .into_iter()
+        .filter(|action| !(cfg!(target_os = "macos") && *action == PowerAction::Hibernate))
+        .collect()
+    }
+
+    // 倒计时中显示的动词
+    pub fn verb(&self) -> &'static str {
+        match self {
+            PowerAction::Nothing => "Doing nothing",
+            PowerAction::Sleep => "Sleeping",
+            PowerAction::Hibernate => "Hibernating",
+            PowerAction::Shutdown => "Shutting down",
+        }
+    }
+
+    // 各平台的命令：Windows 用 shutdown 和 powrprof 的 SetSuspendState，Linux 用 systemctl，
+    // macOS 用 pmset 睡眠、通过 System Events 关机（不需要管理员权限）
+    fn command(&self) -> Option<(&'static str, &'static [&'static str])> {
+        let command: (&'static str, &'static [&'static str]) = match self {
+            PowerAction::Nothing => return None,
+            PowerAction::Sleep if cfg!(windows) => (
+                "powershell",
+                &["-NoProfile", "-NonInteractive", "-Command", WINDOWS_SLEEP],
+            ),
+            PowerAction::Sleep if cfg!(target_os = "macos") => ("pmset", &["sleepnow"]),
+            PowerAction::Sleep => ("systemctl", &["suspend"]),
+            PowerAction::Hibernate if cfg!(windows) => ("shutdown", &["/h"]),
+            PowerAction::Hibernate => ("systemctl", &["hibernate"]),
+            PowerAction::Shutdown if cfg!(windows) => ("shutdown", &["/s", "/t", "0"]),
+            PowerAction::Shutdown if cfg!(target_os = "macos") => (
+                "osascript",
+                &["-e", "tell application \"System Events\" to shut down"],
+            ),
+            PowerAction::Shutdown => ("systemctl", &["poweroff"]),
+        };
+        Some(command)
+    }
+}
+
+impl std::fmt::Display for PowerAction {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        f.write_str(match self {
+            PowerAction::Nothing => "Do Nothing",
+            PowerAction::Sleep => "Sleep",
+            PowerAction::Hibernate => "Hibernate",
+            PowerAction::Shutdown => "Shut Down",
+        })
+    }
+}
+
+// 执行操作，失败时返回带有命令输出的错误信息
+pub fn run(action: PowerAction) -> Result<(), String> {
+    let Some((command, args)) = action.command() else {
+        return Ok(());
+    };
+    let output = execute_command(command, args)?;
+    if output.status.success() {
+        Ok(())
+    } else {
+        Err(format!(
+            "{command} exited with {}: {}",
+            output.status,
+            String::from_utf8_lossy(&output.stderr).trim()
+        ))
+    }
+}
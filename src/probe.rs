@@ -0,0 +1,1168 @@
+use serde::Deserialize;
+use std::collections::HashMap;
+use std::io::Read;
+use std::path::Path;
+use std::process::{Output, Stdio};
+use std::time::{Duration, Instant};
+
+use crate::{command_line, execute_command, rpu_analysis};
+
+// 快速分析（加入队列时）的时间上限，远程源文件读取很慢时不让文件一直停在分析中
+pub const QUICK_PROBE_LIMIT: Duration = Duration::from_secs(20);
+// 深度分析中每项检测的时间上限
+const DEEP_PROBE_LIMIT: Duration = Duration::from_secs(60);
+// 深度分析抽样的位置（占时长的比例）和每处读取的秒数
+const SAMPLE_POSITIONS: [f64; 3] = [0.1, 0.5, 0.9];
+const SAMPLE_SECONDS: f64 = 10.0;
+// 检测可变帧率时每段平均的帧数，抵消 MKV 毫秒级时间戳的取整误差
+const FRAME_RATE_WINDOW: usize = 24;
+// 杜比视界 RPU 摘要读取的开头秒数
+const RPU_SAMPLE_SECONDS: f64 = 60.0;
+
+// ffprobe 分析得到的媒体信息
+#[derive(Debug, Clone, Default)]
+pub struct MediaInfo {
+    pub duration: Option<f64>,
+    pub video: Option<VideoStream>,
+    pub audio: Vec<AudioStream>,
+    pub subtitles: Vec<SubtitleStream>,
+    pub chapters: Vec<Chapter>,
+    // 深度分析的结果，尚未进行时为 None
+    pub deep: Option<DeepProbe>,
+}
+
+impl MediaInfo {
+    pub fn has_dolby_vision(&self) -> bool {
+        self.video
+            .as_ref()
+            .is_some_and(|video| video.dolby_vision.is_some())
+    }
+
+    pub fn has_atmos(&self) -> bool {
+        self.audio.iter().any(AudioStream::is_atmos)
+    }
+
+    // 默认使用的音频轨道：优先 E-AC-3 Atmos (JOC)，否则第一条
+    pub fn preferred_audio(&self) -> Option<&AudioStream> {
+        self.audio
+            .iter()
+            .find(|audio| audio.codec == "eac3" && audio.is_atmos())
+            .or_else(|| self.audio.first())
+    }
+
+    // 输出的音频轨道，保持源文件中的顺序：为文件选择的轨道（ffmpeg 的 0:a:N）；
+    // 未选择或选择的轨道都已不存在时，按 auto 自动选择
+    pub fn audio_tracks(&self, selected: &[usize], auto: AutoAudio) -> Vec<&AudioStream> {
+        let chosen = self
+            .audio
+            .iter()
+            .filter(|audio| selected.contains(&audio.index))
+            .collect::<Vec<_>>();
+        if !chosen.is_empty() {
+            return chosen;
+        }
+        let all = match auto {
+            AutoAudio::Preferred => Vec::new(),
+            AutoAudio::All => self.audio.iter().collect(),
+            AutoAudio::AllExceptCommentary => self
+                .audio
+                .iter()
+                .filter(|audio| !audio.is_commentary())
+                .collect(),
+        };
+        if all.is_empty() {
+            self.preferred_audio().into_iter().collect()
+        } else {
+            all
+        }
+    }
+
+    // TrueHD 轨道的 AC-3 核心：Blu-ray 的 TrueHD 流中内嵌了 AC-3，重新封装为 MKV 时
+    // （如 MakeMKV）会拆分为紧随其后、语言相同的 AC-3 轨道。优先取紧随其后的轨道，
+    // 否则取语言相同的第一条非评论 AC-3 轨道
+    pub fn ac3_core(&self, truehd: &AudioStream) -> Option<&AudioStream> {
+        let candidates = self
+            .audio
+            .iter()
+            .filter(|audio| audio.codec == "ac3" && audio.language == truehd.language);
+        candidates
+            .clone()
+            .find(|audio| audio.index == truehd.index + 1)
+            .or_else(|| candidates.clone().find(|audio| !audio.is_commentary()))
+    }
+
+    // 根据将要保留的轨道估算输出大小（字节）；remux 不改变码流大小
+    pub fn estimated_output_size(
+        &self,
+        audio_tracks: &[usize],
+        auto_audio: AutoAudio,
+        subtitles: &[&SubtitleStream],
+    ) -> Option<u64> {
+        let mut size = self.video.as_ref()?.size?;
+        size += self
+            .audio_tracks(audio_tracks, auto_audio)
+            .iter()
+            .filter_map(|audio| audio.size)
+            .sum::<u64>();
+        if !subtitles.is_empty() {
+            // 带字幕时会另外生成一个包含字幕的完整文件
+            size = size * 2
+                + subtitles
+                    .iter()
+                    .filter_map(|subtitle| subtitle.size)
+                    .sum::<u64>();
+        }
+        Some(size)
+    }
+
+    // 第一个可转换为 mov_text 的字幕轨道
+    pub fn first_text_subtitle(&self) -> Option<&SubtitleStream> {
+        self.subtitles.iter().find(|subtitle| subtitle.is_text())
+    }
+
+    // 只有图形字幕（PGS/VobSub）
+    pub fn has_only_image_subtitles(&self) -> bool {
+        !self.subtitles.is_empty() && self.first_text_subtitle().is_none()
+    }
+
+    // 队列中显示的语言摘要，例如 "A: eng, jpn  S: eng"
+    pub fn language_summary(&self) -> String {
+        let languages = |languages: Vec<Option<&String>>| {
+            languages
+                .into_iter()
+                .map(|language| language.map_or("und", String::as_str))
+                .collect::<Vec<_>>()
+                .join(", ")
+        };
+        let mut summary = String::new();
+        if !self.audio.is_empty() {
+            summary.push_str(&format!(
+                "A: {}",
+                languages(self.audio.iter().map(|a| a.language.as_ref()).collect())
+            ));
+        }
+        if !self.subtitles.is_empty() {
+            if !summary.is_empty() {
+                summary.push_str("  ");
+            }
+            summary.push_str(&format!(
+                "S: {}",
+                languages(self.subtitles.iter().map(|s| s.language.as_ref()).collect())
+            ));
+        }
+        summary
+    }
+}
+
+#[derive(Debug, Clone, Default)]
+pub struct VideoStream {
+    pub codec: String,
+    pub width: u32,
+    pub height: u32,
+    pub size: Option<u64>,
+    pub frame_rate: Option<f64>,
+    // 软胶转（soft telecine / pulldown）：封装帧率与实际平均帧率不一致
+    pub pulldown: bool,
+    // 传输特性，例如 smpte2084 (PQ)、arib-std-b67 (HLG)
+    pub transfer: Option<String>,
+    pub hdr: HdrMetadata,
+    // 帧中带有 HDR10+（SMPTE 2094-40）动态元数据
+    pub hdr10_plus: bool,
+    pub dolby_vision: Option<DolbyVision>,
+}
+
+impl VideoStream {
+    // HLG 基础层，例如 iPhone 拍摄的 profile 8.4 视频
+    pub fn is_hlg(&self) -> bool {
+        self.transfer.as_deref() == Some("arib-std-b67")
+    }
+
+    pub fn is_av1(&self) -> bool {
+        self.codec == "av1"
+    }
+}
+
+// 深度分析：需要读取或解码多处画面，远程源文件上较慢，因此不在加入队列时进行
+#[derive(Debug, Clone, Default)]
+pub struct DeepProbe {
+    // 抽样的时间戳换算出的最低和最高帧率
+    pub frame_rate_range: Option<(f64, f64)>,
+    // cropdetect 检测到的黑边，画面没有黑边时为 None
+    pub crop: Option<Crop>,
+    // 杜比视界源文件开头一段 RPU 的 dovi_tool info --summary
+    pub rpu_summary: Vec<String>,
+    // 超时或失败的检测
+    pub errors: Vec<String>,
+}
+
+impl DeepProbe {
+    // 最高与最低帧率相差超过 2% 时视为可变帧率
+    pub fn variable_frame_rate(&self) -> Option<(f64, f64)> {
+        self.frame_rate_range
+            .filter(|(min, max)| *min > 0.0 && max / min > 1.02)
+    }
+}
+
+// 去掉黑边后的画面区域
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct Crop {
+    pub width: u32,
+    pub height: u32,
+    pub x: u32,
+    pub y: u32,
+}
+
+impl std::fmt::Display for Crop {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        write!(f, "{}x{} at {},{}", self.width, self.height, self.x, self.y)
+    }
+}
+
+// 杜比视界配置记录（DOVI configuration record）
+#[derive(Debug, Clone, PartialEq)]
+pub struct DolbyVision {
+    pub profile: u8,
+    pub level: u8,
+    pub rpu_present: bool,
+    pub el_present: bool,
+    pub bl_present: bool,
+    pub bl_compatibility_id: u8,
+}
+
+impl std::fmt::Display for DolbyVision {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        write!(f, "Profile {}", self.profile)?;
+        if self.bl_compatibility_id != 0 {
+            write!(f, ".{}", self.bl_compatibility_id)?;
+        }
+        write!(
+            f,
+            ", Level {} (RPU: {}, EL: {}, BL: {})",
+            self.level,
+            u8::from(self.rpu_present),
+            u8::from(self.el_present),
+            u8::from(self.bl_present)
+        )
+    }
+}
+
+#[derive(Debug, Clone, Default, PartialEq)]
+pub struct AudioStream {
+    // 音频轨道序号，对应 ffmpeg 的 0:a:N
+    pub index: usize,
+    pub codec: String,
+    pub profile: Option<String>,
+    pub channels: u32,
+    pub language: Option<String>,
+    pub title: Option<String>,
+    pub size: Option<u64>,
+    // 容器中相对视频的起始延迟（毫秒），负数表示音频先于视频开始
+    pub delay_ms: i64,
+    // 容器中标记为评论音轨（comment disposition）
+    pub comment: bool,
+}
+
+// 没有为文件选择音轨时自动选择的音频轨道
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum AutoAudio {
+    // E-AC-3 Atmos 轨道或第一条轨道
+    Preferred,
+    All,
+    // 全部轨道，但不要评论音轨
+    AllExceptCommentary,
+}
+
+#[derive(Debug, Clone, Default, PartialEq)]
+pub struct SubtitleStream {
+    // 字幕轨道序号，对应 ffmpeg 的 0:s:N
+    pub index: usize,
+    pub codec: String,
+    pub language: Option<String>,
+    pub title: Option<String>,
+    pub size: Option<u64>,
+    // 强制字幕：只翻译外语对白或标牌
+    pub forced: bool,
+    // 默认轨道：播放器未选择字幕时显示
+    pub default: bool,
+    // 容器中标记为听障字幕（hearing_impaired disposition）
+    pub hearing_impaired: bool,
+}
+
+impl SubtitleStream {
+    // 文本字幕可以转换为 mov_text；PGS/VobSub 等图形字幕不行
+    pub fn is_text(&self) -> bool {
+        matches!(
+            self.codec.as_str(),
+            "subrip" | "srt" | "ass" | "ssa" | "webvtt" | "mov_text" | "text"
+        )
+    }
+
+    // ASS/SSA 带有样式和定位，封装前先转换为干净的 SRT
+    pub fn is_ass(&self) -> bool {
+        matches!(self.codec.as_str(), "ass" | "ssa")
+    }
+
+    // 听障字幕（SDH）：容器标记为 hearing impaired，或标题中含有 SDH、HI、CC 或 hearing impaired
+    pub fn is_sdh(&self) -> bool {
+        self.hearing_impaired
+            || self.title.as_deref().is_some_and(|title| {
+                let title = title.to_lowercase();
+                title.contains("hearing impaired")
+                    || title
+                        .split(|c: char| !c.is_alphanumeric())
+                        .any(|word| matches!(word, "sdh" | "hi" | "cc"))
+            })
+    }
+
+    // 蓝光的 PGS 图形字幕，可以用 OCR 识别为文本
+    pub fn is_pgs(&self) -> bool {
+        self.codec == "hdmv_pgs_subtitle"
+    }
+}
+
+// 章节的起止时间（秒）
+#[derive(Debug, Clone, Default)]
+pub struct Chapter {
+    pub start: f64,
+    pub end: f64,
+    pub title: Option<String>,
+}
+
+impl std::fmt::Display for SubtitleStream {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        write!(
+            f,
+            "#{} {} [{}]",
+            self.index + 1,
+            self.codec,
+            self.language.as_deref().unwrap_or("und")
+        )?;
+        if let Some(title) = &self.title {
+            write!(f, " {title}")?;
+        }
+        if self.forced {
+            write!(f, " (forced)")?;
+        }
+        if self.hearing_impaired {
+            write!(f, " (SDH)")?;
+        }
+        if self.default {
+            write!(f, " (default)")?;
+        }
+        Ok(())
+    }
+}
+
+impl AudioStream {
+    // ffprobe 对 E-AC-3 JOC 和 TrueHD Atmos 报告的 profile 中包含 "Atmos"
+    pub fn is_atmos(&self) -> bool {
+        self.profile
+            .as_deref()
+            .is_some_and(|profile| profile.contains("Atmos"))
+    }
+
+    // 无损的 LPCM（pcm_s16le、pcm_s24le、pcm_bluray 等）和 FLAC
+    pub fn is_pcm_or_flac(&self) -> bool {
+        self.codec.starts_with("pcm_") || self.codec == "flac"
+    }
+
+    // 容器标记为评论音轨，或标题中含有 commentary（德语 Kommentar）
+    pub fn is_commentary(&self) -> bool {
+        self.comment
+            || self.title.as_deref().is_some_and(|title| {
+                let title = title.to_lowercase();
+                title.contains("comment") || title.contains("kommentar")
+            })
+    }
+}
+
+impl std::fmt::Display for AudioStream {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        write!(
+            f,
+            "#{} {} {}ch [{}]",
+            self.index + 1,
+            self.codec,
+            self.channels,
+            self.language.as_deref().unwrap_or("und")
+        )?;
+        if let Some(profile) = &self.profile {
+            write!(f, " ({profile})")?;
+        }
+        if let Some(title) = &self.title {
+            write!(f, " {title}")?;
+        }
+        if self.delay_ms != 0 {
+            write!(f, " delay {:+} ms", self.delay_ms)?;
+        }
+        Ok(())
+    }
+}
+
+// HDR10 静态元数据
+#[derive(Debug, Clone, Default, PartialEq)]
+pub struct HdrMetadata {
+    pub mastering_display: Option<MasteringDisplay>,
+    pub max_cll: Option<u32>,
+    pub max_fall: Option<u32>,
+}
+
+// 母版显示器信息（色度坐标为 CIE 1931 xy，亮度单位 cd/m²）
+#[derive(Debug, Clone, PartialEq)]
+pub struct MasteringDisplay {
+    pub red: (f64, f64),
+    pub green: (f64, f64),
+    pub blue: (f64, f64),
+    pub white_point: (f64, f64),
+    pub min_luminance: f64,
+    pub max_luminance: f64,
+}
+
+impl HdrMetadata {
+    pub fn is_empty(&self) -> bool {
+        self.mastering_display.is_none() && self.max_cll.is_none() && self.max_fall.is_none()
+    }
+
+    // 生成 MP4Box 导入选项 hdr= 所需的 XML（数值使用 HEVC SEI 的单位）
+    pub fn to_gpac_xml(&self) -> String {
+        let mut xml = String::from("<?xml version=\"1.0\" encoding=\"UTF-8\"?>\n<HDR>\n");
+
+        if let Some(display) = &self.mastering_display {
+            let chroma = |value: f64| (value * 50000.0).round() as u32;
+            xml.push_str(&format!(
+                "  <mdcv display_primaries_0_x=\"{}\" display_primaries_0_y=\"{}\" \
+                 display_primaries_1_x=\"{}\" display_primaries_1_y=\"{}\" \
+                 display_primaries_2_x=\"{}\" display_primaries_2_y=\"{}\" \
+                 white_point_x=\"{}\" white_point_y=\"{}\" \
+                 max_display_mastering_luminance=\"{}\" min_display_mastering_luminance=\"{}\"/>\n",
+                // HEVC 的原色顺序为 G、B、R
+                chroma(display.green.0),
+                chroma(display.green.1),
+                chroma(display.blue.0),
+                chroma(display.blue.1),
+                chroma(display.red.0),
+                chroma(display.red.1),
+                chroma(display.white_point.0),
+                chroma(display.white_point.1),
+                (display.max_luminance * 10000.0).round() as u64,
+                (display.min_luminance * 10000.0).round() as u64,
+            ));
+        }
+
+        if self.max_cll.is_some() || self.max_fall.is_some() {
+            xml.push_str(&format!(
+                "  <clli max_content_light_level=\"{}\" max_pic_average_light_level=\"{}\"/>\n",
+                self.max_cll.unwrap_or(0),
+                self.max_fall.unwrap_or(0),
+            ));
+        }
+
+        xml.push_str("</HDR>\n");
+        xml
+    }
+
+    // x265 重新编码时写入 SEI 的 master-display 和 max-cll 参数（单位与 XML 相同）
+    pub fn to_x265_params(&self) -> Vec<String> {
+        let mut params = Vec::new();
+        if let Some(display) = &self.mastering_display {
+            let point = |(x, y): (f64, f64)| {
+                format!(
+                    "({},{})",
+                    (x * 50000.0).round() as u32,
+                    (y * 50000.0).round() as u32
+                )
+            };
+            params.push(format!(
+                "master-display=G{}B{}R{}WP{}L({},{})",
+                point(display.green),
+                point(display.blue),
+                point(display.red),
+                point(display.white_point),
+                (display.max_luminance * 10000.0).round() as u64,
+                (display.min_luminance * 10000.0).round() as u64,
+            ));
+        }
+        if self.max_cll.is_some() || self.max_fall.is_some() {
+            params.push(format!(
+                "max-cll={},{}",
+                self.max_cll.unwrap_or(0),
+                self.max_fall.unwrap_or(0)
+            ));
+        }
+        params
+    }
+}
+
+impl std::fmt::Display for MasteringDisplay {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        write!(
+            f,
+            "R({:.4}, {:.4}) G({:.4}, {:.4}) B({:.4}, {:.4}) WP({:.4}, {:.4}), L {:.4}-{:.0} cd/m²",
+            self.red.0,
+            self.red.1,
+            self.green.0,
+            self.green.1,
+            self.blue.0,
+            self.blue.1,
+            self.white_point.0,
+            self.white_point.1,
+            self.min_luminance,
+            self.max_luminance,
+        )
+    }
+}
+
+#[derive(Debug, Deserialize)]
+struct ProbeOutput {
+    #[serde(default)]
+    streams: Vec<ProbeStream>,
+    #[serde(default)]
+    chapters: Vec<ProbeChapter>,
+    format: Option<ProbeFormat>,
+}
+
+#[derive(Debug, Deserialize)]
+struct ProbeChapter {
+    start_time: Option<String>,
+    end_time: Option<String>,
+    #[serde(default)]
+    tags: ProbeTags,
+}
+
+#[derive(Debug, Deserialize)]
+struct ProbeFormat {
+    duration: Option<String>,
+}
+
+#[derive(Debug, Deserialize)]
+struct ProbeStream {
+    codec_type: Option<String>,
+    codec_name: Option<String>,
+    start_time: Option<String>,
+    profile: Option<String>,
+    channels: Option<u32>,
+    width: Option<u32>,
+    height: Option<u32>,
+    bit_rate: Option<String>,
+    r_frame_rate: Option<String>,
+    avg_frame_rate: Option<String>,
+    color_transfer: Option<String>,
+    #[serde(default)]
+    tags: ProbeTags,
+    #[serde(default)]
+    disposition: ProbeDisposition,
+    #[serde(default)]
+    side_data_list: Vec<SideData>,
+}
+
+#[derive(Debug, Default, Deserialize)]
+struct ProbeDisposition {
+    #[serde(default)]
+    comment: u8,
+    #[serde(default)]
+    forced: u8,
+    #[serde(default)]
+    default: u8,
+    #[serde(default)]
+    hearing_impaired: u8,
+}
+
+#[derive(Debug, Default, Deserialize)]
+struct ProbeTags {
+    language: Option<String>,
+    title: Option<String>,
+    // mkvmerge 写入的统计标签，例如 NUMBER_OF_BYTES-eng、BPS-eng
+    #[serde(flatten)]
+    other: HashMap<String, String>,
+}
+
+impl ProbeTags {
+    // "und" 表示未指定语言
+    fn language(&self) -> Option<String> {
+        self.language
+            .clone()
+            .filter(|language| !language.is_empty() && language != "und")
+    }
+
+    fn statistic(&self, name: &str) -> Option<u64> {
+        self.other
+            .iter()
+            .find(|(key, _)| *key == name || key.starts_with(&format!("{name}-")))
+            .and_then(|(_, value)| value.trim().parse().ok())
+    }
+}
+
+impl ProbeStream {
+    // 轨道字节数：优先使用统计标签，否则按码率和时长估算
+    fn size(&self, duration: Option<f64>) -> Option<u64> {
+        self.tags.statistic("NUMBER_OF_BYTES").or_else(|| {
+            let bit_rate = self
+                .bit_rate
+                .as_deref()
+                .and_then(|bit_rate| bit_rate.parse::<u64>().ok())
+                .or_else(|| self.tags.statistic("BPS"))?;
+            Some((bit_rate as f64 / 8.0 * duration?) as u64)
+        })
+    }
+}
+
+#[derive(Debug, Deserialize)]
+struct ProbeFrames {
+    #[serde(default)]
+    frames: Vec<ProbeFrame>,
+}
+
+#[derive(Debug, Deserialize)]
+struct ProbeFrame {
+    #[serde(default)]
+    side_data_list: Vec<SideData>,
+}
+
+#[derive(Debug, Default, Deserialize)]
+struct SideData {
+    side_data_type: Option<String>,
+    red_x: Option<String>,
+    red_y: Option<String>,
+    green_x: Option<String>,
+    green_y: Option<String>,
+    blue_x: Option<String>,
+    blue_y: Option<String>,
+    white_point_x: Option<String>,
+    white_point_y: Option<String>,
+    min_luminance: Option<String>,
+    max_luminance: Option<String>,
+    max_content: Option<u32>,
+    max_average: Option<u32>,
+    dv_profile: Option<u8>,
+    dv_level: Option<u8>,
+    rpu_present_flag: Option<u8>,
+    el_present_flag: Option<u8>,
+    bl_present_flag: Option<u8>,
+    dv_bl_signal_compatibility_id: Option<u8>,
+}
+
+// 解析 ffprobe 输出的分数形式，例如 "34000/50000"
+fn parse_rational(value: Option<&String>) -> Option<f64> {
+    let value = value?;
+    match value.split_once('/') {
+        Some((num, den)) => {
+            let den = den.trim().parse::<f64>().ok()?;
+            if den == 0.0 {
+                return None;
+            }
+            Some(num.trim().parse::<f64>().ok()? / den)
+        }
+        None => value.trim().parse::<f64>().ok(),
+    }
+}
+
+fn parse_hdr(side_data: &[SideData], hdr: &mut HdrMetadata) {
+    for data in side_data {
+        match data.side_data_type.as_deref() {
+            Some("Mastering display metadata") if hdr.mastering_display.is_none() => {
+                let point = |x: Option<&String>, y: Option<&String>| {
+                    Some((parse_rational(x)?, parse_rational(y)?))
+                };
+                hdr.mastering_display = (|| {
+                    Some(MasteringDisplay {
+                        red: point(data.red_x.as_ref(), data.red_y.as_ref())?,
+                        green: point(data.green_x.as_ref(), data.green_y.as_ref())?,
+                        blue: point(data.blue_x.as_ref(), data.blue_y.as_ref())?,
+                        white_point: point(
+                            data.white_point_x.as_ref(),
+                            data.white_point_y.as_ref(),
+                        )?,
+                        min_luminance: parse_rational(data.min_luminance.as_ref())?,
+                        max_luminance: parse_rational(data.max_luminance.as_ref())?,
+                    })
+                })();
+            }
+            Some("Content light level metadata") => {
+                hdr.max_cll = hdr.max_cll.or(data.max_content);
+                hdr.max_fall = hdr.max_fall.or(data.max_average);
+            }
+            _ => {}
+        }
+    }
+}
+
+fn parse_dolby_vision(side_data: &[SideData]) -> Option<DolbyVision> {
+    let data = side_data
+        .iter()
+        .find(|data| data.side_data_type.as_deref() == Some("DOVI configuration record"))?;
+    Some(DolbyVision {
+        profile: data.dv_profile?,
+        level: data.dv_level.unwrap_or(0),
+        rpu_present: data.rpu_present_flag == Some(1),
+        el_present: data.el_present_flag == Some(1),
+        bl_present: data.bl_present_flag == Some(1),
+        bl_compatibility_id: data.dv_bl_signal_compatibility_id.unwrap_or(0),
+    })
+}
+
+// 执行命令，超过 limit 时结束进程并返回错误
+fn execute_within(command: &str, args: &[&str], limit: Duration) -> Result<Output, String> {
+    let mut child = command_line::command(command, args)
+        .stdin(Stdio::null())
+        .stdout(Stdio::piped())
+        .stderr(Stdio::piped())
+        .spawn()
+        .map_err(|e| format!("Failed to execute command {command}: {e}"))?;
+    // 在单独的线程中读取输出，避免管道写满后进程阻塞
+    let read = |pipe: Option<Box<dyn Read + Send>>| {
+        std::thread::spawn(move || {
+            let mut buffer = Vec::new();
+            if let Some(mut pipe) = pipe {
+                let _ = pipe.read_to_end(&mut buffer);
+            }
+            buffer
+        })
+    };
+    let stdout = read(child.stdout.take().map(|pipe| Box::new(pipe) as _));
+    let stderr = read(child.stderr.take().map(|pipe| Box::new(pipe) as _));
+    let started = Instant::now();
+    let status = loop {
+        match child.try_wait() {
+            Ok(Some(status)) => break status,
+            Ok(None) if started.elapsed() >= limit => {
+                let _ = child.kill();
+                let _ = child.wait();
+                return Err(format!(
+                    "{command} did not finish within {} s",
+                    limit.as_secs()
+                ));
+            }
+            Ok(None) => std::thread::sleep(Duration::from_millis(50)),
+            Err(e) => return Err(format!("Failed to wait for {command}: {e}")),
+        }
+    };
+    Ok(Output {
+        status,
+        stdout: stdout.join().unwrap_or_default(),
+        stderr: stderr.join().unwrap_or_default(),
+    })
+}
+
+fn run_ffprobe(args: &[&str]) -> Result<Vec<u8>, String> {
+    run_ffprobe_until(args, None)
+}
+
+// deadline 为 None 时不限时
+fn run_ffprobe_until(args: &[&str], deadline: Option<Instant>) -> Result<Vec<u8>, String> {
+    let output = match deadline {
+        Some(deadline) => execute_within(
+            "ffprobe",
+            args,
+            deadline.saturating_duration_since(Instant::now()),
+        )?,
+        None => execute_command("ffprobe", args)?,
+    };
+    if !output.status.success() {
+        return Err(format!(
+            "ffprobe failed: {}",
+            String::from_utf8_lossy(&output.stderr).trim()
+        ));
+    }
+    Ok(output.stdout)
+}
+
+#[derive(Debug, Deserialize)]
+struct ProbePackets {
+    #[serde(default)]
+    packets: Vec<ProbePacket>,
+}
+
+#[derive(Debug, Deserialize)]
+struct ProbePacket {
+    pts_time: Option<String>,
+    flags: Option<String>,
+}
+
+#[derive(Debug, Deserialize)]
+struct ProbePacketCount {
+    #[serde(default)]
+    streams: Vec<PacketCountStream>,
+    format: Option<PacketCountFormat>,
+}
+
+#[derive(Debug, Deserialize)]
+struct PacketCountStream {
+    nb_read_packets: Option<String>,
+    duration: Option<String>,
+}
+
+#[derive(Debug, Deserialize)]
+struct PacketCountFormat {
+    duration: Option<String>,
+}
+
+// 第一条音频流的数据包数和时长（秒）；只读取数据包不解码，裸流没有流时长时使用容器时长
+pub fn audio_packet_count(input_file: &Path) -> Result<(u64, Option<f64>), String> {
+    let stdout = run_ffprobe(&[
+        "-v",
+        "error",
+        "-select_streams",
+        "a:0",
+        "-count_packets",
+        "-show_entries",
+        "stream=nb_read_packets,duration:format=duration",
+        "-of",
+        "json",
+        &input_file.to_string_lossy(),
+    ])?;
+    let probe: ProbePacketCount = serde_json::from_slice(&stdout)
+        .map_err(|e| format!("Failed to parse ffprobe output: {e}"))?;
+    let stream = probe.streams.first();
+    let packets = stream
+        .and_then(|stream| stream.nb_read_packets.as_deref()?.parse().ok())
+        .unwrap_or(0);
+    let duration = stream
+        .and_then(|stream| stream.duration.as_deref()?.parse::<f64>().ok())
+        .or_else(|| probe.format?.duration?.parse().ok());
+    Ok((packets, duration))
+}
+
+// 指定时间之前（含）最近的视频关键帧；流复制只能从关键帧开始
+pub fn keyframe_before(input_file: &Path, seconds: f64) -> Result<f64, String> {
+    // 只读取起点前 30 秒的数据包，不需要解码
+    let interval = format!("{:.3}%{:.3}", (seconds - 30.0).max(0.0), seconds + 0.001);
+    let stdout = run_ffprobe(&[
+        "-v",
+        "error",
+        "-select_streams",
+        "v:0",
+        "-read_intervals",
+        &interval,
+        "-show_entries",
+        "packet=pts_time,flags",
+        "-of",
+        "json",
+        &input_file.to_string_lossy(),
+    ])?;
+    let probe: ProbePackets = serde_json::from_slice(&stdout)
+        .map_err(|e| format!("Failed to parse ffprobe output: {e}"))?;
+    probe
+        .packets
+        .iter()
+        .filter(|packet| {
+            packet
+                .flags
+                .as_deref()
+                .is_some_and(|flags| flags.contains('K'))
+        })
+        .filter_map(|packet| packet.pts_time.as_deref()?.parse::<f64>().ok())
+        .filter(|time| *time <= seconds + 0.001)
+        .reduce(f64::max)
+        .ok_or_else(|| format!("No keyframe found before {seconds:.3}s"))
+}
+
+// 使用 ffprobe 分析文件
+pub fn probe_media(input_file: &Path) -> Result<MediaInfo, String> {
+    probe_until(input_file, None)
+}
+
+// 加入队列时的快速分析：只读取容器信息和第一帧，限时 QUICK_PROBE_LIMIT；
+// 读取第一帧时超时则跳过 HDR10+ 检测
+pub fn quick_probe(input_file: &Path) -> Result<MediaInfo, String> {
+    probe_until(input_file, Some(Instant::now() + QUICK_PROBE_LIMIT))
+}
+
+fn probe_until(input_file: &Path, deadline: Option<Instant>) -> Result<MediaInfo, String> {
+    let input = input_file.to_string_lossy();
+    let stdout = run_ffprobe_until(
+        &[
+            "-v",
+            "error",
+            "-show_format",
+            "-show_streams",
+            "-show_chapters",
+            "-of",
+            "json",
+            &input,
+        ],
+        deadline,
+    )?;
+    let probe: ProbeOutput = serde_json::from_slice(&stdout)
+        .map_err(|e| format!("Failed to parse ffprobe output: {e}"))?;
+
+    let duration = probe
+        .format
+        .and_then(|format| format.duration)
+        .and_then(|duration| duration.parse::<f64>().ok())
+        .filter(|duration| *duration > 0.0);
+
+    let start_time = |stream: &ProbeStream| {
+        stream
+            .start_time
+            .as_deref()
+            .and_then(|start| start.parse::<f64>().ok())
+    };
+    let video_stream = probe
+        .streams
+        .iter()
+        .find(|stream| stream.codec_type.as_deref() == Some("video"));
+    let video_start = video_stream.and_then(start_time);
+
+    let video = video_stream.map(|stream| {
+        let mut hdr = HdrMetadata::default();
+        parse_hdr(&stream.side_data_list, &mut hdr);
+        let real_rate = parse_rational(stream.r_frame_rate.as_ref()).filter(|rate| *rate > 0.0);
+        let average_rate =
+            parse_rational(stream.avg_frame_rate.as_ref()).filter(|rate| *rate > 0.0);
+        // 3:2 pulldown 时封装为 29.97，实际平均帧率为 23.976（比例 5:4）
+        let pulldown = match (real_rate, average_rate) {
+            (Some(real), Some(average)) => (real / average - 1.25).abs() < 0.01,
+            _ => false,
+        };
+        VideoStream {
+            codec: stream.codec_name.clone().unwrap_or_default(),
+            width: stream.width.unwrap_or(0),
+            height: stream.height.unwrap_or(0),
+            size: stream.size(duration),
+            frame_rate: if pulldown {
+                average_rate
+            } else {
+                real_rate.or(average_rate)
+            },
+            pulldown,
+            transfer: stream.color_transfer.clone(),
+            hdr,
+            hdr10_plus: false,
+            dolby_vision: parse_dolby_vision(&stream.side_data_list),
+        }
+    });
+
+    let audio = probe
+        .streams
+        .iter()
+        .filter(|stream| stream.codec_type.as_deref() == Some("audio"))
+        .enumerate()
+        .map(|(index, stream)| AudioStream {
+            index,
+            codec: stream.codec_name.clone().unwrap_or_default(),
+            profile: stream.profile.clone(),
+            channels: stream.channels.unwrap_or(0),
+            language: stream.tags.language(),
+            title: stream.tags.title.clone(),
+            size: stream.size(duration),
+            delay_ms: match (start_time(stream), video_start) {
+                (Some(start), Some(video_start)) => ((start - video_start) * 1000.0).round() as i64,
+                _ => 0,
+            },
+            comment: stream.disposition.comment != 0,
+        })
+        .collect();
+
+    let subtitles = probe
+        .streams
+        .iter()
+        .filter(|stream| stream.codec_type.as_deref() == Some("subtitle"))
+        .enumerate()
+        .map(|(index, stream)| SubtitleStream {
+            index,
+            codec: stream.codec_name.clone().unwrap_or_default(),
+            language: stream.tags.language(),
+            title: stream.tags.title.clone(),
+            size: stream.size(duration),
+            forced: stream.disposition.forced != 0,
+            default: stream.disposition.default != 0,
+            hearing_impaired: stream.disposition.hearing_impaired != 0,
+        })
+        .collect();
+
+    let chapters = probe
+        .chapters
+        .iter()
+        .filter_map(|chapter| {
+            Some(Chapter {
+                start: chapter.start_time.as_deref()?.parse().ok()?,
+                end: chapter.end_time.as_deref()?.parse().ok()?,
+                title: chapter.tags.title.clone(),
+            })
+        })
+        .collect();
+
+    let mut info = MediaInfo {
+        duration,
+        video,
+        audio,
+        subtitles,
+        chapters,
+        deep: None,
+    };
+
+    // HDR10+ 只出现在帧附加数据中；容器层没有 HDR 信息时也从第一帧读取
+    if let Some(video) = info.video.as_mut()
+        && let Ok(stdout) = run_ffprobe_until(
+            &[
+                "-v",
+                "error",
+                "-select_streams",
+                "v:0",
+                "-read_intervals",
+                "%+#1",
+                "-show_entries",
+                "frame=side_data_list",
+                "-of",
+                "json",
+                &input,
+            ],
+            deadline,
+        )
+        && let Ok(frames) = serde_json::from_slice::<ProbeFrames>(&stdout)
+    {
+        for frame in &frames.frames {
+            parse_hdr(&frame.side_data_list, &mut video.hdr);
+            video.hdr10_plus |= frame.side_data_list.iter().any(|data| {
+                data.side_data_type
+                    .as_deref()
+                    .is_some_and(|kind| kind.contains("SMPTE2094-40"))
+            });
+        }
+    }
+
+    Ok(info)
+}
+
+// 深度分析：可变帧率、黑边和杜比视界 RPU 摘要。每项检测单独限时，
+// 失败时记录错误并继续其余检测
+pub fn deep_probe(input_file: &Path, info: &MediaInfo) -> DeepProbe {
+    let mut deep = DeepProbe::default();
+    let Some(video) = &info.video else {
+        deep.errors.push("No video stream".to_string());
+        return deep;
+    };
+    let positions = sample_positions(info.duration);
+    match frame_rate_range(input_file, &positions) {
+        Ok(range) => deep.frame_rate_range = range,
+        Err(e) => deep.errors.push(format!("Frame rate check: {e}")),
+    }
+    match detect_crop(input_file, &positions, video) {
+        Ok(crop) => deep.crop = crop,
+        Err(e) => deep.errors.push(format!("Black bar detection: {e}")),
+    }
+    // dovi_tool 只能从 HEVC 中提取 RPU
+    if video.dolby_vision.is_some() && !video.is_av1() {
+        match rpu_analysis::sample_summary(input_file, RPU_SAMPLE_SECONDS) {
+            Ok(summary) => deep.rpu_summary = summary,
+            Err(e) => deep.errors.push(format!("RPU summary: {e}")),
+        }
+    }
+    deep
+}
+
+// 抽样的起始秒数；时长未知或很短时只从开头读取
+fn sample_positions(duration: Option<f64>) -> Vec<f64> {
+    match duration {
+        Some(duration) if duration > SAMPLE_SECONDS * SAMPLE_POSITIONS.len() as f64 => {
+            SAMPLE_POSITIONS
+                .iter()
+                .map(|position| (duration * position - SAMPLE_SECONDS / 2.0).max(0.0))
+                .collect()
+        }
+        _ => vec![0.0],
+    }
+}
+
+// 读取各抽样位置的视频时间戳（不解码），按每 FRAME_RATE_WINDOW 帧的平均间隔计算帧率范围
+fn frame_rate_range(input_file: &Path, positions: &[f64]) -> Result<Option<(f64, f64)>, String> {
+    let intervals = positions
+        .iter()
+        .map(|start| format!("{start:.3}%+{SAMPLE_SECONDS}"))
+        .collect::<Vec<_>>()
+        .join(",");
+    let stdout = run_ffprobe_until(
+        &[
+            "-v",
+            "error",
+            "-select_streams",
+            "v:0",
+            "-read_intervals",
+            &intervals,
+            "-show_entries",
+            "packet=pts_time",
+            "-of",
+            "json",
+            &input_file.to_string_lossy(),
+        ],
+        Some(Instant::now() + DEEP_PROBE_LIMIT),
+    )?;
+    let probe: ProbePackets = serde_json::from_slice(&stdout)
+        .map_err(|e| format!("Failed to parse ffprobe output: {e}"))?;
+    // B 帧的时间戳不按顺序出现，排序后再计算间隔
+    let mut times = probe
+        .packets
+        .iter()
+        .filter_map(|packet| packet.pts_time.as_deref()?.parse::<f64>().ok())
+        .collect::<Vec<_>>();
+    times.sort_by(f64::total_cmp);
+    times.dedup();
+    Ok(times
+        .windows(FRAME_RATE_WINDOW + 1)
+        // 跳过跨越两处抽样位置的窗口
+        .filter(|window| window.windows(2).all(|pair| pair[1] - pair[0] < 1.0))
+        .map(|window| FRAME_RATE_WINDOW as f64 / (window[FRAME_RATE_WINDOW] - window[0]))
+        .fold(None, |range, rate| match range {
+            None => Some((rate, rate)),
+            Some((min, max)) => Some((f64::min(min, rate), f64::max(max, rate))),
+        }))
+}
+
+// 在各抽样位置解码少量画面运行 cropdetect，取面积最大的结果，避免把暗场景当作黑边
+fn detect_crop(
+    input_file: &Path,
+    positions: &[f64],
+    video: &VideoStream,
+) -> Result<Option<Crop>, String> {
+    let input = input_file.to_string_lossy();
+    let deadline = Instant::now() + DEEP_PROBE_LIMIT;
+    let mut largest: Option<Crop> = None;
+    for start in positions {
+        let output = execute_within(
+            "ffmpeg",
+            &[
+                "-hide_banner",
+                "-ss",
+                &format!("{start:.3}"),
+                "-i",
+                &input,
+                "-map",
+                "0:v:0",
+                "-vf",
+                "cropdetect=round=2",
+                "-frames:v",
+                "24",
+                "-f",
+                "null",
+                "-",
+            ],
+            deadline.saturating_duration_since(Instant::now()),
+        )?;
+        let stderr = String::from_utf8_lossy(&output.stderr);
+        if !output.status.success() {
+            return Err(format!(
+                "ffmpeg failed: {}",
+                stderr.lines().last().unwrap_or_default().trim()
+            ));
+        }
+        if let Some(crop) = stderr.lines().rev().find_map(parse_crop)
+            && largest
+                .is_none_or(|largest| crop.width * crop.height > largest.width * largest.height)
+        {
+            largest = Some(crop);
+        }
+    }
+    Ok(largest.filter(|crop| crop.width < video.width || crop.height < video.height))
+}
+
+// cropdetect 的输出行，例如 "[Parsed_cropdetect_0 @ ...] x1:0 ... crop=3840:1600:0:280"
+fn parse_crop(line: &str) -> Option<Crop> {
+    let values = line.split("crop=").nth(1)?.split_whitespace().next()?;
+    let mut numbers = values.split(':').map(|value| value.parse::<u32>().ok());
+    Some(Crop {
+        width: numbers.next()??,
+        height: numbers.next()??,
+        x: numbers.next()??,
+        y: numbers.next()??,
+    })
+}
@@ -0,0 +1,84 @@
+use std::sync::atomic::{AtomicBool, Ordering};
+use std::sync::{Mutex, OnceLock};
+
+use crate::execute_command;
+
+// 正在运行的外部工具进程。取消处理时结束这些进程以及它们启动的子进程：
+// Unix 上每个工具在单独的进程组中启动，向整个进程组发送 SIGKILL；
+// Windows 上用 taskkill /T 结束工具及其启动的整个进程树
+static RUNNING: Mutex<Vec<u32>> = Mutex::new(Vec::new());
+
+// 登记的进程，丢弃时取消登记（进程已结束或所在的任务被中止）
+pub struct Running(Option<u32>);
+
+impl Drop for Running {
+    fn drop(&mut self) {
+        if let Some(pid) = self.0 {
+            running().retain(|running| *running != pid);
+        }
+    }
+}
+
+impl Running {
+    // 结束这个进程及其子进程，用于超时等只需要结束单个工具的情况
+    pub fn kill(&self) -> bool {
+        self.0.is_some_and(kill_tree)
+    }
+}
+
+fn running() -> std::sync::MutexGuard<'static, Vec<u32>> {
+    RUNNING
+        .lock()
+        .unwrap_or_else(|poisoned| poisoned.into_inner())
+}
+
+// 登记刚启动的进程，返回值需要保留到进程结束
+pub fn register(child: &tokio::process::Child) -> Running {
+    let pid = child.id();
+    if let Some(pid) = pid {
+        running().push(pid);
+    }
+    Running(pid)
+}
+
+fn kill_tree(pid: u32) -> bool {
+    let output = if cfg!(windows) {
+        execute_command("taskkill", &["/T", "/F", "/PID", &pid.to_string()])
+    } else {
+        // 进程组号与组长进程号相同，负数表示整个进程组
+        execute_command("kill", &["-KILL", "--", &format!("-{pid}")])
+    };
+    output.is_ok_and(|out| out.status.success())
+}
+
+// 结束全部登记的进程，返回结束的数量
+pub fn kill_all() -> usize {
+    let pids = std::mem::take(&mut *running());
+    pids.into_iter().filter(|pid| kill_tree(*pid)).count()
+}
+
+// 以后台优先级运行处理工具，整夜的批处理不影响电脑的正常使用；开始批处理时按选项设置
+static BACKGROUND_PRIORITY: AtomicBool = AtomicBool::new(false);
+
+pub fn set_background_priority(enabled: bool) {
+    BACKGROUND_PRIORITY.store(enabled, Ordering::Relaxed);
+}
+
+pub fn background_priority() -> bool {
+    BACKGROUND_PRIORITY.load(Ordering::Relaxed)
+}
+
+// Unix 上以后台优先级启动工具时放在工具之前的命令：nice 降低 CPU 优先级，
+// Linux 上再用 ionice 把磁盘 I/O 设为空闲级别（系统中没有 ionice 时只用 nice）
+pub fn priority_prefix() -> Vec<&'static str> {
+    static IONICE: OnceLock<bool> = OnceLock::new();
+    let ionice = cfg!(target_os = "linux")
+        && *IONICE.get_or_init(|| {
+            execute_command("ionice", &["-c", "3", "true"]).is_ok_and(|out| out.status.success())
+        });
+    let mut prefix = vec!["nice", "-n", "19"];
+    if ionice {
+        prefix.extend(["ionice", "-c", "3"]);
+    }
+    prefix
+}
@@ -0,0 +1,223 @@
+use serde::{Deserialize, Serialize};
+use serde_json::Value;
+use std::path::PathBuf;
+
+use crate::ProcessOptions;
+use crate::{active_area, config};
+
+const SCHEMA: config::Schema = config::Schema {
+    file: "projects.json",
+    name: "queues",
+    migrations: &[audio_track_list, subtitle_mode, frame_rate_value],
+};
+
+// 版本 1 → 2：每个文件只能选择一条音轨（audio_track）改为可以选择多条（audio_tracks）
+fn audio_track_list(mut data: Value) -> Result<Value, String> {
+    let projects = data.get_mut("projects").and_then(Value::as_array_mut);
+    let files = projects
+        .into_iter()
+        .flatten()
+        .filter_map(|project| project.get_mut("files")?.as_array_mut())
+        .flatten()
+        .filter_map(Value::as_object_mut);
+    for file in files {
+        if let Some(track) = file.remove("audio_track") {
+            let tracks = if track.is_null() { vec![] } else { vec![track] };
+            file.insert("audio_tracks".to_string(), Value::Array(tracks));
+        }
+    }
+    Ok(data)
+}
+
+// 版本 2 → 3：是否包含字幕（include_subtitles）改为字幕模式（subtitle_mode），
+// 原来的开启对应只转换一条字幕的 Preferred
+fn subtitle_mode(mut data: Value) -> Result<Value, String> {
+    let projects = data.get_mut("projects").and_then(Value::as_array_mut);
+    let options = projects
+        .into_iter()
+        .flatten()
+        .filter_map(|project| project.get_mut("options")?.as_object_mut());
+    for options in options {
+        if let Some(include) = options.remove("include_subtitles") {
+            let mode = if include.as_bool() == Some(true) {
+                "Preferred"
+            } else {
+                "None"
+            };
+            options.insert("subtitle_mode".to_string(), Value::from(mode));
+        }
+    }
+    Ok(data)
+}
+
+// 版本 3 → 4：帧率由固定的枚举改为有理数值（"24000/1001"），支持自定义帧率
+fn frame_rate_value(mut data: Value) -> Result<Value, String> {
+    let projects = data.get_mut("projects").and_then(Value::as_array_mut);
+    let options = projects
+        .into_iter()
+        .flatten()
+        .filter_map(|project| project.get_mut("options")?.as_object_mut());
+    for options in options {
+        let value = match options.get("frame_rate").and_then(Value::as_str) {
+            Some("Film23976") => "24000/1001",
+            Some("Film24") => "24",
+            Some("Tv29970") => "30000/1001",
+            Some("Tv25") => "25",
+            Some("Hfr60") => "60",
+            Some("Hfr59940") => "60000/1001",
+            _ => continue,
+        };
+        options.insert("frame_rate".to_string(), Value::from(value));
+    }
+    Ok(data)
+}
+
+// 队列中保存的一个文件及其 L5 偏移、裁剪范围、选择的音频轨道、音频偏移和拼接在它之后的分段
+#[derive(Debug, Clone, Default, Serialize, Deserialize)]
+#[serde(default)]
+pub struct SavedFile {
+    pub path: PathBuf,
+    pub active_area: active_area::Form,
+    pub trim_start: String,
+    pub trim_end: String,
+    pub audio_tracks: Vec<usize>,
+    // 手动选择的字幕轨道，None 时按字幕模式选择
+    pub subtitle_tracks: Option<Vec<usize>>,
+    // 外部字幕文件（.srt/.ass）
+    pub external_subtitles: Vec<PathBuf>,
+    pub audio_offset: String,
+    pub subtitle_offset: String,
+    pub parts: Vec<PathBuf>,
+}
+
+// 一个命名队列（如 "Movies backlog"），有自己的文件、输出文件夹和转换选项
+#[derive(Debug, Clone, Default, Serialize, Deserialize)]
+#[serde(default)]
+pub struct Project {
+    pub name: String,
+    pub files: Vec<SavedFile>,
+    pub output_folder: Option<PathBuf>,
+    pub options: ProcessOptions,
+}
+
+#[derive(Debug, Clone, Default, Serialize, Deserialize)]
+#[serde(default)]
+pub struct Projects {
+    // 当前打开的队列，None 表示未保存的队列
+    pub active: Option<String>,
+    pub projects: Vec<Project>,
+    // 暂停的批处理中尚未处理的文件，重新启动程序后也可以继续
+    pub paused: Option<Project>,
+    // 当前队列（文件、输出文件夹和选项），每次变化时保存，启动时恢复
+    pub current: Option<Project>,
+}
+
+impl Projects {
+    pub fn load() -> Self {
+        config::load(&SCHEMA)
+    }
+
+    pub fn save(&self) -> Result<(), String> {
+        config::save(&SCHEMA, self)
+    }
+
+    pub fn names(&self) -> Vec<String> {
+        self.projects
+            .iter()
+            .map(|project| project.name.clone())
+            .collect()
+    }
+
+    pub fn get(&self, name: &str) -> Option<&Project> {
+        self.projects.iter().find(|project| project.name == name)
+    }
+
+    // 同名队列会被覆盖
+    pub fn upsert(&mut self, project: Project) {
+        match self.projects.iter_mut().find(|p| p.name == project.name) {
+            Some(existing) => *existing = project,
+            None => self.projects.push(project),
+        }
+    }
+
+    pub fn remove(&mut self, name: &str) {
+        self.projects.retain(|project| project.name != name);
+        if self.active.as_deref() == Some(name) {
+            self.active = None;
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use serde_json::json;
+
+    fn migrate(mut data: Value) -> Value {
+        for migration in SCHEMA.migrations {
+            data = migration(data).unwrap();
+        }
+        data
+    }
+
+    #[test]
+    fn version_1_queues_are_upgraded() {
+        let data = migrate(json!({
+            "projects": [
+                {
+                    "name": "Movies",
+                    "files": [
+                        { "path": "a.mkv", "audio_track": 2 },
+                        { "path": "b.mkv", "audio_track": null },
+                        { "path": "c.mkv" }
+                    ],
+                    "options": { "include_subtitles": true, "frame_rate": "Film23976" }
+                },
+                {
+                    "name": "Shows",
+                    "options": { "include_subtitles": false, "frame_rate": "Tv25" }
+                }
+            ]
+        }));
+        assert_eq!(
+            data,
+            json!({
+                "projects": [
+                    {
+                        "name": "Movies",
+                        "files": [
+                            { "path": "a.mkv", "audio_tracks": [2] },
+                            { "path": "b.mkv", "audio_tracks": [] },
+                            { "path": "c.mkv" }
+                        ],
+                        "options": { "subtitle_mode": "Preferred", "frame_rate": "24000/1001" }
+                    },
+                    {
+                        "name": "Shows",
+                        "options": { "subtitle_mode": "None", "frame_rate": "25" }
+                    }
+                ]
+            })
+        );
+        let projects = serde_json::from_value::<Projects>(data).unwrap();
+        assert_eq!(projects.projects[0].files[0].audio_tracks, [2]);
+        assert_eq!(projects.projects[1].options.frame_rate.to_value(), "25");
+    }
+
+    #[test]
+    fn migrations_keep_current_and_unexpected_data() {
+        // 已经是新格式或无法识别的值保持不变
+        let current = json!({
+            "projects": [{
+                "files": [{ "audio_tracks": [1] }],
+                "options": { "subtitle_mode": "All", "frame_rate": "48000/1001" }
+            }]
+        });
+        assert_eq!(migrate(current.clone()), current);
+        let unknown = json!({ "projects": [{ "options": { "frame_rate": "Film30" } }] });
+        assert_eq!(migrate(unknown.clone()), unknown);
+        for data in [json!({}), json!({ "projects": "Movies" }), Value::Null] {
+            assert_eq!(migrate(data.clone()), data);
+        }
+    }
+}
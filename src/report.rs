@@ -0,0 +1,395 @@
+use serde_json::json;
+
+use crate::pipeline::{self, DryRun, Step};
+use crate::probe::MediaInfo;
+use crate::{ProcessOptions, QueueItem, format_size, format_utc_now};
+
+fn options_json(options: &ProcessOptions) -> serde_json::Value {
+    json!({
+        "frame_rate": options.frame_rate.to_value(),
+        "dv_profile": options.dv_profile.to_string(),
+        "sample_entry": options.sample_entry.tag(),
+        "enhancement_layer": options.enhancement_layer.to_string(),
+        "subtitle_mode": options.subtitle_mode.to_string(),
+        "sdh_subtitles": options.sdh_subtitles.to_string(),
+        "ocr_subtitles": options.ocr_subtitles,
+        "keep_unsubtitled": options.keep_unsubtitled,
+        "all_audio_tracks": options.all_audio_tracks,
+        "skip_commentary": options.all_audio_tracks && options.skip_commentary,
+        "truehd_ac3_core": options.truehd_ac3_core,
+        "truehd_to_eac3": options.truehd_to_eac3,
+        "aac_stereo": options.aac_stereo,
+        "video_only": options.video_only,
+        "eac3_bitrate": options.eac3_bitrate,
+        "lossless_audio": options.lossless_audio.to_string(),
+        "loudnorm_target": options.loudnorm.then_some(options.loudnorm_target),
+        "default_language": options.default_language(),
+        "annotate_source": options.annotate_source,
+        "verify_source": options.verify_source,
+        "verify_audio": options.verify_audio,
+        "check_audio": options.check_audio,
+        "hdr10_fallback": options.hdr10_fallback,
+        "export_poster": options.export_poster,
+        "non_dv_mode": options.non_dv_mode.to_string(),
+        "existing_outputs": options.existing_outputs.to_string(),
+        "source_after_success": options.source_after_success.to_string(),
+        "device_preset": options.device_preset.to_string(),
+        "major_brand": options.major_brand,
+        "compatible_brands": options.compatible_brands,
+        "mux_extra_args": options.mux_extra_args().unwrap_or_default(),
+        "rpu_workflow": options.rpu_workflow,
+        "burn_subtitles": options.burn_subtitles,
+        "rpu_edit_config": options.rpu_edit_config.as_ref().map(|path| path.to_string_lossy()),
+        "stagger_seconds": options.stagger_seconds,
+        "max_concurrent_jobs": options.max_concurrent_jobs,
+        "retry_count": options.retry_count,
+        "background_priority": options.background_priority,
+        "low_temp_space": options.low_temp_space,
+        "post_command": options.post_command,
+        "temp_folder": options.temp_folder.as_ref().map(|folder| folder.to_string_lossy()),
+    })
+}
+
+fn media_json(info: &MediaInfo) -> serde_json::Value {
+    json!({
+        "duration_seconds": info.duration,
+        "video": info.video.as_ref().map(|video| json!({
+            "codec": video.codec,
+            "width": video.width,
+            "height": video.height,
+            "size_bytes": video.size,
+            "frame_rate": video.frame_rate,
+            "pulldown": video.pulldown,
+            "transfer": video.transfer,
+            "dolby_vision": video.dolby_vision.as_ref().map(|dv| json!({
+                "profile": dv.profile,
+                "level": dv.level,
+                "rpu_present": dv.rpu_present,
+                "el_present": dv.el_present,
+                "bl_present": dv.bl_present,
+                "bl_compatibility_id": dv.bl_compatibility_id,
+            })),
+            "hdr10": {
+                "mastering_display": video.hdr.mastering_display.as_ref().map(ToString::to_string),
+                "max_cll": video.hdr.max_cll,
+                "max_fall": video.hdr.max_fall,
+            },
+            "hdr10_plus": video.hdr10_plus,
+        })),
+        "audio": info.audio.iter().map(|audio| json!({
+            "index": audio.index,
+            "codec": audio.codec,
+            "profile": audio.profile,
+            "channels": audio.channels,
+            "language": audio.language,
+            "title": audio.title,
+            "atmos": audio.is_atmos(),
+            "delay_ms": audio.delay_ms,
+            "size_bytes": audio.size,
+        })).collect::<Vec<_>>(),
+        "subtitles": info.subtitles.iter().map(|subtitle| json!({
+            "index": subtitle.index,
+            "codec": subtitle.codec,
+            "language": subtitle.language,
+            "title": subtitle.title,
+            "text_based": subtitle.is_text(),
+            "forced": subtitle.forced,
+            "default": subtitle.default,
+            "size_bytes": subtitle.size,
+        })).collect::<Vec<_>>(),
+    })
+}
+
+// 队列报告（JSON）
+pub fn json_report(items: &[QueueItem], options: &ProcessOptions) -> String {
+    let files = items
+        .iter()
+        .map(|item| {
+            let mut file = json!({
+                "path": item.path.to_string_lossy(),
+                "external_subtitles": item
+                    .external_subtitles
+                    .iter()
+                    .map(|path| path.to_string_lossy())
+                    .collect::<Vec<_>>(),
+            });
+            match &item.info {
+                Some(Ok(info)) => {
+                    file["media"] = media_json(info);
+                    file["audio_tracks"] = json!(
+                        info.audio_tracks(&item.audio_tracks, options.auto_audio())
+                            .iter()
+                            .map(|audio| audio.index)
+                            .collect::<Vec<_>>()
+                    );
+                    file["subtitle_tracks"] = json!(
+                        options
+                            .subtitle_tracks(
+                                info,
+                                &item.audio_tracks,
+                                item.subtitle_tracks.as_deref()
+                            )
+                            .iter()
+                            .map(|subtitle| subtitle.index)
+                            .collect::<Vec<_>>()
+                    );
+                    file["estimated_output_bytes"] = json!(info.estimated_output_size(
+                        &item.audio_tracks,
+                        options.auto_audio(),
+                        &options.subtitle_tracks(
+                            info,
+                            &item.audio_tracks,
+                            item.subtitle_tracks.as_deref()
+                        )
+                    ));
+                }
+                Some(Err(err)) => file["error"] = json!(err),
+                None => file["error"] = json!("not analyzed yet"),
+            }
+            file
+        })
+        .collect::<Vec<_>>();
+
+    let report = json!({
+        "generated": format_utc_now(),
+        "options": options_json(options),
+        "files": files,
+    });
+    serde_json::to_string_pretty(&report).unwrap_or_default()
+}
+
+// 队列报告（Markdown）
+pub fn markdown_report(items: &[QueueItem], options: &ProcessOptions) -> String {
+    let mut report = format!(
+        "# Rebottle Queue Report\n\nGenerated: {}\n\n## Options\n\n\
+         - Frame rate: {}\n- Dolby Vision profile: {}\n- Sample entry: {}\n\
+         - Profile 7 enhancement layer: {}\n- Subtitles: {}\n- Annotate source: {}\n\
+         - Verify source: {}\n- HDR10 fallback: {}\n- Non-DV files: {}\n- Device preset: {}\n- Brands: {} ({})\n",
+        format_utc_now(),
+        options.frame_rate,
+        options.dv_profile,
+        options.sample_entry.tag(),
+        options.enhancement_layer,
+        options.subtitle_mode,
+        options.annotate_source,
+        options.verify_source,
+        options.hdr10_fallback,
+        options.non_dv_mode,
+        options.device_preset,
+        options.major_brand,
+        options.compatible_brands,
+    );
+
+    for (index, item) in items.iter().enumerate() {
+        report.push_str(&format!(
+            "\n## {}. {}\n\nPath: `{}`\n\n",
+            index + 1,
+            item.path.file_name().unwrap_or_default().to_string_lossy(),
+            item.path.to_string_lossy()
+        ));
+
+        let info = match &item.info {
+            Some(Ok(info)) => info,
+            Some(Err(err)) => {
+                report.push_str(&format!("Analysis failed: {err}\n"));
+                continue;
+            }
+            None => {
+                report.push_str("Not analyzed yet\n");
+                continue;
+            }
+        };
+
+        if let Some(duration) = info.duration {
+            report.push_str(&format!("- Duration: {duration:.1}s\n"));
+        }
+        if let Some(video) = &info.video {
+            report.push_str(&format!(
+                "- Video: {} {}x{}\n",
+                video.codec, video.width, video.height
+            ));
+            report.push_str(&format!(
+                "- Dolby Vision: {}\n",
+                video
+                    .dolby_vision
+                    .as_ref()
+                    .map_or("none".to_string(), ToString::to_string)
+            ));
+            if video.hdr10_plus {
+                report.push_str("- HDR10+: yes\n");
+            }
+            if let Some(display) = &video.hdr.mastering_display {
+                report.push_str(&format!("- Mastering display: {display}\n"));
+            }
+            if video.hdr.max_cll.is_some() || video.hdr.max_fall.is_some() {
+                report.push_str(&format!(
+                    "- MaxCLL/MaxFALL: {}/{}\n",
+                    video.hdr.max_cll.unwrap_or(0),
+                    video.hdr.max_fall.unwrap_or(0)
+                ));
+            }
+        }
+        let selected = info.audio_tracks(&item.audio_tracks, options.auto_audio());
+        for audio in &info.audio {
+            let note = if selected.contains(&audio) {
+                " (selected)"
+            } else {
+                ""
+            };
+            report.push_str(&format!("- Audio: {audio}{note}\n"));
+        }
+        for subtitle in &info.subtitles {
+            report.push_str(&format!("- Subtitle: {subtitle}\n"));
+        }
+        if let Some(size) = info.estimated_output_size(
+            &item.audio_tracks,
+            options.auto_audio(),
+            &options.subtitle_tracks(info, &item.audio_tracks, item.subtitle_tracks.as_deref()),
+        ) {
+            report.push_str(&format!("- Estimated output: {}\n", format_size(size)));
+        }
+    }
+
+    report
+}
+
+// 预演的输出大小：每个输出文件包含视频和输出音轨（转码的音轨按源大小估计），字幕按源大小计入
+fn dry_run_size(info: &MediaInfo, run: &DryRun) -> Option<u64> {
+    if run.skipped.is_some() {
+        return Some(0);
+    }
+    let output = info.video.as_ref()?.size?
+        + run
+            .audio
+            .iter()
+            .filter_map(|(audio, _)| audio.size)
+            .sum::<u64>();
+    Some(
+        output * run.outputs.len() as u64
+            + run
+                .subtitles
+                .iter()
+                .filter_map(|subtitle| subtitle.size)
+                .sum::<u64>(),
+    )
+}
+
+// 用一组选项预演队列中的一个文件，None 表示尚未分析或分析失败
+pub fn dry_run(item: &QueueItem, options: &ProcessOptions) -> Option<(DryRun, Option<u64>)> {
+    let Some(Ok(info)) = &item.info else {
+        return None;
+    };
+    let run = pipeline::dry_run(&item.source(), info, options);
+    let size = dry_run_size(info, &run);
+    Some((run, size))
+}
+
+// 预演结果的逐行描述，用于比较报告和界面
+pub fn dry_run_lines(run: &DryRun, size: Option<u64>, options: &ProcessOptions) -> Vec<String> {
+    if let Some(reason) = &run.skipped {
+        return vec![reason.clone()];
+    }
+    let mut lines = vec![format!(
+        "Steps: {}",
+        run.steps
+            .iter()
+            .map(|step| format!("{step} ({})", step.tool(options)))
+            .collect::<Vec<_>>()
+            .join(" → ")
+    )];
+    lines.push(format!("Outputs: {}", run.outputs.join(", ")));
+    if run.audio.is_empty() {
+        lines.push("Audio: none".to_string());
+    }
+    for (audio, target) in &run.audio {
+        lines.push(match target {
+            Some(target) => format!("Audio: {audio} → {target}"),
+            None => format!("Audio: {audio} (copy)"),
+        });
+    }
+    if run.steps.contains(&Step::AddAacStereo) {
+        lines.push("Audio: AAC stereo downmix of the first track".to_string());
+    }
+    if run.subtitles.is_empty() {
+        lines.push("Subtitles: none".to_string());
+    }
+    for subtitle in &run.subtitles {
+        lines.push(format!("Subtitle: {subtitle}"));
+    }
+    lines.push(format!(
+        "Estimated size: {}",
+        size.map_or("unknown".to_string(), format_size)
+    ));
+    lines
+}
+
+// 两组选项之间不同的设置：(名称, A 的值, B 的值)
+pub fn option_differences(a: &ProcessOptions, b: &ProcessOptions) -> Vec<(String, String, String)> {
+    let (serde_json::Value::Object(a), serde_json::Value::Object(b)) =
+        (options_json(a), options_json(b))
+    else {
+        return Vec::new();
+    };
+    a.into_iter()
+        .filter_map(|(key, value)| {
+            let other = b.get(&key).cloned().unwrap_or_default();
+            (value != other).then(|| (key, value.to_string(), other.to_string()))
+        })
+        .collect()
+}
+
+// 两个预设在当前队列上的预演对比（Markdown），不执行任何命令
+pub fn comparison_report(
+    items: &[QueueItem],
+    (name_a, a): (&str, &ProcessOptions),
+    (name_b, b): (&str, &ProcessOptions),
+) -> String {
+    let mut report = format!(
+        "# Rebottle Preset Comparison\n\nGenerated: {}\n\nDry run of {} file(s), no commands were executed. \
+         Estimates use the source stream sizes, transcoded audio may be smaller.\n\n## Settings\n\n",
+        format_utc_now(),
+        items.len()
+    );
+    let differences = option_differences(a, b);
+    if differences.is_empty() {
+        report.push_str("The presets have the same settings.\n");
+    } else {
+        report.push_str(&format!(
+            "| Setting | {name_a} | {name_b} |\n|---|---|---|\n"
+        ));
+        for (key, value_a, value_b) in differences {
+            report.push_str(&format!("| {key} | {value_a} | {value_b} |\n"));
+        }
+    }
+
+    let mut totals = [Some(0u64), Some(0u64)];
+    for (index, item) in items.iter().enumerate() {
+        report.push_str(&format!(
+            "\n## {}. {}\n\n",
+            index + 1,
+            item.path.file_name().unwrap_or_default().to_string_lossy()
+        ));
+        let (Some((run_a, size_a)), Some((run_b, size_b))) = (dry_run(item, a), dry_run(item, b))
+        else {
+            report.push_str("Not analyzed, not included in the totals\n");
+            continue;
+        };
+        for (total, size) in totals.iter_mut().zip([size_a, size_b]) {
+            *total = total.zip(size).map(|(total, size)| total + size);
+        }
+        let cell = |lines: Vec<String>| lines.join("<br>");
+        report.push_str(&format!(
+            "| {name_a} | {name_b} |\n|---|---|\n| {} | {} |\n",
+            cell(dry_run_lines(&run_a, size_a, a)),
+            cell(dry_run_lines(&run_b, size_b, b))
+        ));
+    }
+
+    let total = |size: Option<u64>| size.map_or("unknown".to_string(), format_size);
+    report.push_str(&format!(
+        "\n## Total\n\n- {name_a}: {}\n- {name_b}: {}\n",
+        total(totals[0]),
+        total(totals[1])
+    ));
+    report
+}
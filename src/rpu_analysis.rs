@@ -0,0 +1,283 @@
+use serde::Deserialize;
+use std::fs::File;
+use std::io::BufReader;
+use std::path::{Path, PathBuf};
+use std::process::{Command, Stdio};
+
+use crate::{command_line, temp_files};
+
+// 图表中最多显示的柱数，场景更多时合并相邻场景
+const MAX_BARS: usize = 240;
+// L1 亮度使用 12 位 PQ 码值
+pub const PQ_MAX: f64 = 4095.0;
+
+// 一个场景（或合并后的几个场景）的 L1 亮度，单位为 PQ 码值
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct Scene {
+    pub first_frame: usize,
+    pub frames: usize,
+    pub max_pq: f64,
+    pub avg_pq: f64,
+}
+
+// 一个文件的杜比视界 RPU 分析结果
+#[derive(Debug, Clone)]
+pub struct Analysis {
+    pub path: PathBuf,
+    pub frames: usize,
+    pub scenes: Vec<Scene>,
+    // dovi_tool info --summary 的输出
+    pub summary: Vec<String>,
+}
+
+impl Analysis {
+    pub fn peak_pq(&self) -> f64 {
+        self.scenes
+            .iter()
+            .map(|scene| scene.max_pq)
+            .fold(0.0, f64::max)
+    }
+
+    // 按帧数加权的平均亮度
+    pub fn average_pq(&self) -> f64 {
+        let frames = self.scenes.iter().map(|scene| scene.frames).sum::<usize>();
+        if frames == 0 {
+            return 0.0;
+        }
+        self.scenes
+            .iter()
+            .map(|scene| scene.avg_pq * scene.frames as f64)
+            .sum::<f64>()
+            / frames as f64
+    }
+
+    // 图表中的柱：场景过多时合并相邻场景，最大值取最大，平均值按帧数加权
+    pub fn bars(&self) -> Vec<Scene> {
+        let group = self.scenes.len().div_ceil(MAX_BARS).max(1);
+        self.scenes
+            .chunks(group)
+            .map(|chunk| {
+                let frames = chunk.iter().map(|scene| scene.frames).sum::<usize>();
+                Scene {
+                    first_frame: chunk[0].first_frame,
+                    frames,
+                    max_pq: chunk.iter().map(|scene| scene.max_pq).fold(0.0, f64::max),
+                    avg_pq: chunk
+                        .iter()
+                        .map(|scene| scene.avg_pq * scene.frames as f64)
+                        .sum::<f64>()
+                        / frames.max(1) as f64,
+                }
+            })
+            .collect()
+    }
+}
+
+// 12 位 PQ 码值转换为亮度（SMPTE ST 2084，cd/m²）
+pub fn pq_to_nits(pq: f64) -> f64 {
+    const M1: f64 = 2610.0 / 16384.0;
+    const M2: f64 = 2523.0 / 4096.0 * 128.0;
+    const C1: f64 = 3424.0 / 4096.0;
+    const C2: f64 = 2413.0 / 4096.0 * 32.0;
+    const C3: f64 = 2392.0 / 4096.0 * 32.0;
+    let e = (pq / PQ_MAX).clamp(0.0, 1.0).powf(1.0 / M2);
+    10000.0 * ((e - C1).max(0.0) / (C2 - C3 * e)).powf(1.0 / M1)
+}
+
+// dovi_tool export 输出中用到的字段，其余字段忽略
+#[derive(Debug, Deserialize)]
+struct ExportedRpu {
+    vdr_dm_data: Option<DmData>,
+}
+
+#[derive(Debug, Deserialize)]
+struct DmData {
+    #[serde(default)]
+    scene_refresh_flag: u64,
+    cmv29_metadata: Option<Cmv29Metadata>,
+}
+
+#[derive(Debug, Deserialize)]
+struct Cmv29Metadata {
+    #[serde(default)]
+    ext_metadata_blocks: Vec<serde_json::Value>,
+}
+
+// 一帧的 L1 (max_pq, avg_pq) 和是否为新场景的开始
+fn frame_l1(rpu: &ExportedRpu) -> Option<(f64, f64, bool)> {
+    let dm = rpu.vdr_dm_data.as_ref()?;
+    let level1 = dm
+        .cmv29_metadata
+        .as_ref()?
+        .ext_metadata_blocks
+        .iter()
+        .find_map(|block| block.get("Level1"))?;
+    Some((
+        level1.get("max_pq")?.as_f64()?,
+        level1.get("avg_pq")?.as_f64()?,
+        dm.scene_refresh_flag == 1,
+    ))
+}
+
+// 按 scene_refresh_flag 把逐帧的 L1 合并为场景；没有 L1 的帧不计入
+fn scenes(rpus: &[ExportedRpu]) -> Vec<Scene> {
+    let mut scenes: Vec<Scene> = Vec::new();
+    for (frame, rpu) in rpus.iter().enumerate() {
+        let Some((max_pq, avg_pq, scene_cut)) = frame_l1(rpu) else {
+            continue;
+        };
+        match scenes.last_mut() {
+            Some(scene) if !scene_cut => {
+                scene.avg_pq =
+                    (scene.avg_pq * scene.frames as f64 + avg_pq) / (scene.frames + 1) as f64;
+                scene.max_pq = scene.max_pq.max(max_pq);
+                scene.frames += 1;
+            }
+            _ => scenes.push(Scene {
+                first_frame: frame,
+                frames: 1,
+                max_pq,
+                avg_pq,
+            }),
+        }
+    }
+    scenes
+}
+
+fn run_dovi_tool(args: &[&str]) -> Result<std::process::Output, String> {
+    let output = Command::new(command_line::resolve("dovi_tool"))
+        .args(args)
+        .stdin(Stdio::null())
+        .output()
+        .map_err(|e| format!("Failed to execute command dovi_tool: {e}"))?;
+    if !output.status.success() {
+        return Err(format!(
+            "dovi_tool {} failed: {}",
+            args.first().copied().unwrap_or_default(),
+            String::from_utf8_lossy(&output.stderr).trim()
+        ));
+    }
+    Ok(output)
+}
+
+// 提取 RPU 到 rpu_file；seconds 为 Some 时只读取开头一段
+fn extract_rpu(input: &Path, rpu_file: &Path, seconds: Option<f64>) -> Result<(), String> {
+    let duration = seconds.map(|seconds| seconds.to_string());
+    let limit = match &duration {
+        Some(duration) => vec!["-t", duration.as_str()],
+        None => Vec::new(),
+    };
+    // 通过管道把视频流交给 dovi_tool，避免把整个 HEVC 流写入磁盘
+    let mut ffmpeg = Command::new(command_line::resolve("ffmpeg"))
+        .args(["-v", "error", "-i"])
+        .arg(input)
+        .args(limit)
+        .args([
+            "-map",
+            "0:v:0",
+            "-c:v",
+            "copy",
+            "-bsf:v",
+            "hevc_mp4toannexb",
+            "-f",
+            "hevc",
+            "-",
+        ])
+        .stdin(Stdio::null())
+        .stdout(Stdio::piped())
+        .stderr(Stdio::null())
+        .spawn()
+        .map_err(|e| format!("Failed to execute command ffmpeg: {e}"))?;
+    let stream = ffmpeg
+        .stdout
+        .take()
+        .ok_or("Failed to read the ffmpeg video stream")?;
+    let extract = Command::new(command_line::resolve("dovi_tool"))
+        .args(["extract-rpu", "-", "-o"])
+        .arg(rpu_file)
+        .stdin(stream)
+        .stdout(Stdio::null())
+        .stderr(Stdio::piped())
+        .output();
+    // dovi_tool 提前退出时 ffmpeg 会因管道关闭而结束
+    let _ = ffmpeg.wait();
+    let extract = extract.map_err(|e| format!("Failed to execute command dovi_tool: {e}"))?;
+    if !extract.status.success() || !rpu_file.exists() {
+        return Err(format!(
+            "dovi_tool extract-rpu failed: {}",
+            String::from_utf8_lossy(&extract.stderr).trim()
+        ));
+    }
+    Ok(())
+}
+
+// dovi_tool info --summary 的非空行
+fn summary(rpu_path: &str) -> Result<Vec<String>, String> {
+    let info = run_dovi_tool(&["info", "--summary", "-i", rpu_path])?;
+    Ok(String::from_utf8_lossy(&info.stdout)
+        .lines()
+        .map(str::trim_end)
+        .filter(|line| !line.trim().is_empty())
+        .map(ToString::to_string)
+        .collect())
+}
+
+fn analyze_in(input: &Path, work_dir: &Path) -> Result<Analysis, String> {
+    let rpu_file = work_dir.join("RPU.bin");
+    let export_file = work_dir.join("RPU.json");
+    let rpu_path = rpu_file.to_string_lossy().to_string();
+
+    extract_rpu(input, &rpu_file, None)?;
+    let summary = summary(&rpu_path)?;
+
+    run_dovi_tool(&[
+        "export",
+        "-i",
+        &rpu_path,
+        "-d",
+        &format!("all={}", export_file.to_string_lossy()),
+    ])?;
+    let file = File::open(&export_file).map_err(|e| format!("Failed to read RPU export: {e}"))?;
+    let rpus: Vec<ExportedRpu> = serde_json::from_reader(BufReader::new(file))
+        .map_err(|e| format!("Failed to parse RPU export: {e}"))?;
+    let scenes = scenes(&rpus);
+    if scenes.is_empty() {
+        return Err("The RPU has no L1 (brightness) metadata".to_string());
+    }
+
+    Ok(Analysis {
+        path: input.to_path_buf(),
+        frames: rpus.len(),
+        scenes,
+        summary,
+    })
+}
+
+// 在系统临时目录中的新目录里运行 f，结束后删除该目录；目录登记在临时文件登记表中
+fn with_work_dir<T>(input: &Path, f: impl FnOnce(&Path) -> Result<T, String>) -> Result<T, String> {
+    let nanos = std::time::SystemTime::now()
+        .duration_since(std::time::UNIX_EPOCH)
+        .map_or(0, |elapsed| elapsed.as_nanos());
+    let work_dir =
+        std::env::temp_dir().join(format!("rebottle-rpu-{}-{nanos}", std::process::id()));
+    std::fs::create_dir_all(&work_dir)
+        .map_err(|e| format!("Failed to create temporary directory: {e}"))?;
+    temp_files::register(input, &work_dir);
+    let result = f(&work_dir);
+    temp_files::remove(&work_dir);
+    result
+}
+
+// 提取文件的 RPU 并分析每个场景的 L1 亮度
+pub fn analyze(input: &Path) -> Result<Analysis, String> {
+    with_work_dir(input, |work_dir| analyze_in(input, work_dir))
+}
+
+// 只提取开头 seconds 秒的 RPU，返回 dovi_tool info --summary；用于深度分析
+pub fn sample_summary(input: &Path, seconds: f64) -> Result<Vec<String>, String> {
+    with_work_dir(input, |work_dir| {
+        let rpu_file = work_dir.join("RPU.bin");
+        extract_rpu(input, &rpu_file, Some(seconds))?;
+        summary(&rpu_file.to_string_lossy())
+    })
+}
@@ -0,0 +1,478 @@
+use iced::futures::channel::mpsc;
+use std::path::{Path, PathBuf};
+
+use crate::logs::Logs;
+use crate::verbosity::ToolVerbosity;
+use crate::{
+    FrameRate, Message, ProcessOptions, cli, execute_command_with_logging, mp4, pipeline, probe,
+};
+
+// 样本的帧数（23.976 fps 约 2 秒），dovi_tool generate 需要生成同样数量的 RPU
+const SAMPLE_FRAMES: &str = "48";
+const SAMPLE_SECONDS: &str = "2";
+// 输出时长与样本相差超过此值（秒）时视为失败
+const DURATION_TOLERANCE: f64 = 0.5;
+
+// dovi_tool generate 的配置：profile 8.1，CM v4.0，1000 nits 母版
+const RPU_CONFIG: &str = r#"{
+    "cm_version": "V40",
+    "length": 48,
+    "level6": {
+        "max_display_mastering_luminance": 1000,
+        "min_display_mastering_luminance": 1,
+        "max_content_light_level": 1000,
+        "max_frame_average_light_level": 400
+    }
+}"#;
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Outcome {
+    Passed,
+    Failed,
+    // 之前的检查失败，没有执行
+    NotRun,
+}
+
+// 一项检查：生成样本的各个阶段、处理流程的各个步骤和输出校验
+#[derive(Debug, Clone)]
+pub struct Check {
+    pub name: String,
+    pub outcome: Outcome,
+    pub detail: String,
+}
+
+impl std::fmt::Display for Check {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        let icon = match self.outcome {
+            Outcome::Passed => "✅",
+            Outcome::Failed => "❌",
+            Outcome::NotRun => "⏭",
+        };
+        write!(f, "{icon} {}", self.name)?;
+        if !self.detail.is_empty() {
+            write!(f, ": {}", self.detail)?;
+        }
+        Ok(())
+    }
+}
+
+#[derive(Debug, Clone, Default)]
+pub struct Report {
+    pub checks: Vec<Check>,
+    // 各外部命令的日志
+    pub logs: Vec<String>,
+    // 失败时保留的临时文件夹，便于检查样本和中间文件
+    pub kept: Option<PathBuf>,
+}
+
+impl Report {
+    pub fn passed(&self) -> bool {
+        self.checks
+            .iter()
+            .all(|check| check.outcome == Outcome::Passed)
+    }
+
+    pub fn tools_missing(&self) -> bool {
+        self.checks
+            .first()
+            .is_some_and(|check| check.name == TOOLS_CHECK && check.outcome == Outcome::Failed)
+    }
+
+    // 每项检查一行，最后是总结
+    pub fn summary(&self) -> Vec<String> {
+        let mut lines = self
+            .checks
+            .iter()
+            .map(ToString::to_string)
+            .collect::<Vec<_>>();
+        let passed = self
+            .checks
+            .iter()
+            .filter(|check| check.outcome == Outcome::Passed)
+            .count();
+        lines.push(if self.passed() {
+            format!("✅ Self-test passed ({passed} checks)")
+        } else {
+            format!(
+                "❌ Self-test failed ({passed}/{} checks passed)",
+                self.checks.len()
+            )
+        });
+        if let Some(folder) = &self.kept {
+            lines.push(format!(
+                "Sample and intermediate files kept in {}",
+                folder.to_string_lossy()
+            ));
+        }
+        lines
+    }
+
+    fn pass(&mut self, name: &str, detail: impl Into<String>) {
+        self.checks.push(Check {
+            name: name.to_string(),
+            outcome: Outcome::Passed,
+            detail: detail.into(),
+        });
+    }
+
+    fn fail(&mut self, name: &str, detail: impl Into<String>) {
+        self.checks.push(Check {
+            name: name.to_string(),
+            outcome: Outcome::Failed,
+            detail: detail.into(),
+        });
+    }
+
+    fn not_run(&mut self, names: &[&str]) {
+        self.checks.extend(names.iter().map(|name| Check {
+            name: name.to_string(),
+            outcome: Outcome::NotRun,
+            detail: String::new(),
+        }));
+    }
+}
+
+const TOOLS_CHECK: &str = "Required tools";
+const SAMPLE_CHECKS: [&str; 4] = [
+    "Generate sample video",
+    "Generate sample audio",
+    "Add Dolby Vision metadata",
+    "Build sample MKV",
+];
+const PROBE_CHECK: &str = "Probe sample";
+const PIPELINE_CHECK: &str = "Processing pipeline";
+const OUTPUT_CHECK: &str = "Verify output";
+
+// 运行外部命令，失败时返回 stderr 的最后一行
+async fn run(logs: &mut Logs, command: &str, args: &[&str]) -> Result<(), String> {
+    match execute_command_with_logging(command, args, &ToolVerbosity::default(), logs).await {
+        Ok(output) if output.status.success() => Ok(()),
+        Ok(output) => {
+            let stderr = String::from_utf8_lossy(&output.stderr);
+            Err(format!(
+                "{command} failed: {}",
+                stderr.trim().lines().last().unwrap_or("no error output")
+            ))
+        }
+        Err(e) => Err(e),
+    }
+}
+
+fn arg(path: &Path) -> String {
+    path.to_string_lossy().to_string()
+}
+
+// 生成带杜比视界 profile 8.1 元数据的 HEVC + E-AC-3 5.1 MKV，依次对应 SAMPLE_CHECKS
+async fn build_sample(
+    report: &mut Report,
+    logs: &mut Logs,
+    folder: &Path,
+) -> Result<PathBuf, (usize, String)> {
+    let hevc = folder.join("sample.hevc");
+    let audio = folder.join("sample.ec3");
+    let config = folder.join("rpu_config.json");
+    let rpu = folder.join("sample_RPU.bin");
+    let dv_hevc = folder.join("sample_DV.hevc");
+    let mp4 = folder.join("sample_DV.mp4");
+    let mkv = folder.join("sample.mkv");
+
+    run(
+        logs,
+        "ffmpeg",
+        &[
+            "-y",
+            "-f",
+            "lavfi",
+            "-i",
+            "testsrc2=size=1280x720:rate=24000/1001",
+            "-frames:v",
+            SAMPLE_FRAMES,
+            "-c:v",
+            "libx265",
+            "-preset",
+            "ultrafast",
+            "-pix_fmt",
+            "yuv420p10le",
+            "-x265-params",
+            "log-level=error:repeat-headers=1:hdr10=1:colorprim=bt2020:transfer=smpte2084:colormatrix=bt2020nc:master-display=G(13250,34500)B(7500,3000)R(34000,16000)WP(15635,16450)L(10000000,1):max-cll=1000,400",
+            "-f",
+            "hevc",
+            &arg(&hevc),
+        ],
+    )
+    .await
+    .map_err(|e| (0, e))?;
+    report.pass(SAMPLE_CHECKS[0], "1280x720 HEVC 10-bit PQ");
+
+    run(
+        logs,
+        "ffmpeg",
+        &[
+            "-y",
+            "-f",
+            "lavfi",
+            "-i",
+            "sine=frequency=440:sample_rate=48000",
+            "-t",
+            SAMPLE_SECONDS,
+            "-ac",
+            "6",
+            "-c:a",
+            "eac3",
+            "-b:a",
+            "384k",
+            &arg(&audio),
+        ],
+    )
+    .await
+    .map_err(|e| (1, e))?;
+    report.pass(SAMPLE_CHECKS[1], "E-AC-3 5.1");
+
+    std::fs::write(&config, RPU_CONFIG)
+        .map_err(|e| (2, format!("Failed to write RPU config: {e}")))?;
+    run(
+        logs,
+        "dovi_tool",
+        &["generate", "-j", &arg(&config), "-o", &arg(&rpu)],
+    )
+    .await
+    .map_err(|e| (2, e))?;
+    run(
+        logs,
+        "dovi_tool",
+        &[
+            "inject-rpu",
+            "-i",
+            &arg(&hevc),
+            "--rpu-in",
+            &arg(&rpu),
+            "-o",
+            &arg(&dv_hevc),
+        ],
+    )
+    .await
+    .map_err(|e| (2, e))?;
+    report.pass(SAMPLE_CHECKS[2], "profile 8.1");
+
+    // ffmpeg 只有在输入带有杜比视界配置时才会把它写入 MKV，因此先用 mp4muxer 封装
+    run(
+        logs,
+        "mp4muxer",
+        &[
+            "-o",
+            &arg(&mp4),
+            "-i",
+            &arg(&dv_hevc),
+            "--input-video-frame-rate",
+            &FrameRate::FILM_23976.to_value(),
+            "--dv-profile",
+            "8",
+            "--dv-bl-compatible-id",
+            "1",
+        ],
+    )
+    .await
+    .map_err(|e| (3, e))?;
+    run(
+        logs,
+        "ffmpeg",
+        &[
+            "-y",
+            "-i",
+            &arg(&mp4),
+            "-i",
+            &arg(&audio),
+            "-map",
+            "0:v",
+            "-map",
+            "1:a",
+            "-c",
+            "copy",
+            "-metadata:s:a:0",
+            "language=eng",
+            &arg(&mkv),
+        ],
+    )
+    .await
+    .map_err(|e| (3, e))?;
+    report.pass(SAMPLE_CHECKS[3], "");
+    Ok(mkv)
+}
+
+// 处理流程的日志中 "Pipeline: a → b" 列出全部步骤，"Step n/N: ..." 表示开始执行第 n 步
+fn pipeline_checks(report: &mut Report, logs: &[String], result: &Result<Vec<PathBuf>, String>) {
+    let steps = logs
+        .iter()
+        .find_map(|line| line.strip_prefix("Pipeline: "))
+        .map(|steps| {
+            steps
+                .split(" → ")
+                .map(ToString::to_string)
+                .collect::<Vec<_>>()
+        })
+        .unwrap_or_default();
+    if steps.is_empty() {
+        match result {
+            Ok(_) => report.pass(PIPELINE_CHECK, ""),
+            Err(e) => report.fail(PIPELINE_CHECK, e.clone()),
+        }
+        return;
+    }
+    let started = logs
+        .iter()
+        .filter(|line| line.starts_with("Step ") && line.ends_with("..."))
+        .count();
+    for (index, step) in steps.iter().enumerate() {
+        match result {
+            Err(e) if index + 1 == started => report.fail(step, e.clone()),
+            _ if index < started => report.pass(step, ""),
+            _ => report.not_run(&[step]),
+        }
+    }
+    if let Err(e) = result
+        && started == 0
+    {
+        report.fail(PIPELINE_CHECK, e.clone());
+    }
+}
+
+// 主输出应带有杜比视界配置、HEVC 视频和 E-AC-3 音频，时长与样本一致
+fn verify_output(output: &Path, sample: &probe::MediaInfo) -> Result<String, String> {
+    let entries = mp4::video_entries(output)?;
+    let entry = entries.first().ok_or("Output has no video track")?;
+    let (config_box, config) = entry
+        .dv_config
+        .as_ref()
+        .ok_or("Output has no Dolby Vision configuration")?;
+    let info = probe::probe_media(output)?;
+    if info.video.as_ref().map(|video| video.codec.as_str()) != Some("hevc") {
+        return Err("Output has no HEVC video".to_string());
+    }
+    if !info.audio.iter().any(|audio| audio.codec == "eac3") {
+        return Err("Output has no E-AC-3 audio".to_string());
+    }
+    if let (Some(expected), Some(actual)) = (sample.duration, info.duration)
+        && (expected - actual).abs() > DURATION_TOLERANCE
+    {
+        return Err(format!(
+            "Output duration {actual:.2}s differs from sample {expected:.2}s"
+        ));
+    }
+    Ok(format!("{} {config_box}: {config}", entry.format))
+}
+
+// 生成样本、用默认选项运行完整的处理流程并校验输出，检查当前环境能否正常转换
+pub async fn run_self_test(sender: &mut mpsc::Sender<Message>) -> Report {
+    let mut report = Report::default();
+    let options = ProcessOptions::default();
+
+    // 生成样本使用 ffmpeg、dovi_tool 和 mp4muxer，处理样本需要的工具按默认选项下杜比视界 HEVC 源的步骤得出
+    let mut tools = vec!["ffmpeg", "dovi_tool", "mp4muxer"];
+    let steps = pipeline::plan(
+        &options,
+        &probe::MediaInfo::default(),
+        &[],
+        true,
+        &pipeline::Source::default(),
+    );
+    cli::add_step_tools(&options, &steps, &mut tools);
+    let missing = tools
+        .into_iter()
+        .filter(|tool| !cli::find_tool(tool))
+        .collect::<Vec<_>>();
+    if !missing.is_empty() {
+        report.fail(
+            TOOLS_CHECK,
+            format!("not found in PATH: {}", missing.join(", ")),
+        );
+        report.not_run(&SAMPLE_CHECKS);
+        report.not_run(&[PROBE_CHECK, PIPELINE_CHECK, OUTPUT_CHECK]);
+        return report;
+    }
+    report.pass(TOOLS_CHECK, "");
+
+    let folder = std::env::temp_dir().join(format!("rebottle-self-test-{}", std::process::id()));
+    let output_folder = folder.join("output");
+    if let Err(e) = std::fs::create_dir_all(&output_folder) {
+        report.fail(
+            SAMPLE_CHECKS[0],
+            format!("Failed to create temporary folder: {e}"),
+        );
+        report.not_run(&SAMPLE_CHECKS[1..]);
+        report.not_run(&[PROBE_CHECK, PIPELINE_CHECK, OUTPUT_CHECK]);
+        return report;
+    }
+
+    let mut logs = Logs::new(sender);
+    let sample = build_sample(&mut report, &mut logs, &folder).await;
+    report.logs.extend(logs.into_lines());
+    let passed = match sample {
+        Err((stage, e)) => {
+            report.fail(SAMPLE_CHECKS[stage], e);
+            report.not_run(&SAMPLE_CHECKS[stage + 1..]);
+            report.not_run(&[PROBE_CHECK, PIPELINE_CHECK, OUTPUT_CHECK]);
+            false
+        }
+        Ok(sample) => match probe::probe_media(&sample) {
+            Err(e) => {
+                report.fail(PROBE_CHECK, e);
+                report.not_run(&[PIPELINE_CHECK, OUTPUT_CHECK]);
+                false
+            }
+            Ok(info) if !info.has_dolby_vision() => {
+                report.fail(
+                    PROBE_CHECK,
+                    "no Dolby Vision configuration found in the sample MKV (ffmpeg 6 or newer is needed)",
+                );
+                report.not_run(&[PIPELINE_CHECK, OUTPUT_CHECK]);
+                false
+            }
+            Ok(info) => {
+                report.pass(PROBE_CHECK, "Dolby Vision detected");
+                let source = pipeline::Source {
+                    path: sample,
+                    active_area: None,
+                    version: 1,
+                    trim: None,
+                    audio_tracks: Vec::new(),
+                    subtitle_tracks: None,
+                    external_subtitles: Vec::new(),
+                    audio_offset: None,
+                    subtitle_offset: None,
+                    parts: Vec::new(),
+                };
+                let (result, mut logs) =
+                    pipeline::process_file(source, output_folder, options, sender, None).await;
+                pipeline_checks(&mut report, &logs, &result);
+                report.logs.append(&mut logs);
+                match result.as_ref().map(|files| files.first()) {
+                    Ok(Some(output)) => match verify_output(output, &info) {
+                        Ok(detail) => {
+                            report.pass(OUTPUT_CHECK, detail);
+                            true
+                        }
+                        Err(e) => {
+                            report.fail(OUTPUT_CHECK, e);
+                            false
+                        }
+                    },
+                    Ok(None) => {
+                        report.fail(OUTPUT_CHECK, "the pipeline wrote no output");
+                        false
+                    }
+                    Err(_) => {
+                        report.not_run(&[OUTPUT_CHECK]);
+                        false
+                    }
+                }
+            }
+        },
+    };
+
+    if passed {
+        let _ = std::fs::remove_dir_all(&folder);
+    } else {
+        report.kept = Some(folder);
+    }
+    report
+}
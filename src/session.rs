@@ -0,0 +1,76 @@
+use serde::{Deserialize, Serialize};
+use std::path::PathBuf;
+
+use crate::config;
+use crate::watch::ReplacedPolicy;
+use crate::{QueueSort, Tab};
+
+const SCHEMA: config::Schema = config::Schema {
+    file: "session.json",
+    name: "session",
+    migrations: &[],
+};
+
+// 界面状态，退出时保存，启动时恢复
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+#[serde(default)]
+pub struct Session {
+    pub window_width: f32,
+    pub window_height: f32,
+    pub window_x: Option<f32>,
+    pub window_y: Option<f32>,
+    pub active_tab: Tab,
+    pub terminal_filter: String,
+    pub terminal_errors_only: bool,
+    pub queue_sort: QueueSort,
+    pub quick_mode: bool,
+    pub show_advanced: bool,
+    // 监视文件夹，重新启动后继续监视
+    pub watch_folder: Option<PathBuf>,
+    // 监视文件夹中的文件被替换时的处理方式
+    pub replaced_policy: ReplacedPolicy,
+    // 是否在本地记录失败统计，默认关闭
+    pub collect_stats: bool,
+    // 上一次批处理测量的吞吐量（源文件字节/秒），用于在新批次的第一个文件完成前估计剩余时间
+    pub throughput: Option<f64>,
+    // 开始批处理前对队列中的文件进行深度分析
+    pub deep_probe_before_start: bool,
+}
+
+impl Default for Session {
+    fn default() -> Self {
+        Self {
+            window_width: 1024.0,
+            window_height: 768.0,
+            window_x: None,
+            window_y: None,
+            active_tab: Tab::Terminal,
+            terminal_filter: String::new(),
+            terminal_errors_only: false,
+            queue_sort: QueueSort::Added,
+            quick_mode: false,
+            show_advanced: false,
+            watch_folder: None,
+            replaced_policy: ReplacedPolicy::Reconvert,
+            collect_stats: false,
+            throughput: None,
+            deep_probe_before_start: true,
+        }
+    }
+}
+
+// 应用配置目录
+pub fn config_dir() -> Option<PathBuf> {
+    dirs::config_dir().map(|dir| dir.join("Rebottle"))
+}
+
+impl Session {
+    // 读取失败时使用默认状态
+    pub fn load() -> Self {
+        config::load(&SCHEMA)
+    }
+
+    pub fn save(&self) -> Result<(), String> {
+        config::save(&SCHEMA, self)
+    }
+}
@@ -0,0 +1,47 @@
+use serde::{Deserialize, Serialize};
+use std::collections::BTreeMap;
+use std::path::{Path, PathBuf};
+
+use crate::config;
+use crate::pipeline::normalize_path;
+
+const SCHEMA: config::Schema = config::Schema {
+    file: "skip_list.json",
+    name: "skip list",
+    migrations: &[],
+};
+
+// 标记为“永不处理”的源文件及原因，添加文件和监视文件夹时都会跳过
+#[derive(Debug, Clone, Default, PartialEq, Serialize, Deserialize)]
+pub struct SkipList(BTreeMap<PathBuf, String>);
+
+impl SkipList {
+    pub fn load() -> Self {
+        config::load(&SCHEMA)
+    }
+
+    pub fn save(&self) -> Result<(), String> {
+        config::save(&SCHEMA, self)
+    }
+
+    // 返回跳过原因；路径规范化后比较，同一文件通过不同路径添加也能识别
+    pub fn reason(&self, path: &Path) -> Option<&str> {
+        self.0.get(&normalize_path(path)).map(String::as_str)
+    }
+
+    pub fn insert(&mut self, path: &Path, reason: String) {
+        self.0.insert(normalize_path(path), reason);
+    }
+
+    pub fn remove(&mut self, path: &Path) {
+        self.0.remove(path);
+    }
+
+    pub fn iter(&self) -> impl Iterator<Item = (&PathBuf, &String)> {
+        self.0.iter()
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.0.is_empty()
+    }
+}
@@ -0,0 +1,105 @@
+use serde::{Deserialize, Serialize};
+use std::collections::BTreeMap;
+
+use crate::config;
+use crate::probe::MediaInfo;
+
+const SCHEMA: config::Schema = config::Schema {
+    file: "stats.json",
+    name: "stats",
+    migrations: &[],
+};
+
+#[derive(Debug, Clone, Copy, Default, PartialEq, Serialize, Deserialize)]
+#[serde(default)]
+pub struct Counts {
+    pub processed: u64,
+    pub failed: u64,
+}
+
+impl Counts {
+    fn record(&mut self, failed: bool) {
+        self.processed += 1;
+        if failed {
+            self.failed += 1;
+        }
+    }
+
+    pub fn failure_rate(&self) -> f32 {
+        if self.processed == 0 {
+            0.0
+        } else {
+            self.failed as f32 / self.processed as f32
+        }
+    }
+}
+
+// 本地失败统计，只保存聚合后的分类计数，不记录文件名或路径
+#[derive(Debug, Clone, Default, PartialEq, Serialize, Deserialize)]
+#[serde(default)]
+pub struct Stats {
+    pub total: Counts,
+    // 失败次数，按失败时运行的工具分类
+    pub failures_by_tool: BTreeMap<String, u64>,
+    // 按源文件特征分类的处理和失败次数
+    pub by_source: BTreeMap<String, Counts>,
+}
+
+// 源文件特征，例如 "Audio: truehd (Atmos)"、"Dolby Vision: profile 7"
+fn characteristics(info: &MediaInfo) -> Vec<String> {
+    let mut traits = Vec::new();
+    match &info.video {
+        Some(video) => {
+            traits.push(format!("Video: {}", video.codec));
+            let resolution = match video.height {
+                height if height > 1080 => "2160p",
+                height if height > 720 => "1080p",
+                _ => "SD/720p",
+            };
+            traits.push(format!("Resolution: {resolution}"));
+            traits.push(match &video.dolby_vision {
+                Some(dv) => format!("Dolby Vision: profile {}", dv.profile),
+                None => "Dolby Vision: none".to_string(),
+            });
+            if video.hdr10_plus {
+                traits.push("Video: HDR10+".to_string());
+            }
+            if video.pulldown {
+                traits.push("Video: pulldown".to_string());
+            }
+        }
+        None => traits.push("Video: none".to_string()),
+    }
+    match info.preferred_audio() {
+        Some(audio) if audio.is_atmos() => traits.push(format!("Audio: {} (Atmos)", audio.codec)),
+        Some(audio) => traits.push(format!("Audio: {}", audio.codec)),
+        None => traits.push("Audio: none".to_string()),
+    }
+    if info.has_only_image_subtitles() {
+        traits.push("Subtitles: image only".to_string());
+    }
+    traits
+}
+
+impl Stats {
+    pub fn load() -> Self {
+        config::load(&SCHEMA)
+    }
+
+    pub fn save(&self) -> Result<(), String> {
+        config::save(&SCHEMA, self)
+    }
+
+    // 记录一个文件的处理结果；failed_tool 为 None 表示成功
+    pub fn record(&mut self, info: Option<&MediaInfo>, failed_tool: Option<&str>) {
+        let failed = failed_tool.is_some();
+        self.total.record(failed);
+        if let Some(tool) = failed_tool {
+            *self.failures_by_tool.entry(tool.to_string()).or_default() += 1;
+        }
+        let traits = info.map_or_else(|| vec!["Source: not analyzed".to_string()], characteristics);
+        for name in traits {
+            self.by_source.entry(name).or_default().record(failed);
+        }
+    }
+}
@@ -0,0 +1,143 @@
+use serde::{Deserialize, Serialize};
+use std::path::{Path, PathBuf};
+use std::sync::Mutex;
+
+use crate::{config, execute_command};
+
+// 会话中创建的全部临时文件（中间文件、未完成的 .part 输出、分析用的临时目录）的登记表，
+// 保存在配置目录的 temp_files.json 中。每个文件记录所属的任务（源文件）和创建它的进程：
+// 任务结束或取消时按任务清理，程序崩溃或被强制结束后由下次启动时的清理删除残留文件，
+// 各处都以登记表为准，不再各自拼接临时路径去删除
+const SCHEMA: config::Schema = config::Schema {
+    file: "temp_files.json",
+    name: "temp file registry",
+    migrations: &[],
+};
+
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct Entry {
+    pub path: PathBuf,
+    // 所属任务的源文件
+    pub job: PathBuf,
+    pub process: u32,
+}
+
+#[derive(Debug, Clone, Default, Serialize, Deserialize)]
+pub struct Registry {
+    entries: Vec<Entry>,
+}
+
+// 同一进程内的读写按顺序进行；每次修改都重新读取文件，保留其他进程（如同时运行的 --cli）登记的内容
+static LOCK: Mutex<()> = Mutex::new(());
+
+fn update<T>(f: impl FnOnce(&mut Registry) -> T) -> T {
+    let _guard = LOCK.lock().unwrap_or_else(|poisoned| poisoned.into_inner());
+    let mut registry: Registry = config::load(&SCHEMA);
+    let result = f(&mut registry);
+    let _ = config::save(&SCHEMA, &registry);
+    result
+}
+
+// 删除文件或目录；已不存在时视为成功
+fn delete(path: &Path) -> bool {
+    let result = if path.is_dir() {
+        std::fs::remove_dir_all(path)
+    } else {
+        std::fs::remove_file(path)
+    };
+    result.is_ok() || !path.exists()
+}
+
+// 登记任务创建的临时文件
+pub fn register(job: &Path, path: &Path) {
+    let entry = Entry {
+        path: path.to_path_buf(),
+        job: job.to_path_buf(),
+        process: std::process::id(),
+    };
+    update(|registry| {
+        if !registry.entries.contains(&entry) {
+            registry.entries.push(entry);
+        }
+    });
+}
+
+// 文件已成为最终输出（.part 重命名后），不再登记
+pub fn release(path: &Path) {
+    update(|registry| registry.entries.retain(|entry| entry.path != path));
+}
+
+// 删除一个临时文件并取消登记
+pub fn remove(path: &Path) {
+    update(|registry| {
+        registry
+            .entries
+            .retain(|entry| entry.path != path || !delete(&entry.path));
+    });
+}
+
+// 任务结束或取消：删除本进程为该任务登记的全部文件，返回删除的数量
+pub fn remove_job(job: &Path) -> usize {
+    let process = std::process::id();
+    update(|registry| {
+        let before = registry.entries.len();
+        registry
+            .entries
+            .retain(|entry| entry.job != job || entry.process != process || !delete(&entry.path));
+        before - registry.entries.len()
+    })
+}
+
+pub fn process_running(process: u32) -> bool {
+    if process == std::process::id() {
+        return true;
+    }
+    let output = if cfg!(windows) {
+        execute_command(
+            "tasklist",
+            &["/FI", &format!("PID eq {process}"), "/NH", "/FO", "CSV"],
+        )
+        .map(|out| String::from_utf8_lossy(&out.stdout).contains(&format!("\"{process}\"")))
+    } else {
+        execute_command("kill", &["-0", &process.to_string()]).map(|out| out.status.success())
+    };
+    output.unwrap_or(false)
+}
+
+// 启动时的清理：删除已经结束的进程登记的文件（崩溃或强制退出的残留），返回用于日志的说明
+pub fn clean_stale() -> Vec<String> {
+    update(|registry| {
+        let mut processes = registry
+            .entries
+            .iter()
+            .map(|entry| entry.process)
+            .collect::<Vec<_>>();
+        processes.sort_unstable();
+        processes.dedup();
+        let running = processes
+            .into_iter()
+            .filter(|process| process_running(*process))
+            .collect::<Vec<_>>();
+
+        let mut notes = Vec::new();
+        registry.entries.retain(|entry| {
+            if running.contains(&entry.process) || !entry.path.exists() {
+                return running.contains(&entry.process);
+            }
+            if !delete(&entry.path) {
+                notes.push(format!(
+                    "⚠️ Could not remove leftover temporary file {}",
+                    entry.path.to_string_lossy()
+                ));
+                return true;
+            }
+            notes.push(format!(
+                "🧹 Removed leftover temporary file {} (from {})",
+                entry.path.to_string_lossy(),
+                entry.job.file_name().unwrap_or_default().to_string_lossy()
+            ));
+            false
+        });
+        notes
+    })
+}
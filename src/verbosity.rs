@@ -0,0 +1,73 @@
+use serde::{Deserialize, Serialize};
+use std::collections::BTreeMap;
+
+// 可以单独设置输出详细程度的外部工具
+pub const TOOLS: [&str; 10] = [
+    "ffmpeg",
+    "mkvmerge",
+    "mkvextract",
+    "mkvpropedit",
+    "mp4muxer",
+    "MP4Box",
+    "dovi_tool",
+    "hdr10plus_tool",
+    "curl",
+    "rclone",
+];
+
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq, Serialize, Deserialize)]
+pub enum Verbosity {
+    Quiet,
+    #[default]
+    Normal,
+    Verbose,
+}
+
+impl Verbosity {
+    pub const ALL: [Verbosity; 3] = [Verbosity::Quiet, Verbosity::Normal, Verbosity::Verbose];
+}
+
+impl std::fmt::Display for Verbosity {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        f.write_str(match self {
+            Verbosity::Quiet => "Quiet",
+            Verbosity::Normal => "Normal",
+            Verbosity::Verbose => "Verbose",
+        })
+    }
+}
+
+// 每个工具的输出详细程度，未设置的工具为 Normal
+#[derive(Debug, Clone, Default, PartialEq, Serialize, Deserialize)]
+pub struct ToolVerbosity(BTreeMap<String, Verbosity>);
+
+impl ToolVerbosity {
+    pub fn get(&self, tool: &str) -> Verbosity {
+        self.0.get(tool).copied().unwrap_or_default()
+    }
+
+    pub fn set(&mut self, tool: &str, verbosity: Verbosity) {
+        if verbosity == Verbosity::Normal {
+            self.0.remove(tool);
+        } else {
+            self.0.insert(tool.to_string(), verbosity);
+        }
+    }
+
+    // 对应工具自身的日志参数，插入在其他参数之前；
+    // mp4muxer、dovi_tool 和 hdr10plus_tool 没有日志级别参数，只按级别过滤记录的输出
+    pub fn flags(&self, tool: &str) -> &'static [&'static str] {
+        match (tool, self.get(tool)) {
+            ("ffmpeg", Verbosity::Quiet) => &["-loglevel", "error"],
+            ("ffmpeg", Verbosity::Verbose) => &["-loglevel", "verbose"],
+            ("mkvmerge" | "mkvextract" | "mkvpropedit", Verbosity::Quiet) => &["--quiet"],
+            ("mkvmerge" | "mkvextract" | "mkvpropedit", Verbosity::Verbose) => &["--verbose"],
+            ("MP4Box", Verbosity::Quiet) => &["-quiet"],
+            ("MP4Box", Verbosity::Verbose) => &["-v"],
+            ("curl", Verbosity::Verbose) => &["--verbose"],
+            ("rclone", Verbosity::Quiet) => &["--quiet"],
+            ("rclone", Verbosity::Verbose) => &["--verbose"],
+            _ => &[],
+        }
+    }
+}
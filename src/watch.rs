@@ -0,0 +1,342 @@
+use iced::futures::SinkExt;
+use iced::futures::channel::mpsc;
+use serde::{Deserialize, Serialize};
+use std::collections::HashMap;
+use std::hash::Hasher;
+use std::io::{Read, Seek, SeekFrom};
+use std::path::{Path, PathBuf};
+use std::sync::Mutex;
+use std::time::{Duration, SystemTime};
+
+use crate::Message;
+
+// 轮询间隔；网络共享上 inotify/FSEvents 不可靠，统一使用轮询
+const POLL_INTERVAL: Duration = Duration::from_secs(5);
+// 共享断开后的重试间隔，按倍数增长
+const RETRY_MIN: Duration = Duration::from_secs(5);
+const RETRY_MAX: Duration = Duration::from_secs(300);
+
+// 监视文件夹的状态
+#[derive(Debug, Clone, PartialEq)]
+pub enum WatchHealth {
+    Watching,
+    Unreachable { error: String, retry_in: u64 },
+}
+
+impl std::fmt::Display for WatchHealth {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self {
+            WatchHealth::Watching => f.write_str("● Watching"),
+            WatchHealth::Unreachable { error, retry_in } => {
+                write!(f, "⚠ Unreachable ({error}), retrying in {retry_in}s")
+            }
+        }
+    }
+}
+
+// 同名文件被替换（如 proper/repack）时的处理方式
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq, Serialize, Deserialize)]
+pub enum ReplacedPolicy {
+    // 重新转换，输出文件名带版本号
+    #[default]
+    Reconvert,
+    Ask,
+    Ignore,
+}
+
+impl ReplacedPolicy {
+    pub const ALL: [ReplacedPolicy; 3] = [
+        ReplacedPolicy::Reconvert,
+        ReplacedPolicy::Ask,
+        ReplacedPolicy::Ignore,
+    ];
+}
+
+impl std::fmt::Display for ReplacedPolicy {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        f.write_str(match self {
+            ReplacedPolicy::Reconvert => "Reconvert as new version",
+            ReplacedPolicy::Ask => "Ask",
+            ReplacedPolicy::Ignore => "Ignore",
+        })
+    }
+}
+
+// 文件大小和修改时间，用于发现变化
+type Stamp = (u64, Option<SystemTime>);
+
+// 已知文件的状态；校验和在第一次需要比较时才计算
+struct Known {
+    stamp: Stamp,
+    checksum: Option<u64>,
+}
+
+// 本程序自己修改过的源文件（mkvpropedit 写入标签）及修改后的大小和修改时间。
+// 监视文件夹发现这样的变化时只更新记录，不当作被替换，否则每次转换后都会重新转换
+static OWN_CHANGES: Mutex<Vec<(PathBuf, Stamp)>> = Mutex::new(Vec::new());
+
+fn stamp(path: &Path) -> Option<Stamp> {
+    let meta = std::fs::metadata(path).ok()?;
+    Some((meta.len(), meta.modified().ok()))
+}
+
+// 在修改源文件之后调用，记录修改后的状态
+pub fn record_own_change(path: &Path) {
+    if let Some(stamp) = stamp(path) {
+        let mut changes = OWN_CHANGES
+            .lock()
+            .unwrap_or_else(|poisoned| poisoned.into_inner());
+        changes.retain(|(changed, _)| changed != path);
+        changes.push((path.to_path_buf(), stamp));
+    }
+}
+
+// 文件的当前状态是否来自本程序的修改；匹配后删除记录，之后的变化仍会被发现
+fn take_own_change(path: &Path, stamp: &Stamp) -> bool {
+    let mut changes = OWN_CHANGES
+        .lock()
+        .unwrap_or_else(|poisoned| poisoned.into_inner());
+    let Some(index) = changes
+        .iter()
+        .position(|(changed, recorded)| changed == path && recorded == stamp)
+    else {
+        return false;
+    };
+    changes.remove(index);
+    true
+}
+
+#[derive(Debug, PartialEq, Eq)]
+enum Change {
+    Arrived,
+    Replaced,
+    // 被 touch 或被本程序修改，只更新记录
+    Updated,
+}
+
+// 判断大小稳定后的文件是新文件、被替换还是只需要更新记录
+fn classify(path: &Path, previous: Option<&Known>, stamp: &Stamp, checksum: Option<u64>) -> Change {
+    let Some(known) = previous else {
+        return Change::Arrived;
+    };
+    if take_own_change(path, stamp) {
+        return Change::Updated;
+    }
+    // 只有修改时间变化且校验和相同（如被 touch）时不算替换；
+    // 启动前已存在的文件没有旧校验和，大小不变时也按替换处理
+    if known.stamp.0 != stamp.0 || known.checksum.is_none() || known.checksum != checksum {
+        Change::Replaced
+    } else {
+        Change::Updated
+    }
+}
+
+// 抽样校验和：文件大小加上开头和结尾各 4 MiB，避免在 NAS 上读取整个文件
+const CHECKSUM_SAMPLE: u64 = 4 * 1024 * 1024;
+
+fn quick_checksum(path: &Path) -> Result<u64, String> {
+    let mut file = std::fs::File::open(path).map_err(|e| e.to_string())?;
+    let size = file.metadata().map_err(|e| e.to_string())?.len();
+    let mut hasher = std::collections::hash_map::DefaultHasher::new();
+    hasher.write_u64(size);
+
+    let mut buffer = Vec::new();
+    (&mut file)
+        .take(CHECKSUM_SAMPLE)
+        .read_to_end(&mut buffer)
+        .map_err(|e| e.to_string())?;
+    if size > CHECKSUM_SAMPLE {
+        file.seek(SeekFrom::Start(
+            size.saturating_sub(CHECKSUM_SAMPLE).max(CHECKSUM_SAMPLE),
+        ))
+        .map_err(|e| e.to_string())?;
+        file.take(CHECKSUM_SAMPLE)
+            .read_to_end(&mut buffer)
+            .map_err(|e| e.to_string())?;
+    }
+    hasher.write(&buffer);
+    Ok(hasher.finish())
+}
+
+async fn checksum(path: &Path) -> Option<u64> {
+    let path = path.to_path_buf();
+    tokio::task::spawn_blocking(move || quick_checksum(&path))
+        .await
+        .ok()?
+        .ok()
+}
+
+fn scan_mkv_files(folder: &Path) -> Result<HashMap<PathBuf, Stamp>, String> {
+    let entries = std::fs::read_dir(folder).map_err(|e| e.to_string())?;
+    let mut files = HashMap::new();
+    for entry in entries {
+        let entry = entry.map_err(|e| e.to_string())?;
+        let path = entry.path();
+        let is_mkv = path
+            .extension()
+            .is_some_and(|extension| extension.eq_ignore_ascii_case("mkv"));
+        if is_mkv && let Ok(meta) = entry.metadata() {
+            files.insert(path, (meta.len(), meta.modified().ok()));
+        }
+    }
+    Ok(files)
+}
+
+// 监视文件夹：启动时已存在的文件视为已处理，之后新出现且大小稳定的 MKV 会加入队列。
+// 已知文件的大小或修改时间变化并稳定后，比较抽样校验和，内容不同时报告为被替换。
+// 共享断开时不会退出，而是按退避间隔重试，重新连接后继续使用已知文件列表，
+// 断开期间新增的文件也会被发现。
+pub fn watch_folder(folder: PathBuf) -> impl iced::futures::Stream<Item = Message> {
+    iced::stream::channel(100, move |mut sender: mpsc::Sender<Message>| async move {
+        let mut seen: Option<HashMap<PathBuf, Known>> = None;
+        // 上一次扫描到的大小和修改时间，连续两次相同才认为复制完成
+        let mut pending: HashMap<PathBuf, Stamp> = HashMap::new();
+        let mut retry = RETRY_MIN;
+        let mut healthy = false;
+
+        loop {
+            let scan_folder = folder.clone();
+            let result = tokio::task::spawn_blocking(move || scan_mkv_files(&scan_folder))
+                .await
+                .unwrap_or_else(|e| Err(e.to_string()));
+
+            let files = match result {
+                Ok(files) => files,
+                Err(error) => {
+                    healthy = false;
+                    let _ = sender
+                        .send(Message::WatchHealthChanged(WatchHealth::Unreachable {
+                            error,
+                            retry_in: retry.as_secs(),
+                        }))
+                        .await;
+                    tokio::time::sleep(retry).await;
+                    retry = (retry * 2).min(RETRY_MAX);
+                    continue;
+                }
+            };
+
+            if !healthy {
+                healthy = true;
+                retry = RETRY_MIN;
+                let _ = sender
+                    .send(Message::WatchHealthChanged(WatchHealth::Watching))
+                    .await;
+            }
+
+            let seen = seen.get_or_insert_with(|| {
+                files
+                    .iter()
+                    .map(|(path, stamp)| {
+                        let known = Known {
+                            stamp: *stamp,
+                            checksum: None,
+                        };
+                        (path.clone(), known)
+                    })
+                    .collect()
+            });
+            let mut arrived = Vec::new();
+            let mut replaced = Vec::new();
+            for (path, stamp) in &files {
+                let previous = seen.get(path);
+                if previous.is_some_and(|known| known.stamp == *stamp) {
+                    continue;
+                }
+                if pending.get(path) != Some(stamp) || stamp.0 == 0 {
+                    pending.insert(path.clone(), *stamp);
+                    continue;
+                }
+                pending.remove(path);
+                let checksum = checksum(path).await;
+                match classify(path, previous, stamp, checksum) {
+                    Change::Arrived => arrived.push(path.clone()),
+                    Change::Replaced => replaced.push(path.clone()),
+                    Change::Updated => {}
+                }
+                seen.insert(
+                    path.clone(),
+                    Known {
+                        stamp: *stamp,
+                        checksum,
+                    },
+                );
+            }
+            // 已删除的文件不再等待
+            pending.retain(|path, _| files.contains_key(path));
+
+            if !arrived.is_empty() {
+                arrived.sort();
+                let _ = sender.send(Message::WatchFilesArrived(arrived)).await;
+            }
+            replaced.sort();
+            for path in replaced {
+                let _ = sender.send(Message::WatchFileReplaced(path)).await;
+            }
+
+            tokio::time::sleep(POLL_INTERVAL).await;
+        }
+    })
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn scratch_file(name: &str, contents: &str) -> PathBuf {
+        let dir =
+            std::env::temp_dir().join(format!("rebottle-watch-{name}-{}", std::process::id()));
+        let _ = std::fs::remove_dir_all(&dir);
+        std::fs::create_dir_all(&dir).unwrap();
+        let path = dir.join("Movie.mkv");
+        std::fs::write(&path, contents).unwrap();
+        path
+    }
+
+    fn known(path: &Path) -> Known {
+        Known {
+            stamp: stamp(path).unwrap(),
+            checksum: quick_checksum(path).ok(),
+        }
+    }
+
+    fn changed(path: &Path, previous: &Known) -> Change {
+        classify(
+            path,
+            Some(previous),
+            &stamp(path).unwrap(),
+            quick_checksum(path).ok(),
+        )
+    }
+
+    #[test]
+    fn own_tag_edits_are_not_replacements() {
+        let path = scratch_file("own", "original source");
+        let before = known(&path);
+        // 模拟 mkvpropedit 写入标签
+        std::fs::write(&path, "original source with REBOTTLE tag").unwrap();
+        record_own_change(&path);
+        assert_eq!(changed(&path, &before), Change::Updated);
+
+        // 记录只匹配一次，之后真正的替换仍会被发现
+        let annotated = known(&path);
+        std::fs::write(&path, "proper release").unwrap();
+        assert_eq!(changed(&path, &annotated), Change::Replaced);
+        let _ = std::fs::remove_dir_all(path.parent().unwrap());
+    }
+
+    #[test]
+    fn other_changes_are_replacements() {
+        let path = scratch_file("other", "original source");
+        let before = known(&path);
+        record_own_change(&path);
+        // 记录之后又被替换，状态不再匹配
+        std::fs::write(&path, "repacked source, longer").unwrap();
+        assert_eq!(changed(&path, &before), Change::Replaced);
+        assert_eq!(
+            classify(&path, None, &stamp(&path).unwrap(), None),
+            Change::Arrived
+        );
+        let _ = std::fs::remove_dir_all(path.parent().unwrap());
+    }
+}